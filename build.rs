@@ -1,21 +1,120 @@
 use std::env;
+use std::path::Path;
 use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 fn main() {
-    let output = Command::new("git")
-        .args(["rev-parse", "--short", "HEAD"])
-        .output()
-        .unwrap();
+    // Packagers building from a tarball can supply the commit themselves
+    println!("cargo:rerun-if-env-changed=BUILD_GIT_HASH");
+    let git_hash = env::var("BUILD_GIT_HASH")
+        .ok()
+        .filter(|hash| !hash.is_empty())
+        .or_else(|| git(&["rev-parse", "--short", "HEAD"]));
+    watch_git_head();
 
-    let git_hash = String::from_utf8(output.stdout).unwrap();
-
-    // version
+    // Kept apart from CARGO_PKG_VERSION, which stays the bare version releases are tagged
+    // with; an empty hash means unknown
     println!(
-        "cargo:rustc-env=CARGO_PKG_VERSION={} {}",
-        env!("CARGO_PKG_VERSION"),
-        git_hash
+        "cargo:rustc-env=BUILD_GIT_HASH={}",
+        git_hash.as_deref().unwrap_or_default()
     );
 
+    // Uncommitted changes to tracked files
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some();
+    println!("cargo:rustc-env=BUILD_GIT_DIRTY={}", dirty);
+
     // // target
     println!("cargo:rustc-env=TARGET={}", env::var("TARGET").unwrap());
+
+    println!("cargo:rustc-env=BUILD_DATE={}", build_date());
+    println!("cargo:rustc-env=BUILD_RUSTC={}", rustc_version());
+    println!(
+        "cargo:rustc-env=BUILD_PROFILE={}",
+        env::var("PROFILE").unwrap()
+    );
+    println!("cargo:rustc-env=BUILD_FEATURES={}", features().join(","));
+}
+
+/// Trimmed stdout of a successful, non-empty git command; `None` without git or outside a
+/// repository (e.g. building from a crates.io tarball or vendored sources).
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let stdout = stdout.trim();
+    (!stdout.is_empty()).then(|| stdout.to_string())
+}
+
+/// Rebuilds when the checked-out commit changes: `.git/HEAD` changes on checkout, and the
+/// branch ref it points to on commit. Sources are watched too so the dirty flag follows
+/// edits. Paths that do not exist are not watched, as cargo would otherwise rerun the
+/// script on every build; without `.git` cargo's default of any package file applies.
+fn watch_git_head() {
+    let head = Path::new(".git/HEAD");
+    if !head.exists() {
+        return;
+    }
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    let reference = std::fs::read_to_string(head).unwrap_or_default();
+    if let Some(reference) = reference.trim().strip_prefix("ref: ") {
+        let path = Path::new(".git").join(reference);
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src");
+}
+
+/// UTC build date (YYYY-MM-DD), from `SOURCE_DATE_EPOCH` for reproducible builds.
+fn build_date() -> String {
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        });
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn rustc_version() -> String {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let output = Command::new(rustc).arg("--version").output().unwrap();
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Enabled Cargo features, as `CARGO_FEATURE_<NAME>` variables name them.
+fn features() -> Vec<String> {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(name, _)| {
+            name.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .filter(|feature| feature != "default")
+        .collect();
+    features.sort();
+    features
 }
@@ -0,0 +1,174 @@
+# Deutsche Meldungen. Fehlende Einträge werden auf Englisch angezeigt.
+
+## Statusmarker der barrierefreien und der Markdown-Ausgabe
+reporter-success = Erfolg
+reporter-failed = Fehlgeschlagen
+reporter-notice = Hinweis
+
+## Fehler
+error-multiple = { $count } Fehler sind aufgetreten:
+error-caused-by = verursacht durch
+error-hint = Tipp
+error-not-found = Datei nicht gefunden: { $path }
+error-permission-denied = Zugriff verweigert: { $path } ist nicht beschreibbar
+error-network = Anfrage an { $url } fehlgeschlagen
+error-cancelled = Abgebrochen
+error-http = { $url } antwortete mit HTTP { $status }
+error-io = E/A-Fehler: { $error }
+error-json = JSON-Fehler: { $error }
+error-regex = Ungültiger regulärer Ausdruck: { $error }
+error-threshold = Schwellenwert überschritten: { $details }
+error-file = '{ $path }' konnte nicht verarbeitet werden ({ $action })
+error-invalid-input = Ungültige Eingabe ({ $format }): { $message }
+hint-path-exists = Prüfen Sie, ob der Pfad existiert
+hint-permissions = Prüfen Sie die Dateiberechtigungen
+hint-regex-escape = Sonderzeichen mit '\' maskieren, um sie wörtlich zu finden
+hint-elevated = Versuchen Sie es mit erweiterten Rechten
+hint-network = Prüfen Sie die Netzwerkverbindung und die Proxy-Einstellungen (HTTPS_PROXY)
+hint-server-error = Der Server hatte ein Problem; versuchen Sie es später erneut
+
+## Absturzberichte
+crash-crashed = { $app } ist unerwartet abgestürzt: { $message }
+crash-report-written = Ein Absturzbericht wurde unter { $path } gespeichert
+crash-report-unsaved = Der Absturzbericht konnte nicht gespeichert werden ({ $error }):
+crash-report-issue = Bitte melden Sie dies unter { $url } und hängen Sie den Bericht an.
+crash-report-maintainers = Bitte melden Sie dies den Maintainern und hängen Sie den Bericht an.
+
+## Strg-C
+cancel-requested = Halte am nächsten sicheren Punkt an; erneut Strg-C drücken, um sofort zu beenden
+
+## Sprachen
+lang-unsupported = Keine Meldungen in '{ $lang }' (verfügbar: { $available }); verwende Englisch
+
+## Versions-Pins
+pin-mismatch = { $path } legt Version { $pinned } fest, dies ist aber Version { $running }; installieren Sie sie mit `{ $app } upgrade --to-pinned`
+
+## run
+run-binary-skipped = Binärdatei übersprungen: { $path }
+run-stats-binary = Dateistatistik für '{ $path }' (binär):
+run-stats-for = Dateistatistik für '{ $path }':
+run-stats = Dateistatistik:
+run-unchanged = '{ $path }': unverändert (zwischengespeichert)
+run-output-unchanged = Ausgabe unverändert (zwischengespeichert): { $path }
+run-kept-modified = Geänderte Ausgabe beibehalten (nicht überschrieben): { $path }
+run-output-written = Ausgabe geschrieben nach: { $path }
+run-output-stdout = Ausgabe auf die Standardausgabe geschrieben
+run-transform-stages = Transformationsschritte:
+run-skipped-paths = { $count } nicht lesbare(r) Pfad(e) übersprungen:
+run-failed-files = { $count } Datei(en) konnten nicht verarbeitet werden:
+run-processed-files = { $count } Dateien verarbeitet.
+run-complete = Verarbeitung abgeschlossen.
+run-stage = { $number }. { $stage } Zeilen { $lines_in } -> { $lines_out }, Bytes { $bytes_in } -> { $bytes_out }, { $ms } ms
+run-batch = Stapel { $path }:
+run-batch-plan = Stapelplan: { $jobs } Aufträge in { $stages } Stufen, bis zu { $workers } gleichzeitig
+run-batch-succeeded = Alle { $count } Aufträge des Stapels erfolgreich
+run-batch-failed = { $failed } von { $total } Aufträgen des Stapels fehlgeschlagen ({ $skipped } ausgelassen)
+run-job-succeeded = erfolgreich
+run-job-failed = fehlgeschlagen: { $error }
+run-job-skipped = ausgelassen
+stat-lines = Zeilen
+stat-words = Wörter
+stat-bytes = Bytes
+stat-chars = Zeichen
+stat-graphemes = Grapheme
+stat-blank-lines = Leerzeilen
+stat-line-length = Zeilenlänge
+stat-line-length-value = min. { $min }, max. { $max }, Ø { $avg }
+stat-compression = Komprimierung
+stat-encoding = Kodierung
+stat-matching-lines = Passende Zeilen
+stat-distinct-lines = Verschiedene Zeilen (geschätzt)
+stat-duplication-ratio = Duplikatanteil
+stat-entropy = Entropie
+stat-entropy-value = { $bits } Bit/Byte
+stat-top-words = Häufigste Wörter
+stat-line-endings = Zeilenenden
+stat-line-endings-value = { $style } ({ $lf } LF, { $crlf } CRLF)
+stat-trailing-whitespace = Zeilen mit Leerraum am Ende
+stat-indentation = Einrückung
+stat-indentation-value = { $style } ({ $tabs } mit Tab, { $spaces } mit Leerzeichen)
+stat-final-newline = Zeilenumbruch am Ende
+stat-yes = ja
+stat-no = nein
+stat-documents = Dokumente
+stat-max-depth = Maximale Tiefe
+stat-keys = Schlüssel
+stat-keys-value = { $keys } ({ $distinct } verschiedene)
+stat-max-array-len = Längstes Array
+stat-types = Typen nach Pfad
+stat-query-matches = Treffer der Abfrage
+stat-rows = Datensätze
+stat-columns = Spalten
+
+## upgrade
+upgrade-checking = Suche nach Updates.
+upgrade-pinned-current = Bereits auf Version { $version }, festgelegt durch { $path }. Nichts zu tun.
+upgrade-up-to-date = Bereits auf der neuesten Version, { $version }. Nichts zu tun.
+upgrade-available = Version { $version } ist verfügbar (installiert: { $current }).
+upgrade-downloading = Lade Version { $version } herunter.
+upgrade-verifying = Prüfsumme wird geprüft.
+upgrade-extracting = Entpacke das Update.
+upgrade-extracting-entries = Entpacke das Update ({ $count } Einträge)
+upgrade-installing = Installiere das Update.
+upgrade-complete = Update auf Version { $version } abgeschlossen.
+upgrade-changelog-none = Keine Version ist neuer als { $version }.
+upgrade-changelog-no-notes = (keine Versionshinweise)
+upgrade-list-current = { $version } (installiert)
+upgrade-list-none = Keine passenden Versionen gefunden.
+upgrade-notice = Eine neue Version ist verfügbar: { $version } (installiert: { $current }). Mit `{ $app } upgrade` aktualisieren.
+
+## selftest
+selftest-pass = OK
+selftest-fail = FEHLER
+selftest-skip = AUSGELASSEN
+selftest-passed = Alle { $count } Selbsttests bestanden
+
+## init
+init-created-config = Konfigurationsdatei { $path } erstellt
+init-installed-completions = Vervollständigungen nach { $path } installiert
+init-created-sample = Beispieldatei { $path } erstellt
+init-kept = { $path } existiert bereits und wurde beibehalten (--force überschreibt)
+init-next = Weiter mit: { $app } run --input { $input }
+render-written = { $bytes } Bytes gerendert nach { $path }
+render-dry-run = Probelauf: würde { $bytes } Bytes nach { $path } schreiben
+hash-ok = OK
+hash-failed = FEHLGESCHLAGEN
+hash-missing = FEHLGESCHLAGEN (nicht lesbar)
+bench-corpus = Benchmark: { $mb } MB Korpus ({ $lines } Zeilen), { $iterations } Durchläufe nach { $warmup } zum Aufwärmen, Strategie { $strategy }, Pipeline "{ $pipeline }"
+bench-throughput = Durchsatz (Median): { $mb_per_sec } MB/s, { $lines_per_sec } Zeilen/s
+bench-baseline = Änderung gegenüber { $version } ({ $mb_per_sec } MB/s): { $change } %
+cache-dir = Ergebnis-Cache: { $path }
+cache-usage = { $entries } zwischengespeicherte Ergebnisse, { $bytes } Bytes
+cache-cleaned = { $entries } zwischengespeicherte Ergebnisse entfernt, { $bytes } Bytes
+daemon-processed = { $count } neue oder geänderte Datei(en) verarbeitet.
+timings = Zeiten:
+
+## Tabellenköpfe
+header-path = Pfad
+header-blank = Leer
+header-avg-length = Ø Länge
+header-column = Spalte
+header-type = Typ
+header-types = Typen
+header-empty = Leer
+header-distinct = Verschieden
+header-min = Min.
+header-max = Max.
+header-mean = Mittel
+header-count = Anzahl
+header-word = Wort
+header-job = Auftrag
+header-status = Status
+header-time = Zeit
+header-stage = Stufe
+header-depends-on = Hängt ab von
+header-key = Schlüssel
+header-value = Wert
+header-origin = Herkunft
+header-version = Version
+header-published = Veröffentlicht
+header-prerelease = Vorabversion
+header-binary = Programm
+header-phase = Phase
+header-calls = Aufrufe
+header-total = Gesamt
@@ -0,0 +1,183 @@
+# English messages, the fallback for every other language.
+# Each entry is `id = text`; `{ $name }` is replaced by the argument `name`.
+
+## Status markers of the accessible and Markdown reporters
+reporter-success = Success
+reporter-failed = Failed
+reporter-notice = Notice
+
+## Errors
+error-multiple = { $count } errors occurred:
+error-caused-by = caused by
+error-hint = hint
+error-not-found = File not found: { $path }
+error-permission-denied = Permission denied: cannot write to { $path }
+error-network = Request to { $url } failed
+error-cancelled = Interrupted
+error-http = { $url } returned HTTP { $status }
+error-io = IO error: { $error }
+error-json = JSON error: { $error }
+error-regex = Invalid regular expression: { $error }
+error-threshold = Threshold exceeded: { $details }
+error-file = Cannot { $action } '{ $path }'
+error-invalid-input = Invalid { $format }: { $message }
+hint-path-exists = Check that the path exists
+hint-permissions = Check the file permissions
+hint-regex-escape = Escape special characters with '\' to match them literally
+hint-elevated = Try running with elevated privileges
+hint-network = Check your network connection and proxy settings (HTTPS_PROXY)
+hint-http-auth = Check the GitHub token with `auth status`; unauthenticated requests are rate limited
+hint-rate-limited = Rate limited; try again later or store a GitHub token with `auth set-token`
+hint-server-error = The server had a problem; try again later
+
+## Crash reports
+crash-crashed = { $app } crashed unexpectedly: { $message }
+crash-report-written = A crash report was written to { $path }
+crash-report-unsaved = The crash report could not be saved ({ $error }):
+crash-report-issue = Please report this at { $url } and attach the report.
+crash-report-maintainers = Please report this to the maintainers and attach the report.
+
+## Ctrl-C
+cancel-requested = Stopping at the next safe point; press Ctrl-C again to quit immediately
+
+## Languages
+lang-unsupported = No messages in '{ $lang }' (available: { $available }); using English
+
+## Version pins
+pin-mismatch = { $path } pins version { $pinned }, but this is version { $running }; run `{ $app } upgrade --to-pinned` to install it
+
+## run
+run-throughput = Processed { $files } file(s), { $bytes_in } bytes in, { $bytes_out } bytes out (discarded), in { $seconds } s: { $rate } MB/s
+run-binary-skipped = Binary file skipped: { $path }
+run-stats-binary = File statistics for '{ $path }' (binary):
+run-stats-for = File statistics for '{ $path }':
+run-stats = File statistics:
+run-unchanged = '{ $path }': unchanged (cached)
+run-output-unchanged = Output unchanged (cached): { $path }
+run-kept-modified = Kept modified output (not overwritten): { $path }
+run-output-written = Output written to: { $path }
+run-output-stdout = Output written to stdout
+run-dedupe = Approximate dedupe: { $lines } line(s) removed (false-positive rate { $rate }, filter { $bytes } bytes)
+run-transform-stages = Transform stages:
+run-skipped-paths = Skipped { $count } unreadable path(s):
+run-failed-files = Failed to process { $count } file(s):
+run-processed-files = Processed { $count } files.
+run-complete = Processing complete.
+run-summary = Summary: { $files } file(s), { $read } bytes read, { $written } bytes written in { $seconds } s ({ $rate } MB/s{ $limit })
+run-summary-limit = , limited to { $limit } MB/s, throttled for { $seconds } s
+run-stage = { $number }. { $stage } lines { $lines_in } -> { $lines_out }, bytes { $bytes_in } -> { $bytes_out }, { $ms } ms
+run-batch = Batch { $path }:
+run-batch-plan = Batch plan: { $jobs } jobs in { $stages } stages, up to { $workers } at a time
+run-batch-succeeded = All { $count } batch jobs succeeded
+run-batch-failed = { $failed } of { $total } batch jobs failed ({ $skipped } skipped)
+run-job-succeeded = succeeded
+run-job-failed = failed: { $error }
+run-job-skipped = skipped
+stat-lines = Lines
+stat-words = Words
+stat-bytes = Bytes
+stat-chars = Chars
+stat-graphemes = Graphemes
+stat-blank-lines = Blank lines
+stat-line-length = Line length
+stat-line-length-value = min { $min }, max { $max }, avg { $avg }
+stat-compression = Compression
+stat-encoding = Encoding
+stat-matching-lines = Matching lines
+stat-hash = Hash ({ $algorithm })
+stat-distinct-lines = Distinct lines (est.)
+stat-duplication-ratio = Duplication ratio
+stat-entropy = Entropy
+stat-entropy-value = { $bits } bits/byte
+stat-top-words = Top words
+stat-line-endings = Line endings
+stat-line-endings-value = { $style } ({ $lf } LF, { $crlf } CRLF)
+stat-trailing-whitespace = Lines with trailing whitespace
+stat-indentation = Indentation
+stat-indentation-value = { $style } ({ $tabs } tab-indented, { $spaces } space-indented)
+stat-final-newline = Final newline
+stat-yes = yes
+stat-no = no
+stat-documents = Documents
+stat-max-depth = Max depth
+stat-keys = Keys
+stat-keys-value = { $keys } ({ $distinct } distinct)
+stat-max-array-len = Longest array
+stat-types = Types by path
+stat-query-matches = Query matches
+stat-rows = Rows
+stat-columns = Columns
+
+## upgrade
+upgrade-checking = Checking for updates.
+upgrade-pinned-current = Already on version { $version }, pinned by { $path }. Nothing to do.
+upgrade-up-to-date = Already on the latest version, { $version }. Nothing to do.
+upgrade-available = Version { $version } is available (running { $current }).
+upgrade-downloading = Downloading version { $version }.
+upgrade-verifying = Verifying the checksum.
+upgrade-extracting = Extracting the update.
+upgrade-extracting-entries = Extracting the update ({ $count } entries)
+upgrade-installing = Installing the update.
+upgrade-complete = Upgrade to version { $version } is complete.
+upgrade-changelog-none = No release is newer than { $version }.
+upgrade-changelog-no-notes = (no release notes)
+upgrade-list-current = { $version } (current)
+upgrade-list-none = No matching releases found.
+upgrade-notice = A new version is available: { $version } (current { $current }). Run `{ $app } upgrade` to update.
+
+## selftest
+selftest-pass = PASS
+selftest-fail = FAIL
+selftest-skip = SKIP
+selftest-passed = All { $count } self-test cases passed
+
+## init
+init-created-config = Created configuration file { $path }
+init-installed-completions = Installed completions to { $path }
+init-zsh-fpath = Add `fpath+=({ $dir })` before `compinit` in ~/.zshrc if it is not there yet
+init-created-sample = Created sample { $path }
+init-kept = { $path } already exists; kept it (pass --force to overwrite)
+init-next = Next: { $app } run --input { $input }
+render-written = Rendered { $bytes } bytes to { $path }
+render-dry-run = Dry run: would write { $bytes } bytes to { $path }
+hash-ok = OK
+hash-failed = FAILED
+hash-missing = FAILED open or read
+bench-corpus = Benchmark: { $mb } MB corpus ({ $lines } lines), { $iterations } iteration(s) after { $warmup } warm-up, { $strategy } strategy, pipeline "{ $pipeline }"
+bench-throughput = Throughput (median): { $mb_per_sec } MB/s, { $lines_per_sec } lines/s
+bench-baseline = Change from { $version } ({ $mb_per_sec } MB/s): { $change }%
+cache-dir = Result cache: { $path }
+cache-usage = { $entries } cached result(s), { $bytes } bytes
+cache-cleaned = Removed { $entries } cached result(s), { $bytes } bytes
+daemon-processed = Processed { $count } new or changed file(s).
+timings = Timings:
+
+## Table headers
+header-path = Path
+header-blank = Blank
+header-avg-length = Avg length
+header-column = Column
+header-type = Type
+header-types = Types
+header-empty = Empty
+header-distinct = Distinct
+header-min = Min
+header-max = Max
+header-mean = Mean
+header-count = Count
+header-word = Word
+header-job = Job
+header-status = Status
+header-time = Time
+header-stage = Stage
+header-depends-on = Depends on
+header-key = Key
+header-value = Value
+header-origin = Origin
+header-version = Version
+header-published = Published
+header-prerelease = Prerelease
+header-binary = Binary
+header-phase = Phase
+header-calls = Calls
+header-total = Total
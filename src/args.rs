@@ -1,12 +1,15 @@
 use clap::Args;
+use std::path::PathBuf;
 use std::str::FromStr;
 use thiserror::Error;
 
 use crate::constants;
 use crate::env_vars;
+use crate::output::{ColorChoice, OutputFormat};
 
 /// Shared arguments available to all commands
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
+#[command(next_help_heading = "Global options")]
 pub struct GlobalArgs {
     /// Path to configuration file (supports .json, .yaml, .yml)
     #[arg(
@@ -25,6 +28,110 @@ pub struct GlobalArgs {
     /// Set log level (syslog-style: emergency, alert, critical, error, warning, notice, info, debug)
     #[arg(short = 'L', long, global = true, value_parser = parse_log_level)]
     pub log_level: Option<LogLevel>,
+
+    /// Reject unknown keys in the configuration file instead of ignoring them
+    #[arg(long, global = true)]
+    pub strict_config: bool,
+
+    /// Language of messages, e.g. `de` or `de_AT` (defaults to LC_ALL, LC_MESSAGES or LANG;
+    /// anything untranslated is shown in English)
+    #[arg(long, global = true, value_name = "LANG")]
+    pub lang: Option<String>,
+
+    /// Do not merge the project configuration files (.<name>.yaml) found in the current
+    /// directory and its parents beneath the configuration file
+    #[arg(long, global = true)]
+    pub no_config_search: bool,
+
+    /// Create the active profile's output directory at startup if it does not exist
+    #[arg(long, global = true)]
+    pub create_dirs: bool,
+
+    /// Ignore every __TEMPLATE_ENV_PREFIX___* environment variable (configuration path,
+    /// profile and all overrides), e.g. to reproduce a problem with a known configuration
+    #[arg(long, global = true)]
+    pub no_env: bool,
+
+    /// Answer yes to confirmation prompts, e.g. before overwriting an existing --output or
+    /// replacing the binary during upgrade
+    #[arg(short = 'y', long, global = true)]
+    pub yes: bool,
+
+    /// Output format for command results
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// When to use colors in text output
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Screen-reader friendly output: no progress bars or colors, plain-sentence steps
+    /// and labeled lines instead of tables
+    #[arg(
+        long = "a11y",
+        global = true,
+        env = env_vars::ACCESSIBLE,
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    pub accessible: bool,
+
+    /// Fail instead of warning when a .<name>-version file in the current directory or a
+    /// parent pins a different version than this binary
+    #[arg(long, global = true)]
+    pub strict_version: bool,
+
+    /// Print how long each phase (configuration loading, reading, processing, writing,
+    /// downloading, extracting) took, on stderr once the commands have finished
+    #[arg(long, global = true)]
+    pub timings: bool,
+
+    /// Do not take the lock that keeps concurrent upgrades, or runs writing to the same
+    /// output directory, from overlapping
+    #[arg(long, global = true)]
+    pub no_lock: bool,
+
+    /// Wait for another instance holding the lock to finish instead of failing
+    #[arg(long, global = true, conflicts_with = "no_lock")]
+    pub wait_for_lock: bool,
+
+    /// Record this invocation (args, environment, config, input digests) to a session file
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Re-execute an invocation previously captured with --record
+    #[arg(long, global = true, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Print help including hidden options
+    #[arg(long, global = true)]
+    pub help_all: bool,
+}
+
+/// The values the options take when none are given on the command line or in the environment.
+impl Default for GlobalArgs {
+    fn default() -> Self {
+        Self {
+            config: String::from(constants::DEFAULT_CONFIG_FILE),
+            verbose: 0,
+            log_level: None,
+            strict_config: false,
+            lang: None,
+            no_config_search: false,
+            create_dirs: false,
+            no_env: false,
+            yes: false,
+            format: OutputFormat::Text,
+            color: ColorChoice::Auto,
+            accessible: false,
+            strict_version: false,
+            timings: false,
+            no_lock: false,
+            wait_for_lock: false,
+            record: None,
+            replay: None,
+            help_all: false,
+        }
+    }
 }
 
 /// Syslog-style log levels
@@ -253,42 +360,36 @@ mod tests {
     #[test]
     fn test_effective_log_level() {
         // Test with no log level and no verbose
-        let args = GlobalArgs {
-            config: String::from(constants::DEFAULT_CONFIG_FILE),
-            verbose: 0,
-            log_level: None,
-        };
+        let args = GlobalArgs::default();
         assert_eq!(effective_log_level(&args), LogLevel::Error); // Default
 
         // Test with explicit log level, no verbose
         let args = GlobalArgs {
-            config: String::from(constants::DEFAULT_CONFIG_FILE),
-            verbose: 0,
             log_level: Some(LogLevel::Warning),
+            ..Default::default()
         };
         assert_eq!(effective_log_level(&args), LogLevel::Warning);
 
         // Test with no log level, with verbose
         let args = GlobalArgs {
-            config: String::from(constants::DEFAULT_CONFIG_FILE),
             verbose: 3,
-            log_level: None,
+            ..Default::default()
         };
         assert_eq!(effective_log_level(&args), LogLevel::Info); // Error + 3 = Info
 
         // Test with log level and verbose
         let args = GlobalArgs {
-            config: String::from(constants::DEFAULT_CONFIG_FILE),
             verbose: 2,
             log_level: Some(LogLevel::Warning),
+            ..Default::default()
         };
         assert_eq!(effective_log_level(&args), LogLevel::Info); // Warning + 2 = Info
 
         // Test capping at Debug
         let args = GlobalArgs {
-            config: String::from(constants::DEFAULT_CONFIG_FILE),
             verbose: 10,
             log_level: Some(LogLevel::Warning),
+            ..Default::default()
         };
         assert_eq!(effective_log_level(&args), LogLevel::Debug); // Capped at Debug
     }
@@ -0,0 +1,219 @@
+//! Picking the release asset built for a platform.
+//!
+//! Release archives are named `<app>-<tag>_<target triple>.tar.gz`, but releases produced
+//! by other tooling often use shorter names such as `<app>-darwin-arm64.tar.gz`. Assets are
+//! therefore matched in tiers: the exact name first, then any archive naming the same OS
+//! and architecture (by their common aliases), and on Linux finally a build for the other
+//! C library (musl or glibc).
+
+/// Operating systems, with the names release assets use for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Os {
+    Linux,
+    Macos,
+    Windows,
+}
+
+const OS_ALIASES: &[(Os, &[&str])] = &[
+    (Os::Linux, &["linux"]),
+    (Os::Macos, &["darwin", "macos", "apple", "osx"]),
+    (Os::Windows, &["windows", "win64", "win32", "win"]),
+];
+
+/// CPU architectures, with the names release assets use for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arch {
+    X86_64,
+    Aarch64,
+    I686,
+    Armv7,
+}
+
+const ARCH_ALIASES: &[(Arch, &[&str])] = &[
+    (Arch::X86_64, &["x86_64", "amd64", "x64"]),
+    (Arch::Aarch64, &["aarch64", "arm64"]),
+    (Arch::I686, &["i686", "i586", "i386"]),
+    (Arch::Armv7, &["armv7", "armhf"]),
+];
+
+/// C libraries Linux builds link against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Libc {
+    Gnu,
+    Musl,
+}
+
+const LIBC_ALIASES: &[(Libc, &[&str])] = &[(Libc::Gnu, &["gnu", "glibc"]), (Libc::Musl, &["musl"])];
+
+/// What a target triple or asset name says about the platform it is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    os: Option<Os>,
+    arch: Option<Arch>,
+    libc: Option<Libc>,
+}
+
+impl Platform {
+    /// Recognizes the platform in `name`, e.g. `aarch64-unknown-linux-musl`, `darwin-arm64`
+    /// or `tool-v1.2.0_windows_amd64.tar.gz`.
+    pub fn detect(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        Self {
+            os: find(&name, OS_ALIASES),
+            arch: find(&name, ARCH_ALIASES),
+            libc: find(&name, LIBC_ALIASES),
+        }
+    }
+}
+
+/// The first value with an alias that is a word of `name`.
+fn find<T: Copy>(name: &str, aliases: &[(T, &[&str])]) -> Option<T> {
+    aliases
+        .iter()
+        .find(|(_, names)| names.iter().any(|alias| has_word(name, alias)))
+        .map(|(value, _)| *value)
+}
+
+/// How closely an asset matched the requested target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Match {
+    /// The name built from the target triple
+    Exact,
+    /// Same OS and architecture, under other names
+    Alias,
+    /// Same OS and architecture, built for the other C library
+    OtherLibc,
+}
+
+/// Picks the asset among `names` for `target` (a triple such as `x86_64-unknown-linux-gnu`,
+/// or an OS and architecture such as `linux-amd64`), as the release `tag` of `app`.
+/// Returns its index and how it matched.
+pub fn select<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    app: &str,
+    tag: &str,
+    target: &str,
+) -> Option<(usize, Match)> {
+    let exact = format!("{}-{}_{}.tar.gz", app, tag, target);
+    let wanted = Platform::detect(target);
+    let app = app.to_ascii_lowercase();
+    names
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            if name == exact {
+                return Some((index, Match::Exact));
+            }
+            let lower = name.to_ascii_lowercase();
+            if !lower.starts_with(&app) || !is_archive(&lower) {
+                return None;
+            }
+            let found = Platform::detect(&lower);
+            if wanted.os.is_none() || wanted.arch.is_none() {
+                return None;
+            }
+            if (found.os, found.arch) != (wanted.os, wanted.arch) {
+                return None;
+            }
+            match (wanted.libc, found.libc) {
+                (Some(wanted), Some(found)) if wanted != found => Some((index, Match::OtherLibc)),
+                _ => Some((index, Match::Alias)),
+            }
+        })
+        // The closest match, and the first listed among equally close ones
+        .min_by_key(|&(index, tier)| (tier, index))
+}
+
+/// Archives `upgrade` can extract.
+fn is_archive(name: &str) -> bool {
+    name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+/// Whether `word` occurs in `name` delimited by `-`, `_`, `.` or the ends, so that `win`
+/// is not found in `darwin` nor `x64` in `x86_64`.
+fn has_word(name: &str, word: &str) -> bool {
+    let bytes = name.as_bytes();
+    let boundary = |i: Option<usize>| i.is_none_or(|i| matches!(bytes[i], b'-' | b'_' | b'.'));
+    name.match_indices(word).any(|(start, _)| {
+        let end = start + word.len();
+        boundary(start.checked_sub(1)) && boundary(Some(end).filter(|&end| end < bytes.len()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pick<'a>(names: &[&'a str], target: &str) -> Option<(&'a str, Match)> {
+        select(names.iter().copied(), "tool", "v1.2.0", target)
+            .map(|(index, tier)| (names[index], tier))
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Platform::detect("aarch64-unknown-linux-musl"),
+            Platform {
+                os: Some(Os::Linux),
+                arch: Some(Arch::Aarch64),
+                libc: Some(Libc::Musl),
+            }
+        );
+        assert_eq!(
+            Platform::detect("tool-Darwin-x64.tar.gz"),
+            Platform {
+                os: Some(Os::Macos),
+                arch: Some(Arch::X86_64),
+                libc: None,
+            }
+        );
+        let windows = Platform::detect("x86_64-pc-windows-msvc");
+        assert_eq!(
+            (windows.os, windows.arch),
+            (Some(Os::Windows), Some(Arch::X86_64))
+        );
+        assert_eq!(Platform::detect("tool-win.tar.gz").os, Some(Os::Windows));
+        assert_eq!(
+            Platform::detect("i686-unknown-linux-gnu").arch,
+            Some(Arch::I686)
+        );
+    }
+
+    #[test]
+    fn test_select_tiers() {
+        let target = "aarch64-unknown-linux-gnu";
+        let exact = "tool-v1.2.0_aarch64-unknown-linux-gnu.tar.gz";
+        let alias = "tool-linux-arm64.tar.gz";
+        let musl = "tool-v1.2.0_aarch64-unknown-linux-musl.tar.gz";
+        let others = [
+            "tool-v1.2.0_x86_64-unknown-linux-gnu.tar.gz",
+            "tool-darwin-arm64.tar.gz",
+            "tool-linux-arm64.tar.gz.sha256",
+            "other-linux-arm64.tar.gz",
+        ];
+
+        let all: Vec<_> = others.iter().copied().chain([musl, alias, exact]).collect();
+        assert_eq!(pick(&all, target), Some((exact, Match::Exact)));
+        assert_eq!(pick(&all[..6], target), Some((alias, Match::Alias)));
+        assert_eq!(pick(&all[..5], target), Some((musl, Match::OtherLibc)));
+        assert_eq!(pick(&others, target), None);
+    }
+
+    #[test]
+    fn test_select_short_target() {
+        let names = [
+            "tool-v1.2.0_x86_64-apple-darwin.tar.gz",
+            "tool-macos-arm64.tgz",
+        ];
+        assert_eq!(
+            pick(&names, "darwin-arm64"),
+            Some(("tool-macos-arm64.tgz", Match::Alias))
+        );
+        assert_eq!(
+            pick(&names, "macos-amd64"),
+            Some(("tool-v1.2.0_x86_64-apple-darwin.tar.gz", Match::Alias))
+        );
+        // Nothing to compare without both an OS and an architecture
+        assert_eq!(pick(&names, "macos"), None);
+    }
+}
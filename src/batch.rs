@@ -0,0 +1,395 @@
+//! Batch specs: named jobs with `depends_on` edges, checked for cycles when loaded and
+//! run with as many jobs at once as the dependencies (and a worker limit) allow.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{mpsc, Mutex};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::error::{Error, Result};
+use crate::jobs::Queue;
+
+/// A batch file (JSON or YAML).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchSpec {
+    pub jobs: Vec<Job>,
+    /// Indices of each job's dependencies
+    #[serde(skip)]
+    depends_on: Vec<Vec<usize>>,
+    /// Indices of the jobs depending on each job
+    #[serde(skip)]
+    dependents: Vec<Vec<usize>>,
+}
+
+/// One job: the arguments of a command, and the jobs that must succeed before it starts.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+}
+
+/// How a job ended.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "status", content = "error")]
+pub enum JobStatus {
+    Succeeded,
+    Failed(String),
+    /// Not started because a dependency failed
+    Skipped,
+}
+
+/// The result of one job of a batch.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobOutcome {
+    pub name: String,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    pub seconds: f64,
+}
+
+impl BatchSpec {
+    /// Loads the batch at `path`, by extension JSON or YAML, and checks its dependencies.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::File {
+            action: "read batch file",
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let invalid = |e: &dyn std::fmt::Display| {
+            Error::Other(format!("Invalid batch file {}: {}", path.display(), e))
+        };
+        let spec: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents).map_err(|e| invalid(&e))?,
+            _ => serde_yaml::from_str(&contents).map_err(|e| invalid(&e))?,
+        };
+        let spec = spec.link().map_err(|e| invalid(&e))?;
+        debug!(
+            "Loaded {} batch jobs from {}",
+            spec.jobs.len(),
+            path.display()
+        );
+        Ok(spec)
+    }
+
+    /// Resolves `depends_on` names to indices, rejecting duplicate names, unknown
+    /// dependencies and cycles.
+    fn link(mut self) -> Result<Self> {
+        let mut index = HashMap::new();
+        for (i, job) in self.jobs.iter().enumerate() {
+            if index.insert(job.name.as_str(), i).is_some() {
+                return Err(Error::Other(format!("duplicate job name '{}'", job.name)));
+            }
+        }
+        let mut depends_on = Vec::with_capacity(self.jobs.len());
+        let mut dependents = vec![Vec::new(); self.jobs.len()];
+        for (i, job) in self.jobs.iter().enumerate() {
+            let mut deps = Vec::new();
+            for name in &job.depends_on {
+                let &dep = index.get(name.as_str()).ok_or_else(|| {
+                    Error::Other(format!(
+                        "job '{}' depends on unknown job '{}'",
+                        job.name, name
+                    ))
+                })?;
+                if !deps.contains(&dep) {
+                    deps.push(dep);
+                    dependents[dep].push(i);
+                }
+            }
+            depends_on.push(deps);
+        }
+        self.depends_on = depends_on;
+        self.dependents = dependents;
+        if let Some(cycle) = self.find_cycle() {
+            let names: Vec<&str> = cycle.iter().map(|&i| self.jobs[i].name.as_str()).collect();
+            return Err(Error::Other(format!(
+                "dependency cycle: {}",
+                names.join(" -> ")
+            )));
+        }
+        Ok(self)
+    }
+
+    /// A dependency cycle, as the jobs along it with the first repeated at the end.
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            New,
+            OnPath,
+            Done,
+        }
+        fn visit(
+            spec: &BatchSpec,
+            job: usize,
+            marks: &mut [Mark],
+            path: &mut Vec<usize>,
+        ) -> Option<Vec<usize>> {
+            marks[job] = Mark::OnPath;
+            path.push(job);
+            for &dep in &spec.depends_on[job] {
+                match marks[dep] {
+                    Mark::OnPath => {
+                        let start = path.iter().position(|&j| j == dep).unwrap_or(0);
+                        let mut cycle = path[start..].to_vec();
+                        cycle.push(dep);
+                        return Some(cycle);
+                    }
+                    Mark::New => {
+                        if let Some(cycle) = visit(spec, dep, marks, path) {
+                            return Some(cycle);
+                        }
+                    }
+                    Mark::Done => {}
+                }
+            }
+            path.pop();
+            marks[job] = Mark::Done;
+            None
+        }
+
+        let mut marks = vec![Mark::New; self.jobs.len()];
+        (0..self.jobs.len()).find_map(|job| {
+            (marks[job] == Mark::New)
+                .then(|| visit(self, job, &mut marks, &mut Vec::new()))
+                .flatten()
+        })
+    }
+
+    /// Names of the jobs `job` depends on.
+    pub fn dependencies(&self, job: usize) -> Vec<&str> {
+        self.depends_on[job]
+            .iter()
+            .map(|&dep| self.jobs[dep].name.as_str())
+            .collect()
+    }
+
+    /// Jobs grouped into stages: each job is in the stage after its latest dependency, so
+    /// the jobs of a stage can run at the same time once the previous stages are done.
+    pub fn stages(&self) -> Vec<Vec<usize>> {
+        let mut stage = vec![None; self.jobs.len()];
+        fn depth(spec: &BatchSpec, job: usize, stage: &mut [Option<usize>]) -> usize {
+            if let Some(depth) = stage[job] {
+                return depth;
+            }
+            let depth = spec.depends_on[job]
+                .iter()
+                .map(|&dep| depth(spec, dep, stage) + 1)
+                .max()
+                .unwrap_or(0);
+            stage[job] = Some(depth);
+            depth
+        }
+        let mut stages: Vec<Vec<usize>> = Vec::new();
+        for job in 0..self.jobs.len() {
+            let depth = depth(self, job, &mut stage);
+            if stages.len() <= depth {
+                stages.resize(depth + 1, Vec::new());
+            }
+            stages[depth].push(job);
+        }
+        stages
+    }
+
+    /// Runs every job with `run`, up to `workers` at a time, each as soon as all of its
+    /// dependencies have succeeded. Jobs depending (directly or not) on a failed job are
+    /// skipped, as are those not started when cancellation was requested. Outcomes are in
+    /// the order of the spec.
+    pub fn execute<F>(&self, workers: usize, run: F) -> Vec<JobOutcome>
+    where
+        F: Fn(&Job) -> Result<()> + Sync,
+    {
+        let count = self.jobs.len();
+        let (sender, ready) = mpsc::channel();
+        let mut schedule = Schedule {
+            waiting: self.depends_on.iter().map(Vec::len).collect(),
+            statuses: vec![None; count],
+            finished: 0,
+            ready: Some(sender),
+        };
+        for job in (0..count).filter(|&job| schedule.waiting[job] == 0) {
+            schedule.push(job);
+        }
+        schedule.close_if_done();
+        let schedule = Mutex::new(schedule);
+
+        // The queue takes jobs as they become ready, waiting on the channel while those
+        // running finish, until the last job to finish closes it
+        Queue::new(workers.min(count)).run(
+            ready,
+            |job| {
+                let spec = &self.jobs[job];
+                info!("Starting batch job '{}'", spec.name);
+                let started = Instant::now();
+                let result = run(spec);
+                let seconds = started.elapsed().as_secs_f64();
+                let mut schedule = schedule.lock().unwrap_or_else(|e| e.into_inner());
+                self.finish(&mut schedule, job, result, seconds);
+                Ok(())
+            },
+            |_| {},
+        );
+        let statuses = schedule
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .statuses;
+
+        self.jobs
+            .iter()
+            .zip(statuses)
+            .map(|(job, status)| {
+                let (status, seconds) = status.unwrap_or((JobStatus::Skipped, 0.0));
+                JobOutcome {
+                    name: job.name.clone(),
+                    status,
+                    seconds,
+                }
+            })
+            .collect()
+    }
+
+    /// Records how `job` went, queueing the dependents it was the last to wait for when
+    /// it succeeded, or skipping every job depending on it when it failed.
+    fn finish(&self, schedule: &mut Schedule, job: usize, result: Result<()>, seconds: f64) {
+        schedule.finished += 1;
+        match result {
+            Ok(()) => {
+                info!("Batch job '{}' succeeded", self.jobs[job].name);
+                schedule.statuses[job] = Some((JobStatus::Succeeded, seconds));
+                for &dependent in &self.dependents[job] {
+                    schedule.waiting[dependent] -= 1;
+                    if schedule.waiting[dependent] == 0 && schedule.statuses[dependent].is_none() {
+                        schedule.push(dependent);
+                    }
+                }
+            }
+            Err(e) => {
+                info!("Batch job '{}' failed: {}", self.jobs[job].name, e);
+                schedule.statuses[job] = Some((JobStatus::Failed(e.to_string()), seconds));
+                let mut blocked = self.dependents[job].clone();
+                while let Some(dependent) = blocked.pop() {
+                    if schedule.statuses[dependent].is_none() {
+                        schedule.statuses[dependent] = Some((JobStatus::Skipped, 0.0));
+                        schedule.finished += 1;
+                        blocked.extend(&self.dependents[dependent]);
+                    }
+                }
+            }
+        }
+        schedule.close_if_done();
+    }
+}
+
+/// Progress of a batch run, shared by the jobs running.
+struct Schedule {
+    /// Number of unfinished dependencies of each job
+    waiting: Vec<usize>,
+    statuses: Vec<Option<(JobStatus, f64)>>,
+    /// Jobs that ran or were skipped
+    finished: usize,
+    /// Where jobs are sent once ready to run, until all have finished
+    ready: Option<mpsc::Sender<usize>>,
+}
+
+impl Schedule {
+    fn push(&self, job: usize) {
+        if let Some(ready) = &self.ready {
+            let _ = ready.send(job);
+        }
+    }
+
+    /// Closes the channel of ready jobs once every job has finished, ending the run.
+    fn close_if_done(&mut self) {
+        if self.finished == self.statuses.len() {
+            self.ready = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(jobs: &[(&str, &[&str])]) -> Result<BatchSpec> {
+        BatchSpec {
+            jobs: jobs
+                .iter()
+                .map(|(name, deps)| Job {
+                    name: name.to_string(),
+                    args: Vec::new(),
+                    depends_on: deps.iter().map(|dep| dep.to_string()).collect(),
+                })
+                .collect(),
+            depends_on: Vec::new(),
+            dependents: Vec::new(),
+        }
+        .link()
+    }
+
+    #[test]
+    fn test_invalid_dependencies() {
+        let error = spec(&[("a", &["c"]), ("b", &["a"]), ("c", &["b"])])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "dependency cycle: a -> c -> b -> a");
+        let error = spec(&[("a", &["a"])]).unwrap_err().to_string();
+        assert_eq!(error, "dependency cycle: a -> a");
+        let error = spec(&[("a", &["missing"])]).unwrap_err().to_string();
+        assert_eq!(error, "job 'a' depends on unknown job 'missing'");
+        let error = spec(&[("a", &[]), ("a", &[])]).unwrap_err().to_string();
+        assert_eq!(error, "duplicate job name 'a'");
+    }
+
+    #[test]
+    fn test_stages() {
+        let spec = spec(&[
+            ("report", &["clean", "stats"]),
+            ("clean", &[]),
+            ("stats", &["clean"]),
+            ("other", &[]),
+        ])
+        .unwrap();
+        assert_eq!(spec.stages(), vec![vec![1, 3], vec![2], vec![0]]);
+        assert_eq!(spec.dependencies(0), ["clean", "stats"]);
+    }
+
+    #[test]
+    fn test_execute_respects_dependencies() {
+        let spec = spec(&[("c", &["a", "b"]), ("a", &[]), ("b", &[]), ("d", &["c"])]).unwrap();
+        let order = Mutex::new(Vec::new());
+        let outcomes = spec.execute(2, |job| {
+            order.lock().unwrap().push(job.name.clone());
+            Ok(())
+        });
+        let order = order.into_inner().unwrap();
+        assert_eq!(&order[2..], ["c", "d"]);
+        assert!(outcomes.iter().all(|o| o.status == JobStatus::Succeeded));
+    }
+
+    #[test]
+    fn test_execute_skips_dependents_of_failures() {
+        let spec = spec(&[("a", &[]), ("b", &["a"]), ("c", &["b"]), ("d", &[])]).unwrap();
+        let outcomes = spec.execute(4, |job| match job.name.as_str() {
+            "a" => Err(Error::Other(String::from("boom"))),
+            _ => Ok(()),
+        });
+        let statuses: Vec<_> = outcomes.into_iter().map(|o| o.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                JobStatus::Failed(String::from("boom")),
+                JobStatus::Skipped,
+                JobStatus::Skipped,
+                JobStatus::Succeeded,
+            ]
+        );
+    }
+}
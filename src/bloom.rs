@@ -0,0 +1,169 @@
+//! Bloom filters for approximate membership with bounded memory.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Approximate set: never misses an inserted value, but may report a value that was not
+/// inserted with a configured probability.
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    bit_count: u64,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// A filter sized for `expected` values at false-positive rate `fp_rate` (0 < rate < 1).
+    pub fn with_rate(expected: usize, fp_rate: f64) -> Self {
+        let n = expected.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (-n * fp_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hashes = ((bit_count as f64 / n) * ln2).round().clamp(1.0, 32.0) as u32;
+        Self {
+            bits: vec![0; bit_count.div_ceil(64) as usize],
+            bit_count,
+            hashes,
+        }
+    }
+
+    /// Inserts `value`; returns `false` if it was (probably) present already.
+    pub fn insert<T: Hash + ?Sized>(&mut self, value: &T) -> bool {
+        let mut new = false;
+        for (word, mask) in self.probes(value) {
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                new = true;
+            }
+        }
+        new
+    }
+
+    /// Whether `value` was (probably) inserted.
+    pub fn contains<T: Hash + ?Sized>(&self, value: &T) -> bool {
+        self.probes(value)
+            .all(|(word, mask)| self.bits[word] & mask != 0)
+    }
+
+    /// Word index and bit mask of each of the `hashes` positions for `value`.
+    fn probes<T: Hash + ?Sized>(&self, value: &T) -> impl Iterator<Item = (usize, u64)> {
+        // Double hashing: k positions derived from two 64-bit hashes. DefaultHasher
+        // uses fixed keys, so results are reproducible between runs.
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let h1 = hasher.finish();
+        h1.hash(&mut hasher);
+        let h2 = hasher.finish() | 1;
+
+        let bit_count = self.bit_count;
+        (0..u64::from(self.hashes)).map(move |i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % bit_count;
+            ((bit / 64) as usize, 1u64 << (bit % 64))
+        })
+    }
+
+    /// Memory used by the bit array.
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+}
+
+/// A Bloom filter that grows when more values arrive than it was sized for, by adding
+/// filters twice as large at half the false-positive rate of the last, so that the rate
+/// of all of them together stays below the configured one.
+pub struct ScalableBloomFilter {
+    filters: Vec<BloomFilter>,
+    /// Values the last filter was sized for, and inserted into it so far
+    capacity: usize,
+    inserted: usize,
+    /// False-positive rate of the last filter
+    fp_rate: f64,
+}
+
+impl ScalableBloomFilter {
+    /// A filter first sized for `capacity` values, at false-positive rate `fp_rate`
+    /// (0 < rate < 1) however many are inserted.
+    pub fn with_rate(capacity: usize, fp_rate: f64) -> Self {
+        let capacity = capacity.max(1);
+        // The rates of the filters add up to fp_rate / 2 * (1 + 1/2 + 1/4 + ...) = fp_rate
+        let fp_rate = fp_rate / 2.0;
+        Self {
+            filters: vec![BloomFilter::with_rate(capacity, fp_rate)],
+            capacity,
+            inserted: 0,
+            fp_rate,
+        }
+    }
+
+    /// Inserts `value`; returns `false` if it was (probably) present already.
+    pub fn insert<T: Hash + ?Sized>(&mut self, value: &T) -> bool {
+        let (last, earlier) = self.filters.split_last_mut().expect("at least one filter");
+        if earlier.iter().any(|filter| filter.contains(value)) || !last.insert(value) {
+            return false;
+        }
+        self.inserted += 1;
+        if self.inserted == self.capacity {
+            self.capacity *= 2;
+            self.inserted = 0;
+            self.fp_rate /= 2.0;
+            self.filters
+                .push(BloomFilter::with_rate(self.capacity, self.fp_rate));
+        }
+        true
+    }
+
+    /// Memory used by the bit arrays.
+    pub fn size_bytes(&self) -> usize {
+        self.filters.iter().map(BloomFilter::size_bytes).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives() {
+        let mut filter = BloomFilter::with_rate(1000, 0.01);
+        for i in 0..1000u32 {
+            filter.insert(&i);
+        }
+        for i in 0..1000u32 {
+            assert!(filter.contains(&i), "{} missing", i);
+            assert!(!filter.insert(&i), "{} reported as new", i);
+        }
+    }
+
+    #[test]
+    fn test_false_positive_rate_near_target() {
+        let mut filter = BloomFilter::with_rate(50_000, 0.01);
+        for i in 0..50_000u32 {
+            filter.insert(&i);
+        }
+        let false_positives = (50_000..150_000u32).filter(|i| filter.contains(i)).count();
+        // Expected ~1,000 of 100,000
+        assert!(false_positives < 2_000, "{}", false_positives);
+    }
+
+    #[test]
+    fn test_sizing() {
+        // ~9.6 bits and 7 hashes per value at 1%
+        let filter = BloomFilter::with_rate(10_000, 0.01);
+        assert_eq!(filter.hashes, 7);
+        assert!((11_000..13_000).contains(&filter.size_bytes()));
+    }
+
+    #[test]
+    fn test_scalable_filter_grows() {
+        let mut filter = ScalableBloomFilter::with_rate(1_000, 0.01);
+        let first = filter.size_bytes();
+        for i in 0..50_000u32 {
+            filter.insert(&i);
+        }
+        assert!(filter.size_bytes() > first * 32);
+        for i in 0..50_000u32 {
+            assert!(!filter.insert(&i), "{} reported as new", i);
+        }
+        // Well past its first capacity, the rate holds
+        let false_positives = (50_000..150_000u32).filter(|i| !filter.insert(i)).count();
+        assert!(false_positives < 2_000, "{}", false_positives);
+    }
+}
@@ -0,0 +1,167 @@
+//! Content-addressed cache of results, kept in the user's cache directory.
+//!
+//! Entries are keyed on a digest of the input's bytes and of the options that shaped the
+//! result, so a changed input or option simply misses; nothing is ever invalidated.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::debug;
+
+use crate::digest::{self, HashAlgorithm};
+use crate::error::{Error, Result};
+use crate::identity;
+
+/// Results cached between runs.
+#[derive(Debug, Clone)]
+pub struct ResultCache {
+    dir: PathBuf,
+}
+
+/// What `clean` removed, or `usage` found.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+impl ResultCache {
+    /// The cache in the user's cache directory, or `None` if there is none.
+    pub fn open() -> Option<Self> {
+        directories::ProjectDirs::from("", "", identity::app_name()).map(|dirs| Self {
+            dir: dirs.cache_dir().join("results"),
+        })
+    }
+
+    /// Directory the results are stored in.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Key of the result of processing the file at `path` with `options`.
+    pub fn key(path: &Path, options: &impl Serialize) -> Result<String> {
+        let content = digest::digest_reader(File::open(path)?, HashAlgorithm::Blake3)?;
+        let key = format!("{}\n{}", content.hex, serde_json::to_string(options)?);
+        Ok(digest::digest_bytes(key.as_bytes(), HashAlgorithm::Blake3).hex)
+    }
+
+    /// The result stored under `key`; unreadable entries count as missing.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let path = self.entry(key);
+        let contents = fs::read(&path).ok()?;
+        match serde_json::from_slice(&contents) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                debug!("Ignoring unreadable cache entry {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Stores `value` under `key`, replacing any earlier entry atomically.
+    pub fn put<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let file_error = |action| {
+            move |source| Error::File {
+                action,
+                path: self.dir.clone(),
+                source,
+            }
+        };
+        fs::create_dir_all(&self.dir).map_err(file_error("create directory"))?;
+        let mut file =
+            tempfile::NamedTempFile::new_in(&self.dir).map_err(file_error("write to"))?;
+        file.write_all(&serde_json::to_vec(value)?)?;
+        file.persist(self.entry(key))
+            .map_err(|e| file_error("write to")(e.error))?;
+        Ok(())
+    }
+
+    /// Number and total size of the stored results.
+    pub fn usage(&self) -> Result<Usage> {
+        let mut usage = Usage::default();
+        for entry in self.entries()? {
+            usage.entries += 1;
+            usage.bytes += entry.metadata()?.len();
+        }
+        Ok(usage)
+    }
+
+    /// Removes every stored result, returning what was removed.
+    pub fn clean(&self) -> Result<Usage> {
+        let mut removed = Usage::default();
+        for entry in self.entries()? {
+            let bytes = entry.metadata()?.len();
+            fs::remove_file(entry.path()).map_err(|source| Error::File {
+                action: "remove",
+                path: entry.path(),
+                source,
+            })?;
+            removed.entries += 1;
+            removed.bytes += bytes;
+        }
+        Ok(removed)
+    }
+
+    fn entry(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// The entry files, none if the directory does not exist yet.
+    fn entries(&self) -> Result<Vec<fs::DirEntry>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(Error::File {
+                    action: "read directory",
+                    path: self.dir.clone(),
+                    source,
+                })
+            }
+        };
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.path().extension().is_some_and(|ext| ext == "json") {
+                files.push(entry);
+            }
+        }
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_clean() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResultCache {
+            dir: dir.path().join("results"),
+        };
+        let input = dir.path().join("input.txt");
+        fs::write(&input, "one two\n").unwrap();
+
+        assert_eq!(cache.usage().unwrap(), Usage::default());
+        let key = ResultCache::key(&input, &("options", 1)).unwrap();
+        assert_eq!(cache.get::<Vec<u32>>(&key), None);
+        cache.put(&key, &vec![1, 2, 3]).unwrap();
+        assert_eq!(cache.get::<Vec<u32>>(&key), Some(vec![1, 2, 3]));
+        // Entries of another shape are misses, not errors
+        assert_eq!(cache.get::<String>(&key), None);
+
+        // Other options or content give other keys
+        assert_ne!(ResultCache::key(&input, &("options", 2)).unwrap(), key);
+        fs::write(&input, "one two three\n").unwrap();
+        assert_ne!(ResultCache::key(&input, &("options", 1)).unwrap(), key);
+
+        let usage = cache.usage().unwrap();
+        assert_eq!(usage.entries, 1);
+        assert_eq!(cache.clean().unwrap(), usage);
+        assert_eq!(cache.get::<Vec<u32>>(&key), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Cooperative cancellation of long-running work.
+//!
+//! While work that checks for it runs (see [`cooperate`]), the first Ctrl-C only sets a
+//! flag that long analyses check between files and chunks, so they stop at the next safe
+//! point (with outputs and manifests consistent); a second Ctrl-C exits at once. Outside
+//! of such work, e.g. while downloading an upgrade or waiting for a lock, nothing would
+//! see the flag, so Ctrl-C exits at once. CPU-bound work runs through [`blocking`],
+//! which keeps the async runtime serving its other tasks (the signal listener,
+//! background update checks) while it runs.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use tracing::debug;
+
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::i18n::tr;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+/// Number of [`Cooperating`] guards alive
+static COOPERATING: AtomicUsize = AtomicUsize::new(0);
+
+/// Listens for Ctrl-C on the current runtime.
+pub fn install() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if COOPERATING.load(Ordering::SeqCst) == 0 || CANCELLED.swap(true, Ordering::SeqCst) {
+                std::process::exit(constants::EXIT_INTERRUPTED);
+            }
+            eprintln!("{}", tr!("cancel-requested"));
+        }
+        debug!("Cannot listen for Ctrl-C; it ends the process right away");
+    });
+}
+
+/// Marks work that checks for cancellation as running until the guard is dropped.
+pub fn cooperate() -> Cooperating {
+    COOPERATING.fetch_add(1, Ordering::SeqCst);
+    Cooperating(())
+}
+
+/// Running work that checks for cancellation, see [`cooperate`].
+#[must_use]
+pub struct Cooperating(());
+
+impl Drop for Cooperating {
+    fn drop(&mut self) {
+        COOPERATING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Whether cancellation was requested.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// Fails with [`Error::Cancelled`] once cancellation was requested; called between units
+/// of work.
+pub fn check() -> Result<()> {
+    if is_cancelled() {
+        return Err(Error::Cancelled);
+    }
+    Ok(())
+}
+
+/// Runs CPU-bound or blocking `work` without stalling the runtime's other tasks.
+pub fn blocking<T>(work: impl FnOnce() -> T) -> T {
+    tokio::task::block_in_place(work)
+}
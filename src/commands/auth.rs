@@ -0,0 +1,104 @@
+use clap::{Args as ClapArgs, Subcommand};
+use serde::Serialize;
+use std::io::{BufRead, IsTerminal};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::output::{print_json, OutputFormat};
+use crate::secrets::{self, Secret, Source};
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    pub action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Store a GitHub token in the OS keyring (read from stdin, or prompted for)
+    SetToken {
+        /// Remove the stored token instead
+        #[arg(long)]
+        delete: bool,
+    },
+
+    /// Show which secrets are available and where they come from
+    Status,
+}
+
+#[derive(Serialize)]
+struct SecretStatus {
+    name: &'static str,
+    set: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<Source>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    masked: Option<String>,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    match args.action {
+        Action::SetToken { delete: true } => {
+            if secrets::delete(Secret::GithubToken)? {
+                ctx.reporter
+                    .success("GitHub token removed from the OS keyring");
+            } else {
+                println!("No GitHub token stored in the OS keyring");
+            }
+            Ok(())
+        }
+        Action::SetToken { delete: false } => set_token(ctx),
+        Action::Status => status(ctx),
+    }
+}
+
+fn set_token(ctx: &Context) -> Result<()> {
+    let token = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password("GitHub token: ")?
+    } else {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        line
+    };
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(Error::Other(String::from("No token provided")));
+    }
+
+    secrets::set(Secret::GithubToken, token)?;
+    ctx.reporter.success(&format!(
+        "GitHub token stored in the OS keyring ({})",
+        secrets::mask(token)
+    ));
+    Ok(())
+}
+
+fn status(ctx: &Context) -> Result<()> {
+    let statuses: Vec<SecretStatus> = Secret::ALL
+        .iter()
+        .map(|&secret| {
+            let found = secrets::get(secret);
+            SecretStatus {
+                name: secret.name(),
+                set: found.is_some(),
+                masked: found.as_ref().map(|(value, _)| secrets::mask(value)),
+                source: found.map(|(_, source)| source),
+            }
+        })
+        .collect();
+
+    match ctx.format {
+        OutputFormat::Json => print_json(&statuses),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            for status in &statuses {
+                match (&status.source, &status.masked) {
+                    (Some(source), Some(masked)) => {
+                        println!("{}: {} (from {})", status.name, masked, source)
+                    }
+                    _ => println!("{}: not set", status.name),
+                }
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use clap::Args as ClapArgs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{debug, info};
+
+use crate::cancel;
+use crate::context::Context;
+use crate::encoding::{self, Encoding};
+use crate::error::{Error, Result};
+use crate::i18n::tr;
+use crate::output::{print_json, reportln, OutputFormat};
+use crate::pin;
+use crate::stats::{throughput_mb_per_sec, FileStats};
+use crate::strategy::{self, Strategy};
+use crate::transform::{Pipeline, SplitMix64};
+
+/// Words the synthetic corpus is made of.
+const VOCABULARY: &str = "the quick brown fox jumps over lazy dog lorem ipsum dolor sit amet \
+    consectetur adipiscing elit sed do eiusmod tempor incididunt ut labore et dolore magna \
+    aliqua 2024 error: warning: info: request_id=42 latency_ms=17 Größe naïve";
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    /// Measured iterations
+    #[arg(short = 'n', long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub iterations: u32,
+
+    /// Unmeasured iterations run first to warm caches
+    #[arg(long, default_value_t = 2)]
+    pub warmup: u32,
+
+    /// Size of the generated corpus in MB
+    #[arg(long, value_name = "MB", default_value_t = 16, value_parser = clap::value_parser!(u64).range(1..))]
+    pub size: u64,
+
+    /// Seed of the generated corpus
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// How the corpus is read
+    #[arg(long, value_enum, default_value_t = Strategy::Memory)]
+    pub strategy: Strategy,
+
+    /// Transform pipeline applied after the statistics, as with `run --transform`
+    #[arg(long, value_name = "PIPELINE", default_value = "uppercase")]
+    pub transform: String,
+
+    /// Write the results as JSON to this file, for comparing later versions against
+    #[arg(long, value_name = "FILE")]
+    pub save: Option<PathBuf>,
+
+    /// Compare the median throughput with results written by an earlier --save
+    #[arg(long, value_name = "FILE")]
+    pub compare: Option<PathBuf>,
+}
+
+/// Timings of the measured iterations, in seconds.
+#[derive(Debug, Serialize, Deserialize)]
+struct Timings {
+    min: f64,
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BenchReport {
+    version: String,
+    corpus_bytes: u64,
+    corpus_lines: usize,
+    iterations: u32,
+    warmup: u32,
+    strategy: String,
+    pipeline: String,
+    seconds: Timings,
+    /// Throughput of the median iteration
+    mb_per_sec: f64,
+    lines_per_sec: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    baseline: Option<Baseline>,
+}
+
+/// The results compared against (--compare).
+#[derive(Debug, Serialize, Deserialize)]
+struct Baseline {
+    version: String,
+    mb_per_sec: f64,
+    /// Change of the median throughput relative to the baseline
+    change_percent: f64,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    let pipeline = Pipeline::parse(&args.transform, &ctx.config.pipelines)?;
+    let baseline = args.compare.as_deref().map(load_report).transpose()?;
+
+    let dir = tempfile::tempdir()?;
+    let corpus = dir.path().join("corpus.txt");
+    let (corpus_bytes, corpus_lines) = generate_corpus(&corpus, args.size * 1_000_000, args.seed)?;
+    let strategy = match args.strategy {
+        Strategy::Auto => Strategy::Auto.resolve(corpus_bytes),
+        strategy => strategy,
+    };
+    info!(
+        "Benchmarking {} iteration(s) over {} bytes with the {} strategy",
+        args.iterations, corpus_bytes, strategy
+    );
+
+    let _cooperating = cancel::cooperate();
+    for _ in 0..args.warmup {
+        cancel::check()?;
+        iteration(&corpus, strategy, &pipeline)?;
+    }
+    let mut samples = Vec::with_capacity(args.iterations as usize);
+    for i in 0..args.iterations {
+        cancel::check()?;
+        let started = Instant::now();
+        iteration(&corpus, strategy, &pipeline)?;
+        let seconds = started.elapsed().as_secs_f64();
+        debug!("Iteration {} took {:.3}s", i + 1, seconds);
+        samples.push(seconds);
+    }
+
+    let seconds = summarize(&mut samples);
+    let mb_per_sec = throughput_mb_per_sec(corpus_bytes, seconds.p50);
+    let mut report = BenchReport {
+        version: pin::running_version().to_string(),
+        corpus_bytes,
+        corpus_lines,
+        iterations: args.iterations,
+        warmup: args.warmup,
+        strategy: strategy.to_string(),
+        pipeline: args.transform,
+        lines_per_sec: if seconds.p50 > 0.0 {
+            corpus_lines as f64 / seconds.p50
+        } else {
+            0.0
+        },
+        seconds,
+        mb_per_sec,
+        baseline: None,
+    };
+    report.baseline = baseline.map(|baseline| Baseline {
+        change_percent: if baseline.mb_per_sec > 0.0 {
+            (report.mb_per_sec / baseline.mb_per_sec - 1.0) * 100.0
+        } else {
+            0.0
+        },
+        version: baseline.version,
+        mb_per_sec: baseline.mb_per_sec,
+    });
+
+    if let Some(path) = &args.save {
+        fs::write(path, serde_json::to_string_pretty(&report)?).map_err(|source| Error::File {
+            action: "write",
+            path: path.clone(),
+            source,
+        })?;
+        info!("Benchmark results written to {}", path.display());
+    }
+
+    match ctx.format {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Text | OutputFormat::Markdown => print_report(&report),
+    }
+    Ok(())
+}
+
+/// Writes lines of random words from [`VOCABULARY`] (with some blank lines) to `path`
+/// until it holds at least `bytes` bytes, returning its size and number of lines.
+fn generate_corpus(path: &Path, bytes: u64, seed: u64) -> Result<(u64, usize)> {
+    let words: Vec<&str> = VOCABULARY.split_whitespace().collect();
+    let mut rng = SplitMix64::new(seed);
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    let (mut written, mut lines) = (0, 0);
+    let mut line = String::new();
+    while written < bytes {
+        line.clear();
+        if rng.below(10) != 0 {
+            for i in 0..1 + rng.below(14) {
+                if i > 0 {
+                    line.push(' ');
+                }
+                line.push_str(words[rng.below(words.len() as u64) as usize]);
+            }
+        }
+        line.push('\n');
+        writer.write_all(line.as_bytes())?;
+        written += line.len() as u64;
+        lines += 1;
+    }
+    writer.flush()?;
+    Ok((written, lines))
+}
+
+/// Processes the corpus once as `run` does: statistics, then the transform pipeline, with
+/// the output discarded.
+fn iteration(corpus: &Path, strategy: Strategy, pipeline: &Pipeline) -> Result<()> {
+    if strategy != Strategy::Memory {
+        let mut file = fs::File::open(corpus)?;
+        strategy::read_stats(&mut file, strategy, Encoding::Auto, None, false, false)?;
+        let mut file = io::BufReader::new(fs::File::open(corpus)?);
+        pipeline.apply_stream(&mut file, &mut io::sink())?;
+        return Ok(());
+    }
+    let raw = fs::read(corpus)?;
+    let content = encoding::decode(&raw, Encoding::Auto).text;
+    let stats = FileStats::from_content(&content, None);
+    let (transformed, _) = pipeline.apply(&content)?;
+    debug!("{} lines in, {} bytes out", stats.lines, transformed.len());
+    Ok(())
+}
+
+/// Sorts `samples` and computes their summary, with nearest-rank percentiles.
+fn summarize(samples: &mut [f64]) -> Timings {
+    samples.sort_by(f64::total_cmp);
+    let percentile = |p: f64| {
+        let rank = (p / 100.0 * samples.len() as f64).ceil() as usize;
+        samples[rank.clamp(1, samples.len()) - 1]
+    };
+    Timings {
+        min: samples[0],
+        mean: samples.iter().sum::<f64>() / samples.len() as f64,
+        p50: percentile(50.0),
+        p90: percentile(90.0),
+        p99: percentile(99.0),
+        max: samples[samples.len() - 1],
+    }
+}
+
+fn load_report(path: &Path) -> Result<BenchReport> {
+    let contents = fs::read_to_string(path).map_err(|source| Error::File {
+        action: "read",
+        path: path.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_str(&contents).map_err(|e| {
+        Error::Other(format!(
+            "{} is not a benchmark result written by --save: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+fn print_report(report: &BenchReport) {
+    reportln!(
+        "{}",
+        tr!(
+            "bench-corpus",
+            mb = format!("{:.1}", report.corpus_bytes as f64 / 1_000_000.0),
+            lines = report.corpus_lines,
+            iterations = report.iterations,
+            warmup = report.warmup,
+            strategy = report.strategy.as_str(),
+            pipeline = report.pipeline.as_str()
+        )
+    );
+    let Timings {
+        min,
+        mean,
+        p50,
+        p90,
+        p99,
+        max,
+    } = report.seconds;
+    reportln!("  {:<6} {:>10}", "", "ms");
+    for (name, seconds) in [
+        ("min", min),
+        ("mean", mean),
+        ("p50", p50),
+        ("p90", p90),
+        ("p99", p99),
+        ("max", max),
+    ] {
+        reportln!("  {:<6} {:>10.2}", name, seconds * 1000.0);
+    }
+    reportln!(
+        "{}",
+        tr!(
+            "bench-throughput",
+            mb_per_sec = format!("{:.1}", report.mb_per_sec),
+            lines_per_sec = format!("{:.0}", report.lines_per_sec)
+        )
+    );
+    if let Some(baseline) = &report.baseline {
+        reportln!(
+            "{}",
+            tr!(
+                "bench-baseline",
+                change = format!("{:+.1}", baseline.change_percent),
+                version = baseline.version.as_str(),
+                mb_per_sec = format!("{:.1}", baseline.mb_per_sec)
+            )
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let mut samples: Vec<f64> = (1..=10).rev().map(f64::from).collect();
+        let timings = summarize(&mut samples);
+        assert_eq!((timings.min, timings.max), (1.0, 10.0));
+        assert_eq!(timings.mean, 5.5);
+        assert_eq!((timings.p50, timings.p90, timings.p99), (5.0, 9.0, 10.0));
+
+        let timings = summarize(&mut [2.0]);
+        assert_eq!((timings.p50, timings.p99), (2.0, 2.0));
+    }
+
+    #[test]
+    fn test_generate_corpus_is_reproducible() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        let (bytes, lines) = generate_corpus(&a, 10_000, 7).unwrap();
+        assert_eq!(generate_corpus(&b, 10_000, 7).unwrap(), (bytes, lines));
+        let content = fs::read_to_string(&a).unwrap();
+        assert_eq!(content, fs::read_to_string(&b).unwrap());
+        assert!(bytes >= 10_000 && bytes as usize == content.len());
+        assert_eq!(content.lines().count(), lines);
+    }
+}
@@ -0,0 +1,59 @@
+use clap::{Args as ClapArgs, Subcommand};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::cache::{ResultCache, Usage};
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::i18n::tr;
+use crate::output::{print_json, reportln, OutputFormat};
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    pub action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Show where results are cached and how much space they take
+    Info,
+
+    /// Remove every cached result
+    Clean,
+}
+
+#[derive(Serialize)]
+struct CacheReport {
+    dir: PathBuf,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    let cache = ResultCache::open()
+        .ok_or_else(|| Error::Other(String::from("No cache directory for this user")))?;
+    let dir = cache.path().to_path_buf();
+    let usage = match args.action {
+        Action::Info => cache.usage()?,
+        Action::Clean => cache.clean()?,
+    };
+    if ctx.format == OutputFormat::Json {
+        return print_json(&CacheReport { dir, usage });
+    }
+    match args.action {
+        Action::Info => {
+            reportln!("{}", tr!("cache-dir", path = dir.display()));
+            reportln!(
+                "{}",
+                tr!("cache-usage", entries = usage.entries, bytes = usage.bytes)
+            );
+        }
+        Action::Clean => ctx.reporter.success(&tr!(
+            "cache-cleaned",
+            entries = usage.entries,
+            bytes = usage.bytes
+        )),
+    }
+    Ok(())
+}
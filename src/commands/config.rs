@@ -0,0 +1,315 @@
+use clap::{Args as ClapArgs, Subcommand};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info};
+
+use crate::config::{self, Config, ConfigScope};
+use crate::context::Context;
+use crate::env_vars;
+use crate::error::{Error, Result};
+use crate::i18n::tr;
+use crate::identity;
+use crate::output::{print_json, OutputFormat};
+
+/// Editor used when neither `VISUAL` nor `EDITOR` is set.
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    pub action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Show the effective settings after applying the config file, profile, environment
+    /// and flags
+    Show {
+        /// Also show where each value came from
+        #[arg(long)]
+        origin: bool,
+    },
+
+    /// Set a configuration value and save the configuration file (or the file of a scope)
+    Set {
+        /// Dotted key path, e.g. profiles.local.parallel_jobs
+        key: String,
+
+        /// New value, coerced to the field's type
+        value: String,
+
+        /// Parse the value as JSON (required for tables and lists)
+        #[arg(long)]
+        json: bool,
+
+        /// Write to the system, user or project configuration file instead of the one
+        /// given with --config; only this key is written to it
+        #[arg(long, value_enum)]
+        scope: Option<ConfigScope>,
+    },
+
+    /// Create the configuration file with every default, or an empty file for a scope
+    Init {
+        /// Create the system, user or project configuration file instead of the one given
+        /// with --config
+        #[arg(long, value_enum)]
+        scope: Option<ConfigScope>,
+
+        /// Overwrite the file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Open the configuration file in $VISUAL or $EDITOR, creating it from defaults if
+    /// missing, and validate it after saving
+    Edit,
+
+    /// Print the JSON Schema describing the configuration file
+    Schema {
+        /// Write the schema to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Serialize)]
+struct SetResult<'a> {
+    key: &'a str,
+    value: serde_json::Value,
+    file: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<ConfigScope>,
+}
+
+#[derive(Serialize)]
+struct InitResult {
+    file: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<ConfigScope>,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    match args.action {
+        Action::Show { origin } => show(origin, ctx),
+        Action::Set {
+            key,
+            value,
+            json,
+            scope,
+        } => set(ctx, &key, &value, json, scope),
+        Action::Init { scope, force } => init(ctx, scope, force),
+        Action::Edit => edit(ctx),
+        Action::Schema { output } => schema(output, ctx),
+    }
+}
+
+fn show(origin: bool, ctx: &Context) -> Result<()> {
+    let entries = ctx.settings.entries();
+    match ctx.format {
+        OutputFormat::Json if origin => print_json(&entries),
+        OutputFormat::Json => print_json(
+            &entries
+                .into_iter()
+                .map(|entry| (entry.key.to_string(), entry.value))
+                .collect::<serde_json::Map<_, _>>(),
+        ),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            let rows: Vec<Vec<String>> = entries
+                .iter()
+                .map(|entry| {
+                    let value = match &entry.value {
+                        serde_json::Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    let mut row = vec![entry.key.to_string(), value];
+                    if origin {
+                        row.push(entry.origin.to_string());
+                    }
+                    row
+                })
+                .collect();
+            let mut headers = vec![tr!("header-key"), tr!("header-value")];
+            if origin {
+                headers.push(tr!("header-origin"));
+            }
+            ctx.reporter.table("", &headers, &rows);
+            Ok(())
+        }
+    }
+}
+
+fn schema(output: Option<PathBuf>, ctx: &Context) -> Result<()> {
+    let schema = Config::json_schema();
+    match output {
+        Some(path) => {
+            std::fs::write(&path, serde_json::to_string_pretty(&schema)? + "\n")?;
+            ctx.reporter
+                .success(&format!("Schema written to: {}", path.display()));
+            Ok(())
+        }
+        None => print_json(&schema),
+    }
+}
+
+fn set(ctx: &Context, key: &str, raw: &str, json: bool, scope: Option<ConfigScope>) -> Result<()> {
+    let (value, file) = match scope {
+        Some(scope) => {
+            let path = scope.path(&std::env::current_dir()?)?;
+            let value = Config::set_in_layer(&path, key, raw, json)
+                .map_err(|e| elevation_needed(e, scope, &path))?;
+            (value, path)
+        }
+        None => {
+            // Edit the file as written, without environment overrides or inherited values
+            let path = PathBuf::from(&ctx.config_path);
+            (Config::set_in_config_file(&path, key, raw, json)?, path)
+        }
+    };
+
+    let result = SetResult {
+        key,
+        value,
+        file,
+        scope,
+    };
+    match ctx.format {
+        OutputFormat::Json => print_json(&result),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            ctx.reporter.success(&format!(
+                "Set {} = {} in {}",
+                result.key,
+                result.value,
+                result.file.display()
+            ));
+            if let Some(scope) = scope {
+                if let Some(origin) = overriding_origin(ctx, key, scope)? {
+                    ctx.reporter.notice(&format!(
+                        "{} is also set in {}, which takes precedence over the {} configuration",
+                        key,
+                        origin.display(),
+                        scope
+                    ));
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The file of higher precedence than `scope` that set `key` in the loaded configuration.
+fn overriding_origin(ctx: &Context, key: &str, scope: ConfigScope) -> Result<Option<PathBuf>> {
+    let dir = std::env::current_dir()?;
+    let mut lower = Vec::new();
+    for other in [ConfigScope::System, ConfigScope::User, ConfigScope::Project] {
+        lower.push(other.path(&dir)?);
+        if other == scope {
+            break;
+        }
+    }
+    Ok(ctx
+        .config
+        .origins
+        .get(key)
+        .filter(|origin| !lower.contains(origin))
+        .cloned())
+}
+
+/// Turns a refused write to the system configuration into an error suggesting elevated
+/// privileges.
+fn elevation_needed(error: Error, scope: ConfigScope, path: &Path) -> Error {
+    match error {
+        Error::Io(e)
+            if scope == ConfigScope::System && e.kind() == std::io::ErrorKind::PermissionDenied =>
+        {
+            Error::PermissionDenied {
+                path: path.to_path_buf(),
+            }
+        }
+        e => e,
+    }
+}
+
+fn init(ctx: &Context, scope: Option<ConfigScope>, force: bool) -> Result<()> {
+    let file = match scope {
+        Some(scope) => scope.path(&std::env::current_dir()?)?,
+        None => PathBuf::from(&ctx.config_path),
+    };
+    if file.exists() && !force {
+        return Err(Error::Other(format!(
+            "{} already exists; pass --force to overwrite it",
+            file.display()
+        )));
+    }
+    match scope {
+        // Layers start empty so that they override nothing until values are set
+        Some(scope) => config::write_value(&file, &serde_json::json!({}))
+            .map_err(|e| elevation_needed(e, scope, &file))?,
+        None => Config::default().save(&ctx.config_path)?,
+    }
+
+    let result = InitResult { file, scope };
+    match ctx.format {
+        OutputFormat::Json => print_json(&result),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            ctx.reporter.success(&format!(
+                "Created configuration file {}",
+                result.file.display()
+            ));
+            Ok(())
+        }
+    }
+}
+
+fn edit(ctx: &Context) -> Result<()> {
+    let path = Path::new(&ctx.config_path);
+    if !path.exists() {
+        info!("Creating {} from defaults", path.display());
+        Config::default().save(&ctx.config_path)?;
+    }
+    let before = std::fs::read(path)?;
+
+    let editor = std::env::var(env_vars::VISUAL)
+        .or_else(|_| std::env::var(env_vars::EDITOR))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+    // Editors are often configured with arguments, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_EDITOR);
+    debug!("Opening {} with {}", path.display(), editor);
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| Error::Other(format!("Failed to start editor '{}': {}", editor, e)))?;
+    if !status.success() {
+        return Err(Error::Other(format!(
+            "Editor '{}' exited with {}; configuration not validated",
+            editor, status
+        )));
+    }
+
+    if std::fs::read(path)? == before {
+        ctx.reporter
+            .success(&format!("No changes made to {}", path.display()));
+        return Ok(());
+    }
+
+    // Validate the file exactly as a later command would load it
+    Config::load(&ctx.config_path, ctx.strict_config).map_err(|e| {
+        Error::Other(format!(
+            "{} is invalid after editing (run `{} config edit` again to fix it): {}",
+            path.display(),
+            identity::app_name(),
+            e
+        ))
+    })?;
+    ctx.reporter
+        .success(&format!("Configuration saved: {}", path.display()));
+    Ok(())
+}
@@ -0,0 +1,346 @@
+use clap::Args as ClapArgs;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, error, info, warn};
+use walkdir::WalkDir;
+
+use super::run;
+use crate::cancel;
+use crate::config;
+use crate::context::Context;
+use crate::digest::{self, HashAlgorithm};
+use crate::error::{Error, Result};
+use crate::glob::PathFilter;
+use crate::i18n::tr;
+use crate::identity;
+use crate::lock;
+use crate::reload::{self, Reloaded};
+use crate::schedule::{self, Schedule};
+use crate::state::StateFile;
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    /// Process the files that are new or changed since the last pass once, then exit
+    /// (without waiting for them to stay the same or for the schedule)
+    #[arg(long)]
+    pub once: bool,
+
+    /// PID file (default: the configuration's `daemon.pid_file`, else `daemon.pid` in the
+    /// runtime or cache directory)
+    #[arg(long, value_name = "FILE")]
+    pub pid_file: Option<PathBuf>,
+}
+
+/// The `daemon` section of the configuration, checked.
+struct Plan {
+    watch: Vec<PathBuf>,
+    run_args: Vec<String>,
+    /// Files picked up, from the --include and --exclude of `run_args`
+    filter: PathFilter,
+    poll: Duration,
+    schedule: Option<Schedule>,
+}
+
+impl Plan {
+    fn new(daemon: &config::Daemon) -> Result<Self> {
+        if daemon.watch.is_empty() {
+            return Err(Error::Other(String::from(
+                "Nothing to watch: list directories under daemon.watch in the configuration",
+            )));
+        }
+        for dir in &daemon.watch {
+            if !dir.is_dir() {
+                return Err(Error::Other(format!(
+                    "daemon.watch: {} is not a directory",
+                    dir.display()
+                )));
+            }
+        }
+        // Mistakes in the arguments show now rather than with the first file
+        let args = run::Args::parse_from(&run_args(&daemon.run_args, Path::new(".")))
+            .map_err(|e| Error::Other(format!("Invalid daemon.run_args: {}", e)))?;
+        // clap already rejects --batch and --text next to --input
+        if args.files_from_git.is_some() {
+            return Err(Error::Other(String::from(
+                "daemon.run_args cannot use --files-from-git",
+            )));
+        }
+        let schedule = daemon
+            .schedule
+            .as_deref()
+            .map(Schedule::parse)
+            .transpose()?;
+        if schedule
+            .as_ref()
+            .is_some_and(|schedule| schedule.next_after(SystemTime::now()).is_none())
+        {
+            return Err(Error::Other(String::from(
+                "daemon.schedule never comes due",
+            )));
+        }
+        Ok(Self {
+            watch: daemon.watch.clone(),
+            run_args: daemon.run_args.clone(),
+            filter: PathFilter::new(&args.include, &args.exclude, args.glob_ignore_case)?,
+            poll: Duration::from_secs(daemon.poll_secs),
+            schedule,
+        })
+    }
+
+    /// When files are processed next, or `None` for as soon as they are complete.
+    fn next_due(&self) -> Option<SystemTime> {
+        let due = self.schedule.as_ref()?.next_after(SystemTime::now())?;
+        info!("Processing files next at {}", schedule::format_utc(due));
+        Some(due)
+    }
+}
+
+/// Files waiting to be processed, by the size and modification time they were last seen
+/// with.
+type Pending = HashMap<PathBuf, (u64, SystemTime)>;
+
+/// What stops the daemon or makes it reload.
+enum Event {
+    Reload,
+    Stop,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    let mut ctx = ctx.clone();
+    let mut plan = Plan::new(&ctx.config.daemon)?;
+    let pid_path = args
+        .pid_file
+        .clone()
+        .or_else(|| ctx.config.daemon.pid_file.clone())
+        .or_else(default_pid_file)
+        .ok_or_else(|| {
+            Error::Other(String::from(
+                "No directory for the PID file; set --pid-file",
+            ))
+        })?;
+    let _pid_file = lock::pid_file(&pid_path, ctx.lock_policy)?;
+    let _cooperating = cancel::cooperate();
+
+    let mut pending = Pending::new();
+    if args.once {
+        let processed = sweep(&plan, &ctx, &mut pending, false, true).await?;
+        ctx.reporter
+            .success(&tr!("daemon-processed", count = processed));
+        return Ok(());
+    }
+
+    let mut signals = Signals::new()?;
+    info!(
+        "Watching {} director(ies) every {} s (pid {})",
+        plan.watch.len(),
+        plan.poll.as_secs(),
+        std::process::id()
+    );
+    let mut due = plan.next_due();
+    loop {
+        let process = due.is_none_or(|due| SystemTime::now() >= due);
+        sweep(&plan, &ctx, &mut pending, true, process).await?;
+        if process && due.is_some() {
+            due = plan.next_due();
+        }
+
+        let event = tokio::select! {
+            _ = tokio::time::sleep(plan.poll) => None,
+            event = signals.next() => Some(event),
+        };
+        cancel::check()?;
+        match event {
+            Some(Event::Reload) => {
+                reload(&mut ctx, &mut plan);
+                due = plan.next_due();
+            }
+            Some(Event::Stop) => {
+                info!("Stopping");
+                return Ok(());
+            }
+            None => {}
+        }
+    }
+}
+
+/// Loads the configuration again, keeping the current one if the new one is invalid.
+fn reload(ctx: &mut Context, plan: &mut Plan) {
+    info!("Reloading the configuration");
+    let reloaded = Reloaded::load(ctx)
+        .and_then(|reloaded| Ok((Plan::new(&reloaded.config.daemon)?, reloaded)));
+    match reloaded {
+        Ok((new_plan, reloaded)) => {
+            *plan = new_plan;
+            reloaded.apply(ctx);
+        }
+        Err(e) => error!("Keeping the current configuration: {}", e),
+    }
+}
+
+/// Looks for new and changed files in the watched directories and, with `process`,
+/// processes those that are complete: unchanged since the previous sweep, unless
+/// `wait` is false. Returns the number of files processed.
+async fn sweep(
+    plan: &Plan,
+    ctx: &Context,
+    pending: &mut Pending,
+    wait: bool,
+    process: bool,
+) -> Result<usize> {
+    let mut processed = 0;
+    let mut seen = HashSet::new();
+    for dir in &plan.watch {
+        let mut state = StateFile::<()>::open(&state_key(dir, plan)).ok_or_else(|| {
+            Error::Other(String::from(
+                "The daemon needs a cache directory for its state",
+            ))
+        })?;
+        let mut recorded = false;
+        for file in scan(dir, &plan.filter) {
+            seen.insert(file.clone());
+            // Files can go away at any time; they are looked at again next time
+            let looked = state.unchanged(&file).and_then(|unchanged| {
+                let metadata = fs::metadata(&file)?;
+                Ok((unchanged, (metadata.len(), metadata.modified()?)))
+            });
+            let (unchanged, current) = match looked {
+                Ok(looked) => looked,
+                Err(e) => {
+                    debug!("Skipping {} for now: {}", file.display(), e);
+                    continue;
+                }
+            };
+            if unchanged.is_some() {
+                pending.remove(&file);
+                continue;
+            }
+            let complete = !wait || pending.get(&file) == Some(&current);
+            if !complete || !process {
+                pending.insert(file, current);
+                continue;
+            }
+            pending.remove(&file);
+
+            info!("Processing {}", file.display());
+            if let Err(e) = process_file(plan, ctx, &file).await {
+                if cancel::is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+                // A file that failed is tried again once it changes
+                error!("Processing {} failed: {}", file.display(), e);
+            }
+            processed += 1;
+            match state.record(&file, ()) {
+                Ok(()) => recorded = true,
+                Err(e) => warn!("Could not record {}: {}", file.display(), e),
+            }
+        }
+        if recorded {
+            if let Err(e) = state.save() {
+                warn!("Could not save the daemon state: {}", e);
+            }
+        }
+    }
+    pending.retain(|file, _| seen.contains(file));
+    Ok(processed)
+}
+
+async fn process_file(plan: &Plan, ctx: &Context, file: &Path) -> Result<()> {
+    let args = run::Args::parse_from(&run_args(&plan.run_args, file)).map_err(Error::Other)?;
+    run::execute(args, ctx).await
+}
+
+/// The configured `run` arguments for `input`.
+fn run_args(configured: &[String], input: &Path) -> Vec<String> {
+    let mut args = configured.to_vec();
+    args.push(String::from("--input"));
+    args.push(input.to_string_lossy().into_owned());
+    args
+}
+
+/// Files below `dir` allowed by `filter` (relative to `dir`) in a stable order, leaving
+/// out hidden files and directories.
+fn scan(dir: &Path, filter: &PathFilter) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Cannot scan {}: {}", dir.display(), e);
+                None
+            }
+        })
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| filter.allows(entry.path().strip_prefix(dir).unwrap_or(entry.path())))
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Name of the state of the watched directory `dir`, which also depends on the arguments
+/// files are processed with.
+fn state_key(dir: &Path, plan: &Plan) -> String {
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let key = format!("daemon\n{}\n{}", dir.display(), plan.run_args.join("\0"));
+    digest::digest_bytes(key.as_bytes(), HashAlgorithm::Blake3).hex
+}
+
+fn default_pid_file() -> Option<PathBuf> {
+    let dirs = directories::ProjectDirs::from("", "", identity::app_name())?;
+    Some(
+        dirs.runtime_dir()
+            .unwrap_or(dirs.cache_dir())
+            .join("daemon.pid"),
+    )
+}
+
+/// Reload requests, and SIGTERM (stop) on Unix.
+#[cfg(unix)]
+struct Signals {
+    reload: reload::Requests,
+    terminate: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self {
+            reload: reload::Requests::new()?,
+            terminate: signal(SignalKind::terminate())?,
+        })
+    }
+
+    async fn next(&mut self) -> Event {
+        tokio::select! {
+            _ = self.reload.recv() => Event::Reload,
+            _ = self.terminate.recv() => Event::Stop,
+        }
+    }
+}
+
+/// Reload requests only: the daemon stops with Ctrl-C.
+#[cfg(not(unix))]
+struct Signals {
+    reload: reload::Requests,
+}
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            reload: reload::Requests::new()?,
+        })
+    }
+
+    async fn next(&mut self) -> Event {
+        self.reload.recv().await;
+        Event::Reload
+    }
+}
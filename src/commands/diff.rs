@@ -0,0 +1,229 @@
+use crate::compression::{self, Compression};
+use crate::context::Context;
+use crate::encoding::{self, Encoding};
+use crate::error::{Error, Result};
+use crate::output::{paint, print_json, OutputFormat, Style};
+use clap::Args as ClapArgs;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Longest `+`/`-` bar drawn by `--stat`.
+const STAT_BAR_WIDTH: usize = 50;
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    /// Original file
+    pub old: PathBuf,
+
+    /// Modified file
+    pub new: PathBuf,
+
+    /// Number of unchanged lines shown around each change
+    #[arg(short = 'U', long, value_name = "LINES", default_value_t = 3)]
+    pub context: usize,
+
+    /// Show only a summary of inserted and deleted lines
+    #[arg(long)]
+    pub stat: bool,
+}
+
+/// Result of comparing two files.
+#[derive(Debug, Serialize)]
+struct DiffReport {
+    old: PathBuf,
+    new: PathBuf,
+    identical: bool,
+    insertions: usize,
+    deletions: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hunks: Option<Vec<Hunk>>,
+}
+
+/// A contiguous group of changes with surrounding context.
+#[derive(Debug, Serialize)]
+struct Hunk {
+    header: String,
+    old_start: usize,
+    old_lines: usize,
+    new_start: usize,
+    new_lines: usize,
+    lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Serialize)]
+struct HunkLine {
+    /// One of "+", "-" or " "
+    tag: &'static str,
+    text: String,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    info!(
+        "Comparing {} and {}",
+        args.old.display(),
+        args.new.display()
+    );
+
+    let old = read_text(&args.old)?;
+    let new = read_text(&args.new)?;
+    let diff = TextDiff::from_lines(&old, &new);
+
+    let (mut insertions, mut deletions) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => insertions += 1,
+            ChangeTag::Delete => deletions += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    debug!("{} insertions, {} deletions", insertions, deletions);
+
+    let hunks = (!args.stat).then(|| {
+        diff.unified_diff()
+            .context_radius(args.context)
+            .iter_hunks()
+            .map(|hunk| {
+                let ops = hunk.ops();
+                let (first, last) = (ops[0], ops[ops.len() - 1]);
+                let old_range = first.old_range().start..last.old_range().end;
+                let new_range = first.new_range().start..last.new_range().end;
+                Hunk {
+                    header: hunk.header().to_string(),
+                    old_start: old_range.start + 1,
+                    old_lines: old_range.len(),
+                    new_start: new_range.start + 1,
+                    new_lines: new_range.len(),
+                    lines: hunk
+                        .iter_changes()
+                        .map(|change| HunkLine {
+                            tag: match change.tag() {
+                                ChangeTag::Insert => "+",
+                                ChangeTag::Delete => "-",
+                                ChangeTag::Equal => " ",
+                            },
+                            text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+                        })
+                        .collect(),
+                }
+            })
+            .collect()
+    });
+
+    let report = DiffReport {
+        old: args.old,
+        new: args.new,
+        identical: insertions == 0 && deletions == 0,
+        insertions,
+        deletions,
+        hunks,
+    };
+
+    match ctx.format {
+        OutputFormat::Json => print_json(&report),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            if args.stat {
+                print_stat(&report, ctx);
+            } else {
+                print_unified(&report, ctx.color);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Reads a (possibly compressed) file as text.
+fn read_text(path: &Path) -> Result<String> {
+    let file = fs::File::open(path).map_err(|source| Error::File {
+        action: "open",
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut raw = Vec::new();
+    compression::decoder(file, Compression::from_path(path))?.read_to_end(&mut raw)?;
+
+    if encoding::is_binary(&raw, Encoding::Auto) {
+        return Err(Error::Other(format!(
+            "Cannot diff binary file: {}",
+            path.display()
+        )));
+    }
+    Ok(encoding::decode(&raw, Encoding::Auto).text)
+}
+
+fn print_unified(report: &DiffReport, color: bool) {
+    let Some(hunks) = report.hunks.as_ref().filter(|hunks| !hunks.is_empty()) else {
+        return;
+    };
+
+    println!(
+        "{}",
+        paint(&format!("--- {}", report.old.display()), Style::Bold, color)
+    );
+    println!(
+        "{}",
+        paint(&format!("+++ {}", report.new.display()), Style::Bold, color)
+    );
+    for hunk in hunks {
+        println!("{}", paint(&hunk.header, Style::Cyan, color));
+        for line in &hunk.lines {
+            let text = format!("{}{}", line.tag, line.text);
+            match line.tag {
+                "+" => println!("{}", paint(&text, Style::Green, color)),
+                "-" => println!("{}", paint(&text, Style::Red, color)),
+                _ => println!("{}", text),
+            }
+        }
+    }
+}
+
+fn print_stat(report: &DiffReport, ctx: &Context) {
+    let color = ctx.color;
+    let changed = report.insertions + report.deletions;
+    if changed > 0 && ctx.reporter.is_accessible() {
+        println!(
+            " {} => {}: {} line{} changed",
+            report.old.display(),
+            report.new.display(),
+            changed,
+            plural(changed)
+        );
+    } else if changed > 0 {
+        // Scale the bar down proportionally when there are many changes
+        let scale = |count: usize| {
+            if changed <= STAT_BAR_WIDTH {
+                count
+            } else {
+                (count * STAT_BAR_WIDTH).div_ceil(changed)
+            }
+        };
+        println!(
+            " {} => {} | {} {}{}",
+            report.old.display(),
+            report.new.display(),
+            changed,
+            paint(&"+".repeat(scale(report.insertions)), Style::Green, color),
+            paint(&"-".repeat(scale(report.deletions)), Style::Red, color)
+        );
+    }
+    println!(
+        " {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+        usize::from(changed > 0),
+        if changed > 0 { "" } else { "s" },
+        report.insertions,
+        plural(report.insertions),
+        report.deletions,
+        plural(report.deletions)
+    );
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
@@ -0,0 +1,169 @@
+use clap::Args as ClapArgs;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+use crate::context::Context;
+use crate::digest::{self, FileDigest, HashAlgorithm};
+use crate::error::{Error, Result};
+use crate::i18n::tr;
+use crate::output::{print_json, reportln, OutputFormat};
+
+/// File name that reads standard input, as with `sha256sum`.
+const STDIN: &str = "-";
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    /// Files to hash ("-" for stdin)
+    #[arg(required_unless_present = "check", conflicts_with = "check")]
+    pub files: Vec<PathBuf>,
+
+    /// Digest algorithm
+    #[arg(short, long, value_enum, default_value_t = HashAlgorithm::Sha256)]
+    pub algorithm: HashAlgorithm,
+
+    /// Verify the files listed in a `<digest>  <file>` checksum file ("-" for stdin)
+    #[arg(short, long, value_name = "SUMFILE")]
+    pub check: Option<PathBuf>,
+}
+
+impl Args {
+    /// The files hashed, or the checksum file verified.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        self.check
+            .iter()
+            .chain(&self.files)
+            .filter(|path| path.as_os_str() != STDIN)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HashEntry {
+    path: PathBuf,
+    #[serde(flatten)]
+    digest: FileDigest,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Ok,
+    Failed,
+    /// The file could not be read
+    Missing,
+}
+
+#[derive(Debug, Serialize)]
+struct CheckEntry {
+    path: PathBuf,
+    status: CheckStatus,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    match &args.check {
+        Some(sumfile) => check(sumfile, args.algorithm, ctx),
+        None => hash(&args.files, args.algorithm, ctx),
+    }
+}
+
+fn hash(files: &[PathBuf], algorithm: HashAlgorithm, ctx: &Context) -> Result<()> {
+    let mut entries = Vec::with_capacity(files.len());
+    for path in files {
+        info!("Hashing {} with {}", path.display(), algorithm);
+        entries.push(HashEntry {
+            path: path.clone(),
+            digest: hash_file(path, algorithm).map_err(|source| Error::File {
+                action: "hash",
+                path: path.clone(),
+                source,
+            })?,
+        });
+    }
+    if ctx.format == OutputFormat::Json {
+        return print_json(&entries);
+    }
+    for entry in &entries {
+        reportln!("{}  {}", entry.digest.hex, entry.path.display());
+    }
+    Ok(())
+}
+
+fn check(sumfile: &Path, algorithm: HashAlgorithm, ctx: &Context) -> Result<()> {
+    let contents = if sumfile.as_os_str() == STDIN {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(sumfile).map_err(|source| Error::File {
+            action: "read checksums file",
+            path: sumfile.to_path_buf(),
+            source,
+        })?
+    };
+
+    let mut entries = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (expected, name) = digest::parse_checksum_line(line).ok_or_else(|| {
+            Error::Other(format!(
+                "{}:{}: expected '<{}>  <file>'",
+                sumfile.display(),
+                number + 1,
+                algorithm
+            ))
+        })?;
+        let path = PathBuf::from(name);
+        let status = match hash_file(&path, algorithm) {
+            Ok(actual) if actual.hex.eq_ignore_ascii_case(expected) => CheckStatus::Ok,
+            Ok(_) => CheckStatus::Failed,
+            Err(e) => {
+                debug!("Cannot read {}: {}", path.display(), e);
+                CheckStatus::Missing
+            }
+        };
+        entries.push(CheckEntry { path, status });
+    }
+
+    if ctx.format == OutputFormat::Json {
+        print_json(&entries)?;
+    } else {
+        for entry in &entries {
+            let status = match entry.status {
+                CheckStatus::Ok => tr!("hash-ok"),
+                CheckStatus::Failed => tr!("hash-failed"),
+                CheckStatus::Missing => tr!("hash-missing"),
+            };
+            reportln!("{}: {}", entry.path.display(), status);
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(Error::Other(format!(
+            "{}: no checksums found",
+            sumfile.display()
+        )));
+    }
+    let failed = entries
+        .iter()
+        .filter(|entry| entry.status != CheckStatus::Ok)
+        .count();
+    if failed > 0 {
+        return Err(Error::Other(format!(
+            "{} of {} file(s) failed verification",
+            failed,
+            entries.len()
+        )));
+    }
+    Ok(())
+}
+
+fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<FileDigest> {
+    if path.as_os_str() == STDIN {
+        return digest::digest_reader(io::stdin().lock(), algorithm);
+    }
+    digest::digest_reader(File::open(path)?, algorithm)
+}
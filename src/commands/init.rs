@@ -0,0 +1,203 @@
+use clap::{Args as ClapArgs, CommandFactory};
+use clap_complete::Shell;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::i18n::tr;
+use crate::identity;
+use crate::output::{print_json, reportln, OutputFormat};
+
+/// Directory sample inputs are written to.
+const SAMPLES_DIR: &str = "samples";
+
+/// Sample inputs, by file name.
+const SAMPLES: &[(&str, &str)] = &[
+    (
+        "hello.txt",
+        "Hello, world!\nThis is a sample input file.\n\nTry: run --input samples/hello.txt\n",
+    ),
+    (
+        "table.csv",
+        "name,language,stars\nripgrep,rust,45000\nfd,rust,32000\njq,c,29000\n",
+    ),
+];
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    /// Install completions for this shell (defaults to the shell in $SHELL when asked
+    /// interactively)
+    #[arg(long, value_enum, value_name = "SHELL")]
+    pub completions: Option<Shell>,
+
+    /// Write sample input files to ./samples
+    #[arg(long)]
+    pub samples: bool,
+
+    /// Overwrite an existing configuration file and sample files
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// What `init` created.
+#[derive(Serialize, Debug, Default)]
+struct InitReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completions: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    samples: Vec<PathBuf>,
+    /// Files left alone because they already existed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    kept: Vec<PathBuf>,
+}
+
+pub async fn execute(mut args: Args, ctx: &Context) -> Result<()> {
+    // Without options, ask about each optional step when there is someone to ask
+    if args.completions.is_none() && !args.samples && ctx.prompter.is_interactive() {
+        if let Some(shell) = Shell::from_env() {
+            let path = completions_path(shell)?;
+            if ctx.prompter.confirm(&format!(
+                "Install {} completions to {}?",
+                shell,
+                path.display()
+            ))? {
+                args.completions = Some(shell);
+            }
+        }
+        args.samples = ctx
+            .prompter
+            .confirm(&format!("Create sample input files in {}/?", SAMPLES_DIR))?;
+    }
+
+    let mut report = InitReport::default();
+
+    let config_path = PathBuf::from(&ctx.config_path);
+    if config_path.exists() && !args.force {
+        info!("Keeping existing {}", config_path.display());
+        report.kept.push(config_path);
+    } else {
+        Config::default().save(&ctx.config_path)?;
+        report.config = Some(config_path);
+    }
+
+    if let Some(shell) = args.completions {
+        report.completions = Some(install_completions(shell)?);
+    }
+
+    if args.samples {
+        for (name, contents) in SAMPLES {
+            let path = Path::new(SAMPLES_DIR).join(name);
+            if path.exists() && !args.force {
+                report.kept.push(path);
+                continue;
+            }
+            write_file(&path, contents)?;
+            report.samples.push(path);
+        }
+    }
+
+    match ctx.format {
+        OutputFormat::Json => print_json(&report),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            print_report(&report, args.completions, ctx);
+            Ok(())
+        }
+    }
+}
+
+fn print_report(report: &InitReport, shell: Option<Shell>, ctx: &Context) {
+    if let Some(path) = &report.config {
+        ctx.reporter
+            .success(&tr!("init-created-config", path = path.display()));
+    }
+    if let Some(path) = &report.completions {
+        ctx.reporter
+            .success(&tr!("init-installed-completions", path = path.display()));
+        if shell == Some(Shell::Zsh) {
+            ctx.reporter.notice(&tr!(
+                "init-zsh-fpath",
+                dir = path.parent().unwrap_or(Path::new("")).display()
+            ));
+        }
+    }
+    for path in &report.samples {
+        ctx.reporter
+            .success(&tr!("init-created-sample", path = path.display()));
+    }
+    for path in &report.kept {
+        ctx.reporter
+            .notice(&tr!("init-kept", path = path.display()));
+    }
+    let input = match report.samples.first() {
+        Some(sample) => sample.display().to_string(),
+        None => String::from("<file>"),
+    };
+    reportln!(
+        "{}",
+        tr!("init-next", app = identity::app_name(), input = input)
+    );
+}
+
+/// Where completions for `shell` are picked up without further setup (zsh excepted,
+/// which needs the directory in its `fpath`).
+fn completions_path(shell: Shell) -> Result<PathBuf> {
+    let dirs = directories::BaseDirs::new()
+        .ok_or_else(|| Error::Other(String::from("Could not determine the home directory")))?;
+    let home = dirs.home_dir();
+    let xdg = |variable: &str, default: &str| {
+        std::env::var_os(variable)
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .unwrap_or_else(|| home.join(default))
+    };
+    let name = identity::app_name();
+    match shell {
+        Shell::Bash => Ok(xdg("XDG_DATA_HOME", ".local/share")
+            .join("bash-completion/completions")
+            .join(name)),
+        Shell::Fish => Ok(xdg("XDG_CONFIG_HOME", ".config")
+            .join("fish/completions")
+            .join(format!("{}.fish", name))),
+        Shell::Zsh => Ok(home.join(".zfunc").join(format!("_{}", name))),
+        _ => Err(Error::Other(format!(
+            "Installing completions for {} is not supported; use bash, zsh or fish",
+            shell
+        ))),
+    }
+}
+
+/// Generates completions for `shell` and writes them where the shell finds them.
+fn install_completions(shell: Shell) -> Result<PathBuf> {
+    let path = completions_path(shell)?;
+    let mut script = Vec::new();
+    clap_complete::generate(
+        shell,
+        &mut crate::Cli::command(),
+        identity::app_name(),
+        &mut script,
+    );
+    write_file(&path, &String::from_utf8_lossy(&script))?;
+    info!("Installed {} completions to {}", shell, path.display());
+    Ok(path)
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|source| Error::File {
+            action: "create directory",
+            path: parent.to_path_buf(),
+            source,
+        })?;
+    }
+    fs::write(path, contents).map_err(|source| Error::File {
+        action: "write",
+        path: path.to_path_buf(),
+        source,
+    })
+}
@@ -1,13 +1,112 @@
+use std::path::PathBuf;
+
 use clap::Subcommand;
 
+use crate::error::Result;
+
+pub mod auth;
+pub mod bench;
+pub mod cache;
+pub mod config;
+pub mod daemon;
+pub mod diff;
+pub mod hash;
+pub mod init;
+pub mod package;
+pub mod render;
 pub mod run;
+pub mod selftest;
+pub mod transform;
 pub mod upgrade;
+pub mod version;
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
+    /// Manage credentials stored in the OS keyring
+    Auth(auth::Args),
+
+    /// Measure processing throughput over a generated corpus
+    Bench(bench::Args),
+
+    /// Inspect or clean the result cache of `run --cache`
+    Cache(cache::Args),
+
+    /// Inspect and modify the configuration file
+    Config(config::Args),
+
+    /// Watch directories and process files as they arrive or on a schedule
+    Daemon(daemon::Args),
+
+    /// Compare two files and show a unified diff
+    Diff(diff::Args),
+
+    /// Print or verify file digests (SHA-256, BLAKE3, ...) in `sha256sum` format
+    Hash(hash::Args),
+
+    /// Create a starter configuration file and, optionally, shell completions and sample
+    /// inputs
+    Init(init::Args),
+
+    /// Generate packaging metadata (Homebrew, Scoop, nfpm) for maintainers
+    Package(package::Args),
+
+    /// Render a template with values from a JSON/YAML file or the active profile
+    Render(render::Args),
+
     /// Run the main functionality
-    Run(run::Args),
+    Run(Box<run::Args>),
+
+    /// Check that this binary works on this host: statistics, transforms, configuration,
+    /// cache and optionally network access
+    Selftest(selftest::Args),
+
+    /// Inspect transform pipelines
+    Transform(transform::Args),
 
     /// Upgrade the CLI to the latest version
     Upgrade(upgrade::Args),
+
+    /// Show build information (commit, build date, compiler, target, features)
+    Version(version::Args),
+}
+
+impl Commands {
+    /// Whether the command can start with a configuration file that fails to load, so
+    /// the file can be repaired.
+    pub fn tolerates_invalid_config(&self) -> bool {
+        matches!(
+            self,
+            Commands::Config(config::Args {
+                action: config::Action::Edit
+                    | config::Action::Set { scope: Some(_), .. }
+                    | config::Action::Init { force: true, .. }
+            }) | Commands::Init(init::Args { force: true, .. })
+        )
+    }
+
+    /// Whether the command writes results, so the output directory is checked at startup.
+    pub fn writes_output(&self) -> bool {
+        matches!(self, Commands::Run(_) | Commands::Daemon(_))
+    }
+
+    /// Files read by the command, captured when recording a session.
+    pub fn inputs(&self) -> Result<Vec<PathBuf>> {
+        match self {
+            Commands::Run(args) => args.input_files(),
+            Commands::Diff(args) => Ok(vec![args.old.clone(), args.new.clone()]),
+            Commands::Hash(args) => Ok(args.input_files()),
+            Commands::Render(args) => Ok(args.input_files()),
+            Commands::Auth(_)
+            | Commands::Bench(_)
+            | Commands::Cache(_)
+            | Commands::Config(_)
+            | Commands::Daemon(_)
+            | Commands::Init(_)
+            | Commands::Package(_)
+            | Commands::Selftest(_)
+            | Commands::Transform(_)
+            | Commands::Upgrade(_)
+            | Commands::Version(_) => Ok(Vec::new()),
+        }
+    }
 }
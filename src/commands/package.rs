@@ -0,0 +1,415 @@
+use clap::{Args as ClapArgs, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::constants;
+use crate::context::Context;
+use crate::digest;
+use crate::error::{Error, Result};
+use crate::identity::{self, Repository};
+use crate::output::{print_json, OutputFormat};
+
+/// Placeholder written where a checksum was not supplied via --checksums.
+const MISSING_SHA256: &str = "REPLACE_WITH_SHA256";
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    pub action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Generate package manager manifests pointing at the GitHub release assets
+    Manifest {
+        /// Manifest to generate (repeatable; defaults to all)
+        #[arg(long = "kind", value_enum, value_name = "KIND")]
+        kinds: Vec<ManifestKind>,
+
+        /// Release version (defaults to the version of this binary)
+        #[arg(long)]
+        version: Option<String>,
+
+        /// `sha256sum`-style file with the digests of the release assets
+        #[arg(long, value_name = "FILE")]
+        checksums: Option<PathBuf>,
+
+        /// Write each manifest to this directory instead of printing it
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+    },
+}
+
+/// Supported packaging formats.
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestKind {
+    /// Homebrew formula (macOS and Linux)
+    Homebrew,
+    /// Scoop manifest (Windows)
+    Scoop,
+    /// nfpm configuration for building .deb and .rpm packages
+    Nfpm,
+}
+
+impl ManifestKind {
+    fn file_name(self) -> String {
+        match self {
+            ManifestKind::Homebrew => format!("{}.rb", identity::app_name()),
+            ManifestKind::Scoop => format!("{}.json", identity::app_name()),
+            ManifestKind::Nfpm => String::from("nfpm.yaml"),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Manifest {
+    kind: ManifestKind,
+    file_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    contents: String,
+}
+
+/// Release assets for one version, following the naming used by the release workflow.
+struct Release {
+    repository: Repository,
+    version: String,
+    tag: String,
+    checksums: HashMap<String, String>,
+}
+
+impl Release {
+    fn asset_name(&self, target: &str) -> String {
+        let extension = if target.contains("windows") {
+            "zip"
+        } else {
+            "tar.gz"
+        };
+        format!(
+            "{}-{}_{}.{}",
+            identity::app_name(),
+            self.tag,
+            target,
+            extension
+        )
+    }
+
+    fn url(&self, target: &str) -> String {
+        format!(
+            "{}/releases/download/{}/{}",
+            self.homepage(),
+            self.tag,
+            self.asset_name(target)
+        )
+    }
+
+    fn homepage(&self) -> String {
+        format!("https://github.com/{}", self.repository)
+    }
+
+    fn sha256(&self, target: &str) -> &str {
+        self.checksums
+            .get(&self.asset_name(target))
+            .map(String::as_str)
+            .unwrap_or(MISSING_SHA256)
+    }
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    match args.action {
+        Action::Manifest {
+            kinds,
+            version,
+            checksums,
+            output_dir,
+        } => manifest(kinds, version, checksums, output_dir, ctx),
+    }
+}
+
+fn manifest(
+    mut kinds: Vec<ManifestKind>,
+    version: Option<String>,
+    checksums: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    ctx: &Context,
+) -> Result<()> {
+    if kinds.is_empty() {
+        kinds = ManifestKind::value_variants().to_vec();
+    }
+    // Repeated kinds are written once, where first given
+    let mut seen = HashSet::new();
+    kinds.retain(|kind| seen.insert(*kind));
+
+    let version = version.unwrap_or_else(|| constants::APP_VERSION.to_string());
+    let version = version.trim_start_matches('v').to_string();
+    let checksums = match &checksums {
+        Some(path) => read_checksums(path)?,
+        None => HashMap::new(),
+    };
+    let release = Release {
+        repository: identity::repository()?.clone(),
+        tag: format!("v{}", version),
+        version,
+        checksums,
+    };
+
+    let mut manifests: Vec<Manifest> = kinds
+        .into_iter()
+        .map(|kind| Manifest {
+            kind,
+            file_name: kind.file_name(),
+            path: None,
+            contents: match kind {
+                ManifestKind::Homebrew => homebrew_formula(&release),
+                ManifestKind::Scoop => scoop_manifest(&release),
+                ManifestKind::Nfpm => nfpm_config(&release),
+            },
+        })
+        .collect();
+
+    let missing = manifests
+        .iter()
+        .filter(|m| m.contents.contains(MISSING_SHA256))
+        .count();
+    if missing > 0 {
+        info!(
+            "{} manifest(s) contain {} placeholders; pass --checksums to fill them in",
+            missing, MISSING_SHA256
+        );
+    }
+
+    if let Some(dir) = &output_dir {
+        fs::create_dir_all(dir)?;
+        for manifest in &mut manifests {
+            let path = dir.join(&manifest.file_name);
+            fs::write(&path, &manifest.contents)?;
+            manifest.path = Some(path);
+        }
+    }
+
+    match ctx.format {
+        OutputFormat::Json => print_json(&manifests),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            for manifest in &manifests {
+                match &manifest.path {
+                    Some(path) => ctx.reporter.success(&format!(
+                        "{} manifest written to: {}",
+                        manifest.kind.to_possible_value().unwrap().get_name(),
+                        path.display()
+                    )),
+                    None if manifests.len() == 1 => print!("{}", manifest.contents),
+                    None => {
+                        println!("# ---- {} ----", manifest.file_name);
+                        print!("{}", manifest.contents);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Parses `<hex>  <file>` lines as written by `sha256sum` and `shasum -a 256`.
+fn read_checksums(path: &Path) -> Result<HashMap<String, String>> {
+    let contents = fs::read_to_string(path).map_err(|source| Error::File {
+        action: "read checksums file",
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let mut checksums = HashMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed = digest::parse_checksum_line(line)
+            .filter(|(hex, _)| hex.len() == 64)
+            .map(|(hex, name)| (name.to_string(), hex.to_ascii_lowercase()));
+        let (name, hex) = parsed.ok_or_else(|| {
+            Error::Other(format!(
+                "{}:{}: expected '<sha256>  <file>'",
+                path.display(),
+                number + 1
+            ))
+        })?;
+        // Checksum files often list paths; assets are matched by file name
+        let name = Path::new(&name)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or(name);
+        checksums.insert(name, hex);
+    }
+    Ok(checksums)
+}
+
+/// Homebrew class name: the binary name in CamelCase.
+fn formula_class(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn homebrew_formula(release: &Release) -> String {
+    let block = |target: &str| {
+        format!(
+            "      url \"{}\"\n      sha256 \"{}\"",
+            release.url(target),
+            release.sha256(target)
+        )
+    };
+    format!(
+        r##"class {class} < Formula
+  desc "{desc}"
+  homepage "{homepage}"
+  version "{version}"
+  license "{license}"
+
+  on_macos do
+    on_arm do
+{macos_arm}
+    end
+    on_intel do
+{macos_intel}
+    end
+  end
+
+  on_linux do
+    on_arm do
+{linux_arm}
+    end
+    on_intel do
+{linux_intel}
+    end
+  end
+
+  def install
+    bin.install "{name}"
+  end
+
+  test do
+    system "#{{bin}}/{name}", "--version"
+  end
+end
+"##,
+        class = formula_class(identity::app_name()),
+        desc = env!("CARGO_PKG_DESCRIPTION"),
+        homepage = release.homepage(),
+        version = release.version,
+        license = env!("CARGO_PKG_LICENSE"),
+        macos_arm = block("aarch64-apple-darwin"),
+        macos_intel = block("x86_64-apple-darwin"),
+        linux_arm = block("aarch64-unknown-linux-musl"),
+        linux_intel = block("x86_64-unknown-linux-musl"),
+        name = identity::app_name(),
+    )
+}
+
+fn scoop_manifest(release: &Release) -> String {
+    let target = "x86_64-pc-windows-msvc";
+    let autoupdate_url = release.url(target).replace(&release.version, "$version");
+    let manifest = serde_json::json!({
+        "version": release.version,
+        "description": env!("CARGO_PKG_DESCRIPTION"),
+        "homepage": release.homepage(),
+        "license": env!("CARGO_PKG_LICENSE"),
+        "architecture": {
+            "64bit": {
+                "url": release.url(target),
+                "hash": release.sha256(target),
+            }
+        },
+        "bin": format!("{}.exe", identity::app_name()),
+        "checkver": "github",
+        "autoupdate": {
+            "architecture": {
+                "64bit": { "url": autoupdate_url }
+            }
+        }
+    });
+    serde_json::to_string_pretty(&manifest).unwrap_or_default() + "\n"
+}
+
+fn nfpm_config(release: &Release) -> String {
+    format!(
+        r#"# Build with: nfpm package --packager deb (or rpm)
+# For ARM packages use arch arm64 and the aarch64-unknown-linux-musl binary.
+name: {name}
+arch: amd64
+platform: linux
+version: {version}
+maintainer: {owner}
+description: {desc}
+homepage: {homepage}
+license: {license}
+contents:
+  - src: ./target/x86_64-unknown-linux-musl/release/{name}
+    dst: /usr/bin/{name}
+    file_info:
+      mode: 0755
+"#,
+        name = identity::app_name(),
+        version = release.version,
+        owner = release.repository.owner,
+        desc = env!("CARGO_PKG_DESCRIPTION"),
+        homepage = release.homepage(),
+        license = env!("CARGO_PKG_LICENSE"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release() -> Release {
+        Release {
+            repository: Repository::parse("acme/tool").unwrap(),
+            version: String::from("1.2.3"),
+            tag: String::from("v1.2.3"),
+            checksums: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_asset_names_match_release_workflow() {
+        let release = release();
+        assert_eq!(
+            release.asset_name("x86_64-unknown-linux-musl"),
+            format!(
+                "{}-v1.2.3_x86_64-unknown-linux-musl.tar.gz",
+                identity::app_name()
+            )
+        );
+        assert!(release
+            .asset_name("x86_64-pc-windows-msvc")
+            .ends_with(".zip"));
+    }
+
+    #[test]
+    fn test_formula_class() {
+        assert_eq!(formula_class("my-cli"), "MyCli");
+        assert_eq!(formula_class("ripgrep_all"), "RipgrepAll");
+    }
+
+    #[test]
+    fn test_scoop_autoupdate_uses_version_variable() {
+        let manifest: serde_json::Value =
+            serde_json::from_str(&scoop_manifest(&release())).unwrap();
+        assert_eq!(manifest["version"], "1.2.3");
+        let url = manifest["autoupdate"]["architecture"]["64bit"]["url"]
+            .as_str()
+            .unwrap();
+        assert!(url.contains("/v$version/"), "{}", url);
+        assert!(!url.contains("1.2.3"), "{}", url);
+    }
+}
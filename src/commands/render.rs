@@ -0,0 +1,162 @@
+use clap::Args as ClapArgs;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+use super::run::{is_bare_file_name, is_yaml, profile_output_dir, STDOUT_OUTPUT};
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::i18n::tr;
+use crate::jsonpath;
+use crate::output::{print_json, OutputFormat};
+use crate::template::Template;
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    /// Template file, in a subset of Handlebars ({{name}}, {{#each items}}, {{#if flag}})
+    ///
+    /// Supported: {{path}} and {{{path}}} lookups such as {{name}}, {{a.b.0}}, {{this}},
+    /// {{../name}} and {{@root.name}}; {{@index}} and {{@key}} inside {{#each}}; the
+    /// blocks {{#each path}}, {{#if path}} and {{#unless path}}, each with an optional
+    /// {{else}}; and {{! comments }} or {{!-- comments --}}. Values are inserted as they
+    /// are, without HTML escaping.
+    ///
+    /// Anything else is rejected with an error naming it: helpers ({{lookup a b}}),
+    /// partials ({{> name}}), whitespace control ({{~name~}}), {{else if}} chains,
+    /// subexpressions, block parameters, [segment] literals, {{^...}} and {{&...}} tags,
+    /// decorators, other @variables and \{{ escapes.
+    pub template: PathBuf,
+
+    /// JSON or YAML file with the values to render (default: the active profile's settings)
+    #[arg(short, long, value_name = "FILE")]
+    pub data: Option<PathBuf>,
+
+    /// Output file (default: stdout); a bare file name goes into the active profile's
+    /// output_dir
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Render and report what would be written, without writing it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl Args {
+    /// The template and data files read by the command.
+    pub fn input_files(&self) -> Vec<PathBuf> {
+        std::iter::once(self.template.clone())
+            .chain(self.data.clone())
+            .collect()
+    }
+}
+
+/// Result of rendering a template.
+#[derive(Debug, Serialize)]
+struct RenderReport {
+    template: PathBuf,
+    /// Output file, or `None` for stdout
+    output: Option<PathBuf>,
+    bytes: usize,
+    written: bool,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    info!("Rendering {}", args.template.display());
+    let source = read(&args.template)?;
+    let template = Template::parse(&source)
+        .map_err(|e| Error::Other(format!("{}: {}", args.template.display(), e)))?;
+    let data = match &args.data {
+        Some(path) => load_data(path)?,
+        None => profile_data(ctx)?,
+    };
+    let rendered = template.render(&data);
+    debug!("Rendered {} bytes", rendered.len());
+
+    let output = match args.output.as_deref() {
+        None | Some(STDOUT_OUTPUT) => None,
+        Some(output) if is_bare_file_name(output) => Some(match profile_output_dir(ctx)? {
+            Some(dir) => dir.join(output),
+            None => PathBuf::from(output),
+        }),
+        Some(output) => Some(PathBuf::from(output)),
+    };
+    let report = RenderReport {
+        template: args.template,
+        output,
+        bytes: rendered.len(),
+        written: !args.dry_run,
+    };
+
+    if !args.dry_run {
+        match &report.output {
+            Some(path) => fs::write(path, &rendered).map_err(|source| Error::File {
+                action: "write",
+                path: path.clone(),
+                source,
+            })?,
+            None => print!("{}", rendered),
+        }
+    }
+
+    // Rendered text on stdout is not followed by a report
+    if report.output.is_none() && report.written {
+        return Ok(());
+    }
+    if ctx.format == OutputFormat::Json {
+        return print_json(&report);
+    }
+    let target = report.output.as_ref().map_or_else(
+        || String::from(STDOUT_OUTPUT),
+        |path| path.display().to_string(),
+    );
+    if args.dry_run {
+        ctx.reporter
+            .notice(&tr!("render-dry-run", bytes = report.bytes, path = target));
+    } else {
+        ctx.reporter
+            .success(&tr!("render-written", bytes = report.bytes, path = target));
+    }
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|source| Error::File {
+        action: "read",
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Reads a single JSON or YAML document (by file extension) to render with.
+fn load_data(path: &Path) -> Result<Value> {
+    let mut documents = jsonpath::parse_documents(&read(path)?, is_yaml(path))
+        .map_err(|e| Error::Other(format!("{}: {}", path.display(), e)))?;
+    if documents.len() != 1 {
+        return Err(Error::Other(format!(
+            "{}: expected one document, found {}",
+            path.display(),
+            documents.len()
+        )));
+    }
+    Ok(documents.remove(0))
+}
+
+/// The active profile's settings, with its name as `profile`.
+fn profile_data(ctx: &Context) -> Result<Value> {
+    let profile = ctx.config.active_profile().ok_or_else(|| {
+        Error::Other(format!(
+            "No --data given and the active profile '{}' is not defined",
+            ctx.config.default_profile
+        ))
+    })?;
+    let mut data = serde_json::to_value(profile)?;
+    if let Value::Object(map) = &mut data {
+        map.insert(
+            String::from("profile"),
+            Value::String(ctx.config.default_profile.clone()),
+        );
+    }
+    Ok(data)
+}
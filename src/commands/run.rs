@@ -1,70 +1,2898 @@
+use crate::batch::{BatchSpec, Job, JobOutcome, JobStatus};
+use crate::bloom::ScalableBloomFilter;
+use crate::cache::ResultCache;
+use crate::cancel;
+use crate::compression::{self, Compression, Encoder, EncodingWriter, OutputCompression};
+use crate::constants;
+use crate::context::Context;
+use crate::digest::{self, FileDigest, HashAlgorithm, HashingReader};
+use crate::disk;
+use crate::encoding::{self, Encoding};
 use crate::error::{Error, Result};
-use clap::Args as ClapArgs;
+use crate::git::{self, GitSelection};
+use crate::glob::PathFilter;
+use crate::i18n::tr;
+use crate::jobs::Queue;
+use crate::jsonpath::{self, JsonPath};
+use crate::jsonstats::{self, JsonStats};
+use crate::lock;
+use crate::manifest::{OnModified, OutputGuard};
+use crate::output::{
+    print_csv, print_json, print_yaml, reportln, OutputFormat, ReportRedirect, StatsFormat,
+};
+use crate::reporter::Reporter;
+use crate::revread;
+use crate::state::StateFile;
+use crate::stats::{throughput_mb_per_sec, AdvancedStats, FileStats, StatsReport, WhitespaceStats};
+use crate::strategy::{self, Strategy};
+use crate::tabular::{self, NumericStats, TableStats};
+use crate::throttle::IoLimiter;
+use crate::timings;
+use crate::transform::{self, LineEnding, Pipeline, StageMetrics, Transform};
+use clap::builder::ArgPredicate;
+use clap::{Args as ClapArgs, ValueEnum};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::Path;
-use tracing::{debug, info, warn};
+use std::io::{self, BufRead, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::{debug, error, info, warn};
+use walkdir::WalkDir;
 
 #[derive(ClapArgs, Debug)]
 pub struct Args {
-    /// Input file path
-    #[arg(short, long)]
+    /// Input file or directory path (directories are processed recursively)
+    #[arg(
+        short,
+        long,
+        required = false,
+        required_unless_present_any = ["files_from_git", "text", "batch"],
+        default_value_if("files_from_git", ArgPredicate::IsPresent, "."),
+        default_value_if("text", ArgPredicate::IsPresent, TEXT_INPUT),
+        default_value_if("batch", ArgPredicate::IsPresent, BATCH_INPUT)
+    )]
     pub input: String,
 
-    /// Optional output file path
-    #[arg(short, long)]
+    /// Run the jobs of a batch file (JSON or YAML), each with its own `run` arguments, as
+    /// many at once as their `depends_on` and the profile's parallel_jobs allow
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["input", "text", "files_from_git", "output", "output_dir"]
+    )]
+    pub batch: Option<PathBuf>,
+
+    /// Print the stages the --batch jobs would run in, without running them
+    #[arg(long, requires = "batch")]
+    pub plan: bool,
+
+    /// Process this text instead of a file (`@-` reads it from stdin)
+    #[arg(
+        long,
+        value_name = "TEXT",
+        conflicts_with_all = ["input", "files_from_git", "output_dir"]
+    )]
+    pub text: Option<String>,
+
+    /// Process only files touched in git: staged, changed, or a revision range such as HEAD~3..
+    /// (limited to the --input directory, which defaults to the current one)
+    #[arg(long, value_name = "SELECTION")]
+    pub files_from_git: Option<GitSelection>,
+
+    /// Only process directory files whose path relative to --input matches this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip directory files whose path relative to --input matches this glob (repeatable;
+    /// `!GLOB` re-includes)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Match --include and --exclude globs case-insensitively
+    #[arg(long)]
+    pub glob_ignore_case: bool,
+
+    /// Optional output file path (`-` writes the processed data to stdout, and the report
+    /// to stderr unless --output-report is given); a bare file name is placed in the active
+    /// profile's output_dir
+    #[arg(short, long, conflicts_with = "output_dir")]
     pub output: Option<String>,
 
+    /// Write the output into the active profile's output_dir under the input's name
+    /// (mirroring the layout of a directory input), creating the directory if needed
+    #[arg(long, conflicts_with_all = ["output", "output_dir", "text", "stats_only", "null_output"])]
+    pub emit_file: bool,
+
+    /// Write the report (text, or JSON with --format json) to this file instead of stdout,
+    /// keeping it apart from the data output
+    #[arg(long, value_name = "PATH")]
+    pub output_report: Option<PathBuf>,
+
+    /// How to handle an output edited since this tool last wrote it (overwrites with --yes,
+    /// prompts when interactive, otherwise fails)
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub on_modified: Option<OnModified>,
+
+    /// Compress outputs (defaults to the profile's `compress`, else the format implied by
+    /// a .gz/.zst/.xz output extension); zstd uses up to the profile's parallel_jobs threads
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub compress: Option<OutputCompression>,
+
+    /// Compression level: 0-9 for gzip and xz, 1-22 for zstd (defaults to the profile's
+    /// `compression_level`, else the format's default)
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    pub compression_level: Option<i32>,
+
+    /// Write outputs into this directory, mirroring the input directory layout
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Place all outputs directly in --output-dir instead of mirroring subdirectories
+    #[arg(long, requires = "output_dir")]
+    pub flatten: bool,
+
+    /// Abort on the first unreadable path instead of skipping it with a warning
+    #[arg(long)]
+    pub strict_fs: bool,
+
+    /// What a file of a directory input that cannot be processed does to the run; files
+    /// that cannot be read are skipped with a warning instead, unless --strict-fs is given
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t)]
+    pub error_policy: ErrorPolicy,
+
     /// Show statistics only (don't process the file)
     #[arg(long)]
     pub stats_only: bool,
+
+    /// Reuse the statistics of inputs seen before with the same options, from the result
+    /// cache (on by default when the config sets `cache.enabled`); applies to --stats-only
+    /// runs of files in text mode
+    #[arg(long, conflicts_with = "no_cache")]
+    pub cache: bool,
+
+    /// Neither read nor fill the result cache, even if the config enables it
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Process only inputs that changed since the last run with the same arguments and
+    /// configuration; the others are reported as they were then, from a state file in the
+    /// cache directory
+    #[arg(long, conflicts_with_all = ["text", "query"])]
+    pub since_last: bool,
+
+    /// Process only the last N lines of each input; uncompressed files are read backwards
+    /// from the end (unless --hash needs every byte), so this stays fast on huge files
+    #[arg(long, value_name = "N", conflicts_with = "head")]
+    pub tail: Option<usize>,
+
+    /// Process only the first N lines of each input; reading stops there (unless --hash
+    /// needs every byte)
+    #[arg(long, value_name = "N")]
+    pub head: Option<usize>,
+
+    /// Process a random sample of about P% of the lines of each input (after --head or
+    /// --tail), kept in input order; reproducible with --seed
+    #[arg(long, value_name = "P%", value_parser = parse_percent)]
+    pub sample: Option<f64>,
+
+    /// How to read each input; streaming and parallel keep memory bounded, also while
+    /// transforming lines, unless an option needs whole inputs (e.g. --replace or --mode csv)
+    #[arg(long, value_enum, default_value_t = Strategy::Auto)]
+    pub strategy: Strategy,
+
+    /// Read and transform everything but discard the output instead of writing it, and
+    /// report throughput only (for benchmarking without write costs)
+    #[arg(long, conflicts_with_all = ["output", "output_dir", "stats_only", "compress", "compression_level"])]
+    pub null_output: bool,
+
+    /// Limit file reads and writes to this many MB/s in total, to spare shared storage
+    #[arg(long, value_name = "MB/s", value_parser = parse_io_limit)]
+    pub io_limit: Option<f64>,
+
+    /// Abort before writing unless this many MB stay free on the output filesystem once the
+    /// outputs (estimated from the input sizes) are written; defaults to the profile's
+    /// `min_free_space_mb`, and without either a likely shortfall is only warned about
+    #[arg(long, value_name = "MB")]
+    pub min_free_space: Option<u64>,
+
+    /// Never read an input larger than this many MB into memory: larger inputs are
+    /// streamed instead, and runs that need them whole fail; defaults to the profile's
+    /// `max_memory_mb`
+    #[arg(long, value_name = "MB")]
+    pub max_memory: Option<u64>,
+
+    /// Report the files processed, bytes read and written, and the effective I/O rate
+    #[arg(long)]
+    pub summary: bool,
+
+    /// Report only the statistics of each input and their totals, in this format
+    /// (instead of the --format report)
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub stats_format: Option<StatsFormat>,
+
+    /// Order the files of the report by this count, largest first
+    #[arg(long, value_enum, value_name = "KEY")]
+    pub sort_by: Option<SortKey>,
+
+    /// How to interpret the input when computing statistics
+    #[arg(long, value_enum, default_value_t = Mode::Text)]
+    pub mode: Mode,
+
+    /// Print the values at this JSONPath (e.g. `$.items[*].name`) of each JSON or YAML
+    /// input to stdout, one JSON value per line; the report goes to stderr
+    #[arg(long, value_name = "JSONPATH", conflicts_with_all = ["output", "output_dir", "emit_file"])]
+    pub query: Option<String>,
+
+    /// Field delimiter for --mode csv (defaults to tab for .tsv files, comma otherwise)
+    #[arg(long, value_name = "CHAR")]
+    pub delimiter: Option<char>,
+
+    /// Input text encoding
+    #[arg(long, value_enum, default_value_t = Encoding::Auto)]
+    pub encoding: Encoding,
+
+    /// How to handle binary (non-text) files
+    #[arg(long, value_enum, default_value_t = BinaryMode::Skip)]
+    pub binary: BinaryMode,
+
+    /// Include a digest of each input, computed while it is read
+    #[arg(long, value_enum, value_name = "ALGORITHM")]
+    pub hash: Option<HashAlgorithm>,
+
+    /// Include distinct-line, duplication and byte-entropy estimates (memory-bounded)
+    #[arg(long)]
+    pub advanced_stats: bool,
+
+    /// Include line-ending, trailing-whitespace, indentation and final-newline checks
+    #[arg(long)]
+    pub whitespace_stats: bool,
+
+    /// Include the N most frequent words in the statistics
+    #[arg(long, value_name = "N")]
+    pub top_words: Option<usize>,
+
+    /// Keep only lines matching this regular expression
+    #[arg(long, value_name = "PATTERN")]
+    pub grep: Option<String>,
+
+    /// Replace matches of PATTERN with REPLACEMENT (supports $1 / ${name} capture groups)
+    #[arg(long, num_args = 2, value_names = ["PATTERN", "REPLACEMENT"])]
+    pub replace: Option<Vec<String>>,
+
+    /// Drop repeated lines using a Bloom filter, which needs far less memory than the set of
+    /// lines exact deduplication keeps, but drops a line seen for the first time with
+    /// probability --dedupe-fp-rate
+    #[arg(long)]
+    pub dedupe_approx: bool,
+
+    /// Distinct lines the --dedupe-approx filter is first sized for; it grows when more
+    /// arrive, keeping to --dedupe-fp-rate
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 100_000,
+        requires = "dedupe_approx"
+    )]
+    pub dedupe_capacity: usize,
+
+    /// False-positive rate for --dedupe-approx (between 0 and 1)
+    #[arg(long, value_name = "RATE", default_value_t = 0.001, value_parser = parse_rate, requires = "dedupe_approx")]
+    pub dedupe_fp_rate: f64,
+
+    /// Fail (exit code 3) if any input has more than N lines
+    #[arg(long, value_name = "N")]
+    pub fail_if_lines_over: Option<usize>,
+
+    /// Fail (exit code 3) if any input is larger than N bytes
+    #[arg(long, value_name = "N")]
+    pub fail_if_bytes_over: Option<usize>,
+
+    /// Fail (exit code 3) if any input has a line matching this regular expression
+    #[arg(long, value_name = "PATTERN")]
+    pub fail_if_matches: Option<String>,
+
+    /// Transform pipeline applied after --grep/--replace, e.g. "trim | dedupe-lines" or "@name"
+    /// for a pipeline defined in the config file
+    #[arg(long, value_name = "PIPELINE")]
+    pub transform: Option<String>,
+
+    /// Sort lines after the transform pipeline (the `sort-lines` transform), keeping equal
+    /// lines in input order; large inputs are merged from sorted runs on disk
+    #[arg(long)]
+    pub sort: bool,
+
+    /// Drop repeated lines after the transform pipeline, keeping first occurrences in order
+    /// (with --sort, keeping one of each); large inputs spill to disk
+    #[arg(long)]
+    pub unique: bool,
+
+    /// Convert line endings after the transform pipeline (the `line-endings` transform)
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub fix_line_endings: Option<LineEnding>,
+
+    /// Seed for randomized transforms (shuffle-lines, sample-lines) and --sample for
+    /// reproducible output
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Threads this run may use, when fewer than the profile's parallel_jobs (batch jobs
+    /// share them)
+    #[arg(skip)]
+    pub threads: Option<u32>,
+}
+
+/// Interpretation of input content.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Plain text
+    #[default]
+    Text,
+    /// Delimited table with a header row (CSV/TSV)
+    Csv,
+    /// JSON document, or newline-delimited JSON values (NDJSON)
+    Json,
+    /// JSON, or YAML for .yaml/.yml inputs, with a summary of the types at each path
+    Structured,
+}
+
+/// How a run of many inputs handles one that fails.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop at the first failure
+    #[default]
+    FailFast,
+    /// Process the other files, logging each failure, and list the failures at the end
+    Continue,
+    /// Process the other files and count the failures by kind at the end
+    Summarize,
+}
+
+/// Count the files of a report can be ordered by.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Lines,
+    Words,
+    Bytes,
+}
+
+impl SortKey {
+    fn count(self, stats: &FileStats) -> usize {
+        match self {
+            SortKey::Lines => stats.lines,
+            SortKey::Words => stats.words,
+            SortKey::Bytes => stats.bytes,
+        }
+    }
+}
+
+/// Handling of files detected as binary.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryMode {
+    /// Report only the byte count
+    BytesOnly,
+    /// Report the file as skipped
+    #[default]
+    Skip,
+    /// Fail the run
+    Error,
+}
+
+/// Name reported for `--text` input.
+const TEXT_INPUT: &str = "<text>";
+
+/// Bytes in a megabyte, as --min-free-space and --max-memory count them.
+const MB: u64 = 1_000_000;
+
+/// `--output` value for writing the processed data to stdout.
+pub const STDOUT_OUTPUT: &str = "-";
+
+/// Placeholder input of a `--batch` run, whose jobs name their own.
+const BATCH_INPUT: &str = "<batch>";
+
+impl Args {
+    /// Files this invocation will read.
+    pub fn input_files(&self) -> Result<Vec<PathBuf>> {
+        let input = Path::new(&self.input);
+        if self.text.is_some() {
+            Ok(Vec::new())
+        } else if let Some(selection) = &self.files_from_git {
+            self.filter_files(input, git::select_files(input, selection)?)
+        } else if input.is_dir() {
+            self.filter_files(input, collect_files(input, &mut Walk::lenient())?)
+        } else if input.exists() {
+            Ok(vec![input.to_path_buf()])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Whether the report of an input follows from its statistics alone, so cached
+    /// statistics can stand in for reading it.
+    fn is_cacheable(&self) -> bool {
+        self.stats_only
+            && self.text.is_none()
+            && self.mode == Mode::Text
+            && self.query.is_none()
+            && self.fail_if_matches.is_none()
+            && self.head.is_none()
+            && self.tail.is_none()
+            && self.sample.is_none()
+            && self.hash.is_none()
+    }
+
+    /// Why inputs must be read whole into memory, if they must; otherwise their
+    /// statistics can be computed from chunks, and their lines streamed to the output.
+    fn needs_whole_input(&self) -> Option<&'static str> {
+        if self.text.is_some() {
+            Some("--text")
+        } else if self.replace.is_some() {
+            Some("--replace")
+        } else if self.mode == Mode::Csv {
+            Some("--mode csv")
+        } else if self.mode == Mode::Json {
+            Some("--mode json")
+        } else if self.mode == Mode::Structured {
+            Some("--mode structured")
+        } else if self.query.is_some() {
+            Some("--query")
+        } else if self.fail_if_matches.is_some() {
+            Some("--fail-if-matches")
+        } else if !matches!(self.encoding, Encoding::Auto | Encoding::Utf8) {
+            Some("--encoding")
+        } else {
+            None
+        }
+    }
+
+    /// Keeps the files below `root` allowed by --include and --exclude.
+    fn filter_files(&self, root: &Path, mut files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        let filter = PathFilter::new(&self.include, &self.exclude, self.glob_ignore_case)?;
+        if !filter.is_empty() {
+            files.retain(|file| filter.allows(file.strip_prefix(root).unwrap_or(file)));
+        }
+        Ok(files)
+    }
+}
+
+/// Parses a probability strictly between 0 and 1.
+fn parse_rate(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate < 1.0 => Ok(rate),
+        _ => Err(format!(
+            "expected a number between 0 and 1, got '{}'",
+            value
+        )),
+    }
+}
+
+/// Parses a percentage above 0 and up to 100, with or without a `%` sign.
+fn parse_percent(value: &str) -> std::result::Result<f64, String> {
+    match value.trim_end_matches('%').parse::<f64>() {
+        Ok(percent) if percent > 0.0 && percent <= 100.0 => Ok(percent),
+        _ => Err(format!(
+            "expected a percentage above 0 and up to 100, got '{}'",
+            value
+        )),
+    }
+}
+
+/// Parses a positive rate in MB/s.
+fn parse_io_limit(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(limit) if limit > 0.0 && limit.is_finite() => Ok(limit),
+        _ => Err(format!(
+            "expected a positive number of MB/s, got '{}'",
+            value
+        )),
+    }
+}
+
+/// Line operations applied to the content before the transform pipeline.
+struct TextOps {
+    grep: Option<Regex>,
+    replace: Option<(Regex, String)>,
+    /// False-positive rate of approximate deduplication, if enabled
+    dedupe_fp_rate: Option<f64>,
+    /// Lines the approximate deduplication filter is first sized for
+    dedupe_capacity: usize,
+}
+
+/// Outcome of `--dedupe-approx` for one input.
+#[derive(Debug, Serialize)]
+struct DedupeReport {
+    /// Configured probability that a unique line was dropped
+    fp_rate: f64,
+    lines_removed: usize,
+    filter_bytes: usize,
+}
+
+impl TextOps {
+    fn from_args(args: &Args) -> Result<Self> {
+        let grep = args.grep.as_deref().map(Regex::new).transpose()?;
+        let replace = match args.replace.as_deref() {
+            Some([pattern, replacement]) => Some((Regex::new(pattern)?, replacement.clone())),
+            _ => None,
+        };
+        Ok(Self {
+            grep,
+            replace,
+            dedupe_fp_rate: args.dedupe_approx.then_some(args.dedupe_fp_rate),
+            dedupe_capacity: args.dedupe_capacity,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.grep.is_none() && self.replace.is_none() && self.dedupe_fp_rate.is_none()
+    }
+
+    /// Whether `line` passes the filter.
+    fn keeps(&self, line: &str) -> bool {
+        self.grep
+            .as_ref()
+            .is_none_or(|re| re.is_match(line.trim_end_matches(['\r', '\n'])))
+    }
+
+    /// A fresh approximate deduplication for one input, if enabled.
+    fn approx_dedupe(&self) -> Option<ApproxDedupe> {
+        self.dedupe_fp_rate.map(|fp_rate| ApproxDedupe {
+            filter: ScalableBloomFilter::with_rate(self.dedupe_capacity, fp_rate),
+            fp_rate,
+            lines_removed: 0,
+        })
+    }
+
+    /// Applies the filter, substitution and deduplication, returning the result, the number
+    /// of lines kept by the filter and the deduplication outcome.
+    fn apply(&self, content: &str) -> (String, usize, Option<DedupeReport>) {
+        let (filtered, matched) = match &self.grep {
+            Some(_) => {
+                let kept: Vec<&str> = content
+                    .split_inclusive('\n')
+                    .filter(|line| self.keeps(line))
+                    .collect();
+                let count = kept.len();
+                (kept.concat(), count)
+            }
+            None => (content.to_string(), content.lines().count()),
+        };
+
+        let replaced = match &self.replace {
+            Some((re, replacement)) => re.replace_all(&filtered, replacement.as_str()).into_owned(),
+            None => filtered,
+        };
+
+        match self.approx_dedupe() {
+            Some(mut dedupe) => {
+                let deduped = replaced
+                    .split_inclusive('\n')
+                    .filter(|line| dedupe.keeps(line))
+                    .collect();
+                (deduped, matched, Some(dedupe.report()))
+            }
+            None => (replaced, matched, None),
+        }
+    }
+}
+
+/// Drops lines a Bloom filter has (probably) seen, one line at a time, so that only the
+/// filter is held however long the input is.
+struct ApproxDedupe {
+    filter: ScalableBloomFilter,
+    fp_rate: f64,
+    lines_removed: usize,
+}
+
+impl ApproxDedupe {
+    /// Whether `line` is the first with its text, counting it as removed if not.
+    fn keeps(&mut self, line: &str) -> bool {
+        let new = self.filter.insert(line.trim_end_matches(['\r', '\n']));
+        if !new {
+            self.lines_removed += 1;
+        }
+        new
+    }
+
+    fn report(&self) -> DedupeReport {
+        DedupeReport {
+            fp_rate: self.fp_rate,
+            lines_removed: self.lines_removed,
+            filter_bytes: self.filter.size_bytes(),
+        }
+    }
+}
+
+/// The lines of an input that pass the filter and approximate deduplication of some
+/// [`TextOps`], read as they are needed. A `--replace` pattern may match across lines,
+/// so it reads inputs whole instead.
+struct FilteredLines<'a> {
+    input: &'a mut dyn BufRead,
+    ops: &'a TextOps,
+    dedupe: Option<ApproxDedupe>,
+    line: Vec<u8>,
+    /// How much of `line` was read
+    read: usize,
+    /// Lines kept so far
+    matched: usize,
+}
+
+impl<'a> FilteredLines<'a> {
+    fn new(input: &'a mut dyn BufRead, ops: &'a TextOps) -> Self {
+        Self {
+            input,
+            ops,
+            dedupe: ops.approx_dedupe(),
+            line: Vec::new(),
+            read: 0,
+            matched: 0,
+        }
+    }
+}
+
+impl Read for FilteredLines<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read == self.line.len() {
+            self.line.clear();
+            self.read = 0;
+            if self.input.read_until(b'\n', &mut self.line)? == 0 {
+                return Ok(0);
+            }
+            let line = String::from_utf8_lossy(&self.line);
+            let kept = self.ops.keeps(&line) && {
+                self.matched += 1;
+                self.dedupe
+                    .as_mut()
+                    .is_none_or(|dedupe| dedupe.keeps(&line))
+            };
+            if !kept {
+                self.line.clear();
+            }
+        }
+        let len = buf.len().min(self.line.len() - self.read);
+        buf[..len].copy_from_slice(&self.line[self.read..self.read + len]);
+        self.read += len;
+        Ok(len)
+    }
+}
+
+/// What is applied to every input of a run.
+struct Processing {
+    ops: TextOps,
+    pipeline: Pipeline,
+    thresholds: Thresholds,
+    limiter: IoLimiter,
+    /// Output compression from --compress or the profile
+    compress: Option<OutputCompression>,
+    /// Level and threads of output compression
+    encoder: Encoder,
+    /// Values to extract with --query
+    query: Option<JsonPath>,
+    /// Largest input in bytes read into memory (--max-memory or the profile)
+    max_memory: Option<u64>,
+    /// Where statistics are reused from, when the result cache is enabled
+    cache: Option<ResultCache>,
+}
+
+impl Processing {
+    /// The encoder for `output` (`None` for stdout): the chosen compression, else the one
+    /// the extension implies.
+    fn encoder(&self, output: Option<&Path>) -> Encoder {
+        let format = match self.compress {
+            Some(compress) => compress.format(),
+            None => output.and_then(Compression::from_path),
+        };
+        Encoder {
+            format,
+            ..self.encoder
+        }
+    }
+}
+
+/// CI gate limits checked against every input.
+struct Thresholds {
+    lines: Option<usize>,
+    bytes: Option<usize>,
+    matches: Option<Regex>,
 }
 
-pub fn execute(args: Args) -> Result<()> {
+/// A threshold that an input tripped.
+#[derive(Debug, Serialize)]
+struct Violation {
+    rule: &'static str,
+    message: String,
+}
+
+impl Thresholds {
+    fn from_args(args: &Args) -> Result<Self> {
+        Ok(Self {
+            lines: args.fail_if_lines_over,
+            bytes: args.fail_if_bytes_over,
+            matches: args
+                .fail_if_matches
+                .as_deref()
+                .map(Regex::new)
+                .transpose()?,
+        })
+    }
+
+    /// Checks `stats` (and `content`, for text inputs) against the limits.
+    fn check(&self, stats: &FileStats, content: Option<&str>) -> Vec<Violation> {
+        let mut violations = self.check_counts(stats, content.is_some());
+        let Some(content) = content else {
+            return violations;
+        };
+        if let Some(re) = &self.matches {
+            let matched = content.lines().filter(|line| re.is_match(line)).count();
+            if matched > 0 {
+                violations.push(Violation {
+                    rule: "matches",
+                    message: format!("{} line(s) match '{}'", matched, re.as_str()),
+                });
+            }
+        }
+        violations
+    }
+
+    /// Checks the byte count, and the line count of `text` inputs, against the limits.
+    fn check_counts(&self, stats: &FileStats, text: bool) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if let Some(limit) = self.bytes.filter(|limit| stats.bytes > *limit) {
+            violations.push(Violation {
+                rule: "bytes-over",
+                message: format!("{} bytes exceeds limit of {}", stats.bytes, limit),
+            });
+        }
+        if let Some(limit) = self.lines.filter(|limit| text && stats.lines > *limit) {
+            violations.push(Violation {
+                rule: "lines-over",
+                message: format!("{} lines exceeds limit of {}", stats.lines, limit),
+            });
+        }
+        violations
+    }
+}
+
+/// Result of processing a single input file.
+#[derive(Debug, Serialize)]
+struct FileReport {
+    path: PathBuf,
+    /// Whether the statistics came from the result cache
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cached: bool,
+    /// Whether the input is unchanged since the last --since-last run, which reported it
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    unchanged: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    binary: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    skipped: bool,
+    encoding: Encoding,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compression: Option<Compression>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest: Option<FileDigest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<PathBuf>,
+    /// Output that was left alone because it was modified since the last run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kept_modified: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matching_lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dedupe: Option<DedupeReport>,
+    /// Values printed for --query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_matches: Option<usize>,
+    stats: FileStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    table: Option<TableStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json: Option<JsonStats>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stages: Vec<StageMetrics>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    violations: Vec<Violation>,
+    /// Size of the processed output, whether or not it was written
+    #[serde(skip)]
+    bytes_out: usize,
+}
+
+impl FileReport {
+    /// A report of a text input with the given statistics and nothing else to note.
+    fn new(path: &Path, stats: FileStats) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            cached: false,
+            unchanged: false,
+            binary: false,
+            skipped: false,
+            encoding: Encoding::Auto,
+            compression: None,
+            digest: None,
+            output: None,
+            kept_modified: None,
+            matching_lines: None,
+            dedupe: None,
+            query_matches: None,
+            stats,
+            table: None,
+            json: None,
+            stages: Vec::new(),
+            violations: Vec::new(),
+            bytes_out: 0,
+        }
+    }
+}
+
+/// Everything `run` reports, rendered as text or serialized as JSON.
+#[derive(Debug, Serialize)]
+struct RunReport {
+    files: Vec<FileReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    skipped_paths: Vec<FsWarning>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed: Vec<FailedFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throughput: Option<Throughput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<Summary>,
+}
+
+/// Processing rate measured with `--null-output`.
+#[derive(Debug, Serialize)]
+struct Throughput {
+    files: usize,
+    bytes_in: u64,
+    bytes_out: u64,
+    seconds: f64,
+    mb_per_sec: f64,
+}
+
+/// File I/O of a run, reported with `--summary`.
+#[derive(Debug, Serialize)]
+struct Summary {
+    files: usize,
+    bytes_read: u64,
+    bytes_written: u64,
+    seconds: f64,
+    /// Bytes read and written per second over the whole run
+    mb_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    io_limit_mb_per_sec: Option<f64>,
+    /// Time spent waiting to stay within --io-limit
+    throttled_seconds: f64,
+}
+
+/// A path that could not be read and was skipped.
+#[derive(Debug, Serialize)]
+struct FsWarning {
+    path: PathBuf,
+    kind: &'static str,
+    message: String,
+}
+
+/// An input that could not be processed, with `--error-policy continue|summarize`.
+#[derive(Debug, Serialize)]
+struct FailedFile {
+    path: PathBuf,
+    /// What went wrong, to count failures by: the kind of an I/O error, the format of
+    /// invalid input, else the error's code
+    kind: String,
+    message: String,
+}
+
+impl FailedFile {
+    fn new(path: &Path, error: &Error) -> Self {
+        let kind = match error {
+            Error::Io(e) | Error::File { source: e, .. } => e.kind().to_string(),
+            Error::InvalidInput { format, .. } => format!("invalid {}", format),
+            _ => error.code().to_string(),
+        };
+        Self {
+            path: path.to_path_buf(),
+            kind,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Error handling policy and collected warnings for filesystem access.
+struct Walk {
+    strict: bool,
+    warnings: Vec<FsWarning>,
+}
+
+impl Walk {
+    fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn lenient() -> Self {
+        Self::new(false)
+    }
+
+    /// Records `error` for `path` as a warning, or returns it in strict mode or
+    /// when it is not a recoverable access problem.
+    fn tolerate(
+        &mut self,
+        path: &Path,
+        error: std::io::Error,
+        kind: Option<&'static str>,
+    ) -> Result<()> {
+        let kind = kind.or(match error.kind() {
+            ErrorKind::PermissionDenied => Some("permission-denied"),
+            ErrorKind::InvalidFilename => Some("name-too-long"),
+            ErrorKind::NotFound => Some("vanished"),
+            _ => None,
+        });
+
+        match kind {
+            Some(kind) if !self.strict => {
+                warn!("Skipping {}: {}", path.display(), error);
+                self.warnings.push(FsWarning {
+                    path: path.to_path_buf(),
+                    kind,
+                    message: error.to_string(),
+                });
+                Ok(())
+            }
+            _ => Err(Error::Io(error)),
+        }
+    }
+}
+
+pub async fn execute(mut args: Args, ctx: &Context) -> Result<()> {
+    if let Some(path) = &args.batch {
+        return run_batch(path, args.plan, ctx);
+    }
     info!("Processing file: {}", args.input);
 
+    // Compile patterns and pipelines before touching the filesystem so typos fail fast
+    let ops = TextOps::from_args(&args)?;
+    let thresholds = Thresholds::from_args(&args)?;
+    let query = args.query.as_deref().map(JsonPath::parse).transpose()?;
+    if args.since_last && args.mode != Mode::Text {
+        return Err(Error::Other(String::from(
+            "--since-last supports only --mode text",
+        )));
+    }
+    // Stages requested by flags of their own run after the pipeline
+    let mut flag_stages = Vec::new();
+    match (args.sort, args.unique) {
+        (true, unique) => flag_stages.push(Transform::SortLines { unique }),
+        (false, true) => flag_stages.push(Transform::DedupeLines),
+        (false, false) => {}
+    }
+    if let Some(to) = args.fix_line_endings {
+        flag_stages.push(Transform::LineEndings { to });
+    }
+    let mut pipeline = match &args.transform {
+        Some(definition) => Pipeline::parse(definition, &ctx.config.pipelines)?,
+        // Default example processing: uppercase conversion
+        None if ops.is_empty() && flag_stages.is_empty() => Pipeline {
+            stages: vec![Transform::Uppercase],
+        },
+        None => Pipeline::default(),
+    };
+    pipeline.stages.extend(flag_stages);
+    if args.sample.is_some() && args.seed.is_none() {
+        let seed = transform::random_seed();
+        info!(
+            "--sample using random seed {} (pass --seed {} to reproduce)",
+            seed, seed
+        );
+        args.seed = Some(seed);
+    }
+    pipeline.resolve_seeds(args.seed);
+
+    if args.text.as_deref() == Some("@-") {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        args.text = Some(text);
+    }
+
     // Check if file exists
-    if !Path::new(&args.input).exists() {
+    let input = Path::new(&args.input);
+    if args.text.is_none() && !input.exists() {
         warn!("File not found: {}", args.input);
-        return Err(Error::Other(format!("File not found: {}", args.input)));
+        return Err(Error::NotFound {
+            path: PathBuf::from(&args.input),
+        });
     }
 
-    // Read and process file
-    debug!("Reading file contents");
-    let content = fs::read_to_string(&args.input)?;
-    let line_count = content.lines().count();
-    let word_count = content.split_whitespace().count();
-    let byte_count = content.len();
+    // The report must not end up in the data written to stdout
+    let _redirect = match &args.output_report {
+        Some(path) => Some(ReportRedirect::new(Box::new(
+            fs::File::create(path).map_err(|source| Error::File {
+                action: "create report file",
+                path: path.clone(),
+                source,
+            })?,
+        ))),
+        None if args.output.as_deref() == Some(STDOUT_OUTPUT) || args.query.is_some() => {
+            Some(ReportRedirect::new(Box::new(std::io::stderr())))
+        }
+        None => None,
+    };
+
+    let directory = args.text.is_none() && input.is_dir();
+    if directory && args.output.is_some() {
+        return Err(Error::Other(String::from(
+            "--output expects a single file; use --output-dir for directory input",
+        )));
+    }
+    // Bare --output names and --emit-file outputs go to the active profile's output_dir
+    match &args.output {
+        Some(output) if is_bare_file_name(output) => {
+            if let Some(dir) = profile_output_dir(ctx)? {
+                args.output = Some(dir.join(output).to_string_lossy().into_owned());
+            }
+        }
+        None if args.emit_file => {
+            let dir = profile_output_dir(ctx)?.ok_or_else(|| {
+                Error::Other(String::from(
+                    "--emit-file needs an active profile with an output_dir",
+                ))
+            })?;
+            if directory {
+                args.output_dir = Some(dir);
+            } else {
+                let name = input.file_name().ok_or_else(|| {
+                    Error::Other(format!(
+                        "--emit-file cannot name an output for '{}'",
+                        args.input
+                    ))
+                })?;
+                args.output = Some(dir.join(name).to_string_lossy().into_owned());
+            }
+        }
+        _ => {}
+    }
+    let input = Path::new(&args.input);
+    let mut walk = Walk::new(args.strict_fs);
+    let files = if let Some(selection) = &args.files_from_git {
+        if !directory {
+            return Err(Error::Other(String::from(
+                "--files-from-git requires --input to be a directory",
+            )));
+        }
+        let files = args.filter_files(input, git::select_files(input, selection)?)?;
+        info!("Selected {} files from git ({})", files.len(), selection);
+        files
+    } else if directory {
+        let files = args.filter_files(input, collect_files(input, &mut walk)?)?;
+        info!("Found {} files in {}", files.len(), input.display());
+        files
+    } else {
+        vec![input.to_path_buf()]
+    };
+
+    let outputs = match (&args.output, &args.output_dir) {
+        _ if args.stats_only => vec![None; files.len()],
+        (Some(output), _) => vec![Some(PathBuf::from(output))],
+        (None, Some(dir)) if directory => plan_outputs(input, &files, dir, args.flatten),
+        (None, Some(dir)) => vec![input.file_name().map(|name| dir.join(name))],
+        (None, None) => vec![None; files.len()],
+    };
+
+    // Outputs are tracked per output directory (or the directory of a single --output)
+    let manifest_root = match (&args.output, &args.output_dir) {
+        _ if args.stats_only => None,
+        (Some(output), _) if output == STDOUT_OUTPUT => None,
+        (Some(output), _) => Some(
+            Path::new(output)
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .to_path_buf(),
+        ),
+        (None, Some(dir)) => Some(dir.clone()),
+        (None, None) => None,
+    };
+    if let Some(root) = &manifest_root {
+        let min_free_mb = args.min_free_space.or_else(|| {
+            ctx.config
+                .active_profile()
+                .and_then(|profile| profile.min_free_space_mb)
+        });
+        let estimate = match &args.text {
+            Some(text) => text.len() as u64,
+            None => files
+                .iter()
+                .filter_map(|file| fs::metadata(file).ok())
+                .map(|metadata| metadata.len())
+                .sum(),
+        };
+        check_free_space(root, estimate, min_free_mb)?;
+    }
+    // Another run writing to the same output directory would race on files and manifest
+    let _lock = match &args.output_dir {
+        Some(dir) if manifest_root.is_some() => lock::output_dir(dir, ctx.lock_policy)?,
+        _ => None,
+    };
+    let guard = Mutex::new(manifest_root.map(|root| {
+        OutputGuard::new(&root, args.on_modified, ctx.prompter, args.output.is_some())
+    }));
+
+    let mut report = RunReport {
+        files: Vec::new(),
+        skipped_paths: Vec::new(),
+        failed: Vec::new(),
+        throughput: None,
+        summary: None,
+    };
+    if let Some(limit) = args.io_limit {
+        info!("Limiting file I/O to {} MB/s", limit);
+    }
+    let profile = ctx.config.active_profile();
+    let parallel_jobs = profile.map_or(1, |profile| profile.parallel_jobs.max(1));
+    let mut processing = Processing {
+        ops,
+        pipeline,
+        thresholds,
+        limiter: IoLimiter::new(args.io_limit),
+        compress: args
+            .compress
+            .or(profile.and_then(|profile| profile.compress)),
+        encoder: Encoder {
+            format: None,
+            level: args
+                .compression_level
+                .or(profile.and_then(|profile| profile.compression_level)),
+            threads: args.threads.unwrap_or(parallel_jobs).min(parallel_jobs),
+        },
+        query,
+        max_memory: args
+            .max_memory
+            .or(profile.and_then(|profile| profile.max_memory_mb))
+            .map(|mb| mb.saturating_mul(MB)),
+        cache: ((args.cache || ctx.config.cache.enabled) && !args.no_cache && args.is_cacheable())
+            .then(ResultCache::open)
+            .flatten(),
+    };
+    // A level the format does not accept fails the run before anything is written
+    if let Some(level) = processing.encoder.level {
+        for output in outputs.iter().flatten() {
+            let output = Some(output.as_path()).filter(|o| *o != Path::new(STDOUT_OUTPUT));
+            if let Some(format) = processing.encoder(output).format {
+                format.check_level(level).map_err(Error::Other)?;
+            }
+        }
+    }
+    let state = Mutex::new(match args.since_last {
+        true => Some(open_state(&args, ctx)?),
+        false => None,
+    });
+    let walk = Mutex::new(walk);
+    // Query values are printed as they are found, so files take turns to keep them in order
+    let workers = match &processing.query {
+        Some(_) => 1,
+        None => (processing.encoder.threads as usize).clamp(1, files.len().max(1)),
+    };
+    // The threads are shared out between the files compressed at the same time
+    processing.encoder.threads = (processing.encoder.threads / workers as u32).max(1);
+    // A single input has nothing to continue with
+    let collect_failures = directory && args.error_policy != ErrorPolicy::FailFast;
+    let started = Instant::now();
+    let _cooperating = cancel::cooperate();
+    let outcome = cancel::blocking(|| {
+        Queue::new(workers).fail_fast(!collect_failures).run(
+            files.iter().zip(outputs),
+            |(file, output)| match process_incremental(
+                &args,
+                &processing,
+                &state,
+                file,
+                output,
+                &guard,
+            ) {
+                Ok(file_report) => Ok(Some(file_report)),
+                Err(_) if cancel::is_cancelled() => Err(Error::Cancelled),
+                Err(e) => {
+                    // Unreadable files are skipped like unreadable paths of the walk, so
+                    // only with --strict-fs are they failures the policy applies to
+                    let e = match e {
+                        Error::Io(e) if directory => {
+                            let mut walk = walk.lock().unwrap_or_else(|e| e.into_inner());
+                            match walk.tolerate(file, e, None) {
+                                Ok(()) => return Ok(None),
+                                Err(e) => e,
+                            }
+                        }
+                        e => e,
+                    };
+                    match args.error_policy {
+                        ErrorPolicy::Continue if collect_failures => {
+                            error!("Failed to process {}: {}", file.display(), e)
+                        }
+                        _ => debug!("Failed to process {}: {}", file.display(), e),
+                    }
+                    Err(e)
+                }
+            },
+            |progress| debug!("Processed {} of {} files", progress.done, files.len()),
+        )
+    });
+    // Remember what was written even when a later file failed
+    if let Some(guard) = &*guard.lock().unwrap_or_else(|e| e.into_inner()) {
+        guard.save()?;
+    }
+    let (processed, failures) = match collect_failures {
+        true => outcome.collect()?,
+        false => (outcome.into_result()?, Vec::new()),
+    };
+    report.files.extend(processed.into_iter().flatten());
+    report.failed = failures
+        .iter()
+        .map(|(index, error)| FailedFile::new(&files[*index], error))
+        .collect();
+    if let Some(state) = &*state.lock().unwrap_or_else(|e| e.into_inner()) {
+        state.save()?;
+    }
+    report.skipped_paths = walk
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .warnings;
+    if let Some(key) = args.sort_by {
+        report
+            .files
+            .sort_by_key(|file| std::cmp::Reverse(key.count(&file.stats)));
+    }
+    info!("Processing complete: {} files", report.files.len());
+
+    if args.null_output {
+        let seconds = started.elapsed().as_secs_f64();
+        let bytes_in = report.files.iter().map(|f| f.stats.bytes as u64).sum();
+        report.throughput = Some(Throughput {
+            files: report.files.len(),
+            bytes_in,
+            bytes_out: report.files.iter().map(|f| f.bytes_out as u64).sum(),
+            seconds,
+            mb_per_sec: throughput_mb_per_sec(bytes_in, seconds),
+        });
+    }
+
+    if args.summary {
+        let seconds = started.elapsed().as_secs_f64();
+        let limiter = &processing.limiter;
+        let usage = limiter.usage();
+        report.summary = Some(Summary {
+            files: report.files.len(),
+            bytes_read: usage.read,
+            bytes_written: usage.written,
+            seconds,
+            mb_per_sec: throughput_mb_per_sec(usage.read + usage.written, seconds),
+            io_limit_mb_per_sec: limiter.limit_mb_per_sec(),
+            throttled_seconds: usage.throttled.as_secs_f64(),
+        });
+    }
+
+    match (args.stats_format, ctx.format) {
+        (Some(format), _) => print_stats_report(&stats_report(&report), format, ctx.reporter)?,
+        (None, OutputFormat::Json) => print_json(&report)?,
+        (None, OutputFormat::Text | OutputFormat::Markdown) => {
+            render_text(&args, &report, directory, ctx.reporter);
+            if let Some(summary) = &report.summary {
+                print_summary(summary);
+            }
+        }
+    }
+
+    // Thresholds and failures are enforced after reporting so CI logs still show the
+    // statistics
+    let mut errors: Vec<Error> = report
+        .files
+        .iter()
+        .flat_map(|file| {
+            file.violations.iter().map(|violation| {
+                Error::ThresholdExceeded(format!("{}: {}", file.path.display(), violation.message))
+            })
+        })
+        .collect();
+    if !report.failed.is_empty() {
+        errors.push(Error::Other(format!(
+            "{} of {} files could not be processed",
+            report.failed.len(),
+            files.len()
+        )));
+    }
+    Error::aggregate(errors)
+}
+
+/// A batch job's arguments, parsed like those of `run`.
+#[derive(clap::Parser, Debug)]
+#[command(name = "run")]
+struct JobArgs {
+    #[command(flatten)]
+    args: Args,
+}
+
+impl JobArgs {
+    fn parse(job: &Job) -> Result<Args> {
+        let invalid = |message: String| {
+            Error::Other(format!(
+                "Invalid arguments for batch job '{}': {}",
+                job.name, message
+            ))
+        };
+        let args = Args::parse_from(&job.args).map_err(invalid)?;
+        // Reports of concurrent jobs cannot each have a destination of their own
+        if args.batch.is_some() || args.output_report.is_some() {
+            return Err(invalid(String::from(
+                "--batch and --output-report cannot be used in batch jobs",
+            )));
+        }
+        Ok(args)
+    }
+}
+
+impl Args {
+    /// Parses arguments of `run` given elsewhere than on the command line, e.g. in a batch
+    /// file or the configuration, failing with the first line of clap's message.
+    pub fn parse_from(args: &[String]) -> std::result::Result<Self, String> {
+        let parsed = <JobArgs as clap::Parser>::try_parse_from(
+            std::iter::once("run").chain(args.iter().map(String::as_str)),
+        )
+        .map_err(|e| {
+            let rendered = e.render().to_string();
+            let first = rendered.lines().next().unwrap_or_default();
+            first.trim_start_matches("error: ").to_string()
+        })?;
+        Ok(parsed.args)
+    }
+}
+
+/// The outcome of every job of a `--batch` run.
+#[derive(Debug, Serialize)]
+struct BatchReport {
+    jobs: Vec<JobOutcome>,
+}
+
+/// Runs (or with `plan`, prints the stages of) the batch at `path`.
+fn run_batch(path: &Path, plan: bool, ctx: &Context) -> Result<()> {
+    let spec = BatchSpec::load(path)?;
+    // Argument mistakes fail the batch before any job has run
+    let jobs = spec
+        .jobs
+        .iter()
+        .map(|job| JobArgs::parse(job).map(|_| ()))
+        .collect::<Vec<_>>();
+    Error::aggregate(jobs.into_iter().filter_map(Result::err).collect())?;
+    let workers = ctx
+        .config
+        .active_profile()
+        .map_or(1, |profile| profile.parallel_jobs.max(1) as usize);
+
+    if plan {
+        return print_plan(&spec, workers, ctx);
+    }
+
+    info!(
+        "Running {} batch jobs from {}, up to {} at a time",
+        spec.jobs.len(),
+        path.display(),
+        workers
+    );
+    let runtime = tokio::runtime::Handle::current();
+    // With one worker the queue runs jobs on this thread, which must leave the runtime
+    // before blocking on it
+    let outcomes = cancel::blocking(|| {
+        spec.execute(workers, |job| {
+            let mut args = JobArgs::parse(job)?;
+            // Jobs already run in parallel, one per allowed job
+            args.threads = Some(1);
+            runtime.block_on(execute(args, ctx))
+        })
+    });
+
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome.status, JobStatus::Failed(_)))
+        .count();
+    let skipped = outcomes
+        .iter()
+        .filter(|outcome| outcome.status == JobStatus::Skipped)
+        .count();
+    let total = outcomes.len();
+    match ctx.format {
+        OutputFormat::Json => print_json(&BatchReport { jobs: outcomes })?,
+        OutputFormat::Text | OutputFormat::Markdown => {
+            reportln!("{}", tr!("run-batch", path = path.display()));
+            let rows: Vec<Vec<String>> = outcomes
+                .iter()
+                .map(|outcome| {
+                    let status = match &outcome.status {
+                        JobStatus::Succeeded => tr!("run-job-succeeded"),
+                        JobStatus::Failed(error) => tr!("run-job-failed", error = error),
+                        JobStatus::Skipped => tr!("run-job-skipped"),
+                    };
+                    vec![
+                        outcome.name.clone(),
+                        status,
+                        format!("{:.3} s", outcome.seconds),
+                    ]
+                })
+                .collect();
+            let headers = [tr!("header-job"), tr!("header-status"), tr!("header-time")];
+            ctx.reporter.table("  ", &headers, &rows);
+            if failed == 0 {
+                ctx.reporter
+                    .success(&tr!("run-batch-succeeded", count = total));
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(Error::Other(tr!(
+            "run-batch-failed",
+            failed = failed,
+            total = total,
+            skipped = skipped
+        )));
+    }
+    Ok(())
+}
+
+/// Prints the stages `spec` runs in.
+fn print_plan(spec: &BatchSpec, workers: usize, ctx: &Context) -> Result<()> {
+    let stages = spec.stages();
+    if ctx.format == OutputFormat::Json {
+        let names = |jobs: &Vec<usize>| -> Vec<&str> {
+            jobs.iter()
+                .map(|&job| spec.jobs[job].name.as_str())
+                .collect()
+        };
+        let stages: Vec<Vec<&str>> = stages.iter().map(names).collect();
+        return print_json(&serde_json::json!({
+            "parallel_jobs": workers,
+            "stages": stages,
+        }));
+    }
+    reportln!(
+        "{}",
+        tr!(
+            "run-batch-plan",
+            jobs = spec.jobs.len(),
+            stages = stages.len(),
+            workers = workers
+        )
+    );
+    let rows: Vec<Vec<String>> = stages
+        .iter()
+        .enumerate()
+        .flat_map(|(stage, jobs)| {
+            jobs.iter().map(move |&job| {
+                vec![
+                    (stage + 1).to_string(),
+                    spec.jobs[job].name.clone(),
+                    spec.dependencies(job).join(", "),
+                ]
+            })
+        })
+        .collect();
+    let headers = [
+        tr!("header-stage"),
+        tr!("header-job"),
+        tr!("header-depends-on"),
+    ];
+    ctx.reporter.table("  ", &headers, &rows);
+    Ok(())
+}
+
+/// Checks before anything is written that outputs of about `estimate` bytes fit in `dir`,
+/// leaving `min_free_mb` MB free if set. A shortfall fails the run when a minimum is set
+/// and is otherwise a warning.
+fn check_free_space(dir: &Path, estimate: u64, min_free_mb: Option<u64>) -> Result<()> {
+    let target = disk::existing_ancestor(dir);
+    let Some(available) = disk::available_space(target) else {
+        debug!("Free space of {} is unknown", target.display());
+        return Ok(());
+    };
+    let needed = estimate.saturating_add(min_free_mb.unwrap_or(0).saturating_mul(MB));
+    debug!(
+        "{} bytes free in {}, about {} bytes needed",
+        available,
+        target.display(),
+        needed
+    );
+    if available >= needed {
+        return Ok(());
+    }
+    match min_free_mb {
+        Some(min_free_mb) => Err(Error::Other(format!(
+            "Not enough free space in '{}': {} MB available, but the outputs need about {} MB and {} MB must stay free (--min-free-space)",
+            dir.display(),
+            available / MB,
+            estimate.div_ceil(MB),
+            min_free_mb
+        ))),
+        None => {
+            warn!(
+                "Outputs of about {} MB may not fit in '{}' ({} MB available)",
+                estimate.div_ceil(MB),
+                dir.display(),
+                available / MB
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Bytes inspected to tell whether `--tail` can split an input at line breaks.
+const SNIFF_LEN: u64 = 4096;
+
+/// Fails unless `head`, the start of an input, is in an encoding whose line breaks are
+/// single `\n` bytes, as `option` (`--tail`, `--head` or `--sample`) requires.
+fn check_line_encoding(head: &[u8], encoding: Encoding, path: &Path, option: &str) -> Result<()> {
+    let head = &head[..head.len().min(SNIFF_LEN as usize)];
+    match encoding::decode(head, encoding).encoding {
+        detected @ (Encoding::Utf16Le | Encoding::Utf16Be) => Err(Error::Other(format!(
+            "{} does not support {} input: {}",
+            option,
+            detected,
+            path.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Keeps an input of `size` bytes above `max_memory` out of memory: it is streamed
+/// instead, or the run fails if the input has to be read whole.
+fn limit_memory(
+    args: &Args,
+    max_memory: Option<u64>,
+    path: &Path,
+    size: u64,
+    strategy: Strategy,
+) -> Result<Strategy> {
+    // --text is in memory already, and --head and --tail keep only some lines
+    let exempt = args.text.is_some() || args.head.is_some() || args.tail.is_some();
+    let Some(max_memory) = max_memory.filter(|max| size > *max && !exempt) else {
+        return Ok(strategy);
+    };
+    if strategy != Strategy::Memory {
+        return Ok(strategy);
+    }
+    let reason = match args.sample {
+        Some(_) => Some("--sample"),
+        None => args.needs_whole_input(),
+    };
+    match reason {
+        Some(reason) => Err(Error::Other(format!(
+            "{} is {} MB, more than --max-memory allows ({} MB), but {} needs the whole input in memory",
+            path.display(),
+            size.div_ceil(MB),
+            max_memory / MB,
+            reason
+        ))),
+        None => {
+            warn!(
+                "{} is {} MB, more than --max-memory allows ({} MB); streaming it instead",
+                path.display(),
+                size.div_ceil(MB),
+                max_memory / MB
+            );
+            Ok(Strategy::Streaming)
+        }
+    }
+}
+
+/// Reads from `reader` up to and including the `lines`th line break.
+fn read_head(reader: impl Read, lines: usize, raw: &mut Vec<u8>) -> io::Result<()> {
+    let mut reader = io::BufReader::new(reader);
+    for _ in 0..lines {
+        if reader.read_until(b'\n', raw)? == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Length of the first `lines` lines of `raw`, including their line breaks.
+fn head_len(raw: &[u8], lines: usize) -> usize {
+    match lines.checked_sub(1) {
+        Some(last) => raw
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == b'\n')
+            .nth(last)
+            .map_or(raw.len(), |(i, _)| i + 1),
+        None => 0,
+    }
+}
+
+/// A random `percent` of the lines of `raw`, in input order.
+fn sample_lines(raw: &[u8], percent: f64, seed: u64) -> Vec<u8> {
+    let lines: Vec<&[u8]> = raw.split_inclusive(|&b| b == b'\n').collect();
+    let n = (lines.len() as f64 * percent / 100.0).ceil() as usize;
+    transform::sample(lines, n, seed).concat()
+}
+
+/// Options a cached result depends on, besides the input itself.
+#[derive(Serialize)]
+struct CacheOptions {
+    version: &'static str,
+    encoding: Encoding,
+    top_words: Option<usize>,
+    advanced_stats: bool,
+    whitespace_stats: bool,
+}
+
+/// What the result cache keeps of an input's report.
+#[derive(Serialize, Deserialize)]
+struct CachedStats {
+    encoding: Encoding,
+    stats: FileStats,
+}
+
+/// What `--since-last` keeps of an input's report.
+#[derive(Clone, Serialize, Deserialize)]
+struct SeenFile {
+    binary: bool,
+    skipped: bool,
+    encoding: Encoding,
+    compression: Option<Compression>,
+    digest: Option<FileDigest>,
+    output: Option<PathBuf>,
+    stats: FileStats,
+}
+
+/// The state of earlier runs with the same version, input, configuration and arguments.
+fn open_state(args: &Args, ctx: &Context) -> Result<StateFile<SeenFile>> {
+    let input = fs::canonicalize(&args.input).map_err(|source| Error::File {
+        action: "resolve",
+        path: PathBuf::from(&args.input),
+        source,
+    })?;
+    // Going through a `Value` sorts the keys of the profile map
+    let config = serde_json::to_value(&ctx.config)?;
+    let invocation = format!(
+        "{}\n{}\n{}\n{:?}",
+        constants::APP_VERSION,
+        input.display(),
+        config,
+        args
+    );
+    let key = digest::digest_bytes(invocation.as_bytes(), HashAlgorithm::Blake3).hex;
+    StateFile::open(&key).ok_or_else(|| {
+        Error::Other(String::from(
+            "--since-last needs a cache directory for its state",
+        ))
+    })
+}
+
+/// Processes a single file unless `--since-last` finds it unchanged since the last run,
+/// in which case it is reported as it was then.
+fn process_incremental(
+    args: &Args,
+    processing: &Processing,
+    state: &Mutex<Option<StateFile<SeenFile>>>,
+    path: &Path,
+    output: Option<PathBuf>,
+    guard: &Mutex<Option<OutputGuard>>,
+) -> Result<FileReport> {
+    let unchanged = state
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+        .map(|state| state.unchanged(path))
+        .transpose()?;
+    let Some(unchanged) = unchanged else {
+        return process_cached(args, processing, path, output, guard);
+    };
+    // An output deleted since, or one that went to stdout, is written again
+    if let Some(seen) = unchanged.filter(|seen| {
+        seen.output
+            .as_deref()
+            .is_none_or(|output| output != Path::new(STDOUT_OUTPUT) && output.exists())
+    }) {
+        debug!("{} is unchanged since the last run", path.display());
+        return Ok(FileReport {
+            unchanged: true,
+            binary: seen.binary,
+            skipped: seen.skipped,
+            encoding: seen.encoding,
+            compression: seen.compression,
+            digest: seen.digest,
+            output: seen.output,
+            ..FileReport::new(path, seen.stats)
+        });
+    }
+
+    let report = process_cached(args, processing, path, output, guard)?;
+    // Inputs failing a threshold, or whose output was kept, are looked at again every run
+    if report.violations.is_empty() && report.kept_modified.is_none() {
+        let seen = SeenFile {
+            binary: report.binary,
+            skipped: report.skipped,
+            encoding: report.encoding,
+            compression: report.compression,
+            digest: report.digest.clone(),
+            output: report.output.clone(),
+            stats: report.stats.clone(),
+        };
+        if let Some(state) = state.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            state.record(path, seen)?;
+        }
+    }
+    Ok(report)
+}
+
+/// Processes a single file, taking its statistics from the result cache if the run uses
+/// it and they are there, and storing them there otherwise.
+fn process_cached(
+    args: &Args,
+    processing: &Processing,
+    path: &Path,
+    output: Option<PathBuf>,
+    guard: &Mutex<Option<OutputGuard>>,
+) -> Result<FileReport> {
+    let Some(cache) = &processing.cache else {
+        return process_file(args, processing, path, output, guard);
+    };
+    let options = CacheOptions {
+        version: constants::APP_VERSION,
+        encoding: args.encoding,
+        top_words: args.top_words,
+        advanced_stats: args.advanced_stats,
+        whitespace_stats: args.whitespace_stats,
+    };
+    let key = ResultCache::key(path, &options)?;
+    if let Some(CachedStats { encoding, stats }) = cache.get(&key) {
+        debug!("Using cached statistics for {}", path.display());
+        return Ok(FileReport {
+            cached: true,
+            encoding,
+            compression: Compression::from_path(path),
+            // Cached statistics are of text inputs, so line limits apply
+            violations: processing.thresholds.check_counts(&stats, true),
+            ..FileReport::new(path, stats)
+        });
+    }
+
+    let report = process_file(args, processing, path, output, guard)?;
+    if !report.binary {
+        let entry = CachedStats {
+            encoding: report.encoding,
+            stats: report.stats.clone(),
+        };
+        // A cache that cannot be written only costs time
+        if let Err(e) = cache.put(&key, &entry) {
+            warn!("Could not cache statistics of {}: {}", path.display(), e);
+        }
+    }
+    Ok(report)
+}
+
+/// Reads, analyzes and (unless `--stats-only`) transforms a single file.
+fn process_file(
+    args: &Args,
+    processing: &Processing,
+    path: &Path,
+    output: Option<PathBuf>,
+    guard: &Mutex<Option<OutputGuard>>,
+) -> Result<FileReport> {
+    let Processing {
+        ops,
+        pipeline,
+        thresholds,
+        limiter,
+        ..
+    } = processing;
+    let size = match &args.text {
+        Some(text) => text.len() as u64,
+        None => fs::metadata(path)?.len(),
+    };
+    let strategy = match args.strategy.resolve(size) {
+        // Only some lines are kept, and they are read into memory
+        _ if args.tail.is_some() || args.head.is_some() || args.sample.is_some() => {
+            Strategy::Memory
+        }
+        Strategy::Memory => Strategy::Memory,
+        chunked => match args.needs_whole_input() {
+            Some(reason) => {
+                debug!(
+                    "Not using {} strategy: {} needs the whole input",
+                    chunked, reason
+                );
+                Strategy::Memory
+            }
+            None => chunked,
+        },
+    };
+    let strategy = limit_memory(args, processing.max_memory, path, size, strategy)?;
+    debug!(
+        "Reading file contents: {} ({} bytes, {} strategy)",
+        path.display(),
+        size,
+        strategy
+    );
+    let read = tracing::trace_span!(target: timings::TARGET, "read").entered();
+    if strategy != Strategy::Memory {
+        if let Some(report) = process_chunked(args, thresholds, limiter, path, strategy)? {
+            drop(read);
+            if args.stats_only {
+                return Ok(report);
+            }
+            return process_streamed(args, processing, report, output, guard);
+        }
+        if processing.max_memory.is_some_and(|max| size > max) {
+            return Err(Error::Other(format!(
+                "{} is larger than --max-memory but is not UTF-8 text, so it cannot be streamed",
+                path.display()
+            )));
+        }
+        debug!(
+            "{} is not UTF-8 text; reading it into memory",
+            path.display()
+        );
+    }
+
+    // Inputs are decompressed while streaming; the digest covers the bytes on disk
+    let mut tail = args.tail;
+    let (compression, reader): (_, Box<dyn Read + '_>) = match &args.text {
+        Some(text) => (None, Box::new(text.as_bytes())),
+        None => {
+            let compression = Compression::from_path(path);
+            let mut file = limiter.reader(fs::File::open(path)?);
+            // Plain files are read from where their last lines start
+            if let Some(lines) = tail.filter(|_| compression.is_none() && args.hash.is_none()) {
+                let mut head = Vec::new();
+                Read::take(&mut file, SNIFF_LEN).read_to_end(&mut head)?;
+                check_line_encoding(&head, args.encoding, path, "--tail")?;
+                let start = revread::tail_start(&mut file, lines)?;
+                file.seek(SeekFrom::Start(start))?;
+                debug!(
+                    "Reading the last {} lines of {} from byte {}",
+                    lines,
+                    path.display(),
+                    start
+                );
+                tail = None;
+            }
+            (compression, Box::new(file))
+        }
+    };
+    let mut source = HashingReader::new(reader, args.hash);
+    let mut raw = Vec::new();
+    let mut decoder = compression::decoder(&mut source, compression)?;
+    match args.head {
+        // Reading stops after the first lines unless the digest needs every byte
+        Some(lines) if args.hash.is_none() => read_head(&mut decoder, lines, &mut raw)?,
+        _ => {
+            decoder.read_to_end(&mut raw)?;
+        }
+    }
+    drop(decoder);
+    let digest = source.finish();
+    if let Some(lines) = tail {
+        check_line_encoding(&raw, args.encoding, path, "--tail")?;
+        let start = revread::tail_start(&mut io::Cursor::new(&raw), lines)?;
+        raw.drain(..start as usize);
+    }
+    if let Some(lines) = args.head {
+        check_line_encoding(&raw, args.encoding, path, "--head")?;
+        raw.truncate(head_len(&raw, lines));
+    }
+    if let Some(percent) = args.sample {
+        check_line_encoding(&raw, args.encoding, path, "--sample")?;
+        raw = sample_lines(&raw, percent, args.seed.unwrap_or_default());
+    }
+    drop(read);
+    let process = tracing::trace_span!(target: timings::TARGET, "process").entered();
+
+    if encoding::is_binary(&raw, args.encoding) {
+        info!("Binary file detected: {}", path.display());
+        if args.binary == BinaryMode::Error {
+            return Err(Error::Other(format!("Binary file: {}", path.display())));
+        }
+        let stats = FileStats {
+            bytes: raw.len(),
+            ..FileStats::default()
+        };
+        return Ok(FileReport {
+            binary: true,
+            skipped: args.binary == BinaryMode::Skip,
+            compression,
+            digest,
+            violations: thresholds.check(&stats, None),
+            ..FileReport::new(path, stats)
+        });
+    }
+
+    let decoded = encoding::decode(&raw, args.encoding);
+    if decoded.lossy {
+        warn!(
+            "{} is not valid {}; invalid sequences were replaced",
+            path.display(),
+            decoded.encoding
+        );
+    }
+    let content = decoded.text;
+
+    let mut stats = FileStats::from_content(&content, args.top_words);
+    if args.advanced_stats {
+        stats.advanced = Some(AdvancedStats::compute(&content, &raw));
+    }
+    if args.whitespace_stats {
+        stats.whitespace = Some(WhitespaceStats::compute(&content));
+    }
+    // Report the raw (decompressed) size rather than the length of the decoded UTF-8 text
+    stats.bytes = raw.len();
 
     debug!(
         "File stats - lines: {}, words: {}, bytes: {}",
-        line_count, word_count, byte_count
+        stats.lines, stats.words, stats.bytes
     );
 
+    let mut report = FileReport {
+        encoding: decoded.encoding,
+        compression,
+        digest,
+        violations: thresholds.check(&stats, Some(&content)),
+        ..FileReport::new(path, stats)
+    };
+
+    if args.mode == Mode::Csv {
+        let delimiter = match args.delimiter {
+            Some(c) if c.is_ascii() => c as u8,
+            Some(c) => {
+                return Err(Error::Other(format!(
+                    "Delimiter must be a single ASCII character, got '{}'",
+                    c
+                )))
+            }
+            None => tabular::default_delimiter(path),
+        };
+        report.table = Some(tabular::analyze(content.as_bytes(), delimiter)?);
+    }
+    if args.mode == Mode::Json {
+        report.json = Some(jsonstats::analyze(&content)?);
+    }
+    if args.mode == Mode::Structured {
+        report.json = Some(jsonstats::analyze_structured(&content, is_yaml(path))?);
+    }
+    if let Some(query) = &processing.query {
+        let mut stdout = io::stdout().lock();
+        let mut matches = 0;
+        for document in jsonpath::parse_documents(&content, is_yaml(path))? {
+            for value in query.select(&document) {
+                writeln!(stdout, "{}", value)?;
+                matches += 1;
+            }
+        }
+        report.query_matches = Some(matches);
+    }
+
     if args.stats_only {
-        println!("File statistics for '{}':", args.input);
-        println!("  Lines: {}", line_count);
-        println!("  Words: {}", word_count);
-        println!("  Bytes: {}", byte_count);
+        return Ok(report);
+    }
+
+    let (filtered, matched, dedupe) = if ops.is_empty() {
+        (content, report.stats.lines, None)
     } else {
-        // Process the file (example: uppercase conversion)
-        let processed = if let Some(output) = args.output {
-            let uppercase_content = content.to_uppercase();
-            fs::write(&output, uppercase_content)?;
-            info!("Processed output written to: {}", output);
-            println!("[SUCCESS] Output written to: {}", output);
-            format!("Processed {} bytes to {}", byte_count, output)
+        ops.apply(&content)
+    };
+    report.dedupe = dedupe;
+    if ops.grep.is_some() {
+        report.matching_lines = Some(matched);
+    }
+
+    let (transformed, stages) = pipeline.apply(&filtered)?;
+    if args.transform.is_some() {
+        report.stages = stages;
+    }
+    report.bytes_out = transformed.len();
+    drop(process);
+
+    if let Some(output) = output
+        .as_ref()
+        .filter(|output| *output == Path::new(STDOUT_OUTPUT))
+    {
+        let _write = tracing::trace_span!(target: timings::TARGET, "write").entered();
+        let stdout = std::io::stdout().lock();
+        compression::encode(
+            limiter.writer(stdout),
+            transformed.as_bytes(),
+            &processing.encoder(None),
+        )?;
+        report.output = Some(output.clone());
+    } else if let Some(output) = output {
+        if let Some(guard) = guard.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            if !guard.allows(&output, || Ok(transformed.clone()))? {
+                report.kept_modified = Some(output);
+                return Ok(report);
+            }
+        }
+        if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(file_error("create output directory", parent))?;
+        }
+        let encoder = processing.encoder(Some(&output));
+        let _write = tracing::trace_span!(target: timings::TARGET, "write").entered();
+        fs::File::create(&output)
+            .and_then(|file| {
+                compression::encode(limiter.writer(file), transformed.as_bytes(), &encoder)
+            })
+            .map_err(file_error("write output", &output))?;
+        info!("Processed output written to: {}", output.display());
+        if let Some(guard) = guard.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            guard.record(&output, encoder.format)?;
+        }
+        report.output = Some(output);
+    }
+
+    Ok(report)
+}
+
+/// Transforms the input of `report`, whose statistics [`process_chunked`] computed, by
+/// reading it again and writing each line of output as it is produced. The first pass
+/// found the input to be UTF-8 throughout, so nothing is written before it is known that
+/// the input can be streamed.
+fn process_streamed(
+    args: &Args,
+    processing: &Processing,
+    mut report: FileReport,
+    output: Option<PathBuf>,
+    guard: &Mutex<Option<OutputGuard>>,
+) -> Result<FileReport> {
+    let _process = tracing::trace_span!(target: timings::TARGET, "process").entered();
+    let limiter = &processing.limiter;
+    let transform = |writer: &mut dyn Write| stream_transform(processing, &report.path, writer);
+    let streamed = match &output {
+        Some(output) if output == Path::new(STDOUT_OUTPUT) => {
+            let mut writer = EncodingWriter::new(
+                limiter.writer(io::stdout().lock()),
+                &processing.encoder(None),
+            )?;
+            let streamed = transform(&mut writer)?;
+            writer.finish()?;
+            streamed
+        }
+        Some(output) => {
+            if let Some(guard) = guard.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                let contents = || {
+                    let mut contents = Vec::new();
+                    transform(&mut contents)?;
+                    Ok(String::from_utf8_lossy(&contents).into_owned())
+                };
+                if !guard.allows(output, contents)? {
+                    report.kept_modified = Some(output.clone());
+                    return Ok(report);
+                }
+            }
+            if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)
+                    .map_err(file_error("create output directory", parent))?;
+            }
+            let encoder = processing.encoder(Some(output));
+            // An output replacing the input is written next to it and moved over it once
+            // the input has been read
+            let replaces_input = matches!(
+                (fs::canonicalize(output), fs::canonicalize(&report.path)),
+                (Ok(output), Ok(input)) if output == input
+            );
+            let (file, temp) = if replaces_input {
+                let dir = output.parent().filter(|p| !p.as_os_str().is_empty());
+                let temp = tempfile::NamedTempFile::new_in(dir.unwrap_or(Path::new(".")))
+                    .map_err(file_error("write output", output))?;
+                (temp.reopen()?, Some(temp))
+            } else {
+                let file = fs::File::create(output).map_err(file_error("write output", output))?;
+                (file, None)
+            };
+            let mut file = OutputWriter::new(limiter.writer(file));
+            let written = EncodingWriter::new(&mut file, &encoder)
+                .map_err(Error::from)
+                .and_then(|mut writer| {
+                    let streamed = transform(&mut writer)?;
+                    writer.finish()?;
+                    Ok(streamed)
+                });
+            let streamed = file.check(written, output)?;
+            if let Some(temp) = temp {
+                fs::metadata(output)
+                    .and_then(|metadata| fs::set_permissions(temp.path(), metadata.permissions()))
+                    .and_then(|()| temp.persist(output).map(drop).map_err(|e| e.error))
+                    .map_err(file_error("write output", output))?;
+            }
+            info!("Processed output written to: {}", output.display());
+            if let Some(guard) = guard.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                guard.record(output, encoder.format)?;
+            }
+            streamed
+        }
+        None => transform(&mut io::sink())?,
+    };
+    report.output = output;
+    if processing.ops.grep.is_some() {
+        report.matching_lines = Some(streamed.matched);
+    }
+    report.dedupe = streamed.dedupe;
+    if args.transform.is_some() {
+        report.stages = streamed.stages;
+    }
+    report.bytes_out = streamed.bytes_out;
+    Ok(report)
+}
+
+/// What streaming an input through the line operations and the pipeline produced.
+struct Streamed {
+    /// Lines kept by the filter
+    matched: usize,
+    dedupe: Option<DedupeReport>,
+    stages: Vec<StageMetrics>,
+    /// Bytes of output, before compression
+    bytes_out: usize,
+}
+
+/// Streams the lines of `path` through the line operations and the pipeline into `output`.
+fn stream_transform(
+    processing: &Processing,
+    path: &Path,
+    output: &mut dyn Write,
+) -> Result<Streamed> {
+    let file = processing.limiter.reader(fs::File::open(path)?);
+    let mut input = io::BufReader::new(compression::decoder(file, Compression::from_path(path))?);
+    // Like decoding in memory, leave out a byte order mark
+    if input.fill_buf()?.starts_with(b"\xEF\xBB\xBF") {
+        input.consume(3);
+    }
+    let mut filtered = io::BufReader::new(FilteredLines::new(&mut input, &processing.ops));
+    let mut output = transform::Counting::new(output);
+    let stages = processing
+        .pipeline
+        .apply_stream(&mut filtered, &mut output)?;
+    let filtered = filtered.get_ref();
+    Ok(Streamed {
+        matched: filtered.matched,
+        dedupe: filtered.dedupe.as_ref().map(ApproxDedupe::report),
+        stages,
+        bytes_out: output.bytes(),
+    })
+}
+
+/// A failure to write the output at `path`, told apart from failures to read inputs,
+/// which may be skipped.
+fn file_error(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Error {
+    let path = path.to_path_buf();
+    move |source| Error::File {
+        action,
+        path,
+        source,
+    }
+}
+
+/// Remembers the first failure to write to an output, so that an error while streaming
+/// into it is reported as a failure to write rather than to read the input.
+struct OutputWriter<W> {
+    inner: W,
+    failed: Option<io::Error>,
+}
+
+impl<W> OutputWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            failed: None,
+        }
+    }
+
+    /// `result`, with an error caused by a failure to write to `path` replaced by that
+    /// failure.
+    fn check<T>(self, result: Result<T>, path: &Path) -> Result<T> {
+        match (result, self.failed) {
+            (Err(_), Some(failed)) => Err(file_error("write output", path)(failed)),
+            (result, _) => result,
+        }
+    }
+
+    /// Keeps the first failure, passing on a copy of it.
+    fn remember<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        result.map_err(|e| {
+            let copy = io::Error::new(e.kind(), e.to_string());
+            self.failed.get_or_insert(e);
+            copy
+        })
+    }
+}
+
+impl<W: Write> Write for OutputWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.remember(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.flush();
+        self.remember(result)
+    }
+}
+
+/// Computes the statistics of `path` from chunks (see [`strategy::read_stats`]), or
+/// returns `None` if the input must be read into memory after all.
+fn process_chunked(
+    args: &Args,
+    thresholds: &Thresholds,
+    limiter: &IoLimiter,
+    path: &Path,
+    strategy: Strategy,
+) -> Result<Option<FileReport>> {
+    let compression = Compression::from_path(path);
+    let mut source = HashingReader::new(limiter.reader(fs::File::open(path)?), args.hash);
+    let stats = strategy::read_stats(
+        &mut compression::decoder(&mut source, compression)?,
+        strategy,
+        args.encoding,
+        args.top_words,
+        args.advanced_stats,
+        args.whitespace_stats,
+    )?;
+    let Some(stats) = stats else {
+        return Ok(None);
+    };
+    debug!(
+        "File stats - lines: {}, words: {}, bytes: {}",
+        stats.lines, stats.words, stats.bytes
+    );
+
+    Ok(Some(FileReport {
+        encoding: Encoding::Utf8,
+        compression,
+        digest: source.finish(),
+        violations: thresholds.check_counts(&stats, true),
+        ..FileReport::new(path, stats)
+    }))
+}
+
+fn render_text(args: &Args, report: &RunReport, directory: bool, reporter: Reporter) {
+    if let Some(throughput) = &report.throughput {
+        reportln!(
+            "{}",
+            tr!(
+                "run-throughput",
+                files = throughput.files,
+                bytes_in = throughput.bytes_in,
+                bytes_out = throughput.bytes_out,
+                seconds = format!("{:.3}", throughput.seconds),
+                rate = format!("{:.2}", throughput.mb_per_sec)
+            )
+        );
+        return;
+    }
+    // The statistics of several inputs are compared in one table instead of block by block
+    let tabulated: Vec<&FileReport> = report
+        .files
+        .iter()
+        .filter(|file| directory && shows_stats(file))
+        .collect();
+    let tabulate = tabulated.len() > 1;
+    if tabulate {
+        let stats = StatsReport::new(
+            tabulated
+                .iter()
+                .map(|file| FileStats {
+                    path: Some(file.path.clone()),
+                    ..file.stats.clone()
+                })
+                .collect(),
+        );
+        reportln!("{}", tr!("run-stats"));
+        print_stats_table(&stats, "  ", reporter);
+    }
+    for file in &report.files {
+        if file.unchanged && file.output.is_none() {
+            reportln!("{}", tr!("run-unchanged", path = file.path.display()));
+        }
+        for violation in &file.violations {
+            reporter.failure(&format!("{}: {}", file.path.display(), violation.message));
+        }
+        if file.skipped {
+            reportln!("{}", tr!("run-binary-skipped", path = file.path.display()));
+            continue;
+        }
+        if file.binary {
+            reportln!("{}", tr!("run-stats-binary", path = file.path.display()));
+            reportln!("  {}: {}", tr!("stat-bytes"), file.stats.bytes);
+            print_digest(file.digest.as_ref());
+            continue;
+        }
+        if let Some(kept) = &file.kept_modified {
+            reporter.notice(&tr!("run-kept-modified", path = kept.display()));
+            continue;
+        }
+        match &file.output {
+            Some(output) if output == Path::new(STDOUT_OUTPUT) => {
+                reporter.success(&tr!("run-output-stdout"))
+            }
+            Some(output) if file.unchanged => {
+                reporter.notice(&tr!("run-output-unchanged", path = output.display()))
+            }
+            Some(output) => reporter.success(&tr!("run-output-written", path = output.display())),
+            None if tabulate => {
+                if !has_details(file) {
+                    continue;
+                }
+                reportln!("{}", tr!("run-stats-for", path = file.path.display()));
+                print_stats_details(&file.stats, reporter);
+            }
+            None if args.stats_only || directory => {
+                reportln!("{}", tr!("run-stats-for", path = file.path.display()));
+                print_stats(&file.stats, reporter);
+            }
+            None => {
+                reportln!("{}", tr!("run-stats"));
+                print_stats(&file.stats, reporter);
+            }
+        }
+        if let Some(table) = &file.table {
+            print_table_stats(table, reporter);
+        }
+        if let Some(json) = &file.json {
+            print_json_stats(json, reporter);
+        }
+        print_digest(file.digest.as_ref());
+        if let Some(compression) = file.compression {
+            reportln!("  {}: {}", tr!("stat-compression"), compression);
+        }
+        if file.encoding != Encoding::Utf8 {
+            reportln!("  {}: {}", tr!("stat-encoding"), file.encoding);
+        }
+        if let Some(matched) = file.matching_lines {
+            reportln!("  {}: {}", tr!("stat-matching-lines"), matched);
+        }
+        if let Some(matches) = file.query_matches {
+            reportln!("  {}: {}", tr!("stat-query-matches"), matches);
+        }
+        if let Some(dedupe) = &file.dedupe {
+            reportln!(
+                "  {}",
+                tr!(
+                    "run-dedupe",
+                    lines = dedupe.lines_removed,
+                    rate = dedupe.fp_rate,
+                    bytes = dedupe.filter_bytes
+                )
+            );
+        }
+        if !file.stages.is_empty() {
+            reportln!("  {}", tr!("run-transform-stages"));
+            for (i, stage) in file.stages.iter().enumerate() {
+                reportln!(
+                    "    {}",
+                    tr!(
+                        "run-stage",
+                        number = i + 1,
+                        stage = format!("{:<20}", stage.stage),
+                        lines_in = stage.lines_in,
+                        lines_out = stage.lines_out,
+                        bytes_in = stage.bytes_in,
+                        bytes_out = stage.bytes_out,
+                        ms = format!("{:.3}", stage.elapsed_ms)
+                    )
+                );
+            }
+        }
+    }
+
+    if !report.skipped_paths.is_empty() {
+        reportln!(
+            "{}",
+            tr!("run-skipped-paths", count = report.skipped_paths.len())
+        );
+        for skipped in &report.skipped_paths {
+            reportln!("  {} ({})", skipped.path.display(), skipped.kind);
+        }
+    }
+
+    if !report.failed.is_empty() {
+        reportln!("{}", tr!("run-failed-files", count = report.failed.len()));
+        if args.error_policy == ErrorPolicy::Summarize {
+            let mut kinds: BTreeMap<&str, usize> = BTreeMap::new();
+            for failed in &report.failed {
+                *kinds.entry(&failed.kind).or_default() += 1;
+            }
+            for (kind, count) in kinds {
+                reportln!("  {}: {}", kind, count);
+            }
+        } else {
+            for failed in &report.failed {
+                reportln!("  {}: {}", failed.path.display(), failed.message);
+            }
+        }
+    }
+
+    if args.stats_only {
+        return;
+    }
+    if directory {
+        reporter.success(&tr!("run-processed-files", count = report.files.len()));
+    } else {
+        reporter.success(&tr!("run-complete"));
+    }
+}
+
+/// The statistics of every input that was not skipped, labelled with its path.
+fn stats_report(report: &RunReport) -> StatsReport {
+    StatsReport::new(
+        report
+            .files
+            .iter()
+            .filter(|file| !file.skipped)
+            .map(|file| FileStats {
+                path: Some(file.path.clone()),
+                ..file.stats.clone()
+            })
+            .collect(),
+    )
+}
+
+fn print_stats_report(report: &StatsReport, format: StatsFormat, reporter: Reporter) -> Result<()> {
+    match format {
+        StatsFormat::Json => print_json(report),
+        StatsFormat::Yaml => print_yaml(report),
+        StatsFormat::Csv => print_csv(&report.rows()),
+        StatsFormat::Table => {
+            print_stats_table(report, "", reporter);
+            Ok(())
+        }
+    }
+}
+
+/// Prints a row of counts per file of `report`, then a row of totals.
+fn print_stats_table(report: &StatsReport, indent: &str, reporter: Reporter) {
+    let rows: Vec<Vec<String>> = report
+        .rows()
+        .into_iter()
+        .map(|row| {
+            vec![
+                row.path,
+                row.lines.to_string(),
+                row.words.to_string(),
+                row.bytes.to_string(),
+                row.chars.to_string(),
+                row.blank_lines.to_string(),
+                format!("{:.2}", row.avg_line_length),
+            ]
+        })
+        .collect();
+    let headers = [
+        tr!("header-path"),
+        tr!("stat-lines"),
+        tr!("stat-words"),
+        tr!("stat-bytes"),
+        tr!("stat-chars"),
+        tr!("header-blank"),
+        tr!("header-avg-length"),
+    ];
+    reporter.table(indent, &headers, &rows);
+}
+
+/// Whether the text report shows the statistics of `file` (rather than its output).
+fn shows_stats(file: &FileReport) -> bool {
+    !file.skipped && !file.binary && file.kept_modified.is_none() && file.output.is_none()
+}
+
+/// Whether the text report has more to say about `file` than the counts of a stats table.
+fn has_details(file: &FileReport) -> bool {
+    file.stats.advanced.is_some()
+        || file.stats.whitespace.is_some()
+        || file.stats.top_words.is_some()
+        || file.table.is_some()
+        || file.json.is_some()
+        || file.digest.is_some()
+        || file.compression.is_some()
+        || file.encoding != Encoding::Utf8
+        || file.matching_lines.is_some()
+        || file.query_matches.is_some()
+        || file.dedupe.is_some()
+        || !file.stages.is_empty()
+}
+
+fn print_summary(summary: &Summary) {
+    let limit = match summary.io_limit_mb_per_sec {
+        Some(limit) => tr!(
+            "run-summary-limit",
+            limit = limit,
+            seconds = format!("{:.3}", summary.throttled_seconds)
+        ),
+        None => String::new(),
+    };
+    reportln!(
+        "{}",
+        tr!(
+            "run-summary",
+            files = summary.files,
+            read = summary.bytes_read,
+            written = summary.bytes_written,
+            seconds = format!("{:.3}", summary.seconds),
+            rate = format!("{:.2}", summary.mb_per_sec),
+            limit = limit
+        )
+    );
+}
+
+fn print_digest(digest: Option<&FileDigest>) {
+    if let Some(digest) = digest {
+        reportln!(
+            "  {}: {}",
+            tr!("stat-hash", algorithm = digest.algorithm),
+            digest.hex
+        );
+    }
+}
+
+fn print_table_stats(table: &TableStats, reporter: Reporter) {
+    reportln!("  {}: {}", tr!("stat-rows"), table.rows);
+    reportln!("  {}: {}", tr!("stat-columns"), table.columns.len());
+    let rows: Vec<Vec<String>> = table
+        .columns
+        .iter()
+        .map(|column| {
+            let numeric = |value: fn(&NumericStats) -> f64| {
+                column
+                    .numeric
+                    .as_ref()
+                    .map(|stats| format!("{:.4}", value(stats)))
+                    .map(|text| text.trim_end_matches('0').trim_end_matches('.').to_string())
+                    .unwrap_or_else(|| String::from("-"))
+            };
+            vec![
+                column.name.clone(),
+                column.inferred_type.as_str().to_string(),
+                column.empty.to_string(),
+                column.distinct.to_string(),
+                numeric(|stats| stats.min),
+                numeric(|stats| stats.max),
+                numeric(|stats| stats.mean),
+            ]
+        })
+        .collect();
+    let headers = [
+        tr!("header-column"),
+        tr!("header-type"),
+        tr!("header-empty"),
+        tr!("header-distinct"),
+        tr!("header-min"),
+        tr!("header-max"),
+        tr!("header-mean"),
+    ];
+    reporter.table("    ", &headers, &rows);
+}
+
+fn print_json_stats(json: &JsonStats, reporter: Reporter) {
+    reportln!("  {}: {}", tr!("stat-documents"), json.documents);
+    reportln!("  {}: {}", tr!("stat-max-depth"), json.max_depth);
+    reportln!(
+        "  {}: {}",
+        tr!("stat-keys"),
+        tr!(
+            "stat-keys-value",
+            keys = json.keys,
+            distinct = json.distinct_keys
+        )
+    );
+    reportln!("  {}: {}", tr!("stat-max-array-len"), json.max_array_len);
+    if !json.types.is_empty() {
+        reportln!("  {}:", tr!("stat-types"));
+        let rows: Vec<Vec<String>> = json
+            .types
+            .iter()
+            .map(|(path, kinds)| {
+                let kinds: Vec<&str> = kinds.iter().copied().collect();
+                vec![path.clone(), kinds.join(" | ")]
+            })
+            .collect();
+        reporter.table("    ", &[tr!("header-path"), tr!("header-types")], &rows);
+    }
+}
+
+fn print_stats(stats: &FileStats, reporter: Reporter) {
+    reportln!("  {}: {}", tr!("stat-lines"), stats.lines);
+    reportln!("  {}: {}", tr!("stat-words"), stats.words);
+    reportln!("  {}: {}", tr!("stat-bytes"), stats.bytes);
+    reportln!("  {}: {}", tr!("stat-chars"), stats.chars);
+    reportln!("  {}: {}", tr!("stat-graphemes"), stats.graphemes);
+    reportln!("  {}: {}", tr!("stat-blank-lines"), stats.blank_lines);
+    reportln!(
+        "  {}: {}",
+        tr!("stat-line-length"),
+        tr!(
+            "stat-line-length-value",
+            min = stats.line_length.min,
+            max = stats.line_length.max,
+            avg = format!("{:.2}", stats.line_length.avg)
+        )
+    );
+    print_stats_details(stats, reporter);
+}
+
+/// Prints the advanced measures and top words of `stats`, if computed.
+fn print_stats_details(stats: &FileStats, reporter: Reporter) {
+    if let Some(advanced) = &stats.advanced {
+        reportln!(
+            "  {}: {}",
+            tr!("stat-distinct-lines"),
+            advanced.distinct_lines
+        );
+        reportln!(
+            "  {}: {:.4}",
+            tr!("stat-duplication-ratio"),
+            advanced.duplication_ratio
+        );
+        reportln!(
+            "  {}: {}",
+            tr!("stat-entropy"),
+            tr!(
+                "stat-entropy-value",
+                bits = format!("{:.4}", advanced.entropy_bits_per_byte)
+            )
+        );
+    }
+    if let Some(whitespace) = &stats.whitespace {
+        print_whitespace_stats(whitespace);
+    }
+    if let Some(top_words) = &stats.top_words {
+        reportln!("  {}:", tr!("stat-top-words"));
+        match reporter {
+            Reporter::Standard | Reporter::Json => {
+                for entry in top_words {
+                    reportln!("    {:>6}  {}", entry.count, entry.word);
+                }
+            }
+            Reporter::Accessible | Reporter::Markdown => {
+                let rows: Vec<Vec<String>> = top_words
+                    .iter()
+                    .map(|entry| vec![entry.count.to_string(), entry.word.clone()])
+                    .collect();
+                reporter.table("    ", &[tr!("header-count"), tr!("header-word")], &rows);
+            }
+        }
+    }
+}
+
+fn print_whitespace_stats(whitespace: &WhitespaceStats) {
+    reportln!(
+        "  {}: {}",
+        tr!("stat-line-endings"),
+        tr!(
+            "stat-line-endings-value",
+            style = whitespace.line_endings.to_string(),
+            lf = whitespace.lf_lines,
+            crlf = whitespace.crlf_lines
+        )
+    );
+    reportln!(
+        "  {}: {}",
+        tr!("stat-trailing-whitespace"),
+        whitespace.trailing_whitespace_lines
+    );
+    reportln!(
+        "  {}: {}",
+        tr!("stat-indentation"),
+        tr!(
+            "stat-indentation-value",
+            style = whitespace.indentation.to_string(),
+            tabs = whitespace.tab_indented_lines,
+            spaces = whitespace.space_indented_lines
+        )
+    );
+    reportln!(
+        "  {}: {}",
+        tr!("stat-final-newline"),
+        if whitespace.final_newline {
+            tr!("stat-yes")
         } else {
-            // Just show stats if no output specified
-            println!("File statistics:");
-            println!("  Lines: {}", line_count);
-            println!("  Words: {}", word_count);
-            println!("  Bytes: {}", byte_count);
-            format!("Analyzed {} bytes", byte_count)
+            tr!("stat-no")
+        }
+    );
+}
+
+/// Recursively lists regular files below `root` (following symlinks), sorted by path.
+fn collect_files(root: &Path, walk: &mut Walk) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root).follow_links(true).sort_by_file_name() {
+        match entry {
+            Ok(entry) if entry.file_type().is_file() => files.push(entry.into_path()),
+            Ok(_) => {}
+            Err(e) => {
+                let path = e.path().unwrap_or(root).to_path_buf();
+                let kind = e.loop_ancestor().map(|_| "symlink-loop");
+                walk.tolerate(&path, std::io::Error::from(e), kind)?;
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `path` names a YAML file, by its extension.
+pub fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    )
+}
+
+/// Whether `output` is a file name without any directory, not even `./`.
+pub fn is_bare_file_name(output: &str) -> bool {
+    output != STDOUT_OUTPUT && Path::new(output).file_name() == Some(output.as_ref())
+}
+
+/// The active profile's output directory as an absolute path, created if missing.
+pub fn profile_output_dir(ctx: &Context) -> Result<Option<PathBuf>> {
+    let Some(dir) = ctx
+        .config
+        .active_profile()
+        .map(|profile| &profile.output_dir)
+        .filter(|dir| !dir.as_os_str().is_empty())
+    else {
+        return Ok(None);
+    };
+    let file_error = |action| {
+        move |source| Error::File {
+            action,
+            path: dir.clone(),
+            source,
+        }
+    };
+    fs::create_dir_all(dir).map_err(file_error("create directory"))?;
+    std::path::absolute(dir)
+        .map(Some)
+        .map_err(file_error("resolve"))
+}
+
+/// Maps each input file to its output path beneath `output_dir`.
+///
+/// Mirrored layouts keep the path relative to `root`. Flattened layouts keep only the
+/// file name; later files whose name is already taken get a `-2`, `-3`, ... suffix.
+fn plan_outputs(
+    root: &Path,
+    files: &[PathBuf],
+    output_dir: &Path,
+    flatten: bool,
+) -> Vec<Option<PathBuf>> {
+    let mut taken = HashSet::new();
+
+    files
+        .iter()
+        .map(|file| {
+            let relative = file.strip_prefix(root).unwrap_or(file);
+            if !flatten {
+                return Some(output_dir.join(relative));
+            }
+
+            let name = PathBuf::from(relative.file_name()?);
+            let mut candidate = name.clone();
+            let mut n = 2;
+            while !taken.insert(candidate.clone()) {
+                candidate = suffixed_name(&name, n);
+                n += 1;
+            }
+            if candidate != name {
+                warn!(
+                    "Output name collision for {}; writing {}",
+                    relative.display(),
+                    candidate.display()
+                );
+            }
+            Some(output_dir.join(candidate))
+        })
+        .collect()
+}
+
+fn suffixed_name(name: &Path, n: usize) -> PathBuf {
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    match name.extension() {
+        Some(ext) => PathBuf::from(format!("{}-{}.{}", stem, n, ext.to_string_lossy())),
+        None => PathBuf::from(format!("{}-{}", stem, n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedupe_approx_keeps_first_occurrences() {
+        let ops = TextOps {
+            grep: None,
+            replace: None,
+            dedupe_fp_rate: Some(0.001),
+            dedupe_capacity: 1,
         };
+        let (output, _, report) = ops.apply("b\na\r\nb\na\nc");
+        let report = report.unwrap();
+        assert_eq!(output, "b\na\r\nc");
+        assert_eq!(report.lines_removed, 2);
+        assert_eq!(report.fp_rate, 0.001);
 
-        info!("Processing complete: {}", processed);
-        println!("[SUCCESS] Processing complete.");
+        // Streamed line by line, with the same result
+        let mut input = "b\na\r\nb\na\nc".as_bytes();
+        let mut lines = FilteredLines::new(&mut input, &ops);
+        let mut streamed = String::new();
+        lines.read_to_string(&mut streamed).unwrap();
+        assert_eq!(streamed, output);
+        assert_eq!(lines.dedupe.unwrap().lines_removed, 2);
     }
 
-    Ok(())
+    #[test]
+    fn test_head_and_sample_lines() {
+        assert_eq!(head_len(b"a\nb\nc", 2), 4);
+        assert_eq!(head_len(b"a\nb\nc", 5), 5);
+        assert_eq!(head_len(b"a\nb\nc", 0), 0);
+
+        let raw: Vec<u8> = (0..100)
+            .flat_map(|i| format!("{}\n", i).into_bytes())
+            .collect();
+        let sample = sample_lines(&raw, 10.0, 7);
+        let numbers: Vec<u32> = String::from_utf8(sample.clone())
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        assert_eq!(numbers.len(), 10);
+        assert!(numbers.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sample, sample_lines(&raw, 10.0, 7));
+        assert_eq!(parse_percent("2.5%"), Ok(2.5));
+        assert!(parse_percent("0").is_err());
+        assert!(parse_percent("101%").is_err());
+    }
+
+    #[test]
+    fn test_walk_tolerates_access_errors() {
+        let mut walk = Walk::lenient();
+        let denied = std::io::Error::from(ErrorKind::PermissionDenied);
+        assert!(walk.tolerate(Path::new("a"), denied, None).is_ok());
+        assert!(walk
+            .tolerate(Path::new("b"), std::io::Error::other("boom"), None)
+            .is_err());
+        assert_eq!(walk.warnings.len(), 1);
+        assert_eq!(walk.warnings[0].kind, "permission-denied");
+
+        let mut strict = Walk::new(true);
+        let denied = std::io::Error::from(ErrorKind::PermissionDenied);
+        assert!(strict.tolerate(Path::new("a"), denied, None).is_err());
+    }
+
+    #[test]
+    fn test_plan_outputs_mirrors_tree() {
+        let root = Path::new("in");
+        let files = vec![PathBuf::from("in/a.txt"), PathBuf::from("in/sub/b.txt")];
+        let outputs = plan_outputs(root, &files, Path::new("out"), false);
+        assert_eq!(
+            outputs,
+            vec![
+                Some(PathBuf::from("out/a.txt")),
+                Some(PathBuf::from("out/sub/b.txt")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_outputs_flatten_disambiguates() {
+        let root = Path::new("in");
+        let files = vec![
+            PathBuf::from("in/a/notes.txt"),
+            PathBuf::from("in/b/notes.txt"),
+            PathBuf::from("in/c/notes.txt"),
+            PathBuf::from("in/notes-2.txt"),
+            PathBuf::from("in/README"),
+            PathBuf::from("in/x/README"),
+        ];
+        let outputs = plan_outputs(root, &files, Path::new("out"), true);
+        assert_eq!(
+            outputs,
+            vec![
+                Some(PathBuf::from("out/notes.txt")),
+                Some(PathBuf::from("out/notes-2.txt")),
+                Some(PathBuf::from("out/notes-3.txt")),
+                Some(PathBuf::from("out/notes-2-2.txt")),
+                Some(PathBuf::from("out/README")),
+                Some(PathBuf::from("out/README-2")),
+            ]
+        );
+    }
 }
@@ -0,0 +1,251 @@
+use clap::Args as ClapArgs;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::Instant;
+use tracing::debug;
+
+use crate::compression::{self, Compression, Encoder};
+use crate::config::Config;
+use crate::context::Context;
+use crate::encoding::Encoding;
+use crate::error::{Error, Result};
+use crate::http;
+use crate::i18n::tr;
+use crate::identity;
+use crate::output::{print_json, reportln, OutputFormat};
+use crate::stats::StatsAccumulator;
+use crate::strategy::{self, Strategy};
+use crate::transform::Pipeline;
+
+/// Input of the statistics and transform cases: 3 lines (one blank), 5 words, 32 bytes.
+const SAMPLE: &str = "alpha beta\n\ngamma delta epsilon\n";
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    /// Also check that the GitHub API can be reached (skipped otherwise)
+    #[arg(long)]
+    pub network: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pass,
+    Fail,
+    Skip,
+}
+
+/// Outcome of one case.
+#[derive(Serialize, Debug)]
+struct CaseResult {
+    name: &'static str,
+    status: Status,
+    /// Why the case failed or was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    seconds: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct SelftestReport {
+    passed: usize,
+    failed: usize,
+    cases: Vec<CaseResult>,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path();
+    let mut cases = vec![
+        case("stats", || check_stats(dir)),
+        case("transform", || check_transform(dir)),
+        case("config", || check_config(dir)),
+        case("cache", check_cache),
+    ];
+    cases.push(if args.network {
+        let started = Instant::now();
+        let result = check_network().await;
+        finish("network", result, started)
+    } else {
+        CaseResult {
+            name: "network",
+            status: Status::Skip,
+            detail: Some(String::from("pass --network to check")),
+            seconds: 0.0,
+        }
+    });
+
+    let count = |status| cases.iter().filter(|case| case.status == status).count();
+    let report = SelftestReport {
+        passed: count(Status::Pass),
+        failed: count(Status::Fail),
+        cases,
+    };
+    match ctx.format {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Text | OutputFormat::Markdown => {
+            for case in &report.cases {
+                let marker = match case.status {
+                    Status::Pass => tr!("selftest-pass"),
+                    Status::Fail => tr!("selftest-fail"),
+                    Status::Skip => tr!("selftest-skip"),
+                };
+                match &case.detail {
+                    Some(detail) => reportln!("{} {}: {}", marker, case.name, detail),
+                    None => reportln!("{} {}", marker, case.name),
+                }
+            }
+        }
+    }
+
+    let total = report.passed + report.failed;
+    if report.failed > 0 {
+        return Err(Error::Other(format!(
+            "{} of {} self-test cases failed",
+            report.failed, total
+        )));
+    }
+    if ctx.format != OutputFormat::Json {
+        ctx.reporter.success(&tr!("selftest-passed", count = total));
+    }
+    Ok(())
+}
+
+fn case(name: &'static str, check: impl FnOnce() -> Result<()>) -> CaseResult {
+    let started = Instant::now();
+    finish(name, check(), started)
+}
+
+fn finish(name: &'static str, result: Result<()>, started: Instant) -> CaseResult {
+    let seconds = started.elapsed().as_secs_f64();
+    debug!("Self-test case {} took {:.3}s", name, seconds);
+    match result {
+        Ok(()) => CaseResult {
+            name,
+            status: Status::Pass,
+            detail: None,
+            seconds,
+        },
+        Err(e) => CaseResult {
+            name,
+            status: Status::Fail,
+            detail: Some(e.to_string()),
+            seconds,
+        },
+    }
+}
+
+fn expect(condition: bool, message: impl FnOnce() -> String) -> Result<()> {
+    if condition {
+        return Ok(());
+    }
+    Err(Error::Other(message()))
+}
+
+/// Statistics of a known file are right, and the same whichever way it is read.
+fn check_stats(dir: &Path) -> Result<()> {
+    let path = dir.join("sample.txt");
+    fs::write(&path, SAMPLE)?;
+
+    let mut memory = StatsAccumulator::new(None, false, false);
+    memory.feed(&fs::read_to_string(&path)?);
+    let memory = memory.finish();
+    let counts = (memory.lines, memory.words, memory.bytes, memory.blank_lines);
+    expect(counts == (3, 5, 32, 1), || {
+        format!(
+            "expected 3 lines, 5 words, 32 bytes and 1 blank line, got {:?}",
+            counts
+        )
+    })?;
+
+    for chunked in [Strategy::Streaming, Strategy::Parallel] {
+        let stats = strategy::read_stats(
+            &mut fs::File::open(&path)?,
+            chunked,
+            Encoding::Auto,
+            None,
+            false,
+            false,
+        )?;
+        expect(stats.as_ref() == Some(&memory), || {
+            format!("{} statistics differ from in-memory ones", chunked)
+        })?;
+    }
+    Ok(())
+}
+
+/// Transforms invert as expected, and their output survives compression.
+fn check_transform(dir: &Path) -> Result<()> {
+    let pipeline = |definition| Pipeline::parse(definition, &BTreeMap::new());
+    let (upper, _) = pipeline("uppercase")?.apply(SAMPLE)?;
+    let (lower, _) = pipeline("lowercase")?.apply(&upper)?;
+    expect(upper != SAMPLE && lower == SAMPLE, || {
+        String::from("uppercase | lowercase did not restore the input")
+    })?;
+
+    for format in [Compression::Gzip, Compression::Zstd] {
+        let path = dir.join(format!("sample.{}", format));
+        let encoder = Encoder {
+            format: Some(format),
+            level: None,
+            threads: 1,
+        };
+        compression::encode(fs::File::create(&path)?, upper.as_bytes(), &encoder)?;
+        let mut decoded = String::new();
+        compression::decoder(fs::File::open(&path)?, Some(format))?.read_to_string(&mut decoded)?;
+        expect(decoded == upper, || {
+            format!("{} output did not decompress to what was written", format)
+        })?;
+    }
+    Ok(())
+}
+
+/// The default configuration is written and read back, as YAML and as JSON.
+fn check_config(dir: &Path) -> Result<()> {
+    for name in ["config.yaml", "config.json"] {
+        let path = dir.join(name);
+        let path = path.to_string_lossy();
+        Config::default().save(&path)?;
+        let loaded = Config::load(&path, true)?;
+        expect(
+            loaded.default_profile == Config::default().default_profile,
+            || format!("{} did not read back as written", name),
+        )?;
+    }
+    Ok(())
+}
+
+/// Files can be created in the cache directory (used for upgrades, locks and crash
+/// reports).
+fn check_cache() -> Result<()> {
+    let dir = directories::ProjectDirs::from("", "", identity::app_name())
+        .map(|dirs| dirs.cache_dir().to_path_buf())
+        .ok_or_else(|| Error::Other(String::from("no cache directory for this user")))?;
+    let file_error = |action, path: &Path| {
+        let path = path.to_path_buf();
+        move |source| Error::File {
+            action,
+            path,
+            source,
+        }
+    };
+    fs::create_dir_all(&dir).map_err(file_error("create directory", &dir))?;
+    let probe = tempfile::NamedTempFile::new_in(&dir).map_err(file_error("write to", &dir))?;
+    probe.close().map_err(file_error("remove from", &dir))
+}
+
+/// The GitHub API answers for this project's repository.
+async fn check_network() -> Result<()> {
+    let url = format!("https://api.github.com/repos/{}", identity::repository()?);
+    let response = http::get(&url, None).await?;
+    if !response.status().is_success() {
+        return Err(Error::Http {
+            url,
+            status: response.status().as_u16(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use clap::{Args as ClapArgs, Subcommand};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::context::Context;
+use crate::error::Result;
+use crate::output::{print_json, OutputFormat};
+use crate::transform::{Pipeline, TRANSFORMS};
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    pub action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Show the parsed stages of a pipeline without running it
+    Explain {
+        /// Pipeline definition, e.g. "trim | dedupe-lines | truncate:width=40" or "@name"
+        pipeline: String,
+    },
+
+    /// List available transforms
+    List,
+}
+
+#[derive(Serialize)]
+struct StageExplanation {
+    index: usize,
+    name: &'static str,
+    description: &'static str,
+    options: BTreeMap<&'static str, String>,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    match args.action {
+        Action::Explain { pipeline } => explain(&pipeline, ctx),
+        Action::List => {
+            for (name, description) in TRANSFORMS {
+                println!("{:<14} {}", name, description);
+            }
+            if !ctx.config.pipelines.is_empty() {
+                println!();
+                println!("Named pipelines (use as @name):");
+                for (name, stages) in &ctx.config.pipelines {
+                    println!("  @{:<12} {}", name, stages.join(" | "));
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn explain(definition: &str, ctx: &Context) -> Result<()> {
+    let pipeline = Pipeline::parse(definition, &ctx.config.pipelines)?;
+    let stages: Vec<StageExplanation> = pipeline
+        .stages
+        .iter()
+        .enumerate()
+        .map(|(i, stage)| StageExplanation {
+            index: i + 1,
+            name: stage.name(),
+            description: stage.description(),
+            options: stage.options(),
+        })
+        .collect();
+
+    if ctx.format == OutputFormat::Json {
+        return print_json(&stages);
+    }
+
+    println!("Pipeline with {} stage(s):", stages.len());
+    for stage in &stages {
+        println!("  {}. {} - {}", stage.index, stage.name, stage.description);
+        for (key, value) in &stage.options {
+            println!("       {} = {}", key, value);
+        }
+    }
+    Ok(())
+}
@@ -1,22 +1,41 @@
 use std::{
     cmp::min,
     env,
-    fs::{create_dir_all, rename},
-    io::{Cursor, ErrorKind},
-    path::Path,
+    fs::create_dir_all,
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use clap::Args as ClapArgs;
-use futures_util::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::ClientBuilder;
-use serde::Deserialize;
-use tracing::{debug, info};
+use clap::{Args as ClapArgs, Subcommand};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn, Instrument};
 
+use crate::asset::{self, Match};
+use crate::config::{ChecksumPolicy, UpgradeSource};
 use crate::constants;
+use crate::context::Context;
+use crate::digest::{self, HashAlgorithm};
+use crate::env_vars;
 use crate::error::{Error, Result};
+use crate::http::{self, network_error};
+use crate::i18n::tr;
+use crate::identity;
+use crate::install::{self, RealFs, Strategy};
+use crate::lock;
+use crate::output::{print_json, reportln, OutputFormat};
+use crate::pin;
+use crate::prompt::Prompter;
+use crate::reporter::{Phase, Reporter};
+use crate::stats::throughput_mb_per_sec;
+use crate::timings;
 
-#[derive(Deserialize, Debug)]
+/// Media type of GitHub REST API responses.
+const GITHUB_JSON: &str = "application/vnd.github+json";
+
+#[derive(Deserialize, Debug, Clone)]
 struct GithubAssetResponse {
     browser_download_url: String,
     name: String,
@@ -26,40 +45,598 @@ struct GithubAssetResponse {
 struct GithubResponse {
     tag_name: String,
     assets: Vec<GithubAssetResponse>,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    published_at: Option<String>,
+    /// Release notes (Markdown)
+    #[serde(default)]
+    body: Option<String>,
 }
 
 #[derive(ClapArgs, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Args {
-    /// Version to upgrade to (defaults to latest)
+    #[command(subcommand)]
+    pub action: Option<Action>,
+
+    /// Version to upgrade to (defaults to latest), or a semver requirement such as ^1.2 or
+    /// ">=1.4, <2" to install the newest release matching it
+    /// [env: __TEMPLATE_ENV_PREFIX___UPGRADE_VERSION]
     #[arg(long)]
     pub version: Option<String>,
 
-    /// Force upgrade even if already on requested version
+    /// Whether a release that is breaking under semver (a new major version, or a new minor
+    /// version before 1.0) may be installed; with false, the newest compatible release is
+    /// installed instead of the latest [env: __TEMPLATE_ENV_PREFIX___UPGRADE_ALLOW_MAJOR]
+    /// [config: upgrade.allow_major] [default: true]
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub allow_major: Option<bool>,
+
+    /// Install exactly the version pinned by the project's .<name>-version file (in the
+    /// current directory or a parent), even if it is older
+    #[arg(long, conflicts_with = "version")]
+    pub to_pinned: bool,
+
+    /// Force upgrade even if already on requested version [env: __TEMPLATE_ENV_PREFIX___UPGRADE_FORCE]
     #[arg(long)]
     pub force: bool,
+
+    /// Install the build for this target instead of the running one: a triple such as
+    /// x86_64-unknown-linux-musl, or an OS and architecture such as darwin-arm64
+    /// [env: __TEMPLATE_ENV_PREFIX___UPGRADE_TARGET]
+    #[arg(long, value_name = "TARGET")]
+    pub target: Option<String>,
+
+    /// Show the release notes of every release between the installed version and the one
+    /// that would be installed, oldest first, without upgrading
+    #[arg(long)]
+    pub changelog: bool,
+
+    /// Only report whether the release that would be installed differs from the running
+    /// version, without downloading it
+    #[arg(long, conflicts_with = "changelog")]
+    pub check: bool,
+
+    /// Whether to check the download against its published SHA-256 checksum: require
+    /// refuses releases without one [env: __TEMPLATE_ENV_PREFIX___UPGRADE_CHECKSUM]
+    /// [config: upgrade.checksum] [default: auto]
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub checksum: Option<ChecksumPolicy>,
+
+    /// Install the binary into this directory instead of replacing the running executable
+    /// [env: __TEMPLATE_ENV_PREFIX___UPGRADE_INSTALL_DIR]
+    #[arg(long, value_name = "DIR")]
+    pub install_dir: Option<PathBuf>,
 }
 
-pub fn execute(args: Args) -> Result<()> {
-    let runtime =
-        tokio::runtime::Runtime::new().map_err(|e| Error::Io(std::io::Error::other(e)))?;
-    runtime.block_on(execute_async(args))
+impl Args {
+    /// Fills in options not given on the command line from the `UPGRADE_*` environment
+    /// variables, so unattended upgrades need no flags or files.
+    fn with_env(mut self) -> Result<Self> {
+        if self.version.is_none() && !self.to_pinned {
+            self.version = env_vars::parse(env_vars::UPGRADE_VERSION)?;
+        }
+        if self.allow_major.is_none() {
+            self.allow_major = env_vars::flag(env_vars::UPGRADE_ALLOW_MAJOR)?;
+        }
+        self.force |= env_vars::flag(env_vars::UPGRADE_FORCE)?.unwrap_or(false);
+        if self.target.is_none() {
+            self.target = env_vars::parse(env_vars::UPGRADE_TARGET)?;
+        }
+        if self.checksum.is_none() {
+            self.checksum = env_vars::parse(env_vars::UPGRADE_CHECKSUM)?;
+        }
+        if self.install_dir.is_none() {
+            self.install_dir = env_vars::parse(env_vars::UPGRADE_INSTALL_DIR)?;
+        }
+        if let Some(Action::List { channel, .. }) = &mut self.action {
+            if channel.is_none() {
+                *channel = env_vars::parse(env_vars::UPGRADE_CHANNEL)?;
+            }
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// List available releases, newest first
+    List {
+        /// Maximum number of releases to show
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=100))]
+        limit: u32,
+
+        /// Only show "stable" releases, or prereleases of a channel such as "beta" or "rc"
+        /// [env: __TEMPLATE_ENV_PREFIX___UPGRADE_CHANNEL]
+        #[arg(long, value_name = "CHANNEL")]
+        channel: Option<String>,
+    },
+}
+
+/// A release as shown by `upgrade list`.
+#[derive(Serialize, Debug, PartialEq)]
+struct ReleaseEntry {
+    version: String,
+    tag: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published: Option<String>,
+    prerelease: bool,
+    /// Whether the release has a pre-built binary for this platform
+    asset_available: bool,
+    /// Whether this is the running version
+    current: bool,
+}
+
+/// Release notes as shown by `upgrade --changelog`.
+#[derive(Serialize, Debug, PartialEq)]
+struct ChangelogEntry {
+    version: String,
+    tag: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published: Option<String>,
+    notes: String,
+}
+
+/// Outcome of an upgrade, including timings for each phase.
+#[derive(Serialize, Debug)]
+struct UpgradeReport {
+    from: String,
+    to: String,
+    upgraded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    telemetry: Option<UpgradeTelemetry>,
+}
+
+/// Phase timings reported for every upgrade, to help spot slow mirrors and proxies.
+#[derive(Serialize, Debug, Default)]
+struct UpgradeTelemetry {
+    download_bytes: u64,
+    download_seconds: f64,
+    download_mb_per_sec: f64,
+    verify_seconds: f64,
+    extract_seconds: f64,
+    apply_seconds: f64,
+    total_seconds: f64,
+    /// HTTP requests sent during the upgrade, including retries
+    http: http::Metrics,
+}
+
+pub async fn execute(args: Args, ctx: &Context) -> Result<()> {
+    let mut args = args.with_env()?;
+    if let Some(Action::List { limit, channel }) = args.action {
+        return list(limit, channel.as_deref(), ctx).await;
+    }
+    if args.to_pinned {
+        let pin = pin::find(&env::current_dir()?)?.ok_or_else(|| {
+            Error::Other(format!(
+                "No {} file found in the current directory or its parents",
+                pin::file_name()
+            ))
+        })?;
+        if pin.is_satisfied() && !args.force {
+            ctx.reporter.step(&tr!(
+                "upgrade-pinned-current",
+                version = pin.version,
+                path = pin.path.display()
+            ));
+            return Ok(());
+        }
+        info!(
+            "Installing version {} pinned by {}",
+            pin.version,
+            pin.path.display()
+        );
+        args.version = Some(pin.version);
+    } else {
+        let allow_major = args
+            .allow_major
+            .or(ctx.config.upgrade.allow_major)
+            .unwrap_or(true);
+        args.version =
+            resolve_version(args.version.take(), allow_major, &ctx.config.upgrade).await?;
+    }
+    if args.changelog {
+        return changelog(args.version.as_deref(), ctx).await;
+    }
+
+    let prompter = match env_vars::flag(env_vars::UPGRADE_YES)? {
+        Some(true) => ctx.prompter.assuming_yes(),
+        _ => ctx.prompter,
+    };
+    // Held until the new binary is in place
+    let _lock = (!args.check)
+        .then(|| lock::upgrade(ctx.lock_policy))
+        .transpose()?;
+    let source = source_with_env(&ctx.config.upgrade)?;
+    let report = upgrade(args, &source, ctx.reporter, prompter).await?;
+    match ctx.format {
+        OutputFormat::Json => print_json(&report),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            if report.upgraded {
+                ctx.reporter
+                    .step(&tr!("upgrade-complete", version = report.to));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The configured upgrade source, with the mirror from the environment if one is set there.
+fn source_with_env(configured: &UpgradeSource) -> Result<UpgradeSource> {
+    let mut source = configured.clone();
+    if let Some(mirror) = env_vars::parse(env_vars::UPGRADE_MIRROR)? {
+        source.mirror = Some(mirror);
+    }
+    Ok(source)
+}
+
+async fn list(limit: u32, channel: Option<&str>, ctx: &Context) -> Result<()> {
+    let releases = fetch_releases().await?;
+    let entries = select_releases(releases, channel, limit as usize);
+
+    match ctx.format {
+        OutputFormat::Json => print_json(&entries),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            if entries.is_empty() {
+                reportln!("{}", tr!("upgrade-list-none"));
+                return Ok(());
+            }
+            let yes_no = |flag: bool| tr!(if flag { "stat-yes" } else { "stat-no" });
+            let rows: Vec<Vec<String>> = entries
+                .iter()
+                .map(|entry| {
+                    let mut version = entry.version.clone();
+                    if entry.current {
+                        version = tr!("upgrade-list-current", version = version);
+                    }
+                    vec![
+                        version,
+                        entry.published.clone().unwrap_or_else(|| String::from("-")),
+                        yes_no(entry.prerelease),
+                        yes_no(entry.asset_available),
+                    ]
+                })
+                .collect();
+            let headers = [
+                tr!("header-version"),
+                tr!("header-published"),
+                tr!("header-prerelease"),
+                tr!("header-binary"),
+            ];
+            ctx.reporter.table("", &headers, &rows);
+            Ok(())
+        }
+    }
+}
+
+/// Prints the notes of the releases after the running version up to `target` (by default
+/// the newest stable release).
+async fn changelog(target: Option<&str>, ctx: &Context) -> Result<()> {
+    let current = constants::APP_VERSION;
+    let entries = changelog_between(fetch_releases().await?, current, target)?;
+
+    match ctx.format {
+        OutputFormat::Json => print_json(&entries),
+        OutputFormat::Text | OutputFormat::Markdown => {
+            if entries.is_empty() {
+                reportln!("{}", tr!("upgrade-changelog-none", version = current));
+                return Ok(());
+            }
+            for entry in &entries {
+                match &entry.published {
+                    Some(date) => reportln!("## {} ({})", entry.tag, date),
+                    None => reportln!("## {}", entry.tag),
+                }
+                reportln!("");
+                if entry.notes.is_empty() {
+                    reportln!("{}", tr!("upgrade-changelog-no-notes"));
+                } else {
+                    reportln!("{}", entry.notes);
+                }
+                reportln!("");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The releases newer than `current` up to and including `target` (by default the newest
+/// stable release), oldest first. Prereleases are only included when `target` is one.
+fn changelog_between(
+    releases: Vec<GithubResponse>,
+    current: &str,
+    target: Option<&str>,
+) -> Result<Vec<ChangelogEntry>> {
+    let version = |release: &GithubResponse| release.tag_name.trim_start_matches('v').to_string();
+    let published: Vec<_> = releases.into_iter().filter(|r| !r.draft).collect();
+    let target = match target {
+        Some(target) => published
+            .iter()
+            .find(|release| release.tag_name == target || version(release) == target)
+            .ok_or_else(|| Error::Other(format!("Release '{}' not found", target)))?,
+        None => match published.iter().find(|release| !release.prerelease) {
+            Some(latest) => latest,
+            None => return Ok(Vec::new()),
+        },
+    };
+    let target_version = version(target);
+    let mut entries: Vec<_> = published
+        .iter()
+        .filter(|release| !release.prerelease || target.prerelease)
+        .filter(|release| {
+            let candidate = version(release);
+            is_newer(&candidate, current) && !is_newer(&candidate, &target_version)
+        })
+        .map(|release| ChangelogEntry {
+            version: version(release),
+            tag: release.tag_name.clone(),
+            published: release
+                .published_at
+                .as_ref()
+                .map(|date| date.chars().take(10).collect()),
+            notes: release.body.clone().unwrap_or_default().trim().to_string(),
+        })
+        .collect();
+    // GitHub lists releases newest first
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Filters published releases by `channel` and converts the first `limit` of them.
+///
+/// `stable` keeps releases that are not prereleases; any other channel keeps
+/// prereleases whose version suffix starts with it (`beta` matches `1.2.0-beta.3`).
+fn select_releases(
+    releases: Vec<GithubResponse>,
+    channel: Option<&str>,
+    limit: usize,
+) -> Vec<ReleaseEntry> {
+    let current = constants::APP_VERSION;
+
+    releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .filter(|release| match channel {
+            None => true,
+            Some("stable") => !release.prerelease,
+            Some(channel) => {
+                let suffix = release.tag_name.split_once('-').map(|(_, s)| s);
+                release.prerelease && suffix.is_some_and(|s| s.starts_with(channel))
+            }
+        })
+        .take(limit)
+        .map(|release| {
+            let version = release.tag_name.trim_start_matches('v').to_string();
+            ReleaseEntry {
+                current: version == current,
+                asset_available: find_platform_asset(&release, env!("TARGET")).is_ok(),
+                published: release
+                    .published_at
+                    .as_ref()
+                    .map(|date| date.chars().take(10).collect()),
+                prerelease: release.prerelease,
+                tag: release.tag_name,
+                version,
+            }
+        })
+        .collect()
+}
+
+/// Fetches the most recent releases (up to the GitHub page size of 100), newest first.
+async fn fetch_releases() -> Result<Vec<GithubResponse>> {
+    let url = format!(
+        "https://api.github.com/repos/{}/releases?per_page=100",
+        identity::repository()?
+    );
+    debug!("Fetching releases from: {}", url);
+
+    let resp = http::get(&url, Some(GITHUB_JSON)).await?;
+    if !resp.status().is_success() {
+        return Err(Error::Http {
+            url,
+            status: resp.status().as_u16(),
+        });
+    }
+
+    resp.json::<Vec<GithubResponse>>()
+        .await
+        .map_err(|e| network_error(&url, e))
+}
+
+/// Starts a background check for a newer release.
+///
+/// The task resolves to the newer version, if any; failures are logged and ignored.
+pub fn spawn_update_check() -> JoinHandle<Option<String>> {
+    tokio::spawn(async {
+        match fetch_release(None).await {
+            Ok(release) => {
+                let latest = release.tag_name.trim_start_matches('v').to_string();
+                is_newer(&latest, constants::APP_VERSION).then_some(latest)
+            }
+            Err(e) => {
+                debug!("Update check failed: {}", e);
+                None
+            }
+        }
+    })
+}
+
+/// Waits at most `budget` for a check started by [`spawn_update_check`] and prints a
+/// notice to stderr if a newer release exists.
+pub async fn report_update_check(
+    check: JoinHandle<Option<String>>,
+    budget: Duration,
+    reporter: Reporter,
+) {
+    let abort = check.abort_handle();
+    match tokio::time::timeout(budget, check).await {
+        Ok(Ok(Some(latest))) => reporter.notice(&tr!(
+            "upgrade-notice",
+            version = latest,
+            current = constants::APP_VERSION,
+            app = identity::app_name()
+        )),
+        Ok(_) => debug!("No update available"),
+        Err(_) => {
+            abort.abort();
+            debug!("Update check did not finish within {:?}", budget);
+        }
+    }
+}
+
+/// Compares dotted numeric versions, ignoring any pre-release suffix.
+fn is_newer(candidate: &str, current: &str) -> bool {
+    if let (Some(candidate), Some(current)) = (parse_version(candidate), parse_version(current)) {
+        return candidate > current;
+    }
+    // Not semver, e.g. "1.2": compare the dotted numbers
+    fn parts(version: &str) -> Vec<u64> {
+        version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    }
+    parts(candidate) > parts(current)
+}
+
+/// Parses a release version or tag such as `v1.2.0` as semver.
+fn parse_version(version: &str) -> Option<Version> {
+    Version::parse(version.strip_prefix('v').unwrap_or(version)).ok()
+}
+
+/// Whether `candidate` is breaking under semver for users of `current`: a new major
+/// version, or a new minor version before 1.0.
+fn is_major_jump(current: &Version, candidate: &Version) -> bool {
+    match (current.major, candidate.major) {
+        (0, 0) => candidate.minor > current.minor,
+        (current, candidate) => candidate > current,
+    }
+}
+
+/// Turns `--version` into the tag to install (`None` for the latest release): semver
+/// requirements are matched against the published releases, and without `allow_major`
+/// the latest release is replaced by the newest compatible one.
+async fn resolve_version(
+    version: Option<String>,
+    allow_major: bool,
+    source: &UpgradeSource,
+) -> Result<Option<String>> {
+    let current = parse_version(constants::APP_VERSION).filter(|_| !allow_major);
+    let requirement = match version {
+        Some(version) => match parse_requirement(&version)? {
+            Some(requirement) => requirement,
+            None => {
+                if let (Some(current), Some(exact)) = (&current, parse_version(&version)) {
+                    if is_major_jump(current, &exact) {
+                        return Err(Error::Other(format!(
+                            "Version {} is a major upgrade from {} (pass --allow-major to install it)",
+                            exact, current
+                        )));
+                    }
+                }
+                return Ok(Some(version));
+            }
+        },
+        // The asset URL needs an exact version, which `get_release_info` asks for
+        None if current.is_none() || source.asset_url.is_some() => return Ok(None),
+        None => VersionReq::STAR,
+    };
+    if source.asset_url.is_some() {
+        return Err(Error::Other(String::from(
+            "upgrade.asset_url is configured, so --version must be an exact version \
+             (requirements are matched against the GitHub releases)",
+        )));
+    }
+    let releases = fetch_releases().await?;
+    let release = pick_release(&releases, &requirement, current.as_ref()).ok_or_else(|| {
+        Error::Other(match &current {
+            Some(current) => format!(
+                "No release matches {} without a major upgrade from {}",
+                requirement, current
+            ),
+            None => format!("No release matches {}", requirement),
+        })
+    })?;
+    info!("Resolved {} to release {}", requirement, release.tag_name);
+    Ok(Some(release.tag_name.clone()))
+}
+
+/// Parses `version` as a requirement such as `^1.2`, `~1.4.1` or `>=1.2, <2`. Exact
+/// versions and other tags are `None`.
+fn parse_requirement(version: &str) -> Result<Option<VersionReq>> {
+    if parse_version(version).is_some() {
+        return Ok(None);
+    }
+    let requirement = VersionReq::parse(version);
+    // An operator makes it a requirement; anything else that fails to parse is a tag
+    if version.starts_with(['^', '~', '=', '<', '>', '*']) || version.contains(',') {
+        return requirement.map(Some).map_err(|e| {
+            Error::Other(format!("Invalid version requirement '{}': {}", version, e))
+        });
+    }
+    Ok(requirement.ok())
+}
+
+/// The newest published release matching `requirement` (and, with `current`, compatible
+/// with it). Prereleases only match requirements that name one, as in semver.
+fn pick_release<'a>(
+    releases: &'a [GithubResponse],
+    requirement: &VersionReq,
+    current: Option<&Version>,
+) -> Option<&'a GithubResponse> {
+    releases
+        .iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| Some((parse_version(&release.tag_name)?, release)))
+        .filter(|(version, _)| requirement.matches(version))
+        .filter(|(version, _)| current.is_none_or(|current| !is_major_jump(current, version)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
 }
 
-async fn execute_async(args: Args) -> Result<()> {
+async fn upgrade(
+    args: Args,
+    source: &UpgradeSource,
+    reporter: Reporter,
+    prompter: Prompter,
+) -> Result<UpgradeReport> {
     info!("Checking for updates...");
+    reporter.step(&tr!("upgrade-checking"));
 
     // Get cache directory for temporary download
     let cache_dir = get_cache_dir()?;
     create_dir_all(&cache_dir).map_err(|e| Error::Io(std::io::Error::other(e)))?;
 
-    // Get release information from GitHub
-    let release_info = get_release_info(&args).await?;
+    // Get release information from GitHub, unless the asset URL is configured outright
+    let target = args.target.as_deref().unwrap_or(env!("TARGET"));
+    let release_info = get_release_info(&args, source, target).await?;
     let current_version = constants::APP_VERSION;
+    let mut report = UpgradeReport {
+        from: current_version.to_string(),
+        to: release_info.tag_name.trim_start_matches('v').to_string(),
+        upgraded: false,
+        telemetry: None,
+    };
 
     // Check if update is needed
-    if !args.force && release_info.tag_name.trim_start_matches('v') == current_version {
+    if !args.force && report.to == current_version {
         info!("Already on the latest version ({})", current_version);
-        return Ok(());
+        reporter.step(&tr!("upgrade-up-to-date", version = current_version));
+        return Ok(report);
+    }
+    if args.check {
+        reporter.step(&tr!(
+            "upgrade-available",
+            version = report.to,
+            current = current_version
+        ));
+        return Ok(report);
     }
 
     info!(
@@ -69,211 +646,783 @@ async fn execute_async(args: Args) -> Result<()> {
     );
 
     // Find the appropriate asset for this platform
-    let asset = find_platform_asset(&release_info)?;
+    let asset = match &source.asset_url {
+        // The release holds just the configured asset
+        Some(_) => release_info.assets[0].clone(),
+        None => mirrored_asset(&release_info, target, source.mirror.as_deref())?,
+    };
+
+    let policy = args.checksum.or(source.checksum).unwrap_or_default();
+    let checksum_url = match policy {
+        ChecksumPolicy::Skip => None,
+        ChecksumPolicy::Auto | ChecksumPolicy::Require => {
+            checksum_url(&release_info, &asset, source)
+        }
+    };
+
+    let executable = std::env::current_exe()?;
+    let destination = match &args.install_dir {
+        Some(dir) => dir.join(executable.file_name().unwrap_or_default()),
+        None => executable.clone(),
+    };
+    if !prompter.confirm(&format!(
+        "Replace {} ({}) with version {}?",
+        destination.display(),
+        current_version,
+        report.to
+    ))? {
+        return Err(Error::Other(format!(
+            "Upgrade cancelled (pass --yes or set {}=true to upgrade without asking)",
+            env_vars::UPGRADE_YES
+        )));
+    }
+
+    let mut telemetry = UpgradeTelemetry::default();
+    let started = Instant::now();
 
     // Download the update
-    download_update(&cache_dir, asset).await?;
+    let archive = download_update(&asset, &report.to, reporter)
+        .instrument(tracing::trace_span!(target: timings::TARGET, "download"))
+        .await?;
+    telemetry.download_bytes = archive.len() as u64;
+    telemetry.download_seconds = started.elapsed().as_secs_f64();
+    telemetry.download_mb_per_sec =
+        throughput_mb_per_sec(telemetry.download_bytes, telemetry.download_seconds);
+    info!(
+        bytes = telemetry.download_bytes,
+        seconds = telemetry.download_seconds,
+        mb_per_sec = telemetry.download_mb_per_sec,
+        "Download finished"
+    );
+
+    let phase = Instant::now();
+    match fetch_checksum(checksum_url.as_deref(), &asset.name).await? {
+        Some(expected) => {
+            let progress = reporter.phase(
+                "verify",
+                &tr!("upgrade-verifying"),
+                Some(archive.len() as u64),
+            );
+            tracing::trace_span!(target: timings::TARGET, "verify")
+                .in_scope(|| verify_checksum(&archive, &expected, &asset.name, &progress))?;
+            progress.finish();
+        }
+        None if policy == ChecksumPolicy::Require => {
+            return Err(Error::Other(format!(
+                "No checksum is published for {} (pass --checksum auto to install it unverified)",
+                asset.name
+            )));
+        }
+        None if policy == ChecksumPolicy::Skip => {
+            debug!("Not verifying the checksum of {}", asset.name)
+        }
+        None => warn!(
+            "No checksum is published for {}; installing it unverified",
+            asset.name
+        ),
+    }
+    telemetry.verify_seconds = phase.elapsed().as_secs_f64();
+
+    let phase = Instant::now();
+    let progress = reporter.phase("extract", &tr!("upgrade-extracting"), None);
+    tracing::trace_span!(target: timings::TARGET, "extract")
+        .in_scope(|| extract_update(&cache_dir, archive, &progress))?;
+    progress.finish();
+    telemetry.extract_seconds = phase.elapsed().as_secs_f64();
 
     // Apply the update
-    apply_update(&cache_dir, &release_info)?;
+    let phase = Instant::now();
+    let progress = reporter.phase("install", &tr!("upgrade-installing"), None);
+    tracing::trace_span!(target: timings::TARGET, "install")
+        .in_scope(|| apply_update(&cache_dir, &destination))?;
+    progress.finish();
+    telemetry.apply_seconds = phase.elapsed().as_secs_f64();
+    telemetry.total_seconds = started.elapsed().as_secs_f64();
+    telemetry.http = http::metrics();
 
     info!(
+        verify_seconds = telemetry.verify_seconds,
+        extract_seconds = telemetry.extract_seconds,
+        apply_seconds = telemetry.apply_seconds,
+        total_seconds = telemetry.total_seconds,
         "Successfully upgraded to version {}",
-        release_info.tag_name.trim_start_matches('v')
+        report.to
     );
 
-    Ok(())
+    report.upgraded = true;
+    report.telemetry = Some(telemetry);
+    Ok(report)
 }
 
 fn get_cache_dir() -> Result<std::path::PathBuf> {
-    if let Some(proj_dirs) = directories::ProjectDirs::from("", "", env!("CARGO_PKG_NAME")) {
+    if let Some(proj_dirs) = directories::ProjectDirs::from("", "", identity::app_name()) {
         Ok(proj_dirs.cache_dir().to_path_buf())
     } else {
         // Fallback to temp directory
-        Ok(std::env::temp_dir().join(format!("{}-update", env!("CARGO_PKG_NAME"))))
+        Ok(std::env::temp_dir().join(format!("{}-update", identity::app_name())))
     }
 }
 
-async fn get_release_info(args: &Args) -> Result<GithubResponse> {
-    let client = ClientBuilder::new()
-        .build()
-        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+async fn get_release_info(
+    args: &Args,
+    source: &UpgradeSource,
+    target: &str,
+) -> Result<GithubResponse> {
+    let Some(template) = &source.asset_url else {
+        return fetch_release(args.version.as_deref()).await;
+    };
+    let tag = args.version.as_deref().ok_or_else(|| {
+        Error::Other(String::from(
+            "upgrade.asset_url is configured, so the version must be given with --version \
+             (the GitHub API is not asked for the latest release)",
+        ))
+    })?;
+    let url = fill_url(template, tag, target, None);
+    debug!("Downloading from the configured asset URL: {}", url);
+    let name = url
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    Ok(GithubResponse {
+        tag_name: tag.to_string(),
+        assets: vec![GithubAssetResponse {
+            browser_download_url: url,
+            name,
+        }],
+        prerelease: false,
+        draft: false,
+        published_at: None,
+        body: None,
+    })
+}
+
+/// The asset of `release` for `target`, with its URL pointing at `mirror` if one is set.
+fn mirrored_asset(
+    release: &GithubResponse,
+    target: &str,
+    mirror: Option<&str>,
+) -> Result<GithubAssetResponse> {
+    let asset = find_platform_asset(release, target)?;
+    let Some(mirror) = mirror else {
+        return Ok(asset.clone());
+    };
+    let url = if mirror.contains('{') {
+        fill_url(mirror, &release.tag_name, target, Some(&asset.name))
+    } else {
+        format!(
+            "{}/{}/{}",
+            mirror.trim_end_matches('/'),
+            release.tag_name,
+            asset.name
+        )
+    };
+    debug!("Downloading {} from mirror: {}", asset.name, url);
+    Ok(GithubAssetResponse {
+        browser_download_url: url,
+        name: asset.name.clone(),
+    })
+}
+
+/// Fills `{tag}`, `{version}` (the tag without a leading `v`), `{target}`, `{app}` and,
+/// if given, `{name}` (the asset name) into a URL template.
+fn fill_url(template: &str, tag: &str, target: &str, name: Option<&str>) -> String {
+    let mut url = template
+        .replace("{tag}", tag)
+        .replace("{version}", tag.trim_start_matches('v'))
+        .replace("{target}", target)
+        .replace("{app}", identity::app_name());
+    if let Some(name) = name {
+        url = url.replace("{name}", name);
+    }
+    url
+}
 
-    let url = if let Some(ref version) = args.version {
+/// Fetches a release by tag, or the latest release when `version` is `None`.
+async fn fetch_release(version: Option<&str>) -> Result<GithubResponse> {
+    let url = if let Some(version) = version {
         format!(
-            "https://api.github.com/repos/{}/{}/releases/tags/{}",
-            constants::GITHUB_OWNER,
-            constants::GITHUB_REPO,
+            "https://api.github.com/repos/{}/releases/tags/{}",
+            identity::repository()?,
             version
         )
     } else {
         format!(
-            "https://api.github.com/repos/{}/{}/releases/latest",
-            constants::GITHUB_OWNER,
-            constants::GITHUB_REPO
+            "https://api.github.com/repos/{}/releases/latest",
+            identity::repository()?
         )
     };
 
     debug!("Fetching release info from: {}", url);
 
-    let resp = client
-        .get(&url)
-        .header("Accept", "application/vnd.github+json")
-        .send()
-        .await
-        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    let resp = http::get(&url, Some(GITHUB_JSON)).await?;
 
     if !resp.status().is_success() {
         if resp.status() == 404 {
-            return Err(Error::Other(if args.version.is_some() {
-                format!("Release '{}' not found", args.version.as_ref().unwrap())
+            return Err(Error::Other(if let Some(version) = version {
+                format!("Release '{}' not found", version)
             } else {
                 "No releases found for this project".to_string()
             }));
         }
-        return Err(Error::Io(std::io::Error::other(format!(
-            "GitHub API returned status: {}",
-            resp.status()
-        ))));
+        return Err(Error::Http {
+            url,
+            status: resp.status().as_u16(),
+        });
     }
 
     resp.json::<GithubResponse>()
         .await
-        .map_err(|e| Error::Io(std::io::Error::other(e)))
+        .map_err(|e| network_error(&url, e))
 }
 
-fn find_platform_asset(release: &GithubResponse) -> Result<&GithubAssetResponse> {
-    let target = env!("TARGET");
-
-    let binary_name = constants::APP_NAME;
-    let expected_name = format!("{}-{}_{}.tar.gz", binary_name, release.tag_name, target);
-
-    release
-        .assets
-        .iter()
-        .find(|asset| asset.name == expected_name)
-        .ok_or_else(|| {
-            Error::Other(format!(
-                "No pre-built binary found for target '{}' in release '{}'",
-                target, release.tag_name
-            ))
-        })
+/// The asset of `release` built for `target`, preferring the exact name (see
+/// [`asset::select`]).
+fn find_platform_asset<'a>(
+    release: &'a GithubResponse,
+    target: &str,
+) -> Result<&'a GithubAssetResponse> {
+    let names = release.assets.iter().map(|asset| asset.name.as_str());
+    let Some((index, tier)) = asset::select(names, identity::app_name(), &release.tag_name, target)
+    else {
+        return Err(Error::Other(format!(
+            "No pre-built binary found for target '{}' in release '{}'",
+            target, release.tag_name
+        )));
+    };
+    let asset = &release.assets[index];
+    match tier {
+        Match::Exact => debug!("Selected asset {}", asset.name),
+        Match::Alias => info!(
+            "Selected asset {} for {} by OS and architecture",
+            asset.name, target
+        ),
+        Match::OtherLibc => warn!(
+            "No build for {} in release '{}'; using {}, built for the other C library",
+            target, release.tag_name, asset.name
+        ),
+    }
+    Ok(asset)
 }
 
-async fn download_update(cache_dir: &Path, asset: &GithubAssetResponse) -> Result<()> {
-    let client = ClientBuilder::new()
-        .build()
-        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
-
+async fn download_update(
+    asset: &GithubAssetResponse,
+    version: &str,
+    reporter: Reporter,
+) -> Result<Vec<u8>> {
     info!("Downloading update from: {}", asset.browser_download_url);
 
-    let res = client
-        .get(&asset.browser_download_url)
-        .send()
-        .await
-        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
-
-    let total_size = res
-        .content_length()
-        .ok_or_else(|| Error::Io(std::io::Error::other("Failed to get content length")))?;
+    let download = http::Download::start(&asset.browser_download_url).await?;
+    let total_size = download.size();
 
-    // Set up progress bar
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})"
-        )
-        .unwrap()
-        .progress_chars("#>-"),
+    let progress = reporter.phase(
+        "download",
+        &tr!("upgrade-downloading", version = version),
+        Some(total_size),
     );
-    pb.set_message("Downloading update");
 
-    // Download to memory buffer
-    let mut downloaded: u64 = 0;
-    let mut stream = res.bytes_stream();
-    let mut buffer = Vec::new();
+    // Download to memory buffer; one bar covers all ranges of a chunked download
+    let buffer = download
+        .fetch(|downloaded| progress.set_position(min(downloaded, total_size)))
+        .await?;
+
+    progress.finish();
+    info!("Download complete");
 
-    while let Some(item) = stream.next().await {
-        let chunk = item.map_err(|e| Error::Io(std::io::Error::other(e)))?;
-        buffer.extend_from_slice(&chunk);
+    Ok(buffer)
+}
 
-        let new = min(downloaded + (chunk.len() as u64), total_size);
-        downloaded = new;
-        pb.set_position(new);
+/// URL of the SHA-256 checksum of `asset`, published as `<asset name>.sha256`: for
+/// GitHub releases when the release lists it (downloaded from the mirror, if one is set,
+/// like the asset), and otherwise next to the configured asset URL, if it is there.
+fn checksum_url(
+    release: &GithubResponse,
+    asset: &GithubAssetResponse,
+    source: &UpgradeSource,
+) -> Option<String> {
+    let name = format!("{}.sha256", asset.name);
+    let published =
+        source.asset_url.is_some() || release.assets.iter().any(|listed| listed.name == name);
+    if !published {
+        return None;
     }
+    // The suffix belongs to the path, before any query or fragment
+    let url = &asset.browser_download_url;
+    let end = url.find(['?', '#']).unwrap_or(url.len());
+    Some(format!("{}.sha256{}", &url[..end], &url[end..]))
+}
 
-    pb.finish_and_clear();
-    info!("Download complete");
+/// Downloads the checksum at `url` and returns the SHA-256 digest it lists for `name`;
+/// `None` when there is no URL or nothing is published there.
+async fn fetch_checksum(url: Option<&str>, name: &str) -> Result<Option<String>> {
+    let Some(url) = url else {
+        return Ok(None);
+    };
+    debug!("Fetching checksum from: {}", url);
+    let resp = http::get(url, None).await?;
+    if resp.status() == 404 {
+        return Ok(None);
+    }
+    if !resp.status().is_success() {
+        return Err(Error::Http {
+            url: url.to_string(),
+            status: resp.status().as_u16(),
+        });
+    }
+    let text = resp.text().await.map_err(|e| network_error(url, e))?;
+    published_checksum(&text, name)
+        .map(|hex| Some(hex.to_ascii_lowercase()))
+        .ok_or_else(|| Error::InvalidInput {
+            format: "checksum file",
+            message: format!("{} lists no SHA-256 checksum for {}", url, name),
+        })
+}
+
+/// The SHA-256 digest for `name` in `sha256sum` output: the line naming it, or a lone
+/// digest with or without a file name.
+fn published_checksum<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let hex = match lines[..] {
+        [line] if !line.trim().contains(char::is_whitespace) => line.trim(),
+        [line] => digest::parse_checksum_line(line)?.0,
+        _ => {
+            lines
+                .iter()
+                .filter_map(|line| digest::parse_checksum_line(line))
+                .find(|(_, listed)| Path::new(listed).file_name() == Some(name.as_ref()))?
+                .0
+        }
+    };
+    (hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit())).then_some(hex)
+}
+
+/// Checks the SHA-256 digest of the downloaded `archive` against `expected`, advancing
+/// `progress` by the bytes hashed.
+fn verify_checksum(archive: &[u8], expected: &str, name: &str, progress: &Phase) -> Result<()> {
+    debug!("Verifying the checksum of {}", name);
+    // The same streaming hasher as `hash`
+    let actual =
+        digest::digest_reader_with_progress(archive, HashAlgorithm::Sha256, |n| progress.inc(n))?
+            .hex;
+    if actual != expected {
+        return Err(Error::Other(format!(
+            "Checksum mismatch for {}: expected {}, got {} (the download is corrupt or was tampered with; nothing was installed)",
+            name, expected, actual
+        )));
+    }
+    info!("Checksum of {} verified", name);
+    Ok(())
+}
 
-    // Extract the archive
+fn extract_update(cache_dir: &Path, buffer: Vec<u8>, progress: &Phase) -> Result<()> {
     debug!("Extracting update archive");
     let mut cursor = Cursor::new(buffer);
     let gz = flate2::read::GzDecoder::new(&mut cursor);
     let mut archive = tar::Archive::new(gz);
 
-    archive
-        .unpack(cache_dir)
-        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    let extract_error = |e| Error::Io(std::io::Error::other(e));
+    let mut entries = 0;
+    for entry in archive.entries().map_err(extract_error)? {
+        entry
+            .and_then(|mut entry| entry.unpack_in(cache_dir))
+            .map_err(extract_error)?;
+        entries += 1;
+        progress.inc(1);
+        progress.set_message(tr!("upgrade-extracting-entries", count = entries));
+    }
+    debug!("Extracted {} entries", entries);
 
     Ok(())
 }
 
-fn apply_update(cache_dir: &Path, _release: &GithubResponse) -> Result<()> {
-    let binary_name = constants::APP_NAME;
+/// Installs the extracted binary as `destination`: the running executable, or a file in
+/// the `--install-dir`, which is created when it is not there yet.
+fn apply_update(cache_dir: &Path, destination: &Path) -> Result<()> {
+    let binary_name = identity::app_name();
     let update_binary = if cfg!(windows) {
         cache_dir.join(format!("{}.exe", binary_name))
     } else {
         cache_dir.join(binary_name)
     };
 
-    if !update_binary.exists() {
-        return Err(Error::Other(format!(
-            "Downloaded binary not found at: {}",
-            update_binary.display()
-        )));
+    if !destination.exists() {
+        debug!("Installing {} as a new file", destination.display());
+        if let Some(dir) = destination.parent() {
+            create_dir_all(dir)?;
+        }
+        #[cfg(unix)]
+        install::match_metadata(&env::current_exe()?, &update_binary)?;
+        return install::install(&RealFs, &update_binary, destination, Strategy::Replace);
     }
+    #[cfg(unix)]
+    install::match_metadata(destination, &update_binary)?;
 
-    let current_exe = env::current_exe().map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    install::install(&RealFs, &update_binary, destination, Strategy::current())
+}
 
-    debug!(
-        "Replacing {} with {}",
-        current_exe.display(),
-        update_binary.display()
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // On Windows, we might need to rename the current executable first
-    #[cfg(windows)]
-    {
-        let backup = current_exe.with_extension("old");
-        if backup.exists() {
-            std::fs::remove_file(&backup).ok();
-        }
-        if let Err(e) = rename(&current_exe, &backup) {
-            warn!("Failed to create backup of current executable: {}", e);
+    fn release(tag: &str, prerelease: bool) -> GithubResponse {
+        GithubResponse {
+            tag_name: String::from(tag),
+            assets: vec![GithubAssetResponse {
+                browser_download_url: String::new(),
+                name: format!("{}-{}_{}.tar.gz", identity::app_name(), tag, env!("TARGET")),
+            }],
+            prerelease,
+            draft: false,
+            published_at: Some(String::from("2024-05-01T10:00:00Z")),
+            body: Some(format!("Notes for {}\n", tag)),
         }
     }
 
-    // Replace the current executable with the new one
-    if let Err(e) = rename(&update_binary, &current_exe) {
-        match e.kind() {
-            ErrorKind::PermissionDenied => {
-                return Err(Error::Other(format!(
-                    "Permission denied: cannot write to {}. Try running with elevated privileges.",
-                    current_exe.display()
-                )));
-            }
-            _ => {
-                return Err(Error::Io(e));
-            }
+    #[test]
+    fn test_select_releases_by_channel() {
+        let releases = || {
+            vec![
+                release("v2.0.0-rc.1", true),
+                release("v1.1.0-beta.2", true),
+                release("v1.0.0", false),
+                GithubResponse {
+                    draft: true,
+                    ..release("v3.0.0", false)
+                },
+            ]
+        };
+        let versions = |entries: Vec<ReleaseEntry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.version).collect()
+        };
+
+        assert_eq!(
+            versions(select_releases(releases(), None, 10)),
+            ["2.0.0-rc.1", "1.1.0-beta.2", "1.0.0"]
+        );
+        assert_eq!(
+            versions(select_releases(releases(), Some("stable"), 10)),
+            ["1.0.0"]
+        );
+        assert_eq!(
+            versions(select_releases(releases(), Some("beta"), 10)),
+            ["1.1.0-beta.2"]
+        );
+        assert_eq!(
+            versions(select_releases(releases(), None, 1)),
+            ["2.0.0-rc.1"]
+        );
+
+        let entry = &select_releases(releases(), Some("stable"), 1)[0];
+        assert!(entry.asset_available);
+        assert_eq!(entry.published.as_deref(), Some("2024-05-01"));
+    }
+
+    #[test]
+    fn test_report_serializes_telemetry() {
+        let report = UpgradeReport {
+            from: String::from("1.0.0"),
+            to: String::from("1.1.0"),
+            upgraded: true,
+            telemetry: Some(UpgradeTelemetry {
+                download_bytes: 10,
+                ..UpgradeTelemetry::default()
+            }),
+        };
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(value["telemetry"]["download_bytes"], 10);
+        assert_eq!(value["telemetry"]["http"]["retries"], 0);
+    }
+
+    #[test]
+    fn test_extract_update_unpacks_entries() {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for (name, contents) in [("app", &b"binary"[..]), ("docs/README.md", b"docs")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, name, contents).unwrap();
         }
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let progress = Reporter::Json.phase("extract", "Extracting the update.", None);
+        extract_update(dir.path(), archive, &progress).unwrap();
+        progress.finish();
+        assert_eq!(std::fs::read(dir.path().join("app")).unwrap(), b"binary");
+        assert_eq!(
+            std::fs::read(dir.path().join("docs/README.md")).unwrap(),
+            b"docs"
+        );
     }
 
-    // Clean up Windows backup file
-    #[cfg(windows)]
-    {
-        let backup = current_exe.with_extension("old");
-        std::fs::remove_file(backup).ok();
+    #[test]
+    fn test_mirror_from_env() {
+        let configured = UpgradeSource {
+            mirror: Some(String::from("https://configured.example.com")),
+            ..UpgradeSource::default()
+        };
+        std::env::remove_var(env_vars::UPGRADE_MIRROR);
+        assert_eq!(
+            source_with_env(&configured).unwrap().mirror,
+            configured.mirror
+        );
+        std::env::set_var(env_vars::UPGRADE_MIRROR, "https://env.example.com/{name}");
+        assert_eq!(
+            source_with_env(&configured).unwrap().mirror.as_deref(),
+            Some("https://env.example.com/{name}")
+        );
+        std::env::remove_var(env_vars::UPGRADE_MIRROR);
     }
 
-    Ok(())
+    #[test]
+    fn test_checksum_url() {
+        let mut release = release("v1.2.0", false);
+        release.assets[0].browser_download_url =
+            String::from("https://example.com/download/tool.tar.gz");
+        let asset = release.assets[0].clone();
+        let github = UpgradeSource::default();
+        assert_eq!(checksum_url(&release, &asset, &github), None);
+
+        release.assets.push(GithubAssetResponse {
+            browser_download_url: String::new(),
+            name: format!("{}.sha256", asset.name),
+        });
+        assert_eq!(
+            checksum_url(&release, &asset, &github).as_deref(),
+            Some("https://example.com/download/tool.tar.gz.sha256")
+        );
+
+        // Next to a configured asset URL, whether or not anything is there
+        let configured = GithubAssetResponse {
+            browser_download_url: String::from("https://example.com/tool.tar.gz?token=1"),
+            name: String::from("tool.tar.gz"),
+        };
+        let source = UpgradeSource {
+            asset_url: Some(String::from("https://example.com/tool.tar.gz?token=1")),
+            ..UpgradeSource::default()
+        };
+        assert_eq!(
+            checksum_url(&release, &configured, &source).as_deref(),
+            Some("https://example.com/tool.tar.gz.sha256?token=1")
+        );
+    }
+
+    #[test]
+    fn test_published_checksum() {
+        let hex = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(
+            published_checksum(&format!("{}\n", hex), "a.tar.gz"),
+            Some(hex)
+        );
+        assert_eq!(
+            published_checksum(&format!("{}  ./a.tar.gz\n", hex), "a.tar.gz"),
+            Some(hex)
+        );
+        let listing = format!("{}  b.tar.gz\n{}  dist/a.tar.gz\n", "0".repeat(64), hex);
+        assert_eq!(published_checksum(&listing, "a.tar.gz"), Some(hex));
+        assert_eq!(published_checksum(&listing, "c.tar.gz"), None);
+        // Not SHA-256
+        assert_eq!(
+            published_checksum("900150983cd24fb0d6963f7d28e17f72", "a"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let progress = Reporter::Json.phase("verify", "Verifying the checksum.", Some(3));
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        verify_checksum(b"abc", abc, "a.tar.gz", &progress).unwrap();
+        let message = verify_checksum(b"abd", abc, "a.tar.gz", &progress)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.starts_with("Checksum mismatch for a.tar.gz"),
+            "{}",
+            message
+        );
+        progress.finish();
+    }
+
+    #[test]
+    fn test_mirrored_asset_urls() {
+        let release = release("v1.2.0", false);
+        let name = &release.assets[0].name;
+        let target = env!("TARGET");
+        let url = |mirror| {
+            mirrored_asset(&release, target, mirror)
+                .unwrap()
+                .browser_download_url
+        };
+
+        assert_eq!(url(None), "");
+        assert_eq!(
+            url(Some("https://artifacts.example.com/tool/")),
+            format!("https://artifacts.example.com/tool/v1.2.0/{}", name)
+        );
+        assert_eq!(
+            url(Some("https://artifacts.example.com/{app}/{version}/{name}")),
+            format!(
+                "https://artifacts.example.com/{}/1.2.0/{}",
+                identity::app_name(),
+                name
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_asset_url_bypasses_github() {
+        let source = UpgradeSource {
+            mirror: None,
+            asset_url: Some(String::from(
+                "https://artifacts.example.com/tool-{version}-{target}.tar.gz?token=1",
+            )),
+            allow_major: None,
+            checksum: None,
+        };
+        let args = Args {
+            action: None,
+            version: Some(String::from("v2.0.0")),
+            allow_major: None,
+            to_pinned: false,
+            force: false,
+            target: Some(String::from("linux-arm64")),
+            changelog: false,
+            check: false,
+            checksum: None,
+            install_dir: None,
+        };
+        let release = get_release_info(&args, &source, "linux-arm64")
+            .await
+            .unwrap();
+        assert_eq!(release.tag_name, "v2.0.0");
+        assert_eq!(release.assets[0].name, "tool-2.0.0-linux-arm64.tar.gz");
+        assert_eq!(
+            release.assets[0].browser_download_url,
+            "https://artifacts.example.com/tool-2.0.0-linux-arm64.tar.gz?token=1"
+        );
+
+        let latest = Args {
+            version: None,
+            ..args
+        };
+        let message = get_release_info(&latest, &source, "linux-arm64")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("--version"), "{}", message);
+    }
+
+    #[test]
+    fn test_changelog_between() {
+        let releases = || {
+            vec![
+                release("v2.0.0-rc.1", true),
+                release("v1.3.0", false),
+                release("v1.2.0", false),
+                GithubResponse {
+                    draft: true,
+                    ..release("v1.1.5", false)
+                },
+                release("v1.1.0", false),
+                release("v1.0.0", false),
+            ]
+        };
+        let tags = |entries: Vec<ChangelogEntry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.tag).collect()
+        };
+
+        let entries = changelog_between(releases(), "1.1.0", None).unwrap();
+        assert_eq!(entries[0].notes, "Notes for v1.2.0");
+        assert_eq!(entries[0].published.as_deref(), Some("2024-05-01"));
+        assert_eq!(tags(entries), ["v1.2.0", "v1.3.0"]);
+        assert_eq!(
+            tags(changelog_between(releases(), "1.0.0", Some("1.2.0")).unwrap()),
+            ["v1.1.0", "v1.2.0"]
+        );
+        assert_eq!(
+            tags(changelog_between(releases(), "1.2.0", Some("v2.0.0-rc.1")).unwrap()),
+            ["v1.3.0", "v2.0.0-rc.1"]
+        );
+        assert!(changelog_between(releases(), "1.3.0", None)
+            .unwrap()
+            .is_empty());
+        assert!(changelog_between(releases(), "1.0.0", Some("9.9.9")).is_err());
+    }
+
+    #[test]
+    fn test_parse_requirement() {
+        let parse = |version| parse_requirement(version).unwrap().map(|r| r.to_string());
+        assert_eq!(parse("^1.2"), Some(String::from("^1.2")));
+        assert_eq!(parse(">=1.4, <2"), Some(String::from(">=1.4, <2")));
+        assert_eq!(parse("1.2"), Some(String::from("^1.2")));
+        assert_eq!(parse("v1.2.0"), None);
+        assert_eq!(parse("1.2.0-rc.1"), None);
+        assert_eq!(parse("nightly"), None);
+        assert!(parse_requirement("^one").is_err());
+    }
+
+    #[test]
+    fn test_is_major_jump() {
+        let v = |version| Version::parse(version).unwrap();
+        assert!(is_major_jump(&v("1.4.0"), &v("2.0.0")));
+        assert!(!is_major_jump(&v("1.4.0"), &v("1.9.3")));
+        assert!(!is_major_jump(&v("2.0.0"), &v("1.0.0")));
+        assert!(is_major_jump(&v("0.3.1"), &v("0.4.0")));
+        assert!(!is_major_jump(&v("0.3.1"), &v("0.3.9")));
+    }
+
+    #[test]
+    fn test_pick_release() {
+        let releases = vec![
+            release("v2.1.0", false),
+            release("v2.0.0", false),
+            release("v1.5.0-rc.1", true),
+            GithubResponse {
+                draft: true,
+                ..release("v1.4.2", false)
+            },
+            release("v1.4.1", false),
+            release("v1.3.0", false),
+            release("nightly", true),
+        ];
+        let pick = |requirement: &str, current: Option<&str>| {
+            let current = current.map(|version| Version::parse(version).unwrap());
+            pick_release(
+                &releases,
+                &VersionReq::parse(requirement).unwrap(),
+                current.as_ref(),
+            )
+            .map(|release| release.tag_name.as_str())
+        };
+        assert_eq!(pick("*", None), Some("v2.1.0"));
+        assert_eq!(pick("*", Some("1.3.0")), Some("v1.4.1"));
+        assert_eq!(pick("^1.2", None), Some("v1.4.1"));
+        assert_eq!(pick("~1.3", None), Some("v1.3.0"));
+        assert_eq!(pick(">=1.5.0-rc.1, <2", None), Some("v1.5.0-rc.1"));
+        assert_eq!(pick(">=2", Some("1.3.0")), None);
+        assert_eq!(pick("^3", None), None);
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("1.2.0", "1.1.9"));
+        assert!(is_newer("2.0.0", "1.10.0"));
+        assert!(!is_newer("1.2.0", "1.2.0"));
+        assert!(!is_newer("1.2.0-rc.1", "1.2.0"));
+        assert!(!is_newer("0.9.0", "1.0.0"));
+        assert!(is_newer("1.2.0", "1.2.0-rc.1"));
+        assert!(is_newer("1.2.0-rc.2", "1.2.0-rc.1"));
+        assert!(is_newer("1.10", "1.9.0"));
+    }
 }
@@ -0,0 +1,76 @@
+use clap::Args as ClapArgs;
+use serde::Serialize;
+
+use crate::constants;
+use crate::context::Context;
+use crate::error::Result;
+use crate::identity;
+use crate::output::{print_json, OutputFormat};
+
+#[derive(ClapArgs, Debug)]
+pub struct Args {}
+
+/// How and from what this binary was built, gathered by `build.rs`.
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// `None` when built without git, e.g. from a crates.io tarball
+    pub git_hash: Option<&'static str>,
+    pub git_dirty: bool,
+    pub build_date: &'static str,
+    pub rustc: &'static str,
+    pub target: &'static str,
+    pub profile: &'static str,
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        BuildInfo {
+            name: identity::app_name(),
+            version: constants::APP_VERSION,
+            git_hash: Some(constants::GIT_HASH).filter(|hash| !hash.is_empty()),
+            git_dirty: constants::GIT_DIRTY == "true",
+            build_date: constants::BUILD_DATE,
+            rustc: constants::RUSTC_VERSION,
+            target: constants::TARGET,
+            profile: constants::BUILD_PROFILE,
+            features: constants::FEATURES
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .collect(),
+        }
+    }
+}
+
+pub async fn execute(_args: Args, ctx: &Context) -> Result<()> {
+    print(ctx.format)
+}
+
+/// Prints the full build report, as used by `version` and `--version --verbose`.
+pub fn print(format: OutputFormat) -> Result<()> {
+    let info = BuildInfo::current();
+    if format == OutputFormat::Json {
+        return print_json(&info);
+    }
+
+    let features = if info.features.is_empty() {
+        String::from("(none)")
+    } else {
+        info.features.join(", ")
+    };
+    let dirty = if info.git_dirty { " (dirty)" } else { "" };
+    println!("{} {}", info.name, info.version);
+    println!(
+        "commit:     {}{}",
+        info.git_hash.unwrap_or("unknown"),
+        dirty
+    );
+    println!("build date: {}", info.build_date);
+    println!("rustc:      {}", info.rustc);
+    println!("target:     {}", info.target);
+    println!("profile:    {}", info.profile);
+    println!("features:   {}", features);
+    Ok(())
+}
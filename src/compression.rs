@@ -0,0 +1,283 @@
+//! Transparent decompression of inputs and optional compression of outputs.
+
+use std::fmt;
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use clap::ValueEnum;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Compression formats, detected from the file extension.
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Format implied by the extension of `path`, if any.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// Levels the encoder accepts (higher compresses better but slower).
+    pub fn levels(self) -> RangeInclusive<i32> {
+        match self {
+            Compression::Gzip | Compression::Xz => 0..=9,
+            Compression::Zstd => 1..=22,
+        }
+    }
+
+    fn default_level(self) -> i32 {
+        match self {
+            Compression::Gzip => 6,
+            Compression::Zstd => zstd::DEFAULT_COMPRESSION_LEVEL,
+            Compression::Xz => 6,
+        }
+    }
+
+    /// Checks that `level` is one this format accepts.
+    pub fn check_level(self, level: i32) -> Result<(), String> {
+        let levels = self.levels();
+        if levels.contains(&level) {
+            return Ok(());
+        }
+        Err(format!(
+            "Compression level {} is out of range for {} ({}-{})",
+            level,
+            self,
+            levels.start(),
+            levels.end()
+        ))
+    }
+}
+
+/// How outputs are compressed, as chosen with `--compress` or a profile's `compress`.
+#[derive(ValueEnum, Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputCompression {
+    Gzip,
+    Zstd,
+    Xz,
+    /// Write outputs uncompressed, even with a .gz/.zst/.xz extension
+    None,
+}
+
+impl OutputCompression {
+    pub fn format(self) -> Option<Compression> {
+        match self {
+            OutputCompression::Gzip => Some(Compression::Gzip),
+            OutputCompression::Zstd => Some(Compression::Zstd),
+            OutputCompression::Xz => Some(Compression::Xz),
+            OutputCompression::None => None,
+        }
+    }
+}
+
+/// Settings of the encoder [`encode`] and [`EncodingWriter`] use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoder {
+    pub format: Option<Compression>,
+    /// Compression level, or the format's default
+    pub level: Option<i32>,
+    /// Threads zstd may compress with; other formats use one
+    pub threads: u32,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Wraps `reader` in a streaming decoder for `compression`.
+pub fn decoder<'a>(
+    reader: impl Read + 'a,
+    compression: Option<Compression>,
+) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(reader)),
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::new(reader)?),
+        Some(Compression::Xz) => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        None => Box::new(reader),
+    })
+}
+
+/// Writes `contents` to `writer`, compressed as `encoder` says.
+pub fn encode(writer: impl Write, contents: &[u8], encoder: &Encoder) -> io::Result<()> {
+    let mut writer = EncodingWriter::new(writer, encoder)?;
+    writer.write_all(contents)?;
+    writer.finish()
+}
+
+/// A writer compressing what is written to it into `W` as an [`Encoder`] says; the
+/// output is complete once [`EncodingWriter::finish`] returns.
+pub struct EncodingWriter<W: Write> {
+    inner: Encoding<W>,
+}
+
+enum Encoding<W: Write> {
+    Plain(BufWriter<W>),
+    Gzip(GzEncoder<BufWriter<W>>),
+    Zstd(zstd::Encoder<'static, BufWriter<W>>),
+    Xz(xz2::write::XzEncoder<BufWriter<W>>),
+}
+
+impl<W: Write> EncodingWriter<W> {
+    pub fn new(writer: W, encoder: &Encoder) -> io::Result<Self> {
+        let file = BufWriter::new(writer);
+        let Some(format) = encoder.format else {
+            return Ok(Self {
+                inner: Encoding::Plain(file),
+            });
+        };
+        let level = encoder.level.unwrap_or_else(|| format.default_level());
+        format
+            .check_level(level)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidInput, message))?;
+        let inner = match format {
+            Compression::Gzip => {
+                Encoding::Gzip(GzEncoder::new(file, flate2::Compression::new(level as u32)))
+            }
+            Compression::Zstd => {
+                let mut zstd = zstd::Encoder::new(file, level)?;
+                if encoder.threads > 1 {
+                    zstd.multithread(encoder.threads)?;
+                }
+                Encoding::Zstd(zstd)
+            }
+            Compression::Xz => Encoding::Xz(xz2::write::XzEncoder::new(file, level as u32)),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Completes the compressed stream and flushes it to the underlying writer.
+    pub fn finish(self) -> io::Result<()> {
+        match self.inner {
+            Encoding::Plain(mut file) => file.flush(),
+            Encoding::Gzip(encoder) => encoder.finish()?.flush(),
+            Encoding::Zstd(encoder) => encoder.finish()?.flush(),
+            Encoding::Xz(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Encoding::Plain(file) => file.write(buf),
+            Encoding::Gzip(encoder) => encoder.write(buf),
+            Encoding::Zstd(encoder) => encoder.write(buf),
+            Encoding::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Encoding::Plain(file) => file.flush(),
+            Encoding::Gzip(encoder) => encoder.flush(),
+            Encoding::Zstd(encoder) => encoder.flush(),
+            Encoding::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            Compression::from_path(Path::new("a.log.gz")),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_path(Path::new("a.ZST")),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            Compression::from_path(Path::new("a.xz")),
+            Some(Compression::Xz)
+        );
+        assert_eq!(Compression::from_path(Path::new("a.txt")), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = TempDir::new().unwrap();
+        for compression in [
+            Some(Compression::Gzip),
+            Some(Compression::Zstd),
+            Some(Compression::Xz),
+            None,
+        ] {
+            let path = dir.path().join("data");
+            encode(
+                File::create(&path).unwrap(),
+                b"hello\nworld\n",
+                &Encoder {
+                    format: compression,
+                    level: None,
+                    threads: 1,
+                },
+            )
+            .unwrap();
+
+            let mut contents = String::new();
+            decoder(File::open(&path).unwrap(), compression)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, "hello\nworld\n");
+        }
+    }
+
+    #[test]
+    fn test_levels_and_threads() {
+        let contents = "a fairly repetitive line\n".repeat(10_000);
+        let encoded = |format, level, threads| {
+            let mut out = Vec::new();
+            let encoder = Encoder {
+                format: Some(format),
+                level,
+                threads,
+            };
+            encode(&mut out, contents.as_bytes(), &encoder).map(|()| out)
+        };
+        let fast = encoded(Compression::Zstd, Some(1), 1).unwrap();
+        let best = encoded(Compression::Zstd, Some(19), 4).unwrap();
+        assert!(best.len() <= fast.len(), "{} > {}", best.len(), fast.len());
+        let mut decoded = String::new();
+        decoder(best.as_slice(), Some(Compression::Zstd))
+            .unwrap()
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, contents);
+
+        let error = encoded(Compression::Gzip, Some(12), 1).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Compression level 12 is out of range for gzip (0-9)"
+        );
+        assert!(encoded(Compression::Gzip, Some(9), 1).is_ok());
+    }
+}
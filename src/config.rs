@@ -1,15 +1,23 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use clap::ValueEnum;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+use crate::compression::OutputCompression;
 use crate::env_vars;
 use crate::error::{Error, Result};
-use std::collections::HashMap;
+use crate::identity;
+use crate::output::OutputFormat;
+use crate::schedule::Schedule;
+use crate::suggest;
+use crate::transform::Transform;
+use std::collections::{BTreeMap, HashMap};
 
 /// Individual profile configuration
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(default)]
 pub struct Profile {
     /// Output directory for this profile
@@ -20,17 +28,220 @@ pub struct Profile {
 
     /// Number of parallel jobs to run
     pub parallel_jobs: u32,
+
+    /// Output format when --format is not given (text, json, markdown)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<OutputFormat>,
+
+    /// Free space in MB that `run` must leave on the output filesystem; runs whose
+    /// estimated outputs would eat into it are aborted before writing (see --min-free-space)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_free_space_mb: Option<u64>,
+
+    /// Largest input in MB that `run` reads into memory; larger ones are streamed, or
+    /// refused when they must be read whole (see --max-memory)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u64>,
+
+    /// How `run` compresses outputs when --compress is not given (gzip, zstd, none)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress: Option<OutputCompression>,
+
+    /// Compression level when --compression-level is not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<i32>,
+}
+
+/// Background check for newer releases while other commands run
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct UpdateCheck {
+    /// Whether to check for updates (opt-in)
+    pub enabled: bool,
+
+    /// Maximum time in milliseconds to wait for the check once the command has finished
+    pub budget_ms: u64,
+}
+
+/// Reuse of `run` results between invocations
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct ResultCache {
+    /// Whether `run --stats-only` reuses the statistics of unchanged inputs (opt-in; see
+    /// `run --cache` and `--no-cache`)
+    pub enabled: bool,
+}
+
+/// Directories the `daemon` command watches, and how it processes what arrives there
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct Daemon {
+    /// Directories (searched recursively) whose new and changed files are processed;
+    /// hidden files, such as those of uploads in progress, are left alone
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub watch: Vec<PathBuf>,
+
+    /// Arguments of `run` for each file, e.g. `["--stats-only"]`; `--input` is added
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub run_args: Vec<String>,
+
+    /// Seconds between scans of the watched directories; a file is processed once it
+    /// has stayed the same for a scan
+    pub poll_secs: u64,
+
+    /// Cron expression (minute hour day month weekday, in UTC) at which files that arrived
+    /// are processed, instead of as soon as they are complete
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+
+    /// PID file of the running daemon (default: `daemon.pid` in the runtime or cache
+    /// directory)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid_file: Option<PathBuf>,
+}
+
+/// Where `upgrade` downloads release binaries from, and which releases it may install
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+pub struct UpgradeSource {
+    /// Base URL serving release assets as `<mirror>/<tag>/<asset name>`, or a URL template
+    /// with `{tag}`, `{version}`, `{name}`, `{target}` and `{app}`; release metadata still
+    /// comes from the GitHub API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<String>,
+
+    /// URL template of the release archive, filled in like `mirror` (without `{name}`);
+    /// the GitHub API is not used at all, so the version must be given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_url: Option<String>,
+
+    /// Whether `upgrade` may install a release that is breaking under semver (a new major
+    /// version, or a new minor version before 1.0); defaults to true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_major: Option<bool>,
+
+    /// Whether `upgrade` checks the download against its published SHA-256 checksum
+    /// (require, auto or skip); defaults to auto
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<ChecksumPolicy>,
+}
+
+/// Whether `upgrade` checks downloads against their published checksums.
+#[derive(
+    ValueEnum, Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumPolicy {
+    /// Refuse to install a download without a published checksum
+    Require,
+    /// Check the checksum when one is published
+    #[default]
+    Auto,
+    /// Install without checking
+    Skip,
+}
+
+impl std::str::FromStr for ChecksumPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+            .map_err(|_| format!("expected require, auto or skip, got '{}'", s))
+    }
 }
 
 /// Main configuration structure for the CLI.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(default)]
 pub struct Config {
+    /// Base configuration files merged beneath this one, in order (paths relative to this file)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extends: Vec<PathBuf>,
+
     /// Default profile to use
     pub default_profile: String,
 
     /// Profile configurations
     pub profiles: HashMap<String, Profile>,
+
+    /// Update check settings
+    pub update_check: UpdateCheck,
+
+    /// Download source and version policy for `upgrade`
+    #[serde(skip_serializing_if = "UpgradeSource::is_default")]
+    pub upgrade: UpgradeSource,
+
+    /// Result cache of `run`
+    #[serde(skip_serializing_if = "ResultCache::is_default")]
+    pub cache: ResultCache,
+
+    /// Watched directories and schedule of `daemon`
+    #[serde(skip_serializing_if = "Daemon::is_default")]
+    pub daemon: Daemon,
+
+    /// Reject unknown keys in this file instead of ignoring them (same as --strict-config)
+    pub strict: bool,
+
+    /// Named transform pipelines (lists of stages), used as `run --transform @name`
+    pub pipelines: BTreeMap<String, Vec<String>>,
+
+    /// Arguments run when the binary is invoked under another name, e.g. through a
+    /// symlink: `{"wcx": ["run", "--stats-only"]}` makes `wcx -i a.txt` run
+    /// `run --stats-only -i a.txt`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Vec<String>>,
+
+    /// GitHub repository (owner/name) to upgrade from when the binary was built from the
+    /// uncustomized template
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+
+    /// File that set each dotted key, for reporting where settings came from
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub origins: BTreeMap<String, PathBuf>,
+}
+
+impl ResultCache {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Daemon {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self {
+            watch: Vec::new(),
+            run_args: Vec::new(),
+            poll_secs: 2,
+            schedule: None,
+            pid_file: None,
+        }
+    }
+}
+
+impl UpgradeSource {
+    fn is_default(&self) -> bool {
+        self.mirror.is_none()
+            && self.asset_url.is_none()
+            && self.allow_major.is_none()
+            && self.checksum.is_none()
+    }
+}
+
+impl Default for UpdateCheck {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            budget_ms: 200,
+        }
+    }
 }
 
 impl Default for Profile {
@@ -39,6 +250,11 @@ impl Default for Profile {
             output_dir: PathBuf::from("./output"),
             log_level: String::from("info"),
             parallel_jobs: 4,
+            output_format: None,
+            min_free_space_mb: None,
+            max_memory_mb: None,
+            compress: None,
+            compression_level: None,
         }
     }
 }
@@ -54,6 +270,11 @@ impl Default for Config {
                 output_dir: PathBuf::from("./output"),
                 log_level: String::from("debug"),
                 parallel_jobs: 4,
+                output_format: None,
+                min_free_space_mb: None,
+                max_memory_mb: None,
+                compress: None,
+                compression_level: None,
             },
         );
 
@@ -63,6 +284,11 @@ impl Default for Config {
                 output_dir: PathBuf::from("/tmp/ci-output"),
                 log_level: String::from("error"),
                 parallel_jobs: 1,
+                output_format: Some(OutputFormat::Json),
+                min_free_space_mb: None,
+                max_memory_mb: None,
+                compress: None,
+                compression_level: None,
             },
         );
 
@@ -72,110 +298,396 @@ impl Default for Config {
                 output_dir: PathBuf::from("./dist"),
                 log_level: String::from("warning"),
                 parallel_jobs: 8,
+                output_format: None,
+                min_free_space_mb: None,
+                max_memory_mb: None,
+                compress: None,
+                compression_level: None,
             },
         );
 
         Self {
+            extends: Vec::new(),
             default_profile: String::from("local"),
             profiles,
+            update_check: UpdateCheck::default(),
+            upgrade: UpgradeSource::default(),
+            cache: ResultCache::default(),
+            daemon: Daemon::default(),
+            strict: false,
+            pipelines: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            repository: None,
+            origins: BTreeMap::new(),
         }
     }
 }
 
 impl Config {
-    /// Loads configuration from the specified file.
+    /// JSON Schema for configuration files, for editor validation and publishing.
+    pub fn json_schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Config)
+    }
+
+    /// Loads configuration from the specified file, expanding `${VAR}` and `${env:VAR}`
+    /// references to environment variables in string values.
+    ///
+    /// Files listed in `extends` are loaded first and deep-merged in order: tables are
+    /// merged key by key, while lists and scalars from later files replace earlier ones,
+    /// so the file at `path` always wins over its bases.
     ///
     /// # Arguments
     /// * `path` - Path to configuration file
+    /// * `strict` - Reject unknown keys even if the file does not set `strict: true`
     ///
     /// # Returns
     /// * `Ok(Config)` - Loaded and validated configuration
     /// * `Err` - If loading or validation fails
-    pub fn load(path: &str) -> Result<Self> {
-        let config = Self::load_from_file(path)?;
+    pub fn load(path: &str, strict: bool) -> Result<Self> {
+        Self::load_with(path, strict, &[])
+    }
+
+    /// Like [`Config::load`], with other configuration files merged beneath the file at
+    /// `path` in the order given, each winning over those before it and the file at `path`
+    /// winning over all of them: the system and user files (see [`scope_files`]), then the
+    /// project files (see [`project_files`]).
+    pub fn load_with_layers(path: &str, strict: bool, layers: &[PathBuf]) -> Result<Self> {
+        Self::load_with(path, strict, layers)
+    }
+
+    fn load_with(path: &str, strict: bool, layers: &[PathBuf]) -> Result<Self> {
+        let (config, unknown) = Self::load_from_file(path, layers)?;
+        if !unknown.is_empty() {
+            if strict || config.strict {
+                return Err(Error::Other(unknown_keys_message(path, &unknown)));
+            }
+            for key in &unknown {
+                warn!("Ignoring unknown configuration key '{}' in {}", key, path);
+            }
+        }
         config.validate()?;
         Ok(config)
     }
 
-    /// Loads configuration from a specific file.
+    /// Loads configuration from a specific file, along with any keys it did not recognize.
     /// Automatically detects format based on file extension (.json, .yaml, .yml).
-    fn load_from_file(path: &str) -> Result<Self> {
+    fn load_from_file(path: &str, layers: &[PathBuf]) -> Result<(Self, Vec<String>)> {
         let path = Path::new(path);
 
-        if !path.exists() {
+        if !path.exists() && layers.is_empty() {
             debug!(
                 "Configuration file not found: {}, using defaults",
                 path.display()
             );
-            return Ok(Self::default());
+            return Ok((Self::default(), Vec::new()));
         }
 
         info!("Loading configuration from: {}", path.display());
 
-        let contents = fs::read_to_string(path).map_err(Error::Io)?;
-
-        // Detect format based on extension
-        let config = match path.extension().and_then(|ext| ext.to_str()) {
-            Some("json") => serde_json::from_str(&contents).map_err(Error::Json)?,
-            Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)
-                .map_err(|e| Error::Other(format!("Failed to parse YAML: {}", e)))?,
-            _ => {
-                // Default to JSON for backward compatibility
-                serde_json::from_str(&contents).map_err(Error::Json)?
+        let mut origins = BTreeMap::new();
+        let mut value = if !layers.is_empty() {
+            // Layers refine the defaults when there is no configuration file
+            let mut merged = if path.exists() {
+                serde_json::Value::Object(serde_json::Map::new())
+            } else {
+                serde_json::to_value(Self::default())?
+            };
+            for layer in layers {
+                info!("Merging configuration layer: {}", layer.display());
+                merge_values(
+                    &mut merged,
+                    read_layered(layer, &mut Vec::new(), &mut origins)?,
+                );
+            }
+            if path.exists() {
+                merge_values(
+                    &mut merged,
+                    read_layered(path, &mut Vec::new(), &mut origins)?,
+                );
             }
+            merged
+        } else {
+            read_layered(path, &mut Vec::new(), &mut origins)?
         };
 
+        let mut errors = Vec::new();
+        expand_env_vars(&mut value, "", &mut errors);
+        Error::aggregate(errors)?;
+
+        let mut unknown = Vec::new();
+        let mut track = serde_path_to_error::Track::new();
+        let deserializer = serde_path_to_error::Deserializer::new(value, &mut track);
+        let mut config: Self =
+            serde_ignored::deserialize(deserializer, |key| unknown.push(key.to_string())).map_err(
+                |e| {
+                    Error::Other(format!(
+                        "Invalid configuration in {} at '{}': {}",
+                        path.display(),
+                        track.path(),
+                        e
+                    ))
+                },
+            )?;
+        config.origins = origins;
+
         debug!("Configuration loaded successfully");
-        Ok(config)
+        Ok((config, unknown))
     }
 
-    /// Merge environment variables onto configuration.
-    pub fn merge_env(&mut self) -> Result<()> {
-        // Check for profile override
-        if let Ok(profile) = std::env::var(env_vars::PROFILE) {
-            self.default_profile = profile;
+    /// Writes the configuration to `path`, choosing JSON or YAML by file extension.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+        let contents = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::to_string(self)
+                .map_err(|e| Error::Other(format!("Failed to serialize YAML: {}", e)))?,
+            _ => serde_json::to_string_pretty(self)?,
+        };
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
         }
+        fs::write(path, contents)?;
+        info!("Configuration written to: {}", path.display());
+        Ok(())
+    }
 
-        // Apply profile-specific overrides if active profile exists
-        if let Some(profile) = self.profiles.get_mut(&self.default_profile) {
-            if let Ok(val) = std::env::var(env_vars::OUTPUT_DIR) {
-                profile.output_dir = PathBuf::from(val);
+    /// The configuration as a JSON value with every section present, including those
+    /// left out of saved files while they hold their defaults.
+    pub fn to_full_value(&self) -> Result<serde_json::Value> {
+        let mut root = serde_json::to_value(self)?;
+        if let Some(object) = root.as_object_mut() {
+            let sections = [
+                ("upgrade", serde_json::to_value(&self.upgrade)?),
+                ("cache", serde_json::to_value(&self.cache)?),
+                ("daemon", serde_json::to_value(&self.daemon)?),
+            ];
+            for (key, section) in sections {
+                object.entry(key).or_insert(section);
             }
+        }
+        Ok(root)
+    }
 
-            if let Ok(val) = std::env::var(env_vars::LOG_LEVEL) {
-                profile.log_level = val;
+    /// Sets the value at a dotted `key` path (e.g. `profiles.local.parallel_jobs`).
+    ///
+    /// `raw` is coerced to the type of the existing field; with `json` it is parsed as
+    /// JSON instead, which allows setting structured values. The updated configuration
+    /// is validated before `self` is modified. Returns the stored value.
+    pub fn set_value(&mut self, key: &str, raw: &str, json: bool) -> Result<serde_json::Value> {
+        let mut root = serde_json::to_value(&*self)?;
+        let segments: Vec<&str> = key.split('.').collect();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(Error::Other(format!("Invalid configuration key '{}'", key)));
+        }
+
+        let mut current = &mut root;
+        for (i, segment) in segments.iter().enumerate() {
+            let object = current.as_object_mut().ok_or_else(|| {
+                Error::Other(format!(
+                    "Cannot set '{}': '{}' is not a table",
+                    key,
+                    segments[..i].join(".")
+                ))
+            })?;
+
+            if !object.contains_key(*segment) {
+                // New profiles start from the defaults so their fields have known types
+                if i == 1 && segments[0] == "profiles" {
+                    object.insert(
+                        segment.to_string(),
+                        serde_json::to_value(Profile::default())?,
+                    );
+                } else {
+                    return Err(Error::Other(format!("Unknown configuration key '{}'", key)));
+                }
             }
+            current = object.get_mut(*segment).expect("key inserted above");
+        }
+
+        let value = coerce_value(key, current, raw, json)?;
+        *current = value.clone();
 
-            if let Ok(val) = std::env::var(env_vars::PARALLEL_JOBS) {
-                if let Ok(parsed) = val.parse() {
-                    profile.parallel_jobs = parsed;
+        let updated: Config = serde_json::from_value(root)
+            .map_err(|e| Error::Other(format!("Invalid value for '{}': {}", key, e)))?;
+        updated.validate()?;
+        *self = updated;
+        Ok(value)
+    }
+
+    /// Sets the value at a dotted `key` in the configuration layer at `path` (a system,
+    /// user or project file), creating the file if needed. Only that key is written: the
+    /// rest of the file stays as it is, so the layer keeps overriding just what it sets.
+    /// The value is coerced and validated as by [`Config::set_value`], against the
+    /// defaults with the file merged over them. Returns the stored value.
+    pub fn set_in_layer(
+        path: &Path,
+        key: &str,
+        raw: &str,
+        json: bool,
+    ) -> Result<serde_json::Value> {
+        let mut merged = serde_json::to_value(Self::default())?;
+        if path.exists() {
+            merge_values(&mut merged, read_value(path)?);
+        }
+        Self::set_in_file(path, key, raw, json, merged)
+    }
+
+    /// Sets the value at a dotted `key` in the configuration file at `path`, creating the
+    /// file if needed. As with [`Config::set_in_layer`] only that key is written, so
+    /// `extends` and `${VAR}` references stay as they are; the value is validated against
+    /// the file with the bases it extends merged beneath it. Returns the stored value.
+    pub fn set_in_config_file(
+        path: &Path,
+        key: &str,
+        raw: &str,
+        json: bool,
+    ) -> Result<serde_json::Value> {
+        let layered = if path.exists() {
+            read_layered(path, &mut Vec::new(), &mut BTreeMap::new())?
+        } else {
+            serde_json::Value::Object(serde_json::Map::new())
+        };
+        Self::set_in_file(path, key, raw, json, layered)
+    }
+
+    /// Sets `key` in the file at `path`, validated against `effective`, the configuration
+    /// the file contributes to.
+    fn set_in_file(
+        path: &Path,
+        key: &str,
+        raw: &str,
+        json: bool,
+        effective: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let mut config: Self = serde_json::from_value(effective).map_err(|e| {
+            Error::Other(format!(
+                "Invalid configuration in {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let value = config.set_value(key, raw, json)?;
+
+        let mut file = if path.exists() {
+            read_value(path)?
+        } else {
+            serde_json::Value::Object(serde_json::Map::new())
+        };
+        let mut current = &mut file;
+        for (i, segment) in key.split('.').enumerate() {
+            let object = current.as_object_mut().ok_or_else(|| {
+                Error::Other(format!(
+                    "Cannot set '{}' in {}: '{}' is not a table",
+                    key,
+                    path.display(),
+                    key.split('.').take(i).collect::<Vec<_>>().join(".")
+                ))
+            })?;
+            current = object
+                .entry(segment)
+                .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+        }
+        *current = value.clone();
+        write_value(path, &file)?;
+        Ok(value)
+    }
+
+    /// The profile selected by `default_profile`, if it is defined.
+    pub fn active_profile(&self) -> Option<&Profile> {
+        self.profiles.get(&self.default_profile)
+    }
+
+    /// Checks up front that the active profile's output directory can be written, so a
+    /// long run does not fail at its first write. An existing directory is probed with a
+    /// temporary file; a missing one is created when `create` is set, and otherwise only
+    /// its nearest existing ancestor is checked.
+    pub fn prepare_output_dir(&self, create: bool) -> Result<()> {
+        let Some(profile) = self.active_profile() else {
+            return Ok(());
+        };
+        let dir = &profile.output_dir;
+        let describe = || {
+            format!(
+                "Output directory '{}' of profile '{}'",
+                dir.display(),
+                self.default_profile
+            )
+        };
+
+        if !dir.exists() {
+            if create {
+                fs::create_dir_all(dir).map_err(|e| {
+                    Error::Other(format!("{} could not be created: {}", describe(), e))
+                })?;
+                info!("Created output directory: {}", dir.display());
+            } else {
+                let ancestor = dir
+                    .ancestors()
+                    .skip(1)
+                    .find(|a| a.as_os_str().is_empty() || a.exists())
+                    .map(|a| {
+                        if a.as_os_str().is_empty() {
+                            Path::new(".")
+                        } else {
+                            a
+                        }
+                    });
+                if let Some(ancestor) = ancestor {
+                    let readonly = fs::metadata(ancestor)
+                        .map(|m| m.permissions().readonly())
+                        .unwrap_or(false);
+                    if readonly {
+                        return Err(Error::Other(format!(
+                            "{} does not exist and cannot be created: '{}' is read-only",
+                            describe(),
+                            ancestor.display()
+                        )));
+                    }
                 }
+                debug!(
+                    "{} does not exist yet (pass --create-dirs to create it at startup)",
+                    describe()
+                );
+                return Ok(());
             }
         }
 
+        if !dir.is_dir() {
+            return Err(Error::Other(format!("{} is not a directory", describe())));
+        }
+        tempfile::Builder::new()
+            .prefix(".write-check")
+            .tempfile_in(dir)
+            .map_err(|e| Error::Other(format!("{} is not writable: {}", describe(), e)))?;
+        debug!("Output directory is writable: {}", dir.display());
         Ok(())
     }
 
     /// Validates the configuration.
     fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
         // Validate that default profile exists
         if !self.profiles.contains_key(&self.default_profile) {
-            return Err(Error::Other(format!(
+            errors.push(Error::Other(format!(
                 "Default profile '{}' not found in profiles",
                 self.default_profile
             )));
         }
 
-        // Validate each profile
-        for (name, profile) in &self.profiles {
+        // Validate each profile, in a stable order so every problem is reported predictably
+        let mut names: Vec<&String> = self.profiles.keys().collect();
+        names.sort();
+        for name in names {
+            let profile = &self.profiles[name];
             if profile.output_dir.as_os_str().is_empty() {
-                return Err(Error::Other(format!(
+                errors.push(Error::Other(format!(
                     "Output directory cannot be empty in profile '{}'",
                     name
                 )));
             }
             if profile.parallel_jobs == 0 {
-                return Err(Error::Other(format!(
+                errors.push(Error::Other(format!(
                     "Parallel jobs must be at least 1 in profile '{}'",
                     name
                 )));
@@ -183,14 +695,454 @@ impl Config {
             // Validate log level
             let valid_levels = ["error", "warn", "warning", "info", "debug", "trace"];
             if !valid_levels.contains(&profile.log_level.to_lowercase().as_str()) {
-                return Err(Error::Other(format!(
+                errors.push(Error::Other(format!(
                     "Invalid log level '{}' in profile '{}'. Valid levels: error, warn, info, debug, trace",
                     profile.log_level, name
                 )));
             }
         }
 
-        Ok(())
+        // Validate named pipelines against the transform registry
+        for (name, stages) in &self.pipelines {
+            if stages.is_empty() {
+                errors.push(Error::Other(format!("Pipeline '{}' has no stages", name)));
+            }
+            for stage in stages {
+                if stage.contains('|') || stage.trim_start().starts_with('@') {
+                    errors.push(Error::Other(format!(
+                        "Pipeline '{}': each entry must be a single transform, got '{}'",
+                        name, stage
+                    )));
+                } else if let Err(e) = Transform::parse(stage.trim()) {
+                    errors.push(Error::Other(format!("Pipeline '{}': {}", name, e)));
+                }
+            }
+        }
+
+        for (name, args) in &self.aliases {
+            if args.is_empty() {
+                errors.push(Error::Other(format!("Alias '{}' has no arguments", name)));
+            }
+            if name.is_empty() || name.contains(['/', '\\']) {
+                errors.push(Error::Other(format!(
+                    "Alias '{}' must be a file name, without a directory",
+                    name
+                )));
+            }
+        }
+
+        if self.daemon.poll_secs == 0 {
+            errors.push(Error::Other(String::from(
+                "daemon.poll_secs must be at least 1",
+            )));
+        }
+        if let Some(Err(e)) = self.daemon.schedule.as_deref().map(Schedule::parse) {
+            errors.push(e);
+        }
+
+        Error::aggregate(errors)
+    }
+}
+
+/// Converts `raw` into a JSON value of the same type as `current`.
+fn coerce_value(
+    key: &str,
+    current: &serde_json::Value,
+    raw: &str,
+    json: bool,
+) -> Result<serde_json::Value> {
+    use serde_json::Value;
+
+    let expected = match current {
+        Value::Null => "any",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "table",
+    };
+    let mismatch = || {
+        Error::Other(format!(
+            "Invalid value '{}' for '{}': expected {}",
+            raw, key, expected
+        ))
+    };
+
+    let value = if json {
+        serde_json::from_str(raw)
+            .map_err(|e| Error::Other(format!("Invalid JSON for '{}': {}", key, e)))?
+    } else {
+        match current {
+            Value::Bool(_) => Value::Bool(raw.parse().map_err(|_| mismatch())?),
+            Value::Number(n) if n.is_f64() => {
+                serde_json::json!(raw.parse::<f64>().map_err(|_| mismatch())?)
+            }
+            Value::Number(_) => Value::from(raw.parse::<i64>().map_err(|_| mismatch())?),
+            Value::Array(_) | Value::Object(_) => {
+                return Err(Error::Other(format!(
+                    "'{}' is a {}; pass the value as JSON with --json",
+                    key, expected
+                )))
+            }
+            Value::String(_) | Value::Null => Value::String(raw.to_string()),
+        }
+    };
+
+    let compatible = match (current, &value) {
+        (Value::Null, _) => true,
+        (Value::Number(n), Value::Number(v)) => n.is_f64() || !v.is_f64(),
+        (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+    };
+    if !compatible {
+        return Err(mismatch());
+    }
+
+    Ok(value)
+}
+
+/// Name of the system and user configuration files.
+const SCOPE_FILE_NAME: &str = "config.yaml";
+
+/// Configuration files beneath the one given with `--config`, from the lowest precedence
+/// to the highest.
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigScope {
+    /// Every user of the machine
+    System,
+    /// The current user
+    User,
+    /// The current directory (and its subdirectories)
+    Project,
+}
+
+impl ConfigScope {
+    /// The file of this scope; for the project scope, the project file in `dir`.
+    pub fn path(self, dir: &Path) -> Result<PathBuf> {
+        match self {
+            ConfigScope::System => Ok(system_config_dir().join(SCOPE_FILE_NAME)),
+            ConfigScope::User => user_config_dir()
+                .map(|dir| dir.join(SCOPE_FILE_NAME))
+                .ok_or_else(|| {
+                    Error::Other(String::from(
+                        "Could not determine the user configuration directory",
+                    ))
+                }),
+            ConfigScope::Project => Ok(dir.join(project_file_name())),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigScope::System => write!(f, "system"),
+            ConfigScope::User => write!(f, "user"),
+            ConfigScope::Project => write!(f, "project"),
+        }
+    }
+}
+
+/// Directory of the system configuration file: `/etc/<app>`, `/Library/Application
+/// Support/<app>` on macOS or `%ProgramData%\<app>` on Windows.
+fn system_config_dir() -> PathBuf {
+    if let Some(dir) = env_vars::var(env_vars::SYSTEM_CONFIG_DIR) {
+        return PathBuf::from(dir);
+    }
+    let name = identity::app_name();
+    #[cfg(windows)]
+    let base = std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"));
+    #[cfg(target_os = "macos")]
+    let base = PathBuf::from("/Library/Application Support");
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let base = PathBuf::from("/etc");
+    base.join(name)
+}
+
+/// Directory of the user configuration file, the platform's per-user configuration
+/// directory for the application.
+fn user_config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env_vars::var(env_vars::USER_CONFIG_DIR) {
+        return Some(PathBuf::from(dir));
+    }
+    directories::ProjectDirs::from("", "", identity::app_name())
+        .map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// The system and user configuration files that exist, system first, except `config`
+/// (the configuration file itself, should it be one of them).
+pub fn scope_files(config: &Path) -> Vec<PathBuf> {
+    let config = fs::canonicalize(config).ok();
+    [ConfigScope::System, ConfigScope::User]
+        .into_iter()
+        .filter_map(|scope| scope.path(Path::new("")).ok())
+        .filter(|path| path.is_file())
+        .filter(|path| config.is_none() || fs::canonicalize(path).ok() != config)
+        .collect()
+}
+
+/// Name of project configuration files.
+pub fn project_file_name() -> String {
+    format!(".{}.yaml", identity::app_name())
+}
+
+/// Project configuration files applying to `dir`: those in it and its ancestors, farthest
+/// first, except `config` (the configuration file itself, should it be one of them).
+pub fn project_files(dir: &Path, config: &Path) -> Vec<PathBuf> {
+    let name = project_file_name();
+    let config = fs::canonicalize(config).ok();
+    let mut files: Vec<PathBuf> = dir
+        .ancestors()
+        .map(|ancestor| ancestor.join(&name))
+        .filter(|path| path.is_file())
+        .filter(|path| config.is_none() || fs::canonicalize(path).ok() != config)
+        .collect();
+    files.reverse();
+    files
+}
+
+/// Writes a configuration value to `path`, as JSON or YAML by file extension.
+pub fn write_value(path: &Path, value: &serde_json::Value) -> Result<()> {
+    let contents = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::to_string(value)
+            .map_err(|e| Error::Other(format!("Failed to serialize YAML: {}", e)))?,
+        _ => serde_json::to_string_pretty(value)?,
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    info!("Configuration written to: {}", path.display());
+    Ok(())
+}
+
+/// Parses a single configuration file into a JSON value.
+/// Automatically detects format based on file extension (.json, .yaml, .yml).
+fn read_value(path: &Path) -> Result<serde_json::Value> {
+    let contents = fs::read_to_string(path).map_err(Error::Io)?;
+
+    // Detect format based on extension
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)
+            .map_err(|e| Error::Other(format!("Failed to parse YAML: {}", e))),
+        // Default to JSON for backward compatibility
+        _ => serde_json::from_str(&contents).map_err(Error::Json),
+    }
+}
+
+/// Reads `path` with the files it `extends` merged beneath it.
+///
+/// `chain` holds the canonical paths of the files currently being resolved, so a file
+/// that (indirectly) extends itself is reported instead of recursing forever. The same
+/// base may still be reached through several branches. `origins` receives the file
+/// that last set each dotted key.
+fn read_layered(
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    origins: &mut BTreeMap<String, PathBuf>,
+) -> Result<serde_json::Value> {
+    let canonical = fs::canonicalize(path).map_err(|e| {
+        Error::Other(match chain.last() {
+            Some(parent) => format!(
+                "Cannot read base configuration '{}' extended by {}: {}",
+                path.display(),
+                parent.display(),
+                e
+            ),
+            None => format!("Cannot read configuration '{}': {}", path.display(), e),
+        })
+    })?;
+    if let Some(start) = chain.iter().position(|p| *p == canonical) {
+        let cycle: Vec<String> = chain[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(Error::Other(format!(
+            "Configuration extends cycle: {}",
+            cycle.join(" -> ")
+        )));
+    }
+
+    let mut value = read_value(path)?;
+    let extends = match value.as_object_mut().and_then(|map| map.remove("extends")) {
+        None => {
+            record_origins(&value, "", path, origins);
+            return Ok(value);
+        }
+        Some(serde_json::Value::Array(items)) => items,
+        Some(_) => {
+            return Err(Error::Other(format!(
+                "'extends' in {} must be a list of file paths",
+                path.display()
+            )))
+        }
+    };
+
+    debug!(
+        "Resolving {} base configuration(s) for {}",
+        extends.len(),
+        path.display()
+    );
+    chain.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = serde_json::Value::Object(serde_json::Map::new());
+    for base in extends {
+        let serde_json::Value::String(base) = base else {
+            return Err(Error::Other(format!(
+                "'extends' in {} must be a list of file paths",
+                path.display()
+            )));
+        };
+        merge_values(&mut merged, read_layered(&dir.join(base), chain, origins)?);
+    }
+    chain.pop();
+
+    record_origins(&value, "", path, origins);
+    merge_values(&mut merged, value);
+    Ok(merged)
+}
+
+/// Records `path` as the origin of every dotted key in `value` below `prefix`.
+fn record_origins(
+    value: &serde_json::Value,
+    prefix: &str,
+    path: &Path,
+    origins: &mut BTreeMap<String, PathBuf>,
+) {
+    let serde_json::Value::Object(map) = value else {
+        return;
+    };
+    for (key, child) in map {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        record_origins(child, &key, path, origins);
+        origins.insert(key, path.to_path_buf());
+    }
+}
+
+/// Deep-merges `overlay` onto `base`: tables merge key by key, anything else replaces.
+fn merge_values(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Replaces `${NAME}` and `${env:NAME}` in every string of `value` with the variable's
+/// value; `$${` produces a literal `${`. Problems are collected into `errors`.
+fn expand_env_vars(value: &mut serde_json::Value, key: &str, errors: &mut Vec<Error>) {
+    match value {
+        serde_json::Value::String(text) => match expand_str(text) {
+            Ok(expanded) => *text = expanded,
+            Err(message) => errors.push(Error::Other(format!(
+                "{} in configuration key '{}'",
+                message, key
+            ))),
+        },
+        serde_json::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                expand_env_vars(item, &format!("{}[{}]", key, i), errors);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for (name, child) in map.iter_mut() {
+                let child_key = if key.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", key, name)
+                };
+                expand_env_vars(child, &child_key, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn expand_str(text: &str) -> std::result::Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if let Some(after) = tail.strip_prefix("$${") {
+            out.push_str("${");
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("Unterminated '${{' in '{}'", text))?;
+            let name = after[..end].strip_prefix("env:").unwrap_or(&after[..end]);
+            if name.is_empty() {
+                return Err(format!("Empty variable reference in '{}'", text));
+            }
+            let value = env_vars::var(name).ok_or_else(|| match env_vars::is_ignored(name) {
+                true => format!("Environment variable '{}' is ignored (--no-env)", name),
+                false => format!("Undefined environment variable '{}'", name),
+            })?;
+            out.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+            rest = &tail[1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Describes unknown keys, suggesting the closest known key name for likely typos.
+fn unknown_keys_message(path: &str, unknown: &[String]) -> String {
+    let mut known = Vec::new();
+    collect_keys(
+        &serde_json::to_value(Config::default()).unwrap_or_default(),
+        &mut known,
+    );
+
+    let keys: Vec<String> = unknown
+        .iter()
+        .map(|key| {
+            let leaf = key.rsplit('.').next().unwrap_or(key);
+            let suggestion = known
+                .iter()
+                .map(|candidate| (suggest::edit_distance(leaf, candidate), candidate))
+                .filter(|(distance, _)| *distance > 0 && *distance <= 2)
+                .min();
+            match suggestion {
+                Some((_, candidate)) => format!("'{}' (did you mean '{}'?)", key, candidate),
+                None => format!("'{}'", key),
+            }
+        })
+        .collect();
+
+    format!(
+        "Unknown configuration key(s) in {}: {}",
+        path,
+        keys.join(", ")
+    )
+}
+
+fn collect_keys(value: &serde_json::Value, keys: &mut Vec<String>) {
+    if let serde_json::Value::Object(map) = value {
+        for (key, child) in map {
+            keys.push(key.clone());
+            collect_keys(child, keys);
+        }
     }
 }
 
@@ -199,6 +1151,345 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_strict_rejects_unknown_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let path = path.to_str().unwrap();
+        fs::write(
+            path,
+            r#"{"profiles": {"local": {"parralel_jobs": 2}}, "colour": true}"#,
+        )
+        .unwrap();
+
+        // Lenient by default
+        let config = Config::load(path, false).unwrap();
+        assert_eq!(config.profiles["local"].parallel_jobs, 4);
+
+        let message = Config::load(path, true).unwrap_err().to_string();
+        assert!(
+            message.contains("'profiles.local.parralel_jobs' (did you mean 'parallel_jobs'?)"),
+            "{}",
+            message
+        );
+        assert!(message.contains("'colour'"), "{}", message);
+    }
+
+    #[test]
+    fn test_strict_from_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let path = path.to_str().unwrap();
+        fs::write(path, "strict: true\ndefault_profle: ci\n").unwrap();
+
+        let message = Config::load(path, false).unwrap_err().to_string();
+        assert!(
+            message.contains("'default_profle' (did you mean 'default_profile'?)"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_expand_str() {
+        std::env::set_var("CONFIG_TEST_EXPAND_HOME", "/home/me");
+        assert_eq!(
+            expand_str("${CONFIG_TEST_EXPAND_HOME}/out").unwrap(),
+            "/home/me/out"
+        );
+        assert_eq!(
+            expand_str("${env:CONFIG_TEST_EXPAND_HOME}").unwrap(),
+            "/home/me"
+        );
+        assert_eq!(
+            expand_str("cost: $5, $${HOME}").unwrap(),
+            "cost: $5, ${HOME}"
+        );
+        assert_eq!(
+            expand_str("${CONFIG_TEST_UNDEFINED}").unwrap_err(),
+            "Undefined environment variable 'CONFIG_TEST_UNDEFINED'"
+        );
+        assert!(expand_str("${OPEN").is_err());
+        assert!(expand_str("${}").is_err());
+    }
+
+    #[test]
+    fn test_load_expands_env_vars() {
+        std::env::set_var("CONFIG_TEST_LOAD_DIR", "/srv/data");
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let path = path.to_str().unwrap();
+        fs::write(
+            path,
+            "profiles:\n  local:\n    output_dir: ${CONFIG_TEST_LOAD_DIR}/out\n",
+        )
+        .unwrap();
+
+        let config = Config::load(path, false).unwrap();
+        assert_eq!(
+            config.profiles["local"].output_dir,
+            PathBuf::from("/srv/data/out")
+        );
+
+        // Setting another key leaves the reference as written
+        Config::set_in_config_file(Path::new(path), "profiles.local.parallel_jobs", "2", false)
+            .unwrap();
+        let raw = fs::read_to_string(path).unwrap();
+        assert!(raw.contains("${CONFIG_TEST_LOAD_DIR}/out"), "{}", raw);
+
+        fs::write(
+            path,
+            "default_profile: ${CONFIG_TEST_MISSING_A}\nprofiles:\n  ci:\n    log_level: ${CONFIG_TEST_MISSING_B}\n",
+        )
+        .unwrap();
+        let message = Config::load(path, false).unwrap_err().to_string();
+        assert!(message.contains("2 errors occurred"), "{}", message);
+        assert!(
+            message
+                .contains("'CONFIG_TEST_MISSING_B' in configuration key 'profiles.ci.log_level'"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_extends_deep_merges_bases() {
+        let temp_dir = TempDir::new().unwrap();
+        let shared = temp_dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::write(
+            shared.join("base.yaml"),
+            "default_profile: team\nprofiles:\n  team:\n    output_dir: /srv/team\n    parallel_jobs: 8\npipelines:\n  clean: [trim]\n",
+        )
+        .unwrap();
+        fs::write(
+            shared.join("ci.json"),
+            r#"{"extends": ["base.yaml"], "profiles": {"team": {"parallel_jobs": 2}}}"#,
+        )
+        .unwrap();
+        let path = temp_dir.path().join("config.json");
+        fs::write(
+            &path,
+            r#"{"extends": ["shared/ci.json"], "profiles": {"team": {"log_level": "debug"}}, "pipelines": {"clean": ["trim", "lowercase"]}}"#,
+        )
+        .unwrap();
+
+        let config = Config::load(path.to_str().unwrap(), true).unwrap();
+        assert_eq!(config.default_profile, "team");
+        let team = &config.profiles["team"];
+        assert_eq!(team.output_dir, PathBuf::from("/srv/team"));
+        assert_eq!(team.parallel_jobs, 2);
+        assert_eq!(team.log_level, "debug");
+        // Lists are replaced, not concatenated
+        assert_eq!(config.pipelines["clean"], ["trim", "lowercase"]);
+        assert!(config.extends.is_empty());
+        // Each key remembers the file that last set it
+        assert_eq!(config.origins["default_profile"], shared.join("base.yaml"));
+        assert_eq!(
+            config.origins["profiles.team.parallel_jobs"],
+            shared.join("ci.json")
+        );
+        assert_eq!(config.origins["profiles.team.log_level"], path);
+        assert!(!config.origins.contains_key("extends"));
+    }
+
+    #[test]
+    fn test_extends_cycle_and_missing_base() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.json");
+        let b = temp_dir.path().join("b.json");
+        fs::write(&a, r#"{"extends": ["b.json"]}"#).unwrap();
+        fs::write(&b, r#"{"extends": ["a.json"]}"#).unwrap();
+
+        let message = Config::load(a.to_str().unwrap(), false)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("extends cycle"), "{}", message);
+        assert!(message.contains("a.json -> "), "{}", message);
+
+        fs::write(&b, r#"{"extends": ["missing.json"]}"#).unwrap();
+        let message = Config::load(a.to_str().unwrap(), false)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.contains("Cannot read base configuration"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_project_files_merged_beneath_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("services/api");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            temp_dir.path().join(project_file_name()),
+            "profiles:\n  local:\n    parallel_jobs: 2\n    log_level: info\n",
+        )
+        .unwrap();
+        fs::write(
+            nested.join(project_file_name()),
+            "profiles:\n  local:\n    parallel_jobs: 6\n",
+        )
+        .unwrap();
+        let path = nested.join("config.json");
+        let projects = project_files(&nested, &path);
+        assert_eq!(
+            projects,
+            [
+                temp_dir.path().join(project_file_name()),
+                nested.join(project_file_name())
+            ]
+        );
+
+        // Without a configuration file, the project files refine the defaults
+        let config = Config::load_with_layers(path.to_str().unwrap(), false, &projects).unwrap();
+        let local = &config.profiles["local"];
+        assert_eq!((local.parallel_jobs, local.log_level.as_str()), (6, "info"));
+        assert!(config.profiles.contains_key("ci"));
+        assert_eq!(
+            config.origins["profiles.local.parallel_jobs"],
+            nested.join(project_file_name())
+        );
+
+        // The configuration file wins over every project file
+        fs::write(&path, r#"{"profiles": {"local": {"log_level": "debug"}}}"#).unwrap();
+        let config = Config::load_with_layers(path.to_str().unwrap(), false, &projects).unwrap();
+        let local = &config.profiles["local"];
+        assert_eq!(
+            (local.parallel_jobs, local.log_level.as_str()),
+            (6, "debug")
+        );
+    }
+
+    #[test]
+    fn test_set_in_layer_writes_only_the_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("user/config.yaml");
+
+        let value =
+            Config::set_in_layer(&path, "profiles.local.parallel_jobs", "3", false).unwrap();
+        assert_eq!(value, 3);
+        Config::set_in_layer(&path, "default_profile", "ci", false).unwrap();
+        let layer = read_value(&path).unwrap();
+        assert_eq!(
+            layer,
+            serde_json::json!({"default_profile": "ci", "profiles": {"local": {"parallel_jobs": 3}}})
+        );
+
+        // Values are type-checked against the defaults, and a rejected value is not written
+        let message = Config::set_in_layer(&path, "profiles.local.parallel_jobs", "x", false)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("expected integer"), "{}", message);
+        assert_eq!(read_value(&path).unwrap(), layer);
+    }
+
+    #[test]
+    fn test_prepare_output_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::default();
+        let target = temp_dir.path().join("nested/out");
+        config.profiles.get_mut("local").unwrap().output_dir = target.clone();
+
+        // Missing but creatable is fine; only --create-dirs creates it
+        config.prepare_output_dir(false).unwrap();
+        assert!(!target.exists());
+        config.prepare_output_dir(true).unwrap();
+        assert!(target.is_dir());
+        config.prepare_output_dir(false).unwrap();
+
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        config.profiles.get_mut("local").unwrap().output_dir = file;
+        let message = config.prepare_output_dir(true).unwrap_err().to_string();
+        assert!(message.contains("is not a directory"), "{}", message);
+        assert!(message.contains("profile 'local'"), "{}", message);
+    }
+
+    #[test]
+    fn test_type_errors_name_the_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let path = path.to_str().unwrap();
+        fs::write(path, r#"{"profiles": {"ci": {"parallel_jobs": "many"}}}"#).unwrap();
+
+        let message = Config::load(path, false).unwrap_err().to_string();
+        assert!(
+            message.contains("'profiles.ci.parallel_jobs'"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_aliases_validated() {
+        let mut config = Config::default();
+        config
+            .aliases
+            .insert(String::from("wcx"), vec![String::from("run")]);
+        assert!(config.validate().is_ok());
+
+        config.aliases.insert(String::from("empty"), Vec::new());
+        config
+            .aliases
+            .insert(String::from("bin/wc"), vec![String::from("run")]);
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("Alias 'empty' has no arguments"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("Alias 'bin/wc' must be a file name"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_pipelines_validated_against_registry() {
+        let mut config = Config::default();
+        config.pipelines.insert(
+            String::from("clean"),
+            vec![String::from("trim"), String::from("lowercase")],
+        );
+        assert!(config.validate().is_ok());
+
+        config.pipelines.insert(
+            String::from("broken"),
+            vec![String::from("trimm"), String::from("truncate:width=x")],
+        );
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("Pipeline 'broken': Unknown transform 'trimm'"),
+            "{}",
+            message
+        );
+        assert!(message.contains("Invalid value 'x'"), "{}", message);
+    }
+
+    #[test]
+    fn test_json_schema_describes_config() {
+        let schema = serde_json::to_value(Config::json_schema()).unwrap();
+        assert_eq!(schema["title"], "Config");
+        assert_eq!(
+            schema["properties"]["update_check"]["allOf"][0]["$ref"],
+            "#/definitions/UpdateCheck"
+        );
+        assert_eq!(
+            schema["definitions"]["Profile"]["properties"]["parallel_jobs"]["type"],
+            "integer"
+        );
+
+        // The default configuration must validate against its own schema's shape
+        let defaults = serde_json::to_value(Config::default()).unwrap();
+        for key in defaults.as_object().unwrap().keys() {
+            assert!(schema["properties"].get(key).is_some(), "{}", key);
+        }
+    }
+
     #[test]
     fn test_default_config() {
         let config = Config::default();
@@ -223,6 +1514,27 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_config_validation_reports_every_problem() {
+        let mut config = Config {
+            default_profile: String::from("nonexistent"),
+            ..Config::default()
+        };
+        config.profiles.get_mut("ci").unwrap().parallel_jobs = 0;
+        config.profiles.get_mut("local").unwrap().log_level = String::from("loud");
+
+        match config.validate().unwrap_err() {
+            Error::Multiple(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                assert_eq!(messages.len(), 3);
+                assert!(messages[0].contains("Default profile 'nonexistent'"));
+                assert!(messages[1].contains("profile 'ci'"));
+                assert!(messages[2].contains("Invalid log level 'loud'"));
+            }
+            other => panic!("expected multiple errors, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_save_and_load_config() {
         let temp_dir = TempDir::new().unwrap();
@@ -237,29 +1549,10 @@ mod tests {
         let json = serde_json::to_string_pretty(&config).unwrap();
         fs::write(config_path_str, json).unwrap();
 
-        let loaded = Config::load(config_path_str).unwrap();
+        let loaded = Config::load(config_path_str, false).unwrap();
         assert_eq!(loaded.profiles["local"].log_level, "trace");
     }
 
-    #[test]
-    fn test_env_override() {
-        std::env::set_var(env_vars::PROFILE, "ci");
-        std::env::set_var(env_vars::OUTPUT_DIR, "/custom/output");
-
-        let mut config = Config::default();
-        config.merge_env().unwrap();
-
-        assert_eq!(config.default_profile, "ci");
-        assert_eq!(
-            config.profiles["ci"].output_dir,
-            PathBuf::from("/custom/output")
-        );
-
-        // Clean up
-        std::env::remove_var(env_vars::PROFILE);
-        std::env::remove_var(env_vars::OUTPUT_DIR);
-    }
-
     #[test]
     fn test_yaml_config_loading() {
         let temp_dir = TempDir::new().unwrap();
@@ -281,13 +1574,76 @@ profiles:
 
         fs::write(config_path_str, yaml).unwrap();
 
-        let loaded = Config::load(config_path_str).unwrap();
+        let loaded = Config::load(config_path_str, false).unwrap();
         assert_eq!(loaded.default_profile, "production");
         assert_eq!(loaded.profiles.len(), 2);
         assert_eq!(loaded.profiles["production"].parallel_jobs, 16);
         assert_eq!(loaded.profiles["dev"].log_level, "trace");
     }
 
+    #[test]
+    fn test_set_value_coerces_types() {
+        let mut config = Config::default();
+
+        let value = config
+            .set_value("profiles.local.parallel_jobs", "8", false)
+            .unwrap();
+        assert_eq!(value, serde_json::json!(8));
+        assert_eq!(config.profiles["local"].parallel_jobs, 8);
+
+        config
+            .set_value("profiles.local.output_dir", "/srv/out", false)
+            .unwrap();
+        assert_eq!(
+            config.profiles["local"].output_dir,
+            PathBuf::from("/srv/out")
+        );
+
+        let err = config
+            .set_value("profiles.local.parallel_jobs", "many", false)
+            .unwrap_err();
+        assert!(err.to_string().contains("expected integer"));
+
+        let err = config
+            .set_value("profiles.local.paralel_jobs", "2", false)
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown configuration key"));
+    }
+
+    #[test]
+    fn test_set_value_structured_and_validated() {
+        let mut config = Config::default();
+
+        let err = config
+            .set_value("profiles.staging", "{}", false)
+            .unwrap_err();
+        assert!(err.to_string().contains("--json"));
+
+        config
+            .set_value(
+                "profiles.staging",
+                r#"{"output_dir": "./staging", "parallel_jobs": 2}"#,
+                true,
+            )
+            .unwrap();
+        assert_eq!(config.profiles["staging"].parallel_jobs, 2);
+        assert_eq!(config.profiles["staging"].log_level, "info");
+
+        // New profile fields are typed from the defaults
+        config
+            .set_value("profiles.fresh.parallel_jobs", "3", false)
+            .unwrap();
+        assert_eq!(config.profiles["fresh"].parallel_jobs, 3);
+
+        // Validation failures leave the configuration untouched
+        assert!(config
+            .set_value("profiles.local.parallel_jobs", "0", false)
+            .is_err());
+        assert!(config.set_value("default_profile", "nope", false).is_err());
+        assert_eq!(config.profiles["local"].parallel_jobs, 4);
+        assert_eq!(config.default_profile, "local");
+    }
+
     #[test]
     fn test_yml_extension() {
         let temp_dir = TempDir::new().unwrap();
@@ -305,7 +1661,7 @@ profiles:
 
         fs::write(config_path_str, yaml).unwrap();
 
-        let loaded = Config::load(config_path_str).unwrap();
+        let loaded = Config::load(config_path_str, false).unwrap();
         assert_eq!(loaded.default_profile, "local");
         assert_eq!(loaded.profiles["local"].parallel_jobs, 2);
     }
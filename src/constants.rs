@@ -12,5 +12,35 @@ pub const APP_NAME: &str = "__TEMPLATE_PACKAGE_NAME__";
 /// Application version from Cargo.toml.
 pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Short hash of the commit this binary was built from; empty when built outside git.
+pub const GIT_HASH: &str = env!("BUILD_GIT_HASH");
+
+/// Whether tracked files had uncommitted changes at build time ("true" or "false").
+pub const GIT_DIRTY: &str = env!("BUILD_GIT_DIRTY");
+
+/// UTC build date (YYYY-MM-DD).
+pub const BUILD_DATE: &str = env!("BUILD_DATE");
+
+/// `rustc --version` of the compiler that built this binary.
+pub const RUSTC_VERSION: &str = env!("BUILD_RUSTC");
+
+/// Target triple this binary was built for.
+pub const TARGET: &str = env!("TARGET");
+
+/// Cargo profile this binary was built with (debug or release).
+pub const BUILD_PROFILE: &str = env!("BUILD_PROFILE");
+
+/// Enabled Cargo features, comma-separated.
+pub const FEATURES: &str = env!("BUILD_FEATURES");
+
 /// Default configuration file name.
 pub const DEFAULT_CONFIG_FILE: &str = "config.json";
+
+/// Process exit code for general failures.
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Process exit code when a `run --fail-if-*` threshold trips.
+pub const EXIT_THRESHOLD: i32 = 3;
+
+/// Process exit code when interrupted with Ctrl-C (128 + SIGINT, as shells report it).
+pub const EXIT_INTERRUPTED: i32 = 130;
@@ -0,0 +1,81 @@
+//! Shared execution context handed to commands.
+
+use clap::ArgMatches;
+
+use crate::args::GlobalArgs;
+use crate::config::Config;
+use crate::env_vars;
+use crate::error::{Error, Result};
+use crate::lock::LockPolicy;
+use crate::output::OutputFormat;
+use crate::prompt::Prompter;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+
+/// State resolved once in `main` and shared by every command.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Path of the configuration file (which may not exist yet)
+    pub config_path: String,
+
+    /// Whether unknown configuration keys are errors (--strict-config)
+    pub strict_config: bool,
+
+    /// Effective configuration (file, profile and environment overrides applied)
+    pub config: Config,
+
+    /// Effective settings and where each one came from
+    pub settings: Settings,
+
+    /// Requested output format
+    pub format: OutputFormat,
+
+    /// Whether text output may use ANSI colors
+    pub color: bool,
+
+    /// How progress, results and tables are rendered (--a11y)
+    pub reporter: Reporter,
+
+    /// Confirmation prompts for destructive operations (--yes)
+    pub prompter: Prompter,
+
+    /// What to do when another instance holds a lock (--no-lock, --wait-for-lock)
+    pub lock_policy: LockPolicy,
+
+    /// Where the configuration was loaded from, or `None` when it came from a replayed
+    /// session
+    pub config_source: Option<ConfigSource>,
+
+    /// Handle for changing the log level, or `None` when `RUST_LOG` sets it
+    pub log_filter: Option<LogFilterHandle>,
+}
+
+/// Handle for changing the log filter after startup.
+pub type LogFilterHandle =
+    tracing_subscriber::reload::Handle<tracing_subscriber::EnvFilter, tracing_subscriber::Registry>;
+
+/// The global options and arguments the configuration was resolved from.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub global: GlobalArgs,
+    pub matches: ArgMatches,
+}
+
+impl Context {
+    /// Loads the configuration again as it was loaded at startup, with the profile and
+    /// overrides applied, for long-running commands to pick up changes.
+    pub fn reload_config(&self) -> Result<(Config, Settings)> {
+        let source = self.config_source.as_ref().ok_or_else(|| {
+            Error::Other(String::from(
+                "The configuration of a replayed session cannot be reloaded",
+            ))
+        })?;
+        let mut config = crate::load_config(&source.global)?;
+        let settings = Settings::resolve(&config, &source.global, &source.matches, env_vars::var)?;
+        settings.apply(&mut config);
+        if settings.output_dir.value != self.settings.output_dir.value {
+            config.prepare_output_dir(source.global.create_dirs)?;
+        }
+        Ok((config, settings))
+    }
+}
@@ -0,0 +1,194 @@
+//! Crash reports: a panic writes the version, target, backtrace and (sanitized) arguments
+//! to a file in the cache directory, and tells the user where to send it, instead of
+//! printing a raw panic message.
+
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::constants;
+use crate::i18n::{self, tr};
+use crate::identity;
+
+/// Placeholder for argument values that may be secret.
+const REDACTED: &str = "<redacted>";
+
+/// Installs the panic hook.
+pub fn install() {
+    panic::set_hook(Box::new(|info| {
+        let report = render(info, &Backtrace::force_capture());
+        let written = reports_dir()
+            .ok_or_else(|| io::Error::other("no cache directory"))
+            .and_then(|dir| write(&dir, &report));
+        eprintln!(
+            "[ERROR] {}",
+            tr!(
+                "crash-crashed",
+                app = identity::app_name(),
+                message = message(info)
+            )
+        );
+        match written {
+            Ok(path) => eprintln!("{}", tr!("crash-report-written", path = path.display())),
+            Err(e) => {
+                eprintln!("{}\n{}", tr!("crash-report-unsaved", error = e), report);
+            }
+        }
+        match identity::repository() {
+            Ok(repository) => eprintln!(
+                "{}",
+                tr!(
+                    "crash-report-issue",
+                    url = format!("https://github.com/{}/issues/new", repository)
+                )
+            ),
+            Err(_) => eprintln!("{}", tr!("crash-report-maintainers")),
+        }
+    }));
+}
+
+/// Directory crash reports are written to.
+fn reports_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", identity::app_name())
+        .map(|dirs| dirs.cache_dir().join("crash-reports"))
+}
+
+/// Writes `report` to a new file in `dir`, returning its path.
+fn write(dir: &Path, report: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = dir.join(format!("crash-{}-{}.txt", seconds, std::process::id()));
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+/// The panic payload as text.
+fn message(info: &PanicHookInfo<'_>) -> String {
+    let payload = info.payload();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("(no message)"))
+}
+
+fn render(info: &PanicHookInfo<'_>, backtrace: &Backtrace) -> String {
+    let home = directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    let args = sanitize_args(std::env::args().skip(1), home.as_deref());
+    let location = info
+        .location()
+        .map(|location| location.to_string())
+        .unwrap_or_default();
+    let mut report = String::new();
+    let _ = writeln!(report, "application: {}", identity::app_name());
+    let _ = writeln!(report, "version:     {}", constants::APP_VERSION);
+    if !constants::GIT_HASH.is_empty() {
+        let _ = writeln!(report, "commit:      {}", constants::GIT_HASH);
+    }
+    let _ = writeln!(report, "target:      {}", constants::TARGET);
+    let _ = writeln!(report, "language:    {}", i18n::language());
+    let _ = writeln!(
+        report,
+        "thread:      {}",
+        std::thread::current().name().unwrap_or("(unnamed)")
+    );
+    let _ = writeln!(report, "arguments:   {}", args.join(" "));
+    let _ = writeln!(report, "panic:       {}", message(info));
+    let _ = writeln!(report, "location:    {}", location);
+    let _ = writeln!(report, "\nbacktrace:\n{}", backtrace);
+    report
+}
+
+/// The arguments with the values of secret-looking options (and inline `--text`)
+/// redacted, and the home directory shortened to `~`.
+fn sanitize_args(args: impl IntoIterator<Item = String>, home: Option<&Path>) -> Vec<String> {
+    let sensitive = |flag: &str| {
+        let flag = flag.to_lowercase();
+        flag == "--text"
+            || ["token", "password", "secret", "key"]
+                .iter()
+                .any(|word| flag.contains(word))
+    };
+    let home = home
+        .and_then(Path::to_str)
+        .filter(|home| !home.is_empty() && *home != "/");
+
+    let mut sanitized = Vec::new();
+    let mut redact_next = false;
+    for arg in args {
+        if redact_next && !arg.starts_with('-') {
+            sanitized.push(String::from(REDACTED));
+            redact_next = false;
+            continue;
+        }
+        redact_next = false;
+        if arg.starts_with('-') {
+            match arg.split_once('=') {
+                Some((flag, _)) if sensitive(flag) => {
+                    sanitized.push(format!("{}={}", flag, REDACTED));
+                    continue;
+                }
+                Some(_) => {}
+                None => redact_next = sensitive(&arg),
+            }
+        }
+        sanitized.push(match home {
+            Some(home) => arg.replace(home, "~"),
+            None => arg,
+        });
+    }
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sanitize_args() {
+        let args = [
+            "auth",
+            "set-token",
+            "--token",
+            "ghp_abc",
+            "--api-key=xyz",
+            "--text",
+            "private notes",
+            "-i",
+            "/home/me/data.txt",
+            "--verbose",
+        ]
+        .map(String::from);
+        assert_eq!(
+            sanitize_args(args, Some(Path::new("/home/me"))),
+            [
+                "auth",
+                "set-token",
+                "--token",
+                REDACTED,
+                "--api-key=<redacted>",
+                "--text",
+                REDACTED,
+                "-i",
+                "~/data.txt",
+                "--verbose",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_report() {
+        let dir = TempDir::new().unwrap();
+        let reports = dir.path().join("crash-reports");
+        let path = write(&reports, "panic: boom\n").unwrap();
+        assert!(path.starts_with(&reports));
+        assert_eq!(fs::read_to_string(path).unwrap(), "panic: boom\n");
+    }
+}
@@ -0,0 +1,242 @@
+//! Content digests computed while input is being read.
+
+use std::fmt;
+use std::io::{self, BufRead, Read};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Supported digest algorithms.
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+/// A finished digest.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileDigest {
+    pub algorithm: HashAlgorithm,
+    pub hex: String,
+}
+
+/// Incremental state for one of the supported algorithms.
+enum Hasher {
+    Sha256(Sha256),
+    Sha1(Sha1),
+    Md5(md5::Md5),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Md5 => Hasher::Md5(md5::Md5::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(bytes),
+            Hasher::Sha1(h) => h.update(bytes),
+            Hasher::Md5(h) => h.update(bytes),
+            Hasher::Blake3(h) => {
+                h.update(bytes);
+            }
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        match self {
+            Hasher::Sha256(h) => hex(&h.finalize()),
+            Hasher::Sha1(h) => hex(&h.finalize()),
+            Hasher::Md5(h) => hex(&h.finalize()),
+            Hasher::Blake3(h) => h.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Reader adapter that feeds every byte it yields into a digest, if one was requested.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Option<(HashAlgorithm, Hasher)>,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R, algorithm: Option<HashAlgorithm>) -> Self {
+        Self {
+            inner,
+            hasher: algorithm.map(|algorithm| (algorithm, Hasher::new(algorithm))),
+        }
+    }
+
+    /// Digest of everything read so far.
+    pub fn finish(self) -> Option<FileDigest> {
+        self.hasher.map(|(algorithm, hasher)| FileDigest {
+            algorithm,
+            hex: hasher.finalize_hex(),
+        })
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some((_, hasher)) = &mut self.hasher {
+            hasher.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+/// Digest of `bytes`.
+pub fn digest_bytes(bytes: &[u8], algorithm: HashAlgorithm) -> FileDigest {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(bytes);
+    FileDigest {
+        algorithm,
+        hex: hasher.finalize_hex(),
+    }
+}
+
+/// Digest of everything `reader` yields, read a buffer at a time.
+pub fn digest_reader(reader: impl Read, algorithm: HashAlgorithm) -> io::Result<FileDigest> {
+    digest_reader_with_progress(reader, algorithm, |_| {})
+}
+
+/// Like [`digest_reader`], calling `progress` with the number of bytes of each buffer
+/// hashed, e.g. to advance a progress bar.
+pub fn digest_reader_with_progress(
+    reader: impl Read,
+    algorithm: HashAlgorithm,
+    mut progress: impl FnMut(u64),
+) -> io::Result<FileDigest> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut reader = io::BufReader::with_capacity(64 * 1024, reader);
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        hasher.update(buffer);
+        let n = buffer.len();
+        reader.consume(n);
+        progress(n as u64);
+    }
+    Ok(FileDigest {
+        algorithm,
+        hex: hasher.finalize_hex(),
+    })
+}
+
+/// Splits a `<hex>  <file>` line as written by `sha256sum` (a `*` before the file name
+/// marks binary mode and is dropped). The digest must be hexadecimal; its length is not
+/// checked.
+pub fn parse_checksum_line(line: &str) -> Option<(&str, &str)> {
+    let (hex, name) = line.split_once(char::is_whitespace)?;
+    let name = name.trim_start().trim_start_matches('*');
+    let valid = !hex.is_empty() && !name.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit());
+    valid.then_some((hex, name))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(algorithm: HashAlgorithm, input: &[u8]) -> String {
+        let mut reader = HashingReader::new(input, Some(algorithm));
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        reader.finish().unwrap().hex
+    }
+
+    #[test]
+    fn test_known_digests() {
+        assert_eq!(
+            digest(HashAlgorithm::Sha256, b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            digest(HashAlgorithm::Sha1, b"abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            digest(HashAlgorithm::Md5, b"abc"),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            digest(HashAlgorithm::Blake3, b"abc"),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[test]
+    fn test_digest_reader_matches_bytes() {
+        let input: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            assert_eq!(
+                digest_reader(&input[..], algorithm).unwrap(),
+                digest_bytes(&input, algorithm)
+            );
+            assert_eq!(
+                digest_bytes(&input, algorithm).hex,
+                digest(algorithm, &input)
+            );
+        }
+    }
+
+    #[test]
+    fn test_digest_reader_reports_progress() {
+        let input = vec![7u8; 200_000];
+        let mut hashed = 0;
+        let digest =
+            digest_reader_with_progress(&input[..], HashAlgorithm::Sha256, |n| hashed += n)
+                .unwrap();
+        assert_eq!(hashed, 200_000);
+        assert_eq!(digest, digest_bytes(&input, HashAlgorithm::Sha256));
+    }
+
+    #[test]
+    fn test_parse_checksum_line() {
+        assert_eq!(
+            parse_checksum_line("ab12  dir/a.txt"),
+            Some(("ab12", "dir/a.txt"))
+        );
+        assert_eq!(
+            parse_checksum_line("AB12 *a b.bin"),
+            Some(("AB12", "a b.bin"))
+        );
+        assert_eq!(parse_checksum_line("xyz  a.txt"), None);
+        assert_eq!(parse_checksum_line("ab12"), None);
+    }
+
+    #[test]
+    fn test_passthrough_without_algorithm() {
+        let mut reader = HashingReader::new(&b"data"[..], None);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"data");
+        assert!(reader.finish().is_none());
+    }
+}
@@ -0,0 +1,57 @@
+//! Free space on the filesystem that outputs are written to.
+
+use std::path::Path;
+
+/// Bytes available to this user on the filesystem holding `path`, or `None` if it
+/// cannot be determined.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is only read after statvfs filled it
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// The directory itself, or its nearest existing ancestor when it is yet to be created.
+pub fn existing_ancestor(dir: &Path) -> &Path {
+    dir.ancestors()
+        .find(|a| !a.as_os_str().is_empty() && a.exists())
+        .unwrap_or(Path::new("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_existing_ancestor() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("a/b");
+        assert_eq!(existing_ancestor(&missing), dir.path());
+        assert_eq!(existing_ancestor(Path::new("missing")), Path::new("."));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_available_space() {
+        let dir = TempDir::new().unwrap();
+        assert!(available_space(dir.path()).is_some());
+        assert!(available_space(&dir.path().join("missing")).is_none());
+    }
+}
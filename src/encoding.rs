@@ -0,0 +1,218 @@
+//! Text decoding for input files that are not (valid) UTF-8.
+
+use std::fmt;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Character encoding of an input file.
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encoding {
+    /// Detect from BOM or content, falling back to Latin-1
+    #[default]
+    Auto,
+    #[value(name = "utf-8")]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[value(name = "utf-16le")]
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[value(name = "utf-16be")]
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    #[value(name = "latin-1")]
+    #[serde(rename = "latin-1")]
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+/// Decoded text along with how it was decoded.
+#[derive(Debug)]
+pub struct Decoded {
+    pub text: String,
+    /// Encoding actually used (never `Auto`)
+    pub encoding: Encoding,
+    /// Whether invalid sequences were replaced with U+FFFD
+    pub lossy: bool,
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Number of leading bytes inspected by the UTF-16 heuristic.
+const SNIFF_LEN: usize = 4096;
+
+/// Decodes `bytes` using `requested`, detecting the encoding when it is `Auto`.
+///
+/// A byte order mark always wins over the requested encoding and is stripped.
+pub fn decode(bytes: &[u8], requested: Encoding) -> Decoded {
+    let (encoding, body) = if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        (Encoding::Utf8, rest)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+        (Encoding::Utf16Le, rest)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+        (Encoding::Utf16Be, rest)
+    } else if requested == Encoding::Auto {
+        (detect(bytes), bytes)
+    } else {
+        (requested, bytes)
+    };
+
+    let (text, lossy) = match encoding {
+        Encoding::Utf8 | Encoding::Auto => match std::str::from_utf8(body) {
+            Ok(text) => (text.to_string(), false),
+            Err(_) => (String::from_utf8_lossy(body).into_owned(), true),
+        },
+        Encoding::Utf16Le => decode_utf16(body, u16::from_le_bytes),
+        Encoding::Utf16Be => decode_utf16(body, u16::from_be_bytes),
+        Encoding::Latin1 => (body.iter().map(|&b| b as char).collect(), false),
+    };
+
+    Decoded {
+        text,
+        encoding,
+        lossy,
+    }
+}
+
+/// Number of leading bytes inspected for NUL bytes when detecting binary content.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// Returns true if `bytes` look like binary data rather than text.
+///
+/// Content is binary when its first few kilobytes contain a NUL byte, unless it is
+/// UTF-16 (by BOM, heuristic, or because `requested` says so).
+pub fn is_binary(bytes: &[u8], requested: Encoding) -> bool {
+    if matches!(requested, Encoding::Utf16Le | Encoding::Utf16Be)
+        || bytes.starts_with(UTF16LE_BOM)
+        || bytes.starts_with(UTF16BE_BOM)
+    {
+        return false;
+    }
+    if requested == Encoding::Auto && matches!(detect(bytes), Encoding::Utf16Le | Encoding::Utf16Be)
+    {
+        return false;
+    }
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// Guesses the encoding of BOM-less content.
+fn detect(bytes: &[u8]) -> Encoding {
+    // Mostly-ASCII UTF-16 has NUL in every other byte (which is also valid UTF-8)
+    let sample = &bytes[..bytes.len().min(SNIFF_LEN)];
+    let pairs = sample.len() / 2;
+    if pairs > 0 {
+        let even_nuls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+        let odd_nuls = sample
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count();
+        if odd_nuls * 10 > pairs * 3 && even_nuls * 20 < pairs {
+            return Encoding::Utf16Le;
+        }
+        if even_nuls * 10 > pairs * 3 && odd_nuls * 20 < pairs {
+            return Encoding::Utf16Be;
+        }
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        Encoding::Utf8
+    } else {
+        Encoding::Latin1
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> (String, bool) {
+    let chunks = bytes.chunks_exact(2);
+    let mut lossy = !chunks.remainder().is_empty();
+    let units = chunks.map(|pair| to_unit([pair[0], pair[1]]));
+
+    let mut text: String = char::decode_utf16(units)
+        .map(|c| {
+            c.unwrap_or_else(|_| {
+                lossy = true;
+                char::REPLACEMENT_CHARACTER
+            })
+        })
+        .collect();
+    if !bytes.len().is_multiple_of(2) {
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+    (text, lossy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_auto_detects_utf8_and_strips_bom() {
+        let decoded = decode("héllo".as_bytes(), Encoding::Auto);
+        assert_eq!(decoded.text, "héllo");
+        assert_eq!(decoded.encoding, Encoding::Utf8);
+
+        let decoded = decode(b"\xEF\xBB\xBFhi", Encoding::Auto);
+        assert_eq!(decoded.text, "hi");
+    }
+
+    #[test]
+    fn test_utf16_bom_and_heuristic() {
+        let mut bytes = UTF16LE_BOM.to_vec();
+        bytes.extend(utf16le("hi ✓"));
+        let decoded = decode(&bytes, Encoding::Auto);
+        assert_eq!(decoded.text, "hi ✓");
+        assert_eq!(decoded.encoding, Encoding::Utf16Le);
+
+        let bytes: Vec<u8> = "line one\nline two\n"
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes())
+            .collect();
+        let decoded = decode(&bytes, Encoding::Auto);
+        assert_eq!(decoded.encoding, Encoding::Utf16Be);
+        assert_eq!(decoded.text, "line one\nline two\n");
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(is_binary(b"\x7fELF\x02\x01\x00\x00\x00", Encoding::Auto));
+        assert!(!is_binary(b"plain text\n", Encoding::Auto));
+        assert!(!is_binary(&utf16le("text that is utf-16"), Encoding::Auto));
+        assert!(is_binary(b"a\x00b", Encoding::Utf8));
+    }
+
+    #[test]
+    fn test_latin1_fallback() {
+        let decoded = decode(b"caf\xE9", Encoding::Auto);
+        assert_eq!(decoded.text, "café");
+        assert_eq!(decoded.encoding, Encoding::Latin1);
+        assert!(!decoded.lossy);
+    }
+
+    #[test]
+    fn test_lossy_decoding() {
+        let decoded = decode(b"caf\xE9", Encoding::Utf8);
+        assert_eq!(decoded.text, "caf\u{FFFD}");
+        assert!(decoded.lossy);
+
+        let decoded = decode(&[0x68, 0x00, 0x69], Encoding::Utf16Le);
+        assert_eq!(decoded.text, "h\u{FFFD}");
+        assert!(decoded.lossy);
+    }
+}
@@ -1,4 +1,16 @@
-//! Environment variable names for the application.
+//! Environment variable names for the application, and the lookup every
+//! `PREFIX`-variable read goes through so `--no-env` can switch them off.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::{debug, info};
+
+use crate::error::{Error, Result};
+
+/// Prefix shared by all application environment variables.
+pub const PREFIX: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_");
 
 // CLI arg overrides
 pub const CONFIG: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_CONFIG");
@@ -8,5 +20,128 @@ pub const PROFILE: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_PROFILE");
 pub const OUTPUT_DIR: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_OUTPUT_DIR");
 pub const LOG_LEVEL: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_LOG_LEVEL");
 pub const PARALLEL_JOBS: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_PARALLEL_JOBS");
+pub const UPDATE_CHECK: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPDATE_CHECK");
+/// Directories of the system and user configuration files (`config set --scope`)
+pub const SYSTEM_CONFIG_DIR: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_SYSTEM_CONFIG_DIR");
+pub const USER_CONFIG_DIR: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_USER_CONFIG_DIR");
+/// GitHub repository (owner/name) of an uncustomized build, like the `repository` key
+pub const REPOSITORY: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_REPOSITORY");
+
+// Upgrade options for unattended upgrades; flags on the command line take precedence
+pub const UPGRADE_VERSION: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_VERSION");
+pub const UPGRADE_CHANNEL: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_CHANNEL");
+pub const UPGRADE_FORCE: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_FORCE");
+pub const UPGRADE_TARGET: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_TARGET");
+pub const UPGRADE_YES: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_YES");
+pub const UPGRADE_ALLOW_MAJOR: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_ALLOW_MAJOR");
+pub const UPGRADE_CHECKSUM: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_CHECKSUM");
+pub const UPGRADE_INSTALL_DIR: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_INSTALL_DIR");
+/// Overrides `upgrade.mirror` in the configuration
+pub const UPGRADE_MIRROR: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_UPGRADE_MIRROR");
+
+// Secret overrides (take precedence over the OS keyring)
+pub const GITHUB_TOKEN: &str = concat!("__TEMPLATE_ENV_PREFIX__", "_GITHUB_TOKEN");
 
 // Other
+/// Conventional switch for screen-reader friendly output (same as --a11y)
+pub const ACCESSIBLE: &str = "ACCESSIBLE";
+/// Preferred editor for `config edit`, checked before `EDITOR`
+pub const VISUAL: &str = "VISUAL";
+pub const EDITOR: &str = "EDITOR";
+/// Locale of messages, in order of precedence (after --lang)
+pub const LC_ALL: &str = "LC_ALL";
+pub const LC_MESSAGES: &str = "LC_MESSAGES";
+pub const LANG: &str = "LANG";
+
+/// Set by `--no-env`
+static IGNORED: AtomicBool = AtomicBool::new(false);
+
+/// Makes every `PREFIX` variable read as unset from now on, logging the ones that are set.
+pub fn ignore_all() {
+    IGNORED.store(true, Ordering::Relaxed);
+    let mut present: Vec<String> = std::env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .filter(|name| name.starts_with(PREFIX))
+        .collect();
+    present.sort();
+    for name in present {
+        info!("Ignoring environment variable {} (--no-env)", name);
+    }
+}
+
+/// Reads environment variable `name`; `PREFIX` variables are unset after [`ignore_all`].
+pub fn var(name: &str) -> Option<String> {
+    if is_ignored(name) {
+        return None;
+    }
+    std::env::var(name).ok()
+}
+
+/// Reads `name` as a `T`, naming the variable when its value is invalid.
+pub fn parse<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    // Empty counts as unset, as configuration tools often export every variable
+    let Some(value) = var(name).filter(|value| !value.trim().is_empty()) else {
+        return Ok(None);
+    };
+    debug!("Using {}={}", name, value);
+    value
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|e| Error::Other(format!("Invalid value for {}: {}", name, e)))
+}
+
+/// Reads `name` as a boolean: 1/0, true/false, yes/no or on/off.
+pub fn flag(name: &str) -> Result<Option<bool>> {
+    let Some(value) = var(name) else {
+        return Ok(None);
+    };
+    debug!("Using {}={}", name, value);
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(Some(true)),
+        "0" | "false" | "no" | "off" | "" => Ok(Some(false)),
+        _ => Err(Error::Other(format!(
+            "Invalid value for {}: expected true or false, got '{}'",
+            name, value
+        ))),
+    }
+}
+
+/// Whether `name` is hidden by `--no-env`.
+pub fn is_ignored(name: &str) -> bool {
+    IGNORED.load(Ordering::Relaxed) && name.starts_with(PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_values() {
+        let name = concat!("__TEMPLATE_ENV_PREFIX__", "_TEST_TYPED_VALUES");
+        std::env::set_var(name, " 42 ");
+        assert_eq!(parse::<u32>(name).unwrap(), Some(42));
+        std::env::set_var(name, "");
+        assert_eq!(parse::<u32>(name).unwrap(), None);
+        std::env::set_var(name, "many");
+        let error = parse::<u32>(name).unwrap_err().to_string();
+        assert!(
+            error.starts_with(&format!("Invalid value for {}", name)),
+            "{}",
+            error
+        );
+
+        std::env::set_var(name, "Yes");
+        assert_eq!(flag(name).unwrap(), Some(true));
+        std::env::set_var(name, "off");
+        assert_eq!(flag(name).unwrap(), Some(false));
+        std::env::set_var(name, "maybe");
+        assert!(flag(name).is_err());
+        std::env::remove_var(name);
+        assert_eq!(flag(name).unwrap(), None);
+    }
+}
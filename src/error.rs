@@ -1,5 +1,13 @@
+use std::fmt::{self, Write};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::constants;
+use crate::i18n::{self, tr};
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Error, Debug)]
@@ -10,6 +18,334 @@ pub enum Error {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("Invalid regular expression: {0}")]
+    Regex(#[from] regex::Error),
+
+    #[error("Threshold exceeded: {0}")]
+    ThresholdExceeded(String),
+
+    /// A file that must exist does not
+    #[error("File not found: {}", .path.display())]
+    NotFound { path: PathBuf },
+
+    /// A file operation failed, e.g. `action` "open" or "read session file"
+    #[error("Cannot {action} '{}'", .path.display())]
+    File {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The current user may not write to `path`
+    #[error("Permission denied: cannot write to {}", .path.display())]
+    PermissionDenied { path: PathBuf },
+
+    /// A request could not be sent or its response not read
+    #[error("Request to {url} failed")]
+    Network {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The server answered with an unsuccessful status
+    #[error("{url} returned HTTP {status}")]
+    Http { url: String, status: u16 },
+
+    /// An input is not valid in the format it is read as, e.g. `format` "JSON"
+    #[error("Invalid {format}: {message}")]
+    InvalidInput {
+        format: &'static str,
+        message: String,
+    },
+
+    /// Stopped early because of Ctrl-C
+    #[error("Interrupted")]
+    Cancelled,
+
+    /// Several independent failures, reported together
+    #[error("{}", MultipleDisplay(.0))]
+    Multiple(Vec<Error>),
+
     #[error("{0}")]
     Other(String),
 }
+
+impl Error {
+    /// Process exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::ThresholdExceeded(_) => constants::EXIT_THRESHOLD,
+            Error::Cancelled => constants::EXIT_INTERRUPTED,
+            // Only a batch of threshold failures keeps the dedicated exit code
+            Error::Multiple(errors)
+                if errors
+                    .iter()
+                    .all(|e| e.exit_code() == constants::EXIT_THRESHOLD) =>
+            {
+                constants::EXIT_THRESHOLD
+            }
+            _ => constants::EXIT_FAILURE,
+        }
+    }
+
+    /// Stable identifier for scripts to branch on (`--format json`).
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "io",
+            Error::Json(_) => "json",
+            Error::Regex(_) => "regex",
+            Error::ThresholdExceeded(_) => "threshold_exceeded",
+            Error::NotFound { .. } => "not_found",
+            Error::File { .. } => "file",
+            Error::PermissionDenied { .. } => "permission_denied",
+            Error::Network { .. } => "network",
+            Error::Http { .. } => "http",
+            Error::InvalidInput { .. } => "invalid_input",
+            Error::Cancelled => "cancelled",
+            Error::Multiple(_) => "multiple",
+            Error::Other(_) => "other",
+        }
+    }
+
+    /// Suggestion for resolving the error, when there is an obvious one, in English.
+    pub fn hint(&self) -> Option<&'static str> {
+        self.hint_id().map(i18n::english)
+    }
+
+    /// Message id of the hint.
+    fn hint_id(&self) -> Option<&'static str> {
+        match self {
+            Error::Io(e) | Error::File { source: e, .. } => match e.kind() {
+                ErrorKind::NotFound => Some("hint-path-exists"),
+                ErrorKind::PermissionDenied => Some("hint-permissions"),
+                _ => None,
+            },
+            Error::Regex(_) => Some("hint-regex-escape"),
+            Error::NotFound { .. } => Some("hint-path-exists"),
+            Error::PermissionDenied { .. } => Some("hint-elevated"),
+            Error::Network { .. } => Some("hint-network"),
+            Error::Http {
+                status: 401 | 403, ..
+            } => Some("hint-http-auth"),
+            Error::Http { status: 429, .. } => Some("hint-rate-limited"),
+            Error::Http { status: 500.., .. } => Some("hint-server-error"),
+            _ => None,
+        }
+    }
+
+    /// The message in the user's language, where it has a translation.
+    fn localized(&self) -> String {
+        match self {
+            Error::Io(e) => tr!("error-io", error = e),
+            Error::Json(e) => tr!("error-json", error = e),
+            Error::Regex(e) => tr!("error-regex", error = e),
+            Error::ThresholdExceeded(details) => tr!("error-threshold", details = details),
+            Error::File { action, path, .. } => {
+                tr!("error-file", action = action, path = path.display())
+            }
+            Error::InvalidInput { format, message } => {
+                tr!("error-invalid-input", format = format, message = message)
+            }
+            Error::NotFound { path } => tr!("error-not-found", path = path.display()),
+            Error::PermissionDenied { path } => {
+                tr!("error-permission-denied", path = path.display())
+            }
+            Error::Network { url, .. } => tr!("error-network", url = url),
+            Error::Http { url, status } => tr!("error-http", url = url, status = status),
+            Error::Cancelled => tr!("error-cancelled"),
+            _ => self.to_string(),
+        }
+    }
+
+    /// The message followed by the causes it does not already include and the hint, as
+    /// printed for people, in their language.
+    pub fn render(&self) -> String {
+        if let Error::Multiple(errors) = self {
+            let mut out = tr!("error-multiple", count = errors.len());
+            for (i, error) in errors.iter().enumerate() {
+                let rendered = error.render().replace('\n', "\n      ");
+                let _ = write!(out, "\n  [{}] {}", i + 1, rendered);
+            }
+            return out;
+        }
+
+        let message = self.to_string();
+        let mut out = self.localized();
+        for cause in new_causes(self, &message) {
+            let _ = write!(out, "\n  {}: {}", tr!("error-caused-by"), cause);
+        }
+        if let Some(hint) = self.hint_id() {
+            let _ = write!(out, "\n  {}: {}", tr!("error-hint"), tr!(hint));
+        }
+        out
+    }
+
+    /// The error as the structured object printed to stderr in JSON mode.
+    pub fn report(&self) -> ErrorReport {
+        let mut source_chain = Vec::new();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            source_chain.push(cause.to_string());
+            source = cause.source();
+        }
+        let errors = match self {
+            Error::Multiple(errors) => errors.iter().map(Error::report).collect(),
+            _ => Vec::new(),
+        };
+        ErrorReport {
+            code: self.code(),
+            message: self.to_string(),
+            hint: self.hint(),
+            source_chain,
+            errors,
+        }
+    }
+
+    /// Folds collected errors into a result: none is success, one is returned as-is,
+    /// and several become [`Error::Multiple`].
+    pub fn aggregate(mut errors: Vec<Error>) -> Result<()> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::Multiple(errors)),
+        }
+    }
+}
+
+/// Serialized form of an [`Error`].
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub code: &'static str,
+    pub message: String,
+    pub hint: Option<&'static str>,
+    /// Messages of the underlying causes, outermost first
+    pub source_chain: Vec<String>,
+    /// The individual failures of an [`Error::Multiple`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ErrorReport>,
+}
+
+/// Renders each error on its own line with a 1-based index and any underlying cause
+/// not already part of its message.
+struct MultipleDisplay<'a>(&'a [Error]);
+
+impl fmt::Display for MultipleDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} errors occurred:", self.0.len())?;
+        for (i, error) in self.0.iter().enumerate() {
+            let message = error.to_string();
+            write!(f, "\n  [{}] {}", i + 1, message.replace('\n', "\n      "))?;
+
+            for cause in new_causes(error, &message) {
+                write!(f, "\n      caused by: {}", cause)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Messages of the causes of `error`, outermost first, that are not part of `message`.
+fn new_causes(error: &Error, message: &str) -> Vec<String> {
+    let mut causes = Vec::new();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        if !message.contains(&cause_message) {
+            causes.push(cause_message);
+        }
+        source = cause.source();
+    }
+    causes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate() {
+        assert!(Error::aggregate(Vec::new()).is_ok());
+
+        let single = Error::aggregate(vec![Error::Other(String::from("one"))]).unwrap_err();
+        assert_eq!(single.to_string(), "one");
+
+        let multiple = Error::aggregate(vec![
+            Error::Other(String::from("first")),
+            Error::Io(std::io::Error::other("disk full")),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            multiple.to_string(),
+            "2 errors occurred:\n  [1] first\n  [2] IO error: disk full"
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let error = Error::Io(std::io::Error::new(ErrorKind::NotFound, "no such file"));
+        let report = serde_json::to_value(error.report()).unwrap();
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "code": "io",
+                "message": "IO error: no such file",
+                "hint": "Check that the path exists",
+                "source_chain": ["no such file"],
+            })
+        );
+
+        let multiple = Error::Multiple(vec![
+            Error::Other(String::from("first")),
+            Error::ThresholdExceeded(String::from("second")),
+        ]);
+        let report = serde_json::to_value(multiple.report()).unwrap();
+        assert_eq!(report["code"], "multiple");
+        assert_eq!(report["hint"], serde_json::Value::Null);
+        assert_eq!(report["errors"][1]["code"], "threshold_exceeded");
+    }
+
+    #[test]
+    fn test_render() {
+        let error = Error::File {
+            action: "open",
+            path: PathBuf::from("data.txt"),
+            source: std::io::Error::new(ErrorKind::PermissionDenied, "access denied"),
+        };
+        assert_eq!(
+            error.render(),
+            "Cannot open 'data.txt'\n  caused by: access denied\n  hint: Check the file permissions"
+        );
+
+        // Causes already in the message are not repeated
+        let io = Error::Io(std::io::Error::other("disk full"));
+        assert_eq!(io.render(), "IO error: disk full");
+
+        let multiple = Error::Multiple(vec![
+            Error::NotFound {
+                path: PathBuf::from("a.txt"),
+            },
+            Error::Other(String::from("second")),
+        ]);
+        assert_eq!(
+            multiple.render(),
+            "2 errors occurred:\n  [1] File not found: a.txt\n        hint: Check that the path exists\n  [2] second"
+        );
+    }
+
+    #[test]
+    fn test_multiple_exit_code() {
+        let thresholds = Error::Multiple(vec![
+            Error::ThresholdExceeded(String::from("a")),
+            Error::ThresholdExceeded(String::from("b")),
+        ]);
+        assert_eq!(thresholds.exit_code(), constants::EXIT_THRESHOLD);
+
+        let mixed = Error::Multiple(vec![
+            Error::ThresholdExceeded(String::from("a")),
+            Error::Other(String::from("b")),
+        ]);
+        assert_eq!(mixed.exit_code(), constants::EXIT_FAILURE);
+    }
+}
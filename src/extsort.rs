@@ -0,0 +1,437 @@
+//! Line sorting and deduplication of inputs larger than memory.
+//!
+//! Both read lines from a reader and write the result to a writer. Inputs up to a spill
+//! size are handled in memory; larger ones are sorted as runs written to temporary files
+//! and merged into the output, or deduplicated through temporary hash buckets whose
+//! first occurrences are merged back into input order.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashSet};
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use tempfile::TempDir;
+use tracing::debug;
+
+use crate::cancel;
+use crate::error::Result;
+
+/// Inputs larger than this many bytes are sorted or deduplicated via temporary files.
+pub const SPILL_BYTES: usize = 64 * 1024 * 1024;
+
+/// Most sorted runs merged at once; more are merged in several passes, so that the number
+/// of open files stays bounded.
+const MERGE_WIDTH: usize = 64;
+
+/// Buckets the lines of a spilled input, or of a bucket still above the spill size, are
+/// split into.
+const BUCKET_COUNT: usize = 16;
+
+/// Levels of buckets at most; lines that still share a bucket are mostly equal, so the
+/// set of distinct lines kept for the bucket is small anyway.
+const MAX_DEPTH: u32 = 4;
+
+/// The text of `line` without its line ending, which lines are compared by.
+fn body(line: &[u8]) -> &[u8] {
+    let end = line
+        .iter()
+        .rposition(|&b| b != b'\r' && b != b'\n')
+        .map_or(0, |i| i + 1);
+    &line[..end]
+}
+
+/// Adds a line ending to `line` if it is the last line and has none.
+fn terminate(line: &mut Vec<u8>) {
+    if !line.ends_with(b"\n") {
+        line.push(b'\n');
+    }
+}
+
+/// Memory a held line takes, counting its allocation and bookkeeping as well as its text,
+/// so that short lines do not hold many times the spill size.
+fn held_size(line: &Vec<u8>) -> usize {
+    line.capacity() + std::mem::size_of::<Vec<u8>>()
+}
+
+/// The next line of `reader`, with its line ending if it has one.
+fn read_line(reader: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    Ok((reader.read_until(b'\n', &mut line)? > 0).then_some(line))
+}
+
+/// Temporary files of one sort or deduplication, removed with it.
+struct Spill {
+    dir: TempDir,
+    files: usize,
+}
+
+impl Spill {
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            dir: tempfile::tempdir()?,
+            files: 0,
+        })
+    }
+
+    /// Creates the next temporary file.
+    fn create(&mut self) -> io::Result<(PathBuf, BufWriter<File>)> {
+        let path = self.dir.path().join(format!("spill-{}", self.files));
+        self.files += 1;
+        let writer = BufWriter::new(File::create(&path)?);
+        Ok((path, writer))
+    }
+}
+
+/// Sorts the lines of `input` by their text into `output`, keeping equal lines in input
+/// order, and with `unique` keeps only the first of them. Inputs above `spill_bytes` are
+/// sorted in runs of about that size, which are merged from temporary files.
+pub fn sort_lines(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    unique: bool,
+    spill_bytes: usize,
+) -> Result<()> {
+    let mut spill = None;
+    let mut runs = Vec::new();
+    let mut lines = Vec::new();
+    let mut run_bytes = 0;
+    while let Some(mut line) = read_line(input)? {
+        terminate(&mut line);
+        run_bytes += held_size(&line);
+        lines.push(line);
+        if run_bytes >= spill_bytes {
+            let spill = match &mut spill {
+                Some(spill) => spill,
+                None => spill.insert(Spill::new()?),
+            };
+            runs.push(write_run(spill, &mut lines, unique)?);
+            run_bytes = 0;
+        }
+    }
+    let Some(mut spill) = spill else {
+        sort_run(&mut lines, unique);
+        for line in lines {
+            output.write_all(&line)?;
+        }
+        return Ok(());
+    };
+    if !lines.is_empty() {
+        runs.push(write_run(&mut spill, &mut lines, unique)?);
+    }
+
+    debug!("Merging {} sorted runs", runs.len());
+    while runs.len() > MERGE_WIDTH {
+        cancel::check()?;
+        // The merged runs are the earliest, so the result takes their place at the front
+        let (path, mut writer) = spill.create()?;
+        merge(&runs[..MERGE_WIDTH], &mut writer, unique)?;
+        writer.flush()?;
+        for run in runs.drain(..MERGE_WIDTH) {
+            fs::remove_file(run)?;
+        }
+        runs.insert(0, path);
+    }
+    merge(&runs, output, unique)
+}
+
+/// Sorts `lines` by their text, stably, dropping repeats after the first with `unique`.
+fn sort_run(lines: &mut Vec<Vec<u8>>, unique: bool) {
+    lines.sort_by(|a, b| body(a).cmp(body(b)));
+    if unique {
+        lines.dedup_by(|a, b| body(a) == body(b));
+    }
+}
+
+/// Sorts `lines` into a new temporary file, leaving `lines` empty.
+fn write_run(spill: &mut Spill, lines: &mut Vec<Vec<u8>>, unique: bool) -> Result<PathBuf> {
+    cancel::check()?;
+    sort_run(lines, unique);
+    let (path, mut writer) = spill.create()?;
+    for line in lines.drain(..) {
+        writer.write_all(&line)?;
+    }
+    writer.flush()?;
+    Ok(path)
+}
+
+/// Merges the sorted `runs` into `output`; of equal lines the one from the earliest run
+/// comes first, and with `unique` only that one.
+fn merge(runs: &[PathBuf], output: &mut dyn Write, unique: bool) -> Result<()> {
+    let mut readers = runs
+        .iter()
+        .map(|path| File::open(path).map(BufReader::new))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (run, reader) in readers.iter_mut().enumerate() {
+        if let Some(line) = read_line(reader)? {
+            heap.push(Head { line, run });
+        }
+    }
+    let mut previous: Option<Vec<u8>> = None;
+    while let Some(Head { line, run }) = heap.pop() {
+        if let Some(next) = read_line(&mut readers[run])? {
+            heap.push(Head { line: next, run });
+        }
+        if unique && previous.as_deref().map(body) == Some(body(&line)) {
+            continue;
+        }
+        output.write_all(&line)?;
+        previous = Some(line);
+    }
+    Ok(())
+}
+
+/// The next line of a sorted run. The heap is a max-heap, so the order is reversed: the
+/// smallest line, and of equal lines the one from the earliest run, comes out first.
+#[derive(PartialEq, Eq)]
+struct Head {
+    line: Vec<u8>,
+    run: usize,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        body(&other.line)
+            .cmp(body(&self.line))
+            .then(other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Copies the lines of `input` to `output`, dropping lines whose text appeared on an
+/// earlier line and keeping line order and endings. Inputs above `spill_bytes` are split
+/// into temporary buckets by line hash (again for buckets above it), so only the distinct
+/// lines of one bucket are held at a time, and the first occurrences found in each
+/// bucket are merged back by line number.
+pub fn unique_lines(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    spill_bytes: usize,
+) -> Result<()> {
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    let mut held = 0;
+    while held <= spill_bytes {
+        let Some(line) = read_line(input)? else {
+            let mut seen = HashSet::new();
+            for line in lines.iter().filter(|line| seen.insert(body(line))) {
+                output.write_all(line)?;
+            }
+            return Ok(());
+        };
+        held += held_size(&line);
+        lines.push(line);
+    }
+
+    let mut spill = Spill::new()?;
+    let records = lines
+        .into_iter()
+        .map(Ok)
+        .chain(std::iter::from_fn(|| read_line(input).transpose()))
+        .zip(0..)
+        .map(|(line, index)| line.map(|line| (index, line)));
+    let buckets = scatter(&mut spill, records, 0)?;
+    debug!("Deduplicating via {} spill buckets", buckets.len());
+    let mut kept = Vec::with_capacity(buckets.len());
+    for (path, size) in buckets {
+        kept.push(first_occurrences(&mut spill, path, size, 1, spill_bytes)?);
+    }
+    merge_by_index(&kept, |_, line| output.write_all(line))
+}
+
+/// Writes each `(index, line)` record to one of [`BUCKET_COUNT`] new temporary files by
+/// the hash of its text, returning the files and their sizes.
+fn scatter(
+    spill: &mut Spill,
+    records: impl Iterator<Item = io::Result<(u64, Vec<u8>)>>,
+    depth: u32,
+) -> Result<Vec<(PathBuf, u64)>> {
+    let mut buckets = Vec::with_capacity(BUCKET_COUNT);
+    let mut writers = Vec::with_capacity(BUCKET_COUNT);
+    for _ in 0..BUCKET_COUNT {
+        let (path, writer) = spill.create()?;
+        buckets.push((path, 0));
+        writers.push(writer);
+    }
+    for record in records {
+        let (index, line) = record?;
+        // Every level hashes differently, so that a bucket's lines spread over the next
+        let mut hasher = DefaultHasher::new();
+        depth.hash(&mut hasher);
+        body(&line).hash(&mut hasher);
+        let bucket = (hasher.finish() % BUCKET_COUNT as u64) as usize;
+        buckets[bucket].1 += write_record(&mut writers[bucket], index, &line)?;
+    }
+    for mut writer in writers {
+        writer.flush()?;
+    }
+    Ok(buckets)
+}
+
+/// Keeps the records of the bucket at `path` whose text is not on an earlier record, in
+/// a new temporary file in line order; buckets above `spill_bytes` are split up first.
+fn first_occurrences(
+    spill: &mut Spill,
+    path: PathBuf,
+    size: u64,
+    depth: u32,
+    spill_bytes: usize,
+) -> Result<PathBuf> {
+    cancel::check()?;
+    let mut reader = BufReader::new(File::open(&path)?);
+    if size > spill_bytes as u64 && depth < MAX_DEPTH {
+        let records = std::iter::from_fn(|| read_record(&mut reader).transpose());
+        let buckets = scatter(spill, records, depth)?;
+        fs::remove_file(&path)?;
+        let mut kept = Vec::with_capacity(buckets.len());
+        for (path, size) in buckets {
+            kept.push(first_occurrences(
+                spill,
+                path,
+                size,
+                depth + 1,
+                spill_bytes,
+            )?);
+        }
+        let (path, mut writer) = spill.create()?;
+        merge_by_index(&kept, |index, line| {
+            write_record(&mut writer, index, line).map(drop)
+        })?;
+        writer.flush()?;
+        for part in kept {
+            fs::remove_file(part)?;
+        }
+        return Ok(path);
+    }
+
+    // Equal lines share a bucket, and each bucket lists its lines in input order
+    let mut seen = HashSet::new();
+    let (kept, mut writer) = spill.create()?;
+    while let Some((index, line)) = read_record(&mut reader)? {
+        if !seen.contains(body(&line)) {
+            write_record(&mut writer, index, &line)?;
+            seen.insert(body(&line).to_vec());
+        }
+    }
+    writer.flush()?;
+    drop(reader);
+    fs::remove_file(path)?;
+    Ok(kept)
+}
+
+/// Passes the records of the files at `paths`, each in line order, to `emit` in line
+/// order.
+fn merge_by_index(
+    paths: &[PathBuf],
+    mut emit: impl FnMut(u64, &[u8]) -> io::Result<()>,
+) -> Result<()> {
+    let mut readers = paths
+        .iter()
+        .map(|path| File::open(path).map(BufReader::new))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut lines = Vec::with_capacity(readers.len());
+    let mut heap = BinaryHeap::new();
+    for (file, reader) in readers.iter_mut().enumerate() {
+        let record = read_record(reader)?;
+        if let Some((index, _)) = &record {
+            heap.push(Reverse((*index, file)));
+        }
+        lines.push(record.map(|(_, line)| line).unwrap_or_default());
+    }
+    while let Some(Reverse((index, file))) = heap.pop() {
+        emit(index, &lines[file])?;
+        if let Some((next, line)) = read_record(&mut readers[file])? {
+            heap.push(Reverse((next, file)));
+            lines[file] = line;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a line and its index, returning the bytes written.
+fn write_record(writer: &mut impl Write, index: u64, line: &[u8]) -> io::Result<u64> {
+    writer.write_all(&index.to_le_bytes())?;
+    writer.write_all(&(line.len() as u64).to_le_bytes())?;
+    writer.write_all(line)?;
+    Ok(16 + line.len() as u64)
+}
+
+/// Reads a record written by [`write_record`].
+fn read_record(reader: &mut impl Read) -> io::Result<Option<(u64, Vec<u8>)>> {
+    let mut header = [0; 16];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let (index, len) = header.split_at(8);
+    let index = u64::from_le_bytes(index.try_into().unwrap_or_default());
+    let len = u64::from_le_bytes(len.try_into().unwrap_or_default());
+    let mut line = vec![0; len as usize];
+    reader.read_exact(&mut line)?;
+    Ok(Some((index, line)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(content: &str, unique: bool, spill_bytes: usize) -> String {
+        let mut output = Vec::new();
+        sort_lines(&mut content.as_bytes(), &mut output, unique, spill_bytes).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn deduped(content: &str, spill_bytes: usize) -> String {
+        let mut output = Vec::new();
+        unique_lines(&mut content.as_bytes(), &mut output, spill_bytes).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_sort_lines_in_memory() {
+        assert_eq!(sorted("b\nc\r\na\nb", false, SPILL_BYTES), "a\nb\nb\nc\r\n");
+        assert_eq!(sorted("b\nc\na\nb\r\n", true, SPILL_BYTES), "a\nb\nc\n");
+        assert_eq!(sorted("", true, SPILL_BYTES), "");
+    }
+
+    #[test]
+    fn test_sort_lines_spills_stably() {
+        // Equal texts with different endings show which of them came first
+        let content: String = (0..300)
+            .map(|i| {
+                let ending = if i % 2 == 0 { "\n" } else { "\r\n" };
+                format!("{}{}", (i * 37) % 50, ending)
+            })
+            .collect();
+        for unique in [false, true] {
+            let expected = sorted(&content, unique, SPILL_BYTES);
+            assert_eq!(sorted(&content, unique, 64), expected);
+            // A run per line, merged in several passes
+            assert_eq!(sorted(&content, unique, 1), expected);
+        }
+        assert_eq!(sorted(&content, true, 64).lines().count(), 50);
+    }
+
+    #[test]
+    fn test_unique_lines_spills() {
+        let content: String = (0..500).map(|i| format!("{}\n", (i * 7) % 60)).collect();
+        let expected = deduped(&content, SPILL_BYTES);
+        assert_eq!(expected.lines().count(), 60);
+        assert!(expected.starts_with("0\n7\n14\n"));
+        // Buckets above the spill size are split again, down to the deepest level
+        for spill_bytes in [64, 1] {
+            assert_eq!(deduped(&content, spill_bytes), expected);
+        }
+
+        assert_eq!(deduped("a\r\nb\na\nb", 1), "a\r\nb\n");
+        assert_eq!(deduped("a\r\nb\na\nc", 1), "a\r\nb\nc");
+    }
+}
@@ -0,0 +1,116 @@
+//! Input selection from git history, by shelling out to the `git` binary.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use tracing::debug;
+
+use crate::error::{Error, Result};
+
+/// Which files touched in a change to select.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitSelection {
+    /// Files staged in the index
+    Staged,
+    /// Files differing from HEAD (staged or not) plus untracked, non-ignored files
+    Changed,
+    /// Files changed in a revision range such as `HEAD~3..` or `main...feature`
+    Range(String),
+}
+
+impl FromStr for GitSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "" => Err(String::from(
+                "expected 'staged', 'changed' or a revision range",
+            )),
+            "staged" => Ok(GitSelection::Staged),
+            "changed" => Ok(GitSelection::Changed),
+            range if range.starts_with('-') => Err(format!("'{}' is not a revision range", range)),
+            range => Ok(GitSelection::Range(range.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for GitSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitSelection::Staged => write!(f, "staged"),
+            GitSelection::Changed => write!(f, "changed"),
+            GitSelection::Range(range) => write!(f, "{}", range),
+        }
+    }
+}
+
+/// Existing files under `dir` selected by `selection`, sorted, as paths joined onto `dir`.
+///
+/// Deleted files are left out since there is nothing to read.
+pub fn select_files(dir: &Path, selection: &GitSelection) -> Result<Vec<PathBuf>> {
+    let diff = ["diff", "--name-only", "-z", "--relative", "--diff-filter=d"];
+    let mut names = match selection {
+        GitSelection::Staged => git(dir, &[&diff[..], &["--cached"]].concat())?,
+        GitSelection::Changed => {
+            let mut names = git(dir, &[&diff[..], &["HEAD"]].concat())?;
+            names.extend(git(
+                dir,
+                &["ls-files", "-z", "--others", "--exclude-standard"],
+            )?);
+            names
+        }
+        GitSelection::Range(range) => git(dir, &[&diff[..], &[range.as_str(), "--"]].concat())?,
+    };
+    names.sort();
+    names.dedup();
+
+    Ok(names
+        .into_iter()
+        .map(|name| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect())
+}
+
+/// Runs git in `dir` and splits its NUL-separated output.
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<String>> {
+    debug!("Running git {} in {}", args.join(" "), dir.display());
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| Error::Other(format!("Failed to run git: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::Other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!("staged".parse(), Ok(GitSelection::Staged));
+        assert_eq!("changed".parse(), Ok(GitSelection::Changed));
+        assert_eq!(
+            "HEAD~3..".parse(),
+            Ok(GitSelection::Range(String::from("HEAD~3..")))
+        );
+        assert!("".parse::<GitSelection>().is_err());
+        assert!("--output=x".parse::<GitSelection>().is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! Glob and ignore-pattern matching for the commands that walk directories: `run`'s
+//! --include and --exclude (in batch jobs too) and the daemon, which applies those of its
+//! `run_args` to the files of the watched directories. Commands given files by name, such
+//! as `hash` and `diff`, have nothing to select.
+//!
+//! Semantics follow `.gitignore`:
+//! - Paths are matched in their `/`-separated form relative to a root.
+//! - `*` and `?` never cross a `/`; `[a-z]`, `[!a-z]` and `{a,b}` work as usual; `\` escapes.
+//! - `**` as a whole segment matches any number of directories (`a/**/b`, `**/b`, `a/**`).
+//! - A pattern without a `/` matches the file name at any depth; one with a `/` (a leading
+//!   `/` is allowed) is anchored to the root. A trailing `/` matches everything below.
+//! - In a [`GlobSet`], `!pattern` re-includes paths and the last matching pattern wins.
+//!
+//! Compiled patterns are cached process-wide, so repeated use of the same pattern (for
+//! example once per job of a batch, or again at each daemon reload) compiles it only once.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use regex::Regex;
+
+use crate::error::{Error, Result};
+
+/// Compiled regexes keyed by pattern and case-insensitivity.
+type PatternCache = HashMap<(String, bool), Arc<Regex>>;
+
+/// A single compiled glob pattern.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Arc<Regex>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str, case_insensitive: bool) -> Result<Self> {
+        static CACHE: OnceLock<Mutex<PatternCache>> = OnceLock::new();
+
+        let key = (pattern.to_string(), case_insensitive);
+        let cache = CACHE.get_or_init(Default::default);
+        if let Some(regex) = cache.lock().unwrap().get(&key) {
+            return Ok(Self {
+                regex: Arc::clone(regex),
+            });
+        }
+
+        let source = translate(pattern)
+            .map_err(|e| Error::Other(format!("Invalid glob '{}': {}", pattern, e)))?;
+        let regex = regex::RegexBuilder::new(&source)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| Error::Other(format!("Invalid glob '{}': {}", pattern, e)))?;
+        let regex = Arc::new(regex);
+        cache.lock().unwrap().insert(key, Arc::clone(&regex));
+
+        Ok(Self { regex })
+    }
+
+    /// Whether `path` (relative to the root the pattern applies to) matches.
+    pub fn is_match(&self, path: &Path) -> bool {
+        self.regex.is_match(&normalize(path))
+    }
+}
+
+/// An ordered list of patterns where `!pattern` negates and the last match wins.
+#[derive(Debug, Clone, Default)]
+pub struct GlobSet {
+    rules: Vec<(Glob, bool)>,
+}
+
+impl GlobSet {
+    pub fn new<S: AsRef<str>>(patterns: &[S], case_insensitive: bool) -> Result<Self> {
+        let rules = patterns
+            .iter()
+            .map(|pattern| {
+                let pattern = pattern.as_ref();
+                match pattern.strip_prefix('!') {
+                    Some(negated) => Ok((Glob::new(negated, case_insensitive)?, true)),
+                    None => Ok((Glob::new(pattern, case_insensitive)?, false)),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(path))
+            .is_some_and(|(_, negated)| !negated)
+    }
+}
+
+/// Include and exclude patterns applied together: a path is allowed when it matches
+/// an include pattern (or none were given) and no exclude pattern.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    pub fn new<S: AsRef<str>>(
+        include: &[S],
+        exclude: &[S],
+        case_insensitive: bool,
+    ) -> Result<Self> {
+        Ok(Self {
+            include: GlobSet::new(include, case_insensitive)?,
+            exclude: GlobSet::new(exclude, case_insensitive)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn allows(&self, path: &Path) -> bool {
+        (self.include.is_empty() || self.include.is_match(path)) && !self.exclude.is_match(path)
+    }
+}
+
+/// `/`-separated form of `path` without a leading `./`.
+fn normalize(path: &Path) -> String {
+    let text = path.to_string_lossy();
+    let text = if cfg!(windows) {
+        text.replace('\\', "/")
+    } else {
+        text.into_owned()
+    };
+    text.trim_start_matches("./").to_string()
+}
+
+/// Translates a glob into an anchored regular expression.
+fn translate(pattern: &str) -> std::result::Result<String, String> {
+    if pattern.is_empty() {
+        return Err(String::from("empty pattern"));
+    }
+
+    let mut pattern = pattern.to_string();
+    if pattern.ends_with('/') {
+        pattern.push_str("**");
+    }
+    let anchored = pattern.trim_end_matches("/**").contains('/');
+    let body = pattern.strip_prefix('/').unwrap_or(&pattern);
+
+    let mut out = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let chars: Vec<char> = body.chars().collect();
+    let mut braces = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let segment_start = i == 0 || chars[i - 1] == '/';
+        match c {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let segment_end = matches!(chars.get(i + 2), None | Some('/'));
+                if segment_start && segment_end {
+                    if chars.get(i + 2) == Some(&'/') {
+                        // `**/` matches zero or more whole directories
+                        out.push_str("(?:[^/]*/)*");
+                        i += 3;
+                    } else {
+                        // Trailing `**` matches everything below
+                        out.push_str(".*");
+                        i += 2;
+                    }
+                    continue;
+                }
+                out.push_str("[^/]*");
+                i += 2;
+                continue;
+            }
+            '*' => out.push_str("[^/]*"),
+            '?' => out.push_str("[^/]"),
+            '[' => {
+                let close = chars[i + 1..]
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .find(|(_, &c)| c == ']')
+                    .map(|(offset, _)| i + 1 + offset)
+                    .ok_or("unclosed '['")?;
+                let mut class: String = chars[i + 1..close].iter().collect();
+                if let Some(rest) = class.strip_prefix('!') {
+                    class = format!("^{}", rest);
+                }
+                out.push('[');
+                out.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                out.push(']');
+                i = close + 1;
+                continue;
+            }
+            '{' => {
+                braces += 1;
+                out.push_str("(?:");
+            }
+            '}' if braces > 0 => {
+                braces -= 1;
+                out.push(')');
+            }
+            ',' if braces > 0 => out.push('|'),
+            '\\' => {
+                let escaped = chars.get(i + 1).ok_or("trailing '\\'")?;
+                out.push_str(&regex::escape(&escaped.to_string()));
+                i += 2;
+                continue;
+            }
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    if braces > 0 {
+        return Err(String::from("unclosed '{'"));
+    }
+    out.push('$');
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern, false).unwrap().is_match(Path::new(path))
+    }
+
+    #[test]
+    fn test_glob_matrix() {
+        let cases = [
+            // (pattern, path, expected)
+            ("*.log", "app.log", true),
+            ("*.log", "logs/deep/app.log", true),
+            ("*.log", "app.log.1", false),
+            ("/*.log", "logs/app.log", false),
+            ("/*.log", "app.log", true),
+            ("logs/*.log", "logs/app.log", true),
+            ("logs/*.log", "logs/old/app.log", false),
+            ("logs/*.log", "other/logs/app.log", false),
+            ("logs/**/*.log", "logs/app.log", true),
+            ("logs/**/*.log", "logs/a/b/app.log", true),
+            ("**/fixtures/*", "fixtures/x", true),
+            ("**/fixtures/*", "a/b/fixtures/x", true),
+            ("**/fixtures/*", "a/fixtures/b/x", false),
+            ("target/", "target/debug/app", true),
+            ("target/", "src/target/debug", true),
+            ("build/out/", "build/out/a/b", true),
+            ("build/**", "build/a", true),
+            ("build/**", "build", false),
+            ("a**b", "axxb", true),
+            ("a**b", "ax/xb", false),
+            ("**", "any/thing/at/all", true),
+            ("file?.txt", "file1.txt", true),
+            ("file?.txt", "file10.txt", false),
+            ("file?.txt", "file/.txt", false),
+            ("[abc].rs", "b.rs", true),
+            ("[!abc].rs", "b.rs", false),
+            ("[!abc].rs", "d.rs", true),
+            ("[a-c]x", "cx", true),
+            ("*.{rs,toml}", "src/main.rs", true),
+            ("*.{rs,toml}", "Cargo.toml", true),
+            ("*.{rs,toml}", "Cargo.lock", false),
+            ("\\*.txt", "*.txt", true),
+            ("\\*.txt", "a.txt", false),
+            ("a.b", "axb", false),
+            ("./notes.md", "notes.md", false),
+            ("notes.md", "./notes.md", true),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                matches(pattern, path),
+                expected,
+                "pattern {:?} against {:?}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_case_sensitivity() {
+        assert!(!matches("*.LOG", "app.log"));
+        assert!(Glob::new("*.LOG", true)
+            .unwrap()
+            .is_match(Path::new("App.log")));
+        // Case variants are cached separately
+        assert!(!matches("*.LOG", "app.log"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for pattern in ["", "[abc", "{a,b", "trailing\\"] {
+            let message = Glob::new(pattern, false).unwrap_err().to_string();
+            assert!(message.starts_with("Invalid glob"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_glob_set_last_match_wins() {
+        let set = GlobSet::new(&["*.log", "!keep.log", "keep/**"], false).unwrap();
+        assert!(set.is_match(Path::new("a/drop.log")));
+        assert!(!set.is_match(Path::new("a/keep.log")));
+        assert!(set.is_match(Path::new("keep/keep.log")));
+        assert!(!set.is_match(Path::new("main.rs")));
+        assert!(!GlobSet::default().is_match(Path::new("anything")));
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let filter = PathFilter::new(&["*.rs", "*.md"], &["target/"], false).unwrap();
+        assert!(filter.allows(Path::new("src/main.rs")));
+        assert!(filter.allows(Path::new("README.md")));
+        assert!(!filter.allows(Path::new("target/gen.rs")));
+        assert!(!filter.allows(Path::new("Cargo.toml")));
+
+        let exclude_only = PathFilter::new(&[], &["*.tmp"], false).unwrap();
+        assert!(exclude_only.allows(Path::new("a.txt")));
+        assert!(!exclude_only.allows(Path::new("a.tmp")));
+        assert!(PathFilter::default().is_empty());
+    }
+}
@@ -0,0 +1,483 @@
+//! HTTP client shared by commands that use the network.
+//!
+//! The client identifies the application in its user agent, keeps connections alive
+//! between requests, retries transient failures, and logs each request at debug level.
+//! Large downloads are fetched as parallel byte ranges (see [`Download`]).
+
+use std::cell::Cell;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use futures_util::{StreamExt, TryStreamExt};
+use reqwest::header::{HeaderValue, ACCEPT, ACCEPT_RANGES, AUTHORIZATION, RANGE, RETRY_AFTER};
+use reqwest::{Client, ClientBuilder, Response, StatusCode};
+use serde::Serialize;
+use tracing::debug;
+
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::identity;
+use crate::secrets::{self, Secret};
+
+/// Attempts per request, including the first.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled for each further retry.
+const BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// Longest `Retry-After` the client is willing to wait.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+static RETRIES: AtomicU64 = AtomicU64::new(0);
+
+/// Requests sent by this process.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// Requests sent, counting each retry
+    pub requests: u64,
+    /// Requests repeated after a transient failure
+    pub retries: u64,
+}
+
+/// Counts of the requests sent so far.
+pub fn metrics() -> Metrics {
+    Metrics {
+        requests: REQUESTS.load(Ordering::Relaxed),
+        retries: RETRIES.load(Ordering::Relaxed),
+    }
+}
+
+/// The shared client, built on first use.
+pub fn client() -> Result<&'static Client> {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let client = ClientBuilder::new()
+        .user_agent(format!(
+            "{}/{}",
+            identity::app_name(),
+            constants::APP_VERSION
+        ))
+        .connect_timeout(Duration::from_secs(10))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(4)
+        .build()
+        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+/// The Authorization header for GitHub requests, from the stored token if there is one.
+fn github_authorization() -> Result<Option<&'static HeaderValue>> {
+    static AUTHORIZATION_VALUE: OnceLock<Option<HeaderValue>> = OnceLock::new();
+
+    if let Some(value) = AUTHORIZATION_VALUE.get() {
+        return Ok(value.as_ref());
+    }
+    let value = match secrets::get(Secret::GithubToken) {
+        Some((token, source)) => {
+            debug!("Authenticating GitHub requests with token from {}", source);
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| {
+                Error::Other(String::from("GitHub token contains invalid characters"))
+            })?;
+            value.set_sensitive(true);
+            Some(value)
+        }
+        None => None,
+    };
+    Ok(AUTHORIZATION_VALUE.get_or_init(|| value).as_ref())
+}
+
+/// Whether `url` is served by GitHub, and so may see the GitHub token (mirrors and other
+/// hosts must not, nor the signed asset URLs GitHub redirects downloads to).
+fn is_github(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .is_some_and(|host| host == "github.com" || host.ends_with(".github.com"))
+}
+
+/// Sends a GET request for `url`, with `accept` as the Accept header if given.
+///
+/// Requests to GitHub are authenticated with the stored token, if any; reqwest drops the
+/// header when a download redirects to another host.
+///
+/// Connection failures, timeouts, rate limiting and server errors are retried with
+/// exponential backoff (or after the server's `Retry-After`); the last response is
+/// returned whatever its status, for the caller to interpret.
+pub async fn get(url: &str, accept: Option<&str>) -> Result<Response> {
+    send(url, accept, None).await
+}
+
+/// Like [`get`], for the bytes `range` (end excluded) of `url`.
+async fn get_range(url: &str, range: &Range<u64>) -> Result<Response> {
+    send(url, None, Some(range)).await
+}
+
+async fn send(url: &str, accept: Option<&str>, range: Option<&Range<u64>>) -> Result<Response> {
+    let client = client()?;
+    let authorization = if is_github(url) {
+        github_authorization()?
+    } else {
+        None
+    };
+    let mut attempt = 1;
+    loop {
+        let mut request = client.get(url);
+        if let Some(accept) = accept {
+            request = request.header(ACCEPT, accept);
+        }
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization.clone());
+        }
+        if let Some(range) = range {
+            request = request.header(RANGE, format!("bytes={}-{}", range.start, range.end - 1));
+        }
+
+        REQUESTS.fetch_add(1, Ordering::Relaxed);
+        let started = Instant::now();
+        let result = request.send().await;
+        let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let delay = match &result {
+            Ok(response) => {
+                debug!(
+                    "GET {} -> {} in {:.0} ms (attempt {})",
+                    url,
+                    response.status(),
+                    elapsed_ms,
+                    attempt
+                );
+                is_transient(response.status())
+                    .then(|| retry_delay(attempt, response.headers().get(RETRY_AFTER)))
+            }
+            Err(e) => {
+                debug!(
+                    "GET {} failed in {:.0} ms (attempt {}): {}",
+                    url, elapsed_ms, attempt, e
+                );
+                (e.is_connect() || e.is_timeout()).then(|| retry_delay(attempt, None))
+            }
+        };
+
+        match delay {
+            Some(delay) if attempt < MAX_ATTEMPTS => {
+                debug!("Retrying GET {} in {} ms", url, delay.as_millis());
+                RETRIES.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            _ => return result.map_err(|e| network_error(url, e)),
+        }
+    }
+}
+
+/// A download whose response headers have arrived.
+///
+/// Large downloads from servers that accept byte ranges are split into [`CHUNKS`] ranges
+/// fetched in parallel, each retried on its own when it fails part way, which is faster
+/// on high-latency links than a single stream.
+pub struct Download {
+    url: String,
+    response: Response,
+    size: u64,
+    ranges: bool,
+    /// Size from which the download is split into ranges
+    threshold: u64,
+}
+
+/// Size from which downloads are split into ranges fetched in parallel.
+const CHUNKED_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// Ranges a large download is split into.
+const CHUNKS: u64 = 4;
+
+impl Download {
+    /// Requests `url`, failing unless the server answers with its content and length.
+    pub async fn start(url: &str) -> Result<Self> {
+        let response = get(url, None).await?;
+        if !response.status().is_success() {
+            return Err(Error::Http {
+                url: url.to_string(),
+                status: response.status().as_u16(),
+            });
+        }
+        let size = response
+            .content_length()
+            .ok_or_else(|| Error::Io(std::io::Error::other("Failed to get content length")))?;
+        let ranges = response
+            .headers()
+            .get(ACCEPT_RANGES)
+            .is_some_and(|value| value.as_bytes() == b"bytes");
+        Ok(Self {
+            url: url.to_string(),
+            response,
+            size,
+            ranges,
+            threshold: CHUNKED_THRESHOLD,
+        })
+    }
+
+    /// Size of the content in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Receives the content, calling `progress` with the number of bytes received so far.
+    pub async fn fetch(self, progress: impl Fn(u64)) -> Result<Vec<u8>> {
+        if !self.ranges || self.size < self.threshold {
+            let mut buffer = Vec::with_capacity(self.size as usize);
+            let mut stream = self.response.bytes_stream();
+            while let Some(item) = stream.next().await {
+                let chunk = item.map_err(|e| network_error(&self.url, e))?;
+                buffer.extend_from_slice(&chunk);
+                progress(buffer.len() as u64);
+            }
+            return Ok(buffer);
+        }
+
+        // The ranges are requested afresh, so the first response is not read
+        drop(self.response);
+        let chunk_size = self.size.div_ceil(CHUNKS);
+        debug!(
+            "Downloading {} in {} ranges of up to {} bytes",
+            self.url, CHUNKS, chunk_size
+        );
+        let received = Cell::new(0u64);
+        let report = |delta: i64| {
+            received.set(received.get().saturating_add_signed(delta));
+            progress(received.get());
+        };
+        let ranges = (0..self.size)
+            .step_by(chunk_size as usize)
+            .map(|start| start..(start + chunk_size).min(self.size));
+        let chunks: Vec<_> = futures_util::stream::iter(ranges)
+            .map(|range| fetch_range(&self.url, range, &report))
+            .buffered(CHUNKS as usize)
+            .try_collect()
+            .await?;
+        Ok(chunks.concat())
+    }
+}
+
+/// Downloads bytes `range` of `url`, starting over when the transfer breaks off.
+async fn fetch_range(url: &str, range: Range<u64>, report: &impl Fn(i64)) -> Result<Vec<u8>> {
+    let expected = (range.end - range.start) as usize;
+    let mut attempt = 1;
+    loop {
+        let mut buffer = Vec::with_capacity(expected);
+        let result = async {
+            let response = get_range(url, &range).await?;
+            if response.status() != StatusCode::PARTIAL_CONTENT {
+                return Err(Error::Http {
+                    url: url.to_string(),
+                    status: response.status().as_u16(),
+                });
+            }
+            let mut stream = response.bytes_stream();
+            while let Some(item) = stream.next().await {
+                let chunk = item.map_err(|e| network_error(url, e))?;
+                buffer.extend_from_slice(&chunk);
+                report(chunk.len() as i64);
+            }
+            if buffer.len() != expected {
+                return Err(Error::Io(std::io::Error::other(format!(
+                    "Received {} of {} bytes",
+                    buffer.len(),
+                    expected
+                ))));
+            }
+            Ok(())
+        }
+        .await;
+        match result {
+            Ok(()) => return Ok(buffer),
+            // A server that does not honor the range would fail every attempt the same way
+            Err(e) if attempt < MAX_ATTEMPTS && !matches!(e, Error::Http { .. }) => {
+                let delay = retry_delay(attempt, None);
+                debug!(
+                    "Range {}-{} of {} failed ({}); retrying in {} ms",
+                    range.start,
+                    range.end - 1,
+                    url,
+                    e,
+                    delay.as_millis()
+                );
+                report(-(buffer.len() as i64));
+                RETRIES.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Wraps a transport error for `url`.
+pub fn network_error(url: &str, source: reqwest::Error) -> Error {
+    Error::Network {
+        url: url.to_string(),
+        source,
+    }
+}
+
+/// Whether a response with `status` may succeed when repeated.
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// How long to wait before repeating a request that failed on `attempt` (from 1): the
+/// server's `Retry-After` in seconds, if any, otherwise exponential backoff.
+fn retry_delay(attempt: u32, retry_after: Option<&HeaderValue>) -> Duration {
+    retry_after
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| BASE_DELAY * 2u32.pow(attempt - 1))
+        .min(MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transient_statuses() {
+        assert!(is_transient(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_transient(StatusCode::BAD_GATEWAY));
+        assert!(!is_transient(StatusCode::NOT_FOUND));
+        assert!(!is_transient(StatusCode::OK));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1, None), Duration::from_millis(250));
+        assert_eq!(retry_delay(3, None), Duration::from_secs(1));
+        let retry_after = HeaderValue::from_static("2");
+        assert_eq!(retry_delay(1, Some(&retry_after)), Duration::from_secs(2));
+        let retry_after = HeaderValue::from_static("3600");
+        assert_eq!(retry_delay(1, Some(&retry_after)), MAX_DELAY);
+        let retry_after = HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(
+            retry_delay(2, Some(&retry_after)),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn test_token_only_sent_to_github() {
+        assert!(is_github("https://api.github.com/repos/acme/tool/releases"));
+        assert!(!is_github(
+            "https://objects.githubusercontent.com/release-assets/1"
+        ));
+        assert!(is_github(
+            "https://GitHub.com/acme/tool/releases/download/v1/a.tar.gz"
+        ));
+        assert!(!is_github(
+            "https://artifacts.example.com/github.com/tool.tar.gz"
+        ));
+        assert!(!is_github("https://github.com.example.com/tool.tar.gz"));
+        assert!(!is_github("not a url"));
+    }
+
+    /// Serves `content` on localhost, honoring byte ranges, and cuts off the first response
+    /// for the range starting at `break_at` halfway. Returns the URL and the number of
+    /// requests served.
+    async fn serve(content: Vec<u8>, break_at: u64) -> (String, std::sync::Arc<AtomicU64>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/asset.tar.gz", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(AtomicU64::new(0));
+        let served = requests.clone();
+        let broken = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let (content, broken) = (content.clone(), broken.clone());
+                served.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        let n = socket.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    let request = String::from_utf8_lossy(&request).to_lowercase();
+                    let range = request.lines().find_map(|line| {
+                        let (start, end) = line.strip_prefix("range: bytes=")?.split_once('-')?;
+                        Some(start.parse::<usize>().ok()?..end.parse::<usize>().ok()? + 1)
+                    });
+                    let (head, body) = match range {
+                        Some(range) => (
+                            format!(
+                                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\
+                                 Content-Range: bytes {}-{}/{}\r\n",
+                                range.len(),
+                                range.start,
+                                range.end - 1,
+                                content.len()
+                            ),
+                            &content[range],
+                        ),
+                        None => (
+                            format!(
+                                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n",
+                                content.len()
+                            ),
+                            &content[..],
+                        ),
+                    };
+                    let head = head + "Connection: close\r\n\r\n";
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    let cut = request.contains(&format!("range: bytes={}-", break_at))
+                        && !broken.swap(true, Ordering::SeqCst);
+                    let body = if cut { &body[..body.len() / 2] } else { body };
+                    let _ = socket.write_all(body).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_chunked_download_retries_broken_range() {
+        let content: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let (url, requests) = serve(content.clone(), 250).await;
+
+        let mut download = Download::start(&url).await.unwrap();
+        assert_eq!(download.size(), 1000);
+        download.threshold = 100;
+        let positions = std::cell::RefCell::new(Vec::new());
+        let received = download
+            .fetch(|position| positions.borrow_mut().push(position))
+            .await
+            .unwrap();
+        assert_eq!(received, content);
+        assert_eq!(positions.borrow().last(), Some(&1000));
+        assert!(positions.borrow().iter().all(|&position| position <= 1000));
+        // The first request, four ranges, and the broken one again
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_small_download_is_one_stream() {
+        let content = b"small".to_vec();
+        let (url, requests) = serve(content.clone(), 0).await;
+        let download = Download::start(&url).await.unwrap();
+        assert_eq!(download.fetch(|_| {}).await.unwrap(), content);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,246 @@
+//! Translated user-facing messages.
+//!
+//! Messages live in catalogs under `locales/`, one `<lang>.ftl` file per language,
+//! embedded at build time. They are read as a small subset of Fluent, without a Fluent
+//! runtime: each entry is a single line `id = text`, where `{ $name }` is replaced by the
+//! argument of that name, and lines starting with `#` are comments. Multiline messages,
+//! terms, attributes and selectors (such as plural forms) are not supported, so messages
+//! are phrased to read well with any count. The language comes from `--lang`, or else
+//! `LC_ALL`, `LC_MESSAGES` or `LANG`; a message missing from its catalog falls back to
+//! English. Use [`tr!`] to look messages up.
+//!
+//! To ship another language, add `locales/<lang>.ftl` and list it in [`CATALOGS`].
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use tracing::debug;
+
+use crate::env_vars;
+
+/// Language every message exists in.
+pub const FALLBACK: &str = "en";
+
+/// Embedded catalogs, by language tag.
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+];
+
+static LANGUAGE: OnceLock<Catalogs> = OnceLock::new();
+
+/// The catalog of the selected language and the fallback.
+struct Catalogs {
+    language: &'static str,
+    selected: HashMap<&'static str, &'static str>,
+    fallback: HashMap<&'static str, &'static str>,
+}
+
+/// Selects the language of messages: `lang` if given (e.g. from `--lang`), otherwise the
+/// locale environment. Only the first call has an effect; messages looked up before it
+/// use the locale environment.
+pub fn init(lang: Option<&str>) {
+    let requested = lang.map(str::to_string).or_else(locale_from_env);
+    let _ = LANGUAGE.set(load(requested.as_deref()));
+}
+
+/// The language messages are shown in.
+pub fn language() -> &'static str {
+    catalogs().language
+}
+
+/// Whether there is a catalog for `lang` (a language tag or locale).
+pub fn supports(lang: &str) -> bool {
+    resolve(lang).is_some()
+}
+
+/// Languages with a catalog.
+pub fn available() -> impl Iterator<Item = &'static str> {
+    CATALOGS.iter().map(|(language, _)| *language)
+}
+
+fn catalogs() -> &'static Catalogs {
+    LANGUAGE.get_or_init(|| load(locale_from_env().as_deref()))
+}
+
+fn load(requested: Option<&str>) -> Catalogs {
+    let language = requested.and_then(resolve).unwrap_or(FALLBACK);
+    debug!(
+        "Messages in '{}' (requested {:?})",
+        language,
+        requested.unwrap_or("nothing")
+    );
+    let catalog = |language: &str| {
+        CATALOGS
+            .iter()
+            .find(|(tag, _)| *tag == language)
+            .map(|(_, source)| parse(source))
+            .unwrap_or_default()
+    };
+    Catalogs {
+        language,
+        selected: catalog(language),
+        fallback: catalog(FALLBACK),
+    }
+}
+
+/// The first locale set in `LC_ALL`, `LC_MESSAGES` and `LANG`, in that order.
+fn locale_from_env() -> Option<String> {
+    [env_vars::LC_ALL, env_vars::LC_MESSAGES, env_vars::LANG]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// The catalog for a locale such as `de_AT.UTF-8`, `pt-BR` or `de`: an exact match of the
+/// language and region, else of the language alone. `C` and `POSIX` mean English.
+fn resolve(locale: &str) -> Option<&'static str> {
+    let locale = locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-");
+    if locale.eq_ignore_ascii_case("C") || locale.eq_ignore_ascii_case("POSIX") {
+        return Some(FALLBACK);
+    }
+    let language = locale.split('-').next().unwrap_or_default();
+    available()
+        .find(|tag| tag.eq_ignore_ascii_case(&locale))
+        .or_else(|| available().find(|tag| tag.eq_ignore_ascii_case(language)))
+}
+
+/// Parses a catalog: `id = text` lines, with `#` comments and blank lines ignored. Lines
+/// without `=` are not part of the supported subset and are skipped.
+fn parse(source: &'static str) -> HashMap<&'static str, &'static str> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(id, text)| (id.trim(), text.trim()))
+        .collect()
+}
+
+impl Catalogs {
+    fn lookup(&self, id: &str, args: &[(&str, &dyn Display)]) -> String {
+        let Some(text) = self.selected.get(id).or_else(|| self.fallback.get(id)) else {
+            debug!("No message '{}' in any catalog", id);
+            return id.to_string();
+        };
+        format_message(text, args)
+    }
+}
+
+/// The message `id` in the selected language with `args` filled in. An id missing from
+/// every catalog is returned as is, so a typo shows up instead of an empty line.
+pub fn message(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    catalogs().lookup(id, args)
+}
+
+/// The English text of the message `id` (without arguments), for output that must not
+/// depend on the user's language, such as JSON for scripts.
+pub fn english(id: &str) -> &'static str {
+    catalogs().fallback.get(id).copied().unwrap_or_default()
+}
+
+/// Replaces each `{ $name }` in `text` with the argument `name`.
+fn format_message(text: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start + 1..start + end];
+        match placeholder
+            .trim()
+            .strip_prefix('$')
+            .and_then(|name| args.iter().find(|(arg, _)| *arg == name))
+        {
+            Some((_, value)) => out.push_str(&value.to_string()),
+            None => out.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Looks up a translated message: `tr!("id")` or `tr!("id", name = value, ...)`.
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::message($id, &[])
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::message(
+            $id,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        )
+    };
+}
+pub(crate) use tr;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_resolve_locale() {
+        assert_eq!(resolve("de_AT.UTF-8"), Some("de"));
+        assert_eq!(resolve("de"), Some("de"));
+        assert_eq!(resolve("en_US"), Some("en"));
+        assert_eq!(resolve("C.UTF-8"), Some("en"));
+        assert_eq!(resolve("xx_YY"), None);
+    }
+
+    #[test]
+    fn test_format_message() {
+        let path = "a.txt";
+        assert_eq!(
+            format_message("Output written to: { $path }", &[("path", &path)]),
+            "Output written to: a.txt"
+        );
+        assert_eq!(
+            format_message(
+                "{$count} of {$total}, {$unknown}",
+                &[("count", &1), ("total", &2)]
+            ),
+            "1 of 2, {$unknown}"
+        );
+    }
+
+    #[test]
+    fn test_catalogs_are_consistent() {
+        let english = parse(CATALOGS[0].1);
+        assert_eq!(CATALOGS[0].0, FALLBACK);
+        for (language, source) in &CATALOGS[1..] {
+            let translated: HashSet<_> = parse(source).into_keys().collect();
+            let unknown: Vec<_> = translated
+                .iter()
+                .filter(|id| !english.contains_key(*id))
+                .collect();
+            assert!(
+                unknown.is_empty(),
+                "{}: unknown ids {:?}",
+                language,
+                unknown
+            );
+        }
+    }
+
+    #[test]
+    fn test_fallback_to_english() {
+        let catalogs = Catalogs {
+            language: "de",
+            selected: parse("greeting = Hallo { $name }"),
+            fallback: parse("greeting = Hello { $name }\nfarewell = Bye"),
+        };
+        let name = "Ada";
+        assert_eq!(catalogs.lookup("greeting", &[("name", &name)]), "Hallo Ada");
+        assert_eq!(catalogs.lookup("farewell", &[]), "Bye");
+        assert_eq!(catalogs.lookup("no-such-message", &[]), "no-such-message");
+    }
+}
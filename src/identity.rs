@@ -0,0 +1,156 @@
+//! The application's name and the GitHub repository it is released from.
+//!
+//! Both are baked in when `scripts/replace_templates.sh` customizes the template. Until
+//! then they are resolved at runtime, so an uncustomized build still works: the
+//! repository from the `repository` in Cargo.toml or the configuration file, and the
+//! name from the executable.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use tracing::debug;
+
+use crate::constants;
+use crate::error::{Error, Result};
+
+/// Start of a template placeholder, split so that customizing the template leaves it
+/// intact.
+const PLACEHOLDER: &str = concat!("__", "TEMPLATE_");
+
+static APP_NAME: OnceLock<String> = OnceLock::new();
+static REPOSITORY: OnceLock<Option<Repository>> = OnceLock::new();
+
+/// A GitHub repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    pub owner: String,
+    pub name: String,
+}
+
+impl Repository {
+    /// Parses `owner/name`, optionally as a `https://github.com/owner/name` URL.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().trim_end_matches('/');
+        let value = value.strip_suffix(".git").unwrap_or(value);
+        let path = value
+            .strip_prefix("https://github.com/")
+            .or_else(|| value.strip_prefix("http://github.com/"))
+            .unwrap_or(value);
+        let (owner, name) = path.split_once('/')?;
+        let valid = |part: &str| {
+            !part.is_empty()
+                && !part.starts_with(PLACEHOLDER)
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        (valid(owner) && valid(name)).then(|| Repository {
+            owner: owner.to_string(),
+            name: name.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Repository {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
+
+/// The application name: the compile-time name unless it is a template placeholder,
+/// otherwise the file name of `executable`.
+fn resolve_app_name(executable: Option<&Path>) -> String {
+    [constants::APP_NAME, env!("CARGO_PKG_NAME")]
+        .into_iter()
+        .find(|name| !name.starts_with(PLACEHOLDER))
+        .map(String::from)
+        .or_else(|| {
+            executable
+                .and_then(Path::file_stem)
+                .and_then(|stem| stem.to_str())
+                .map(String::from)
+        })
+        .unwrap_or_else(|| String::from(env!("CARGO_PKG_NAME")))
+}
+
+/// The repository from the first source that is not a template placeholder: the
+/// compile-time constants, the `repository` in Cargo.toml, then `configured`.
+fn resolve_repository(configured: Option<&str>) -> Option<Repository> {
+    let compiled = format!("{}/{}", constants::GITHUB_OWNER, constants::GITHUB_REPO);
+    [
+        Some(compiled.as_str()),
+        option_env!("CARGO_PKG_REPOSITORY"),
+        configured,
+    ]
+    .iter()
+    .flatten()
+    .find_map(|source| Repository::parse(source))
+}
+
+/// Resolves the repository with `configured` (the `repository` configuration key or
+/// its environment variable) as the fallback; later calls have no effect.
+pub fn init(configured: Option<&str>) {
+    let repository = REPOSITORY.get_or_init(|| resolve_repository(configured));
+    debug!(
+        "Application '{}', repository {}",
+        app_name(),
+        repository
+            .as_ref()
+            .map_or_else(|| String::from("(unknown)"), Repository::to_string)
+    );
+}
+
+/// Name of the application, as used for release assets, plugins and the keyring.
+pub fn app_name() -> &'static str {
+    APP_NAME.get_or_init(|| resolve_app_name(std::env::current_exe().ok().as_deref()))
+}
+
+/// Repository releases are published to.
+pub fn repository() -> Result<&'static Repository> {
+    let repository = REPOSITORY.get_or_init(|| resolve_repository(None));
+    repository.as_ref().ok_or_else(|| {
+        Error::Other(String::from(
+            "No GitHub repository configured; set `repository` (owner/name) in the configuration file or customize the template with scripts/replace_templates.sh",
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repository() {
+        let expected = Some(Repository {
+            owner: String::from("acme"),
+            name: String::from("tool"),
+        });
+        assert_eq!(Repository::parse("acme/tool"), expected);
+        assert_eq!(
+            Repository::parse("https://github.com/acme/tool.git"),
+            expected
+        );
+        assert_eq!(Repository::parse("https://github.com/acme/tool/"), expected);
+        assert_eq!(Repository::parse("acme"), None);
+        assert_eq!(Repository::parse("acme/tool/extra"), None);
+        let placeholder = format!("{}REPO_OWNER__/{}REPO__", PLACEHOLDER, PLACEHOLDER);
+        assert_eq!(Repository::parse(&placeholder), None);
+    }
+
+    #[test]
+    fn test_resolve_falls_back_at_runtime() {
+        if constants::GITHUB_OWNER.starts_with(PLACEHOLDER) {
+            assert_eq!(
+                resolve_repository(Some("acme/tool")),
+                Repository::parse("acme/tool")
+            );
+        }
+        let app_name = resolve_app_name(Some(Path::new("/usr/bin/tool")));
+        if constants::APP_NAME.starts_with(PLACEHOLDER) {
+            assert_eq!(app_name, "tool");
+        } else {
+            assert_eq!(app_name, constants::APP_NAME);
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! Replacing the installed executable with a downloaded build.
+//!
+//! Filesystem access goes through [`FileSystem`] so both replacement strategies and their
+//! failure modes (permission denied, cross-device moves, locked files) can be exercised in
+//! tests on any OS.
+//!
+//! Windows does not let a running executable be deleted, so after an upgrade the previous
+//! binary (moved aside to `.old`) is left to a detached helper that deletes it once this
+//! process has exited; should that fail too, the next start removes it.
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+
+/// Windows `ERROR_SHARING_VIOLATION`: the file is open in another process.
+const ERROR_SHARING_VIOLATION: i32 = 32;
+
+/// Filesystem operations used while installing.
+pub trait FileSystem {
+    fn exists(&self, path: &Path) -> bool;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// Arranges for `path` to be deleted once this process has exited.
+    fn schedule_removal(&self, path: &Path) -> io::Result<()>;
+}
+
+/// The real filesystem.
+pub struct RealFs;
+
+impl FileSystem for RealFs {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    #[cfg(windows)]
+    fn schedule_removal(&self, path: &Path) -> io::Result<()> {
+        use std::os::windows::process::CommandExt;
+        use std::process::{Command, Stdio};
+
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        // Retries every second for up to two minutes: the delete fails until this process
+        // has exited and released its image
+        let path = path.display();
+        let script = format!(
+            "for /L %i in (1,1,120) do (del /F /Q \"{path}\" 2>nul & \
+             if not exist \"{path}\" exit /b 0 & ping -n 2 127.0.0.1 >nul)"
+        );
+        Command::new("cmd")
+            .arg("/C")
+            .raw_arg(script)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .creation_flags(DETACHED_PROCESS | CREATE_NO_WINDOW)
+            .spawn()
+            .map(drop)
+    }
+
+    #[cfg(not(windows))]
+    fn schedule_removal(&self, path: &Path) -> io::Result<()> {
+        // Running executables can be deleted here
+        fs::remove_file(path)
+    }
+}
+
+/// How the running executable is replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Rename the new binary over the old one (Unix keeps the running image alive)
+    Replace,
+    /// Move the running executable aside first, since Windows refuses to overwrite it
+    /// but allows renaming it
+    MoveAside,
+}
+
+impl Strategy {
+    /// The strategy for the platform this binary was built for.
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Strategy::MoveAside
+        } else {
+            Strategy::Replace
+        }
+    }
+}
+
+/// Where the previous executable is kept while [`Strategy::MoveAside`] installs.
+pub fn backup_path(target: &Path) -> PathBuf {
+    target.with_extension("old")
+}
+
+/// Installs `new` as `target`.
+///
+/// On failure `target` is left as it was: with [`Strategy::MoveAside`] the previous
+/// executable is moved back into place.
+pub fn install(fs: &dyn FileSystem, new: &Path, target: &Path, strategy: Strategy) -> Result<()> {
+    if !fs.exists(new) {
+        return Err(Error::Other(format!(
+            "Downloaded binary not found at: {}",
+            new.display()
+        )));
+    }
+    debug!("Replacing {} with {}", target.display(), new.display());
+
+    match strategy {
+        Strategy::Replace => move_into_place(fs, new, target),
+        Strategy::MoveAside => {
+            let backup = backup_path(target);
+            // A backup left by an earlier upgrade can be removed once that binary exited
+            if fs.exists(&backup) {
+                fs.remove_file(&backup).ok();
+            }
+            fs.rename(target, &backup)
+                .map_err(|e| install_error(e, target))?;
+
+            if let Err(e) = move_into_place(fs, new, target) {
+                if let Err(restore) = fs.rename(&backup, target) {
+                    warn!(
+                        "Could not restore {} from {}: {}",
+                        target.display(),
+                        backup.display(),
+                        restore
+                    );
+                }
+                return Err(e);
+            }
+
+            // Fails while the old binary is still running, as it is when upgrading itself
+            if let Err(e) = fs.remove_file(&backup) {
+                debug!("Cannot remove {} yet: {}", backup.display(), e);
+                match fs.schedule_removal(&backup) {
+                    Ok(()) => debug!("Scheduled removal of {}", backup.display()),
+                    Err(e) => debug!(
+                        "Could not schedule removal of {} ({}); the next start removes it",
+                        backup.display(),
+                        e
+                    ),
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Removes the backup an earlier [`Strategy::MoveAside`] install left next to `exe` (the
+/// running executable), if there is one. Returns whether one was removed.
+pub fn remove_leftover_backup(fs: &dyn FileSystem, exe: &Path) -> bool {
+    let backup = backup_path(exe);
+    if !fs.exists(&backup) {
+        return false;
+    }
+    match fs.remove_file(&backup) {
+        Ok(()) => {
+            debug!("Removed {} left by the last upgrade", backup.display());
+            true
+        }
+        Err(e) => {
+            // Still running, e.g. a long command started before the upgrade
+            debug!("Cannot remove {} yet: {}", backup.display(), e);
+            false
+        }
+    }
+}
+
+/// Gives `new` the permissions, owner and extended attributes of `current` (the installed
+/// executable), so that it runs even when the archive it came from lacked the execute bit.
+/// Only failing to set the permissions is an error: the owner can only be changed with
+/// privileges, and not every filesystem keeps extended attributes.
+#[cfg(unix)]
+pub fn match_metadata(current: &Path, new: &Path) -> Result<()> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let metadata = |path: &Path| {
+        fs::metadata(path).map_err(|source| Error::File {
+            action: "read metadata of",
+            path: path.to_path_buf(),
+            source,
+        })
+    };
+    let (current_meta, new_meta) = (metadata(current)?, metadata(new)?);
+
+    // The owner may always run it, whatever the current mode says
+    let mode = (current_meta.mode() & 0o7777) | 0o100;
+    fs::set_permissions(new, fs::Permissions::from_mode(mode)).map_err(|source| Error::File {
+        action: "set permissions of",
+        path: new.to_path_buf(),
+        source,
+    })?;
+    debug!("Set mode of {} to {:o}", new.display(), mode);
+
+    if (new_meta.uid(), new_meta.gid()) != (current_meta.uid(), current_meta.gid()) {
+        if let Err(e) =
+            std::os::unix::fs::chown(new, Some(current_meta.uid()), Some(current_meta.gid()))
+        {
+            debug!("Cannot change the owner of {}: {}", new.display(), e);
+        }
+    }
+
+    match crate::xattr::copy_all(current, new) {
+        Ok(names) if !names.is_empty() => debug!("Copied extended attributes {:?}", names),
+        Ok(_) => {}
+        Err(e) => debug!(
+            "Cannot read extended attributes of {}: {}",
+            current.display(),
+            e
+        ),
+    }
+    Ok(())
+}
+
+/// Moves `new` to `target`, copying when they are on different filesystems.
+fn move_into_place(fs: &dyn FileSystem, new: &Path, target: &Path) -> Result<()> {
+    match fs.rename(new, target) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            // Copy next to the target first so the final step is still an atomic rename
+            let staged = target.with_extension("new");
+            debug!(
+                "{} is on another filesystem; staging at {}",
+                new.display(),
+                staged.display()
+            );
+            fs.copy(new, &staged)
+                .map_err(|e| install_error(e, target))?;
+            if let Err(e) = fs.rename(&staged, target) {
+                fs.remove_file(&staged).ok();
+                return Err(install_error(e, target));
+            }
+            fs.remove_file(new).ok();
+            Ok(())
+        }
+        Err(e) => Err(install_error(e, target)),
+    }
+}
+
+fn install_error(e: io::Error, target: &Path) -> Error {
+    if e.kind() == ErrorKind::PermissionDenied {
+        return Error::PermissionDenied {
+            path: target.to_path_buf(),
+        };
+    }
+    if e.kind() == ErrorKind::ResourceBusy || e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) {
+        return Error::Other(format!(
+            "{} is in use by another process; close it and try again",
+            target.display()
+        ));
+    }
+    Error::Io(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use tempfile::TempDir;
+
+    /// Real filesystem with failures injected for chosen operations.
+    #[derive(Default)]
+    struct FaultyFs {
+        /// (operation, path the operation targets, error kind); each fault fires once
+        faults: RefCell<Vec<(&'static str, PathBuf, ErrorKind)>>,
+        /// Paths passed to `schedule_removal`, which deletes nothing
+        scheduled: RefCell<Vec<PathBuf>>,
+    }
+
+    impl FaultyFs {
+        fn fail(self, operation: &'static str, path: &Path, kind: ErrorKind) -> Self {
+            self.faults
+                .borrow_mut()
+                .push((operation, path.to_path_buf(), kind));
+            self
+        }
+
+        fn check(&self, operation: &str, path: &Path) -> io::Result<()> {
+            let mut faults = self.faults.borrow_mut();
+            match faults
+                .iter()
+                .position(|(op, p, _)| *op == operation && p == path)
+            {
+                Some(i) => Err(io::Error::from(faults.remove(i).2)),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl FileSystem for FaultyFs {
+        fn exists(&self, path: &Path) -> bool {
+            path.exists()
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.check("rename", to)?;
+            fs::rename(from, to)
+        }
+
+        fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+            self.check("copy", to)?;
+            fs::copy(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.check("remove", path)?;
+            fs::remove_file(path)
+        }
+
+        fn schedule_removal(&self, path: &Path) -> io::Result<()> {
+            self.check("schedule", path)?;
+            self.scheduled.borrow_mut().push(path.to_path_buf());
+            Ok(())
+        }
+    }
+
+    /// A fake installed binary and a downloaded update in separate directories.
+    struct Setup {
+        _dir: TempDir,
+        new: PathBuf,
+        target: PathBuf,
+    }
+
+    fn setup() -> Setup {
+        let dir = TempDir::new().unwrap();
+        let cache = dir.path().join("cache");
+        let bin = dir.path().join("bin");
+        fs::create_dir_all(&cache).unwrap();
+        fs::create_dir_all(&bin).unwrap();
+        let new = cache.join("app");
+        let target = bin.join("app");
+        fs::write(&new, "v2").unwrap();
+        fs::write(&target, "v1").unwrap();
+        Setup {
+            _dir: dir,
+            new,
+            target,
+        }
+    }
+
+    fn contents(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_both_strategies_replace_binary() {
+        for strategy in [Strategy::Replace, Strategy::MoveAside] {
+            let s = setup();
+            install(&RealFs, &s.new, &s.target, strategy).unwrap();
+            assert_eq!(contents(&s.target), "v2", "{:?}", strategy);
+            assert!(!s.new.exists());
+            assert!(!backup_path(&s.target).exists());
+        }
+    }
+
+    #[test]
+    fn test_missing_download() {
+        let s = setup();
+        fs::remove_file(&s.new).unwrap();
+        let message = install(&RealFs, &s.new, &s.target, Strategy::Replace)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.contains("Downloaded binary not found"),
+            "{}",
+            message
+        );
+        assert_eq!(contents(&s.target), "v1");
+    }
+
+    #[test]
+    fn test_permission_denied_keeps_old_binary() {
+        for strategy in [Strategy::Replace, Strategy::MoveAside] {
+            let s = setup();
+            let fs = FaultyFs::default().fail("rename", &s.target, ErrorKind::PermissionDenied);
+            let message = install(&fs, &s.new, &s.target, strategy)
+                .unwrap_err()
+                .to_string();
+            assert!(message.starts_with("Permission denied"), "{}", message);
+            assert_eq!(contents(&s.target), "v1", "{:?}", strategy);
+            assert!(!backup_path(&s.target).exists());
+        }
+    }
+
+    #[test]
+    fn test_cross_device_copies_then_renames() {
+        for strategy in [Strategy::Replace, Strategy::MoveAside] {
+            let s = setup();
+            let fs = FaultyFs::default().fail("rename", &s.target, ErrorKind::CrossesDevices);
+            // The first rename onto the target is moving the update in, for both strategies
+            install(&fs, &s.new, &s.target, strategy).unwrap();
+            assert_eq!(contents(&s.target), "v2", "{:?}", strategy);
+            assert!(!s.new.exists());
+            assert!(!s.target.with_extension("new").exists());
+        }
+    }
+
+    #[test]
+    fn test_cross_device_copy_failure_keeps_old_binary() {
+        let s = setup();
+        let fs = FaultyFs::default()
+            .fail("rename", &s.target, ErrorKind::CrossesDevices)
+            .fail(
+                "copy",
+                &s.target.with_extension("new"),
+                ErrorKind::StorageFull,
+            );
+        assert!(install(&fs, &s.new, &s.target, Strategy::Replace).is_err());
+        assert_eq!(contents(&s.target), "v1");
+        assert_eq!(contents(&s.new), "v2");
+    }
+
+    #[test]
+    fn test_locked_binary_on_windows_path() {
+        let s = setup();
+        let backup = backup_path(&s.target);
+        let fs = FaultyFs::default().fail("rename", &backup, ErrorKind::ResourceBusy);
+        let message = install(&fs, &s.new, &s.target, Strategy::MoveAside)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("in use by another process"), "{}", message);
+        assert_eq!(contents(&s.target), "v1");
+    }
+
+    #[test]
+    fn test_move_aside_restores_backup_when_install_fails() {
+        let s = setup();
+        // Moving aside (target -> .old) succeeds, then moving the update in fails; the
+        // fault fires once, so the restore rename goes through
+        let fs = FaultyFs::default().fail("rename", &s.target, ErrorKind::Other);
+        assert!(install(&fs, &s.new, &s.target, Strategy::MoveAside).is_err());
+        assert_eq!(contents(&s.target), "v1");
+        assert!(!backup_path(&s.target).exists());
+        assert_eq!(contents(&s.new), "v2");
+    }
+
+    #[test]
+    fn test_stale_and_busy_backups() {
+        let s = setup();
+        let backup = backup_path(&s.target);
+        fs::write(&backup, "v0").unwrap();
+        // Neither the stale backup nor the one of the running binary can be deleted, as on
+        // Windows while those binaries are still running; neither fails the install
+        let fs = FaultyFs::default()
+            .fail("remove", &backup, ErrorKind::Other)
+            .fail("remove", &backup, ErrorKind::Other);
+        install(&fs, &s.new, &s.target, Strategy::MoveAside).unwrap();
+        assert_eq!(contents(&s.target), "v2");
+        assert_eq!(contents(&backup), "v1");
+        assert_eq!(*fs.scheduled.borrow(), vec![backup]);
+    }
+
+    #[test]
+    fn test_leftover_backup_removed_on_next_start() {
+        let s = setup();
+        let backup = backup_path(&s.target);
+        // Neither deleting nor scheduling works, as when the helper cannot be started
+        let fs = FaultyFs::default()
+            .fail("remove", &backup, ErrorKind::PermissionDenied)
+            .fail("schedule", &backup, ErrorKind::Other);
+        install(&fs, &s.new, &s.target, Strategy::MoveAside).unwrap();
+        assert_eq!(contents(&backup), "v1");
+
+        // The old binary is still running at first, then it has exited
+        let fs = FaultyFs::default().fail("remove", &backup, ErrorKind::PermissionDenied);
+        assert!(!remove_leftover_backup(&fs, &s.target));
+        assert!(remove_leftover_backup(&fs, &s.target));
+        assert!(!backup.exists());
+        assert!(!remove_leftover_backup(&fs, &s.target));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_match_metadata_makes_new_binary_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let s = setup();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        // Extracted from an archive that lost the execute bits
+        fs::set_permissions(&s.target, fs::Permissions::from_mode(0o750)).unwrap();
+        fs::set_permissions(&s.new, fs::Permissions::from_mode(0o644)).unwrap();
+        match_metadata(&s.target, &s.new).unwrap();
+        assert_eq!(mode(&s.new), 0o750);
+
+        // Never leaves the owner unable to run it
+        fs::set_permissions(&s.target, fs::Permissions::from_mode(0o644)).unwrap();
+        match_metadata(&s.target, &s.new).unwrap();
+        assert_eq!(mode(&s.new), 0o744);
+    }
+}
@@ -0,0 +1,237 @@
+//! Bounded work queue for running many independent jobs at once.
+//!
+//! Workers take items from the iterator only as they become free, so no more than
+//! `workers` jobs are in flight and a lazily produced list is never read further ahead
+//! than that. Each job's result or error is collected with the index of its item, and the
+//! queue stops taking items once cancellation was requested or, when failing fast, after
+//! the first failure. Progress is aggregated on the calling thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use crate::cancel;
+use crate::error::{Error, Result};
+
+/// Jobs finished so far, reported after each one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Jobs finished, including those that failed
+    pub done: usize,
+    pub failed: usize,
+    /// Number of items, if the iterator knows it
+    pub total: Option<usize>,
+}
+
+/// Errors of jobs by index of their item.
+pub type Failures = Vec<(usize, Error)>;
+
+/// What a queue run produced.
+#[derive(Debug)]
+pub struct Outcome<R> {
+    /// Results of the jobs that succeeded, by index of their item, in item order
+    pub results: Vec<(usize, R)>,
+    /// Errors of the jobs that failed, by index of their item, in item order
+    pub errors: Failures,
+    /// Whether items were left untaken, after cancellation or a failure when failing fast
+    pub stopped: bool,
+}
+
+impl<R> Outcome<R> {
+    /// The results in item order and the errors of the items that failed, or
+    /// [`Error::Cancelled`] if cancellation cut the run short.
+    pub fn collect(self) -> Result<(Vec<R>, Failures)> {
+        if cancel::is_cancelled() && (self.stopped || !self.errors.is_empty()) {
+            return Err(Error::Cancelled);
+        }
+        let results = self.results.into_iter().map(|(_, result)| result).collect();
+        Ok((results, self.errors))
+    }
+
+    /// The results in item order, or the error of the first item that failed
+    /// ([`Error::Cancelled`] if cancellation cut the run short).
+    pub fn into_result(self) -> Result<Vec<R>> {
+        let (results, errors) = self.collect()?;
+        match errors.into_iter().next() {
+            Some((_, error)) => Err(error),
+            None => Ok(results),
+        }
+    }
+}
+
+/// Runs jobs on up to a given number of threads.
+#[derive(Debug, Clone, Copy)]
+pub struct Queue {
+    workers: usize,
+    fail_fast: bool,
+}
+
+impl Queue {
+    /// A queue running up to `workers` jobs at once (at least one).
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+            fail_fast: false,
+        }
+    }
+
+    /// With `fail_fast`, stops taking items after the first job that fails; those running
+    /// finish.
+    pub fn fail_fast(self, fail_fast: bool) -> Self {
+        Self { fail_fast, ..self }
+    }
+
+    /// Runs `work` on each of `items`, calling `progress` on this thread after every job.
+    /// With a single worker the jobs run on this thread, one after the other.
+    pub fn run<I, R, F, P>(&self, items: I, work: F, mut progress: P) -> Outcome<R>
+    where
+        I: IntoIterator,
+        I::IntoIter: Send,
+        R: Send,
+        F: Fn(I::Item) -> Result<R> + Sync,
+        P: FnMut(Progress),
+    {
+        let items = items.into_iter();
+        let total = match items.size_hint() {
+            (low, Some(high)) if low == high => Some(low),
+            _ => None,
+        };
+        let workers = self.workers.min(total.unwrap_or(usize::MAX)).max(1);
+        let items = Mutex::new(items.enumerate());
+        let stop = AtomicBool::new(false);
+        let mut outcome = Outcome {
+            results: Vec::new(),
+            errors: Vec::new(),
+            stopped: false,
+        };
+        let mut current = Progress {
+            done: 0,
+            failed: 0,
+            total,
+        };
+        let mut finish = |index: usize, result: Result<R>| {
+            current.done += 1;
+            match result {
+                Ok(result) => outcome.results.push((index, result)),
+                Err(error) => {
+                    current.failed += 1;
+                    outcome.errors.push((index, error));
+                    if self.fail_fast {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+            progress(current);
+        };
+        let next = || {
+            if stop.load(Ordering::Relaxed) || cancel::is_cancelled() {
+                return None;
+            }
+            items.lock().unwrap_or_else(|e| e.into_inner()).next()
+        };
+
+        if workers == 1 {
+            while let Some((index, item)) = next() {
+                finish(index, work(item));
+            }
+        } else {
+            let (sender, receiver) = mpsc::channel();
+            thread::scope(|scope| {
+                for _ in 0..workers {
+                    let (sender, next, work) = (sender.clone(), &next, &work);
+                    // The item is taken under the lock, the job runs outside of it
+                    scope.spawn(move || {
+                        while let Some((index, item)) = next() {
+                            if sender.send((index, work(item))).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+                drop(sender);
+                for (index, result) in receiver {
+                    finish(index, result);
+                }
+            });
+        }
+
+        outcome.stopped = items
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .next()
+            .is_some();
+        outcome.results.sort_by_key(|(index, _)| *index);
+        outcome.errors.sort_by_key(|(index, _)| *index);
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bounded_and_ordered() {
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let mut reported = Vec::new();
+        let outcome = Queue::new(3).run(
+            0..20u64,
+            |item| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                // Later items finish first
+                thread::sleep(Duration::from_millis(20 - item));
+                running.fetch_sub(1, Ordering::SeqCst);
+                match item % 7 {
+                    3 => Err(Error::Other(format!("item {}", item))),
+                    _ => Ok(item * 2),
+                }
+            },
+            |progress| reported.push(progress),
+        );
+
+        assert!(most.load(Ordering::SeqCst) <= 3);
+        assert!(!outcome.stopped);
+        let indices: Vec<usize> = outcome.errors.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, [3, 10, 17]);
+        assert_eq!(outcome.results.len(), 17);
+        assert!(outcome.results.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(outcome.results[3], (4, 8));
+        assert_eq!(reported.len(), 20);
+        assert_eq!(
+            reported.last(),
+            Some(&Progress {
+                done: 20,
+                failed: 3,
+                total: Some(20)
+            })
+        );
+        assert!(matches!(
+            outcome.into_result(),
+            Err(Error::Other(message)) if message == "item 3"
+        ));
+    }
+
+    #[test]
+    fn test_fail_fast_stops_taking_items() {
+        let taken = AtomicUsize::new(0);
+        let outcome = Queue::new(1).fail_fast(true).run(
+            0..10,
+            |item| {
+                taken.fetch_add(1, Ordering::SeqCst);
+                match item {
+                    2 => Err(Error::Other(String::from("boom"))),
+                    _ => Ok(item),
+                }
+            },
+            |_| {},
+        );
+        assert_eq!(taken.load(Ordering::SeqCst), 3);
+        assert!(outcome.stopped);
+        assert_eq!(outcome.results.len(), 2);
+        assert_eq!(outcome.errors.len(), 1);
+    }
+}
@@ -0,0 +1,203 @@
+//! A subset of JSONPath for extracting values from JSON and YAML documents.
+//!
+//! Supported: the root `$`, child keys (`.name`, `['name']`), array indexes (`[0]`,
+//! `[-1]` from the end), wildcards (`.*`, `[*]`) and recursive descent (`..name`, `..*`).
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// One step of a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    /// Applies the inner step to the value and everything below it
+    Descendants(Box<Step>),
+}
+
+/// A parsed JSONPath expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self> {
+        let invalid =
+            |reason: &str| Error::Other(format!("Invalid JSONPath '{}': {}", path, reason));
+        let mut rest = path
+            .trim()
+            .strip_prefix('$')
+            .ok_or_else(|| invalid("must start with '$'"))?;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            let (step, remaining) = if let Some(after) = rest.strip_prefix("..") {
+                let (step, remaining) = match after.strip_prefix('[') {
+                    Some(bracket) => {
+                        parse_bracket(bracket).ok_or_else(|| invalid("bad '[...]'"))?
+                    }
+                    None => {
+                        parse_name(after).ok_or_else(|| invalid("expected a name after '..'"))?
+                    }
+                };
+                (Step::Descendants(Box::new(step)), remaining)
+            } else if let Some(after) = rest.strip_prefix('.') {
+                parse_name(after).ok_or_else(|| invalid("expected a name after '.'"))?
+            } else if let Some(after) = rest.strip_prefix('[') {
+                parse_bracket(after).ok_or_else(|| invalid("bad '[...]'"))?
+            } else {
+                return Err(invalid("expected '.' or '['"));
+            };
+            steps.push(step);
+            rest = remaining;
+        }
+        Ok(Self { steps })
+    }
+
+    /// The values `root` has at this path, in document order.
+    pub fn select<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![root];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for value in current {
+                apply(step, value, &mut next);
+            }
+            current = next;
+        }
+        current
+    }
+}
+
+/// Parses the documents of `content`: JSON values one after the other (as in NDJSON), or
+/// with `yaml`, `---`-separated YAML documents.
+pub fn parse_documents(content: &str, yaml: bool) -> Result<Vec<Value>> {
+    if yaml {
+        serde_yaml::Deserializer::from_str(content)
+            .map(|document| {
+                Value::deserialize(document).map_err(|e| Error::InvalidInput {
+                    format: "YAML",
+                    message: e.to_string(),
+                })
+            })
+            .collect()
+    } else {
+        serde_json::Deserializer::from_str(content)
+            .into_iter()
+            .map(|document| {
+                document.map_err(|e| Error::InvalidInput {
+                    format: "JSON",
+                    message: e.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Parses a `name` or `*` step, returning it and the rest of the path.
+fn parse_name(path: &str) -> Option<(Step, &str)> {
+    let end = path.find(['.', '[']).unwrap_or(path.len());
+    let (name, rest) = path.split_at(end);
+    match name {
+        "" => None,
+        "*" => Some((Step::Wildcard, rest)),
+        _ => Some((Step::Key(name.to_string()), rest)),
+    }
+}
+
+/// Parses the inside of `[...]` (after the `[`), returning the step and the rest of the path.
+fn parse_bracket(path: &str) -> Option<(Step, &str)> {
+    let quote = path.chars().next().filter(|c| *c == '\'' || *c == '"');
+    if let Some(quote) = quote {
+        let inner = &path[1..];
+        let end = inner.find(quote)?;
+        let rest = inner[end + 1..].strip_prefix(']')?;
+        return Some((Step::Key(inner[..end].to_string()), rest));
+    }
+    let (inner, rest) = path.split_once(']')?;
+    match inner.trim() {
+        "*" => Some((Step::Wildcard, rest)),
+        index => Some((Step::Index(index.parse().ok()?), rest)),
+    }
+}
+
+fn apply<'a>(step: &Step, value: &'a Value, out: &mut Vec<&'a Value>) {
+    match (step, value) {
+        (Step::Key(key), Value::Object(map)) => out.extend(map.get(key)),
+        (Step::Index(index), Value::Array(items)) => {
+            let index = if *index < 0 {
+                items.len().checked_sub(index.unsigned_abs() as usize)
+            } else {
+                Some(*index as usize)
+            };
+            out.extend(index.and_then(|index| items.get(index)));
+        }
+        (Step::Wildcard, Value::Object(map)) => out.extend(map.values()),
+        (Step::Wildcard, Value::Array(items)) => out.extend(items),
+        (Step::Descendants(step), value) => {
+            apply(step, value, out);
+            let children: Box<dyn Iterator<Item = &Value>> = match value {
+                Value::Object(map) => Box::new(map.values()),
+                Value::Array(items) => Box::new(items.iter()),
+                _ => Box::new(std::iter::empty()),
+            };
+            for child in children {
+                apply(&Step::Descendants(step.clone()), child, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn select(path: &str, value: &Value) -> Vec<Value> {
+        JsonPath::parse(path)
+            .unwrap()
+            .select(value)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_select() {
+        let doc = json!({
+            "name": "app",
+            "items": [{"id": 1, "tags": ["a"]}, {"id": 2}, {"id": 3, "odd key": true}],
+            "owner": {"id": 9}
+        });
+        assert_eq!(select("$", &doc), vec![doc.clone()]);
+        assert_eq!(select("$.name", &doc), vec![json!("app")]);
+        assert_eq!(
+            select("$.items[*].id", &doc),
+            vec![json!(1), json!(2), json!(3)]
+        );
+        assert_eq!(select("$.items[-1]['odd key']", &doc), vec![json!(true)]);
+        assert_eq!(select("$.items[0].tags[0]", &doc), vec![json!("a")]);
+        assert_eq!(select("$..id", &doc).len(), 4);
+        assert!(select("$.missing[5]", &doc).is_empty());
+        assert!(select("$.items[7]", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_parse_documents() {
+        let documents = parse_documents("a: 1\n---\n- x\n", true).unwrap();
+        assert_eq!(documents, vec![json!({"a": 1}), json!(["x"])]);
+        let documents = parse_documents("{\"a\": 1}\n2\n", false).unwrap();
+        assert_eq!(documents, vec![json!({"a": 1}), json!(2)]);
+        assert!(parse_documents("{", false).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for path in ["name", "$.", "$[x]", "$['open", "$..", "$name"] {
+            assert!(JsonPath::parse(path).is_err(), "{}", path);
+        }
+    }
+}
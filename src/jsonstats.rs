@@ -0,0 +1,356 @@
+//! Structural statistics for JSON, NDJSON and YAML data, gathered while parsing so that
+//! no document is ever built in memory.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::hll::HyperLogLog;
+
+/// Shape of JSON data.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonStats {
+    /// Top-level values: one for a JSON document, one per line for NDJSON
+    pub documents: usize,
+    /// Deepest nesting of objects and arrays (a top-level scalar has depth 0)
+    pub max_depth: usize,
+    pub objects: usize,
+    pub arrays: usize,
+    /// Members of all objects
+    pub keys: usize,
+    /// Approximate number of distinct key names
+    pub distinct_keys: u64,
+    /// Elements of the longest array
+    pub max_array_len: usize,
+    /// Types found at each path, e.g. `$.items[*].id`, with array elements merged
+    /// (`--mode structured` only)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub types: BTreeMap<String, BTreeSet<&'static str>>,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    max_depth: usize,
+    objects: usize,
+    arrays: usize,
+    keys: usize,
+    key_names: HyperLogLog,
+    max_array_len: usize,
+    /// Types by path, if collected
+    types: Option<BTreeMap<String, BTreeSet<&'static str>>>,
+    /// Path of the value being visited, while types are collected
+    path: String,
+}
+
+impl Accumulator {
+    fn new(types: bool) -> Self {
+        Self {
+            types: types.then(BTreeMap::new),
+            path: String::from("$"),
+            ..Self::default()
+        }
+    }
+
+    fn record(&mut self, kind: &'static str) {
+        if let Some(types) = &mut self.types {
+            match types.get_mut(&self.path) {
+                Some(kinds) => {
+                    kinds.insert(kind);
+                }
+                None => {
+                    types.insert(self.path.clone(), BTreeSet::from([kind]));
+                }
+            }
+        }
+    }
+
+    fn finish(self, documents: usize) -> JsonStats {
+        JsonStats {
+            documents,
+            max_depth: self.max_depth,
+            objects: self.objects,
+            arrays: self.arrays,
+            keys: self.keys,
+            distinct_keys: self.key_names.estimate(),
+            max_array_len: self.max_array_len,
+            types: self.types.unwrap_or_default(),
+        }
+    }
+}
+
+/// Parses the JSON values in `content` one after the other (so a single document as well
+/// as whitespace- or newline-separated values), counting what they contain.
+pub fn analyze(content: &str) -> Result<JsonStats> {
+    analyze_json(content, false)
+}
+
+/// Like [`analyze`], for JSON or (with `yaml`) YAML documents, and also summarizes the
+/// types found at each path.
+pub fn analyze_structured(content: &str, yaml: bool) -> Result<JsonStats> {
+    if !yaml {
+        return analyze_json(content, true);
+    }
+    let mut acc = Accumulator::new(true);
+    let mut documents = 0;
+    for document in serde_yaml::Deserializer::from_str(content) {
+        Value {
+            acc: &mut acc,
+            depth: 0,
+        }
+        .deserialize(document)
+        .map_err(|e| Error::InvalidInput {
+            format: "YAML",
+            message: e.to_string(),
+        })?;
+        documents += 1;
+    }
+    Ok(acc.finish(documents))
+}
+
+fn analyze_json(content: &str, types: bool) -> Result<JsonStats> {
+    let mut acc = Accumulator::new(types);
+    let mut documents = 0;
+    let mut deserializer = serde_json::Deserializer::from_str(content);
+    // Fails while anything but whitespace is left
+    while deserializer.end().is_err() {
+        Value {
+            acc: &mut acc,
+            depth: 0,
+        }
+        .deserialize(&mut deserializer)
+        .map_err(|e| Error::InvalidInput {
+            format: "JSON",
+            message: e.to_string(),
+        })?;
+        documents += 1;
+    }
+    Ok(acc.finish(documents))
+}
+
+/// Visits a value at `depth` (the number of containers around it) and everything in it.
+struct Value<'a> {
+    acc: &'a mut Accumulator,
+    depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for Value<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Value<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> std::result::Result<(), E> {
+        self.acc.record("boolean");
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> std::result::Result<(), E> {
+        self.acc.record("integer");
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> std::result::Result<(), E> {
+        self.acc.record("integer");
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> std::result::Result<(), E> {
+        self.acc.record("number");
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> std::result::Result<(), E> {
+        self.acc.record("string");
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<(), E> {
+        self.acc.record("null");
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        let Value { acc, depth } = self;
+        acc.record("array");
+        acc.arrays += 1;
+        acc.max_depth = acc.max_depth.max(depth + 1);
+        let parent = acc.path.len();
+        if acc.types.is_some() {
+            acc.path.push_str("[*]");
+        }
+        let mut len = 0;
+        while seq
+            .next_element_seed(Value {
+                acc: &mut *acc,
+                depth: depth + 1,
+            })?
+            .is_some()
+        {
+            len += 1;
+        }
+        acc.path.truncate(parent);
+        acc.max_array_len = acc.max_array_len.max(len);
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        let Value { acc, depth } = self;
+        acc.record("object");
+        acc.objects += 1;
+        acc.max_depth = acc.max_depth.max(depth + 1);
+        let parent = acc.path.len();
+        while map.next_key_seed(Key { acc: &mut *acc })?.is_some() {
+            acc.keys += 1;
+            map.next_value_seed(Value {
+                acc: &mut *acc,
+                depth: depth + 1,
+            })?;
+            acc.path.truncate(parent);
+        }
+        Ok(())
+    }
+}
+
+/// Records an object key without allocating it (unless types are collected, when it is
+/// appended to the path of the value that follows).
+struct Key<'a> {
+    acc: &'a mut Accumulator,
+}
+
+impl Key<'_> {
+    fn record(self, key: &str) {
+        self.acc.key_names.insert(key);
+        if self.acc.types.is_some() {
+            self.acc.path.push('.');
+            self.acc.path.push_str(key);
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Key<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        // YAML keys may be numbers or booleans
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Key<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an object key")
+    }
+
+    fn visit_str<E: de::Error>(self, key: &str) -> std::result::Result<(), E> {
+        self.record(key);
+        Ok(())
+    }
+
+    fn visit_bool<E: de::Error>(self, key: bool) -> std::result::Result<(), E> {
+        self.record(&key.to_string());
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, key: i64) -> std::result::Result<(), E> {
+        self.record(&key.to_string());
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, key: u64) -> std::result::Result<(), E> {
+        self.record(&key.to_string());
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, key: f64) -> std::result::Result<(), E> {
+        self.record(&key.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_document() {
+        let stats = analyze(
+            r#"{"name": "a", "tags": ["x", "y", "z"], "owner": {"name": "b", "ids": [[1], []]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            stats,
+            JsonStats {
+                documents: 1,
+                max_depth: 4,
+                objects: 2,
+                arrays: 4,
+                keys: 5,
+                distinct_keys: 4,
+                max_array_len: 3,
+                types: BTreeMap::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_analyze_structured_types() {
+        let json = r#"{"items": [{"id": 1, "name": "a"}, {"id": 2.5, "name": null}], "ok": true}"#;
+        let stats = analyze_structured(json, false).unwrap();
+        let types = |path: &str| stats.types[path].iter().copied().collect::<Vec<_>>();
+        assert_eq!(types("$"), ["object"]);
+        assert_eq!(types("$.items"), ["array"]);
+        assert_eq!(types("$.items[*].id"), ["integer", "number"]);
+        assert_eq!(types("$.items[*].name"), ["null", "string"]);
+        assert_eq!(types("$.ok"), ["boolean"]);
+
+        let yaml = "items:\n  - id: 1\n    name: a\n  - id: 2\n---\n3: [x]\n";
+        let stats = analyze_structured(yaml, true).unwrap();
+        assert_eq!(stats.documents, 2);
+        assert_eq!(stats.keys, 5);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.max_array_len, 2);
+        assert!(stats.types.contains_key("$.3[*]"));
+        assert!(analyze_structured("a: [", true).is_err());
+    }
+
+    #[test]
+    fn test_analyze_ndjson_and_scalars() {
+        let stats = analyze("{\"id\": 1}\n{\"id\": 2, \"ok\": true}\n\n3\n").unwrap();
+        assert_eq!(stats.documents, 3);
+        assert_eq!(stats.keys, 3);
+        assert_eq!(stats.distinct_keys, 2);
+        assert_eq!(stats.max_depth, 1);
+
+        let stats = analyze("  ").unwrap();
+        assert_eq!((stats.documents, stats.max_depth), (0, 0));
+    }
+
+    #[test]
+    fn test_analyze_rejects_invalid_json() {
+        let message = analyze("{\"id\": 1}\n{\"id\": }\n")
+            .unwrap_err()
+            .to_string();
+        assert!(message.starts_with("Invalid JSON:"), "{}", message);
+        assert!(message.contains("line 2"), "{}", message);
+    }
+}
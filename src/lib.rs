@@ -0,0 +1,12 @@
+//! Library API for programs that compute or read the statistics `run` reports, without
+//! going through the command line.
+//!
+//! [`stats::StatsReport`] is what `run --stats-format json|yaml` prints: the
+//! [`stats::FileStats`] of each input and their totals. Statistics of text are gathered
+//! with [`stats::StatsAccumulator`].
+
+// The crate is named after the template placeholder until scripts/replace_templates.sh runs
+#![allow(non_snake_case)]
+
+mod hll;
+pub mod stats;
@@ -0,0 +1,215 @@
+//! Advisory lock files, so that two instances do not replace the binary or write to the
+//! same output directory at the same time.
+//!
+//! Locks live in the cache directory and hold the pid of their owner. The operating system
+//! releases them when the owner exits, so a crashed instance never leaves a stale lock.
+//! Within one process, e.g. for the jobs of a batch, a lock already held is shared rather
+//! than taken again.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::error::{Error, Result};
+use crate::identity;
+
+/// What to do when another instance holds a lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockPolicy {
+    /// Fail with an error naming the other instance
+    Fail,
+    /// Wait until the other instance releases it (--wait-for-lock)
+    Wait,
+    /// Do not lock at all (--no-lock)
+    Ignore,
+}
+
+/// A held lock, released when dropped.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: Arc<File>,
+}
+
+/// Locks this process holds through [`acquire`], by path.
+static HELD: Mutex<Option<HashMap<PathBuf, Weak<File>>>> = Mutex::new(None);
+
+/// Takes the lock for upgrading the binary.
+pub fn upgrade(policy: LockPolicy) -> Result<Option<InstanceLock>> {
+    acquire("upgrade", policy)
+}
+
+/// Takes the lock for writing to the output directory `dir`.
+pub fn output_dir(dir: &Path, policy: LockPolicy) -> Result<Option<InstanceLock>> {
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let digest: String = Sha256::digest(dir.as_os_str().as_encoded_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    acquire(&format!("output-{}", digest), policy)
+}
+
+/// A PID file held by a long-running instance, removed when dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    /// `None` when written without locking (--no-lock)
+    _lock: Option<InstanceLock>,
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            debug!("Could not remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Takes the PID file at `path`, failing (or waiting) while another instance holds it.
+/// With [`LockPolicy::Ignore`] the PID is written all the same, without locking the file.
+pub fn pid_file(path: &Path, policy: LockPolicy) -> Result<PidFile> {
+    let lock = match policy {
+        LockPolicy::Ignore => {
+            let file_error = |action| {
+                let path = path.to_path_buf();
+                move |source| Error::File {
+                    action,
+                    path,
+                    source,
+                }
+            };
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent).map_err(file_error("create PID file directory"))?;
+            }
+            fs::write(path, std::process::id().to_string())
+                .map_err(file_error("write PID file"))?;
+            None
+        }
+        _ => Some(lock(path, policy)?),
+    };
+    Ok(PidFile {
+        path: path.to_path_buf(),
+        _lock: lock,
+    })
+}
+
+/// Directory lock files are created in.
+fn locks_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", identity::app_name())
+        .map(|dirs| dirs.cache_dir().join("locks"))
+}
+
+fn acquire(name: &str, policy: LockPolicy) -> Result<Option<InstanceLock>> {
+    if policy == LockPolicy::Ignore {
+        return Ok(None);
+    }
+    let Some(dir) = locks_dir() else {
+        debug!("No cache directory; not locking {}", name);
+        return Ok(None);
+    };
+    let path = dir.join(format!("{}.lock", name));
+    // Taking the lock again would fail (or wait) on this very process
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    let held = held.get_or_insert_with(HashMap::new);
+    if let Some(file) = held.get(&path).and_then(Weak::upgrade) {
+        debug!("Sharing {}, already locked by this process", path.display());
+        return Ok(Some(InstanceLock { _file: file }));
+    }
+    let lock = lock(&path, policy)?;
+    held.insert(path, Arc::downgrade(&lock._file));
+    Ok(Some(lock))
+}
+
+/// Locks the file at `path`, creating it if needed, and records this process in it.
+fn lock(path: &Path, policy: LockPolicy) -> Result<InstanceLock> {
+    let file_error = |action| {
+        let path = path.to_path_buf();
+        move |source| Error::File {
+            action,
+            path,
+            source,
+        }
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(file_error("create lock directory"))?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(file_error("open lock file"))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let holder = match holder(&mut file) {
+                Some(pid) => format!(" (pid {})", pid),
+                None => String::new(),
+            };
+            if policy == LockPolicy::Fail {
+                return Err(Error::Other(format!(
+                    "Another instance is running{} and holds {}; wait for it with \
+                     --wait-for-lock, or skip the check with --no-lock",
+                    holder,
+                    path.display()
+                )));
+            }
+            info!("Waiting for another instance{} to finish", holder);
+            file.lock().map_err(file_error("lock"))?;
+        }
+        Err(TryLockError::Error(source)) => return Err(file_error("lock")(source)),
+    }
+
+    file.set_len(0)
+        .and_then(|()| file.seek(SeekFrom::Start(0)))
+        .and_then(|_| write!(file, "{}", std::process::id()))
+        .map_err(file_error("write lock file"))?;
+    debug!("Locked {}", path.display());
+    Ok(InstanceLock {
+        _file: Arc::new(file),
+    })
+}
+
+/// Pid recorded by the instance holding the lock, if it can be read.
+fn holder(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("locks/test.lock");
+
+        let held = lock(&path, LockPolicy::Fail).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        let error = lock(&path, LockPolicy::Fail).unwrap_err().to_string();
+        assert!(
+            error.contains(&format!(
+                "Another instance is running (pid {})",
+                std::process::id()
+            )),
+            "{}",
+            error
+        );
+
+        drop(held);
+        assert!(lock(&path, LockPolicy::Fail).is_ok());
+    }
+}
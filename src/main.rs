@@ -1,75 +1,576 @@
-use clap::Parser;
-use tracing::{debug, info};
+use std::path::Path;
+use std::time::Duration;
+
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use tracing::{debug, info, warn};
 
 mod args;
+mod asset;
+mod batch;
+mod bloom;
+mod cache;
+mod cancel;
 mod commands;
+mod compression;
 mod config;
 mod constants;
+mod context;
+mod crash;
+mod digest;
+mod disk;
+mod encoding;
 mod env_vars;
 mod error;
+mod extsort;
+mod git;
+mod glob;
+mod hll;
+mod http;
+mod i18n;
+mod identity;
+mod install;
+mod jobs;
+mod jsonpath;
+mod jsonstats;
+mod lock;
+mod manifest;
+mod output;
+mod pin;
+mod plugin;
+mod prompt;
+mod reload;
+mod reporter;
+mod revread;
+mod schedule;
+#[cfg(feature = "scripting")]
+mod script;
+mod secrets;
+mod session;
+mod settings;
+mod state;
+mod stats;
+mod strategy;
+mod suggest;
+mod tabular;
+mod template;
+mod term;
+mod throttle;
+mod timings;
+mod transform;
+mod xattr;
 
 use args::{effective_log_level, GlobalArgs};
 use commands::Commands;
 use config::Config;
-use error::Result;
+use context::Context;
+use error::{Error, Result};
+use i18n::tr;
+use output::OutputFormat;
+use session::Session;
+use settings::Settings;
+
+/// Separates commands run one after another in one process.
+const THEN: &str = "--then";
 
 #[derive(Parser, Debug)]
 #[command(name = env!("CARGO_PKG_NAME"))]
 #[command(about = "A Rust CLI application template")]
-#[command(version)]
+#[command(disable_version_flag = true)]
+#[command(
+    after_help = "Chain commands with --then to run them in one process with the same configuration, e.g. `run -i a.txt --then run -i b.txt`"
+)]
 struct Cli {
     #[command(flatten)]
     global: GlobalArgs,
 
+    /// Print version (with --verbose, the full build report)
+    #[arg(short = 'V', long)]
+    version: bool,
+
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("[ERROR] {}", e);
-        std::process::exit(1);
+    crash::install();
+
+    // Errors are reported in the requested format as soon as it is known
+    let mut error_format = OutputFormat::Text;
+    // One runtime for every command and background task; commands are async so those
+    // needing network or concurrency share it rather than starting their own
+    let result = tokio::runtime::Runtime::new()
+        .map_err(Error::from)
+        .and_then(|runtime| runtime.block_on(run(&mut error_format)));
+    if let Err(e) = result {
+        match error_format {
+            OutputFormat::Json => match serde_json::to_string(&e.report()) {
+                Ok(json) => eprintln!("{}", json),
+                Err(_) => eprintln!("[ERROR] {}", e.render()),
+            },
+            OutputFormat::Text | OutputFormat::Markdown => eprintln!("[ERROR] {}", e.render()),
+        }
+        std::process::exit(e.exit_code());
     }
 }
 
-fn run() -> Result<()> {
-    let cli = Cli::parse();
+/// The CLI definition with help wrapped to the terminal width.
+fn cli_command() -> clap::Command {
+    Cli::command()
+        .term_width(term::help_width())
+        .max_term_width(term::MAX_HELP_WIDTH)
+}
+
+async fn run(error_format: &mut OutputFormat) -> Result<()> {
+    let invocation = expand_alias(std::env::args().collect());
+    let args = invocation.clone();
+    if args.iter().skip(1).any(|arg| arg == "--help-all") {
+        return print_help_all(&args);
+    }
+
+    let (args, mut chained) = split_chain(args);
+    let mut matches = match cli_command().try_get_matches_from(&args) {
+        Ok(matches) => matches,
+        Err(e) => std::process::exit(run_plugin_or_exit(e, &args)?),
+    };
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // A replayed session supplies its own arguments, environment and configuration
+    let session = match &cli.global.replay {
+        Some(path) => {
+            let session = Session::load(path)?;
+            session.apply_env();
+            let args = std::iter::once(String::from(env!("CARGO_PKG_NAME")))
+                .chain(session.args.iter().cloned())
+                .collect();
+            let (args, session_chained) = split_chain(args);
+            chained = session_chained;
+            matches = cli_command()
+                .try_get_matches_from(args)
+                .map_err(|e| Error::Other(format!("Invalid arguments in session: {}", e)))?;
+            cli = Cli::from_arg_matches(&matches)
+                .map_err(|e| Error::Other(format!("Invalid arguments in session: {}", e)))?;
+            Some(session)
+        }
+        None => None,
+    };
 
-    // Initialize tracing based on effective log level
+    *error_format = cli.global.format;
+
+    if cli.version {
+        if cli.global.verbose > 0 || cli.global.format == OutputFormat::Json {
+            return commands::version::print(cli.global.format);
+        }
+        match constants::GIT_HASH {
+            "" => println!("{} {}", env!("CARGO_PKG_NAME"), constants::APP_VERSION),
+            hash => println!(
+                "{} {} ({})",
+                env!("CARGO_PKG_NAME"),
+                constants::APP_VERSION,
+                hash
+            ),
+        }
+        return Ok(());
+    }
+
+    let Some(command) = cli.command.take() else {
+        cli_command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "a subcommand is required",
+            )
+            .exit();
+    };
+    // Parse the whole chain before running any of it
+    let mut commands = vec![command];
+    for segment in &chained {
+        commands.push(parse_chained(segment)?);
+    }
+
+    // Initialize tracing from the flags alone so configuration loading is logged; the
+    // level is adjusted once the configuration and environment have been resolved
     let log_level = effective_log_level(&cli.global);
-    init_tracing(log_level);
+    let log_filter = init_tracing(log_level, cli.global.timings);
+    i18n::init(cli.global.lang.as_deref());
+    cancel::install();
+
+    // The executable replaced by an upgrade on Windows stays behind until it has exited
+    if install::Strategy::current() == install::Strategy::MoveAside {
+        if let Ok(exe) = std::env::current_exe() {
+            install::remove_leftover_backup(&install::RealFs, &exe);
+        }
+    }
+
+    if cli.global.no_env {
+        env_vars::ignore_all();
+        // clap already read the configuration path from the environment
+        if matches.value_source("config") == Some(ValueSource::EnvVariable) {
+            cli.global.config = constants::DEFAULT_CONFIG_FILE.to_string();
+        }
+    }
 
     // Load configuration
-    let mut config = Config::load(&cli.global.config)?;
-    config.merge_env()?;
+    let config_load = tracing::trace_span!(target: timings::TARGET, "config load").entered();
+    let mut config = match &session {
+        Some(session) => {
+            info!("Replaying recorded session: {:?}", session.args);
+            session.verify()?;
+            session.config.clone()
+        }
+        None => match load_config(&cli.global) {
+            Err(e) if commands.len() == 1 && commands[0].tolerates_invalid_config() => {
+                warn!("Ignoring invalid configuration: {}", e);
+                Config::default()
+            }
+            result => result?,
+        },
+    };
+
+    let settings = Settings::resolve(&config, &cli.global, &matches, env_vars::var)?;
+    settings.apply(&mut config);
+    drop(config_load);
+    *error_format = settings.format.value;
+    if let Some(handle) = &log_filter {
+        if settings.log_level.value != log_level {
+            let filter = tracing_subscriber::EnvFilter::new(settings.log_level.value.as_filter());
+            if handle.reload(filter).is_ok() {
+                debug!(
+                    "Log level set to {} ({})",
+                    settings.log_level.value, settings.log_level.origin
+                );
+            }
+        }
+    }
+
+    identity::init(
+        env_vars::var(env_vars::REPOSITORY)
+            .or_else(|| config.repository.clone())
+            .as_deref(),
+    );
+
+    if commands.iter().any(Commands::writes_output) {
+        config.prepare_output_dir(cli.global.create_dirs)?;
+    }
+
+    if let Some(path) = &cli.global.record {
+        let args = session::strip_session_args(invocation.iter().skip(1).cloned());
+        let mut inputs = Vec::new();
+        for command in &commands {
+            inputs.extend(command.inputs()?);
+        }
+        Session::capture(args, &config, &inputs)?.save(path)?;
+    }
 
     // Log configuration file being used
     info!("Using configuration file: {}", cli.global.config);
 
-    debug!("CLI arguments: {:?}", cli);
-    debug!("Configuration: {:?}", config);
+    debug!("CLI arguments: {:?}", cli.global);
+    debug!("Commands: {:?}", commands);
+
+    let ctx = Context {
+        config_path: cli.global.config.clone(),
+        strict_config: cli.global.strict_config,
+        config,
+        format: settings.format.value,
+        color: settings.color.value.enabled() && !settings.accessible.value,
+        reporter: reporter::Reporter::new(settings.accessible.value, settings.format.value),
+        prompter: prompt::Prompter::new(
+            cli.global.yes,
+            settings.color.value.enabled() && !settings.accessible.value,
+        ),
+        lock_policy: match (cli.global.no_lock, cli.global.wait_for_lock) {
+            (true, _) => lock::LockPolicy::Ignore,
+            (false, true) => lock::LockPolicy::Wait,
+            (false, false) => lock::LockPolicy::Fail,
+        },
+        settings,
+        config_source: session.is_none().then(|| context::ConfigSource {
+            global: cli.global.clone(),
+            matches: matches.clone(),
+        }),
+        log_filter,
+    };
+
+    debug!("Configuration: {:?}", ctx.config);
+
+    // Upgrading is how a mismatch gets fixed, so it is not held to the pin
+    if !commands
+        .iter()
+        .any(|command| matches!(command, Commands::Upgrade(_)))
+    {
+        check_pinned_version(cli.global.strict_version, ctx.reporter)?;
+    }
+    if let Some(lang) = cli
+        .global
+        .lang
+        .as_deref()
+        .filter(|lang| !i18n::supports(lang))
+    {
+        let available: Vec<_> = i18n::available().collect();
+        ctx.reporter.notice(&tr!(
+            "lang-unsupported",
+            lang = lang,
+            available = available.join(", ")
+        ));
+    }
+
     info!("Starting command execution.");
 
-    match cli.command {
-        Commands::Run(args) => commands::run::execute(args),
-        Commands::Upgrade(args) => commands::upgrade::execute(args),
+    // The opt-in update check runs alongside the command, never instead of it
+    let update_check = (ctx.config.update_check.enabled
+        && !commands
+            .iter()
+            .any(|command| matches!(command, Commands::Upgrade(_))))
+    .then(commands::upgrade::spawn_update_check);
+
+    // Chained commands run in order and stop at the first failure
+    let chain_length = commands.len();
+    let mut result = Ok(());
+    for (i, command) in commands.into_iter().enumerate() {
+        if chain_length > 1 {
+            debug!("Running command {} of {}", i + 1, chain_length);
+        }
+        result = execute(command, &ctx).await;
+        if result.is_err() {
+            break;
+        }
+    }
+
+    if let Some(check) = update_check {
+        let budget = Duration::from_millis(ctx.config.update_check.budget_ms);
+        commands::upgrade::report_update_check(check, budget, ctx.reporter).await;
+    }
+
+    if cli.global.timings {
+        timings::print(ctx.format, ctx.reporter);
+    }
+
+    result
+}
+
+/// Loads the configuration file, with the system and user configuration files and the
+/// project configuration files applying to the current directory beneath it (the latter
+/// unless `--no-config-search` is given).
+fn load_config(global: &GlobalArgs) -> Result<Config> {
+    load_layered_config(
+        &global.config,
+        global.strict_config,
+        !global.no_config_search,
+    )
+}
+
+/// Loads the configuration file at `path` over the system and user files and, with
+/// `search`, the project files of the current directory.
+fn load_layered_config(path: &str, strict: bool, search: bool) -> Result<Config> {
+    let mut layers = config::scope_files(Path::new(path));
+    if search {
+        layers.extend(config::project_files(
+            &std::env::current_dir()?,
+            Path::new(path),
+        ));
+    }
+    Config::load_with_layers(path, strict, &layers)
+}
+
+/// Compares this binary with the version pinned for the current directory, warning about
+/// a mismatch or a pin file that cannot be read, or failing with `strict`.
+fn check_pinned_version(strict: bool, reporter: reporter::Reporter) -> Result<()> {
+    let pin = match pin::find(&std::env::current_dir()?) {
+        Ok(Some(pin)) => pin,
+        Ok(None) => return Ok(()),
+        Err(e) if !strict => {
+            reporter.notice(&e.render());
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    if pin.is_satisfied() {
+        return Ok(());
+    }
+    let message = tr!(
+        "pin-mismatch",
+        path = pin.path.display(),
+        pinned = pin.version,
+        running = pin::running_version(),
+        app = identity::app_name()
+    );
+    if strict {
+        return Err(Error::Other(message));
+    }
+    reporter.notice(&message);
+    Ok(())
+}
+
+async fn execute(command: Commands, ctx: &Context) -> Result<()> {
+    match command {
+        Commands::Auth(args) => commands::auth::execute(args, ctx).await,
+        Commands::Bench(args) => commands::bench::execute(args, ctx).await,
+        Commands::Cache(args) => commands::cache::execute(args, ctx).await,
+        Commands::Config(args) => commands::config::execute(args, ctx).await,
+        Commands::Daemon(args) => commands::daemon::execute(args, ctx).await,
+        Commands::Diff(args) => commands::diff::execute(args, ctx).await,
+        Commands::Hash(args) => commands::hash::execute(args, ctx).await,
+        Commands::Init(args) => commands::init::execute(args, ctx).await,
+        Commands::Package(args) => commands::package::execute(args, ctx).await,
+        Commands::Render(args) => commands::render::execute(args, ctx).await,
+        Commands::Run(args) => commands::run::execute(*args, ctx).await,
+        Commands::Selftest(args) => commands::selftest::execute(args, ctx).await,
+        Commands::Transform(args) => commands::transform::execute(args, ctx).await,
+        Commands::Upgrade(args) => commands::upgrade::execute(args, ctx).await,
+        Commands::Version(args) => commands::version::execute(args, ctx).await,
     }
 }
 
-fn init_tracing(log_level: args::LogLevel) {
-    let filter = log_level.as_filter();
+/// Expands an invocation under another name, e.g. through a symlink, into the
+/// arguments the configuration's `aliases` map that name to, followed by the given
+/// arguments. The configuration is read ahead of parsing from `--config` or its
+/// environment variable (unless `--no-env` is given), merged over the system, user and
+/// project files like for any command; any problem with it is reported once the
+/// arguments are parsed.
+fn expand_alias(args: Vec<String>) -> Vec<String> {
+    let Some(name) = args
+        .first()
+        .and_then(|program| std::path::Path::new(program).file_stem())
+        .and_then(|stem| stem.to_str())
+    else {
+        return args;
+    };
+    if name == identity::app_name() {
+        return args;
+    }
+
+    let mut config_path = None;
+    let mut no_config_search = false;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        if arg == "--config" || arg == "-C" {
+            config_path = rest.next().cloned();
+        } else if let Some(path) = arg.strip_prefix("--config=").or(arg.strip_prefix("-C")) {
+            config_path = Some(path.to_string());
+        } else if arg == "--no-env" {
+            env_vars::ignore_all();
+        } else if arg == "--no-config-search" {
+            no_config_search = true;
+        }
+    }
+    let config_path = config_path
+        .or_else(|| env_vars::var(env_vars::CONFIG))
+        .unwrap_or_else(|| constants::DEFAULT_CONFIG_FILE.to_string());
+    let Some(alias) = load_layered_config(&config_path, false, !no_config_search)
+        .ok()
+        .and_then(|config| config.aliases.get(name).cloned())
+    else {
+        return args;
+    };
+
+    let mut expanded = vec![args[0].clone()];
+    expanded.extend(alias);
+    expanded.extend(args.into_iter().skip(1));
+    expanded
+}
+
+/// Splits the command line at each `--then` into the first invocation (binary name, global
+/// options and command) and the commands chained after it.
+fn split_chain(args: Vec<String>) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut segments = args.split(|arg| arg == THEN);
+    let first = segments.next().unwrap_or_default().to_vec();
+    let chained = segments.map(<[String]>::to_vec).collect();
+    (first, chained)
+}
+
+/// Parses a command chained with `--then`. Global options apply to the whole chain, so
+/// they must come before the first command.
+fn parse_chained(segment: &[String]) -> Result<Commands> {
+    if segment.is_empty() {
+        return Err(Error::Other(format!(
+            "{} must be followed by a command",
+            THEN
+        )));
+    }
+    let args = std::iter::once(String::from(env!("CARGO_PKG_NAME"))).chain(segment.to_vec());
+    let matches = cli_command()
+        .try_get_matches_from(args)
+        .unwrap_or_else(|e| suggest::improve_error(e, &cli_command(), &[]).exit());
+    if let Some(id) = matches
+        .ids()
+        .find(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+    {
+        return Err(Error::Other(format!(
+            "Global option '{}' after {} must come before the first command",
+            id, THEN
+        )));
+    }
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    cli.command
+        .ok_or_else(|| Error::Other(format!("{} must be followed by a command", THEN)))
+}
+
+/// Runs the plugin named by an unknown subcommand and returns its exit code; any other
+/// parse error exits with clap's message plus suggestions that include plugins.
+fn run_plugin_or_exit(error: clap::Error, args: &[String]) -> Result<i32> {
+    use clap::error::{ContextKind, ContextValue, ErrorKind};
+
+    let plugins = plugin::discover(std::env::var_os("PATH").as_deref());
+    if error.kind() == ErrorKind::InvalidSubcommand {
+        if let Some(ContextValue::String(name)) = error.get(ContextKind::InvalidSubcommand) {
+            if let Some(path) = plugins.get(name) {
+                // Everything after the subcommand is the plugin's
+                let rest = args
+                    .iter()
+                    .skip(1)
+                    .position(|arg| arg == name)
+                    .map_or(args.len(), |i| i + 2);
+                return plugin::run(name, path, &args[rest..]);
+            }
+        }
+    }
+    let names: Vec<String> = plugins.into_keys().collect();
+    suggest::improve_error(error, &cli_command(), &names).exit()
+}
+
+/// Prints help for the subcommand named in `args` with hidden options revealed.
+fn print_help_all(args: &[String]) -> Result<()> {
+    let mut command = term::reveal_hidden(cli_command());
+    command.build();
+
+    let mut target = &mut command;
+    for arg in args.iter().skip(1).filter(|arg| !arg.starts_with('-')) {
+        let name = arg.as_str();
+        if target.find_subcommand(name).is_none() {
+            continue;
+        }
+        target = target.find_subcommand_mut(name).expect("subcommand exists");
+    }
+    target.print_long_help()?;
+    Ok(())
+}
+
+/// Installs the global subscriber, recording phase durations when `timings` is set.
+/// Returns a handle to adjust the level later, or `None` when `RUST_LOG` is set, since it
+/// takes precedence over every other setting.
+fn init_tracing(log_level: args::LogLevel, timings: bool) -> Option<context::LogFilterHandle> {
+    use tracing_subscriber::prelude::*;
+
+    let from_env = tracing_subscriber::EnvFilter::try_from_default_env().ok();
+    let adjustable = from_env.is_none();
+    let filter =
+        from_env.unwrap_or_else(|| tracing_subscriber::EnvFilter::new(log_level.as_filter()));
+    let (filter, handle) = tracing_subscriber::reload::Layer::new(filter);
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(filter)),
+    // The level filters log output only, so that phase spans reach the timings layer
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(false)
+                .with_thread_ids(false)
+                .with_thread_names(false)
+                .with_writer(std::io::stderr)
+                .compact()
+                .with_filter(filter),
         )
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_thread_names(false)
-        .with_writer(std::io::stderr)
-        .compact()
+        .with(timings.then(timings::layer))
         .init();
 
     debug!("Logging initialized at level: {}", log_level);
+    adjustable.then_some(handle)
 }
@@ -0,0 +1,353 @@
+//! Record of the outputs `run` has written, so outputs edited by hand since the last run
+//! are noticed instead of silently overwritten.
+//!
+//! The manifest is a small JSON file in the output directory holding the SHA-256 of each
+//! output as it was written, and the compression of compressed outputs. Runs sharing an
+//! output directory in one process (the jobs of a batch) each save only what they
+//! recorded, on top of what the others saved.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use tracing::{debug, warn};
+
+use crate::compression::{self, Compression};
+use crate::constants;
+use crate::digest::{HashAlgorithm, HashingReader};
+use crate::error::{Error, Result};
+use crate::prompt::Prompter;
+
+/// What to do with an output that was modified since it was last written.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnModified {
+    /// Replace it with the new output
+    Overwrite,
+    /// Keep the modified file and leave it out of this run
+    Skip,
+    /// Stop with an error
+    Fail,
+}
+
+/// Digests of the outputs written below one directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(skip)]
+    root: PathBuf,
+    /// Keys recorded since loading
+    #[serde(skip)]
+    recorded: BTreeSet<String>,
+    /// SHA-256 of each output, keyed by its `/`-separated path relative to the root
+    outputs: BTreeMap<String, String>,
+    /// Compression of the compressed outputs, keyed like `outputs`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    compression: BTreeMap<String, Compression>,
+}
+
+impl Manifest {
+    /// File name of the manifest inside the output directory.
+    pub fn file_name() -> String {
+        format!(".{}-outputs.json", constants::APP_NAME)
+    }
+
+    /// Loads the manifest for `root`; a missing or unreadable one starts empty.
+    pub fn load(root: &Path) -> Self {
+        let path = root.join(Self::file_name());
+        let mut manifest = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!(
+                    "Ignoring unreadable output manifest {}: {}",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        manifest.root = root.to_path_buf();
+        manifest
+    }
+
+    /// Whether this tool has written `output` before.
+    pub fn is_recorded(&self, output: &Path) -> bool {
+        self.outputs.contains_key(&self.key(output))
+    }
+
+    /// Whether `output` exists and differs from what was last recorded for it.
+    ///
+    /// Files the manifest knows nothing about are not considered modified.
+    pub fn is_modified(&self, output: &Path) -> Result<bool> {
+        let Some(recorded) = self.outputs.get(&self.key(output)) else {
+            return Ok(false);
+        };
+        match sha256_file(output) {
+            Ok(current) => Ok(current != *recorded),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(source) => Err(read_error(output, source)),
+        }
+    }
+
+    /// Records the current contents of `output` as written by this tool, compressed with
+    /// `compression`.
+    pub fn record(&mut self, output: &Path, compression: Option<Compression>) -> Result<()> {
+        let digest = sha256_file(output).map_err(|source| read_error(output, source))?;
+        let key = self.key(output);
+        match compression {
+            Some(compression) => self.compression.insert(key.clone(), compression),
+            None => self.compression.remove(&key),
+        };
+        self.outputs.insert(key.clone(), digest);
+        self.recorded.insert(key);
+        Ok(())
+    }
+
+    /// The compression `output` was last written with.
+    pub fn compression(&self, output: &Path) -> Option<Compression> {
+        self.compression.get(&self.key(output)).copied()
+    }
+
+    /// Writes what was recorded into the manifest on disk, if anything was.
+    pub fn save(&self) -> Result<()> {
+        static SAVING: Mutex<()> = Mutex::new(());
+        if self.recorded.is_empty() {
+            return Ok(());
+        }
+        let _saving = SAVING.lock().unwrap_or_else(|e| e.into_inner());
+        let mut merged = Self::load(&self.root);
+        for key in &self.recorded {
+            merged
+                .outputs
+                .insert(key.clone(), self.outputs[key].clone());
+            match self.compression.get(key) {
+                Some(compression) => merged.compression.insert(key.clone(), *compression),
+                None => merged.compression.remove(key),
+            };
+        }
+        let path = self.root.join(Self::file_name());
+        debug!("Saving output manifest: {}", path.display());
+        fs::write(&path, serde_json::to_string_pretty(&merged)? + "\n")?;
+        Ok(())
+    }
+
+    fn key(&self, output: &Path) -> String {
+        let relative = output.strip_prefix(&self.root).unwrap_or(output);
+        relative.to_string_lossy().replace('\\', "/")
+    }
+}
+
+/// Decides, output by output, whether `run` may write over an existing file.
+pub struct OutputGuard {
+    manifest: Manifest,
+    policy: Option<OnModified>,
+    prompter: Prompter,
+    /// Ask before replacing files this tool did not write
+    confirm_unknown: bool,
+}
+
+impl OutputGuard {
+    /// Without a `policy`, modified outputs are overwritten with `--yes`, prompted for
+    /// when interactive, and an error otherwise.
+    pub fn new(
+        root: &Path,
+        policy: Option<OnModified>,
+        prompter: Prompter,
+        confirm_unknown: bool,
+    ) -> Self {
+        Self {
+            manifest: Manifest::load(root),
+            policy,
+            prompter,
+            confirm_unknown,
+        }
+    }
+
+    /// Whether new contents may be written to `output`. `contents` produces them as text,
+    /// which is only needed to show a diff.
+    pub fn allows(
+        &mut self,
+        output: &Path,
+        contents: impl FnOnce() -> Result<String>,
+    ) -> Result<bool> {
+        if !self.manifest.is_recorded(output) {
+            if self.confirm_unknown
+                && output.exists()
+                && !self
+                    .prompter
+                    .confirm(&format!("Overwrite existing {}?", output.display()))?
+            {
+                return Err(Error::Other(format!(
+                    "Not overwriting {} (pass --yes to overwrite without asking)",
+                    output.display()
+                )));
+            }
+            return Ok(true);
+        }
+        if !self.manifest.is_modified(output)? {
+            return Ok(true);
+        }
+
+        let policy = match self.policy {
+            Some(policy) => policy,
+            None if self.prompter.assume_yes() => OnModified::Overwrite,
+            None if self.prompter.is_interactive() => self.ask(output, contents)?,
+            None => OnModified::Fail,
+        };
+        match policy {
+            OnModified::Overwrite => {
+                warn!("Overwriting modified output: {}", output.display());
+                Ok(true)
+            }
+            OnModified::Skip => {
+                warn!("Keeping modified output: {}", output.display());
+                Ok(false)
+            }
+            OnModified::Fail => Err(Error::Other(format!(
+                "{} was modified since it was last written; pass --on-modified overwrite or skip",
+                output.display()
+            ))),
+        }
+    }
+
+    pub fn record(&mut self, output: &Path, compression: Option<Compression>) -> Result<()> {
+        self.manifest.record(output, compression)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.manifest.save()
+    }
+
+    /// Asks what to do with a modified output, showing a diff on request.
+    fn ask(&self, output: &Path, contents: impl FnOnce() -> Result<String>) -> Result<OnModified> {
+        let question = format!(
+            "{} was modified since it was last written",
+            output.display()
+        );
+        let mut contents = Some(contents);
+        let mut new = String::new();
+        loop {
+            let choice = self
+                .prompter
+                .select(&question, &["Overwrite", "Skip", "Show diff", "Fail"])?;
+            match choice {
+                Some(0) => return Ok(OnModified::Overwrite),
+                Some(1) => return Ok(OnModified::Skip),
+                Some(2) => {
+                    if let Some(contents) = contents.take() {
+                        new = contents()?;
+                    }
+                    let current = read_text(output, self.manifest.compression(output))?;
+                    let diff = TextDiff::from_lines(current.as_str(), new.as_str());
+                    let name = output.display().to_string();
+                    eprint!(
+                        "{}",
+                        diff.unified_diff()
+                            .header(&format!("{} (on disk)", name), &format!("{} (new)", name))
+                    );
+                }
+                _ => return Ok(OnModified::Fail),
+            }
+        }
+    }
+}
+
+/// Reads an output written with `compression` as text for diffing.
+fn read_text(path: &Path, compression: Option<Compression>) -> Result<String> {
+    let mut raw = Vec::new();
+    fs::File::open(path)
+        .and_then(|file| compression::decoder(file, compression)?.read_to_end(&mut raw))
+        .map_err(|source| read_error(path, source))?;
+    Ok(String::from_utf8_lossy(&raw).into_owned())
+}
+
+/// A failure to read the output at `path`, told apart from failures to read inputs.
+fn read_error(path: &Path, source: io::Error) -> Error {
+    Error::File {
+        action: "read output",
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut reader = HashingReader::new(fs::File::open(path)?, Some(HashAlgorithm::Sha256));
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(reader.finish().map(|digest| digest.hex).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detects_modified_outputs() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("nested/out.txt");
+        fs::create_dir_all(output.parent().unwrap()).unwrap();
+        fs::write(&output, "written").unwrap();
+
+        let mut manifest = Manifest::load(temp_dir.path());
+        // Unknown files are not treated as modified
+        assert!(!manifest.is_modified(&output).unwrap());
+        manifest.record(&output, None).unwrap();
+        manifest.save().unwrap();
+
+        let manifest = Manifest::load(temp_dir.path());
+        assert!(manifest.outputs.contains_key("nested/out.txt"));
+        assert_eq!(manifest.compression(&output), None);
+        assert!(!manifest.is_modified(&output).unwrap());
+        fs::write(&output, "edited").unwrap();
+        assert!(manifest.is_modified(&output).unwrap());
+        fs::remove_file(&output).unwrap();
+        assert!(!manifest.is_modified(&output).unwrap());
+    }
+
+    #[test]
+    fn test_guard_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("out.txt");
+        fs::write(&output, "written").unwrap();
+        let mut manifest = Manifest::load(temp_dir.path());
+        manifest.record(&output, None).unwrap();
+        manifest.save().unwrap();
+        fs::write(&output, "edited").unwrap();
+        let new = || Ok(String::from("new"));
+
+        let guard = |policy| {
+            OutputGuard::new(
+                temp_dir.path(),
+                policy,
+                Prompter::non_interactive(false),
+                true,
+            )
+        };
+        assert!(guard(Some(OnModified::Overwrite))
+            .allows(&output, new)
+            .unwrap());
+        assert!(!guard(Some(OnModified::Skip)).allows(&output, new).unwrap());
+        let message = guard(None).allows(&output, new).unwrap_err().to_string();
+        assert!(message.contains("--on-modified"), "{}", message);
+
+        // --yes overwrites without a policy
+        let mut yes =
+            OutputGuard::new(temp_dir.path(), None, Prompter::non_interactive(true), true);
+        assert!(yes.allows(&output, new).unwrap());
+
+        // Files this tool never wrote are confirmed, which proceeds without a terminal
+        let unknown = temp_dir.path().join("unknown.txt");
+        fs::write(&unknown, "mine").unwrap();
+        assert!(guard(None).allows(&unknown, new).unwrap());
+    }
+
+    #[test]
+    fn test_unreadable_manifest_starts_empty() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(Manifest::file_name()), "not json").unwrap();
+        assert!(Manifest::load(temp_dir.path()).outputs.is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+//! Output formats shared by all commands.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{IsTerminal, Write};
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::{Error, Result};
+
+/// How command results are written to stdout.
+#[derive(
+    ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// Pretty-printed JSON document
+    Json,
+    /// Text with Markdown tables and emphasis, e.g. for CI job summaries
+    Markdown,
+}
+
+/// Writes `value` to stdout as pretty-printed JSON.
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    report_line(format_args!("{}", serde_json::to_string_pretty(value)?));
+    Ok(())
+}
+
+/// Writes `value` to stdout as YAML.
+pub fn print_yaml<T: Serialize>(value: &T) -> Result<()> {
+    let yaml = serde_yaml::to_string(value)
+        .map_err(|e| Error::Other(format!("Failed to serialize YAML: {}", e)))?;
+    report_line(format_args!("{}", yaml.trim_end()));
+    Ok(())
+}
+
+/// Writes `rows` to stdout as CSV with a header row.
+pub fn print_csv<T: Serialize>(rows: &[T]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer
+            .serialize(row)
+            .map_err(|e| Error::Other(format!("Failed to serialize CSV: {}", e)))?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| Error::Other(format!("Failed to serialize CSV: {}", e)))?;
+    report_line(format_args!(
+        "{}",
+        String::from_utf8_lossy(&bytes).trim_end()
+    ));
+    Ok(())
+}
+
+/// How `run --stats-format` renders the statistics of its inputs.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// Aligned text table with a totals row
+    Table,
+    Json,
+    Yaml,
+    /// One row per file, then the totals
+    Csv,
+}
+
+thread_local! {
+    /// Where reports go instead of stdout while redirected. Each invocation of a command
+    /// reports from the thread it runs on, so concurrent batch jobs redirect only their own.
+    static REPORT_SINK: RefCell<Option<Box<dyn Write + Send>>> = const { RefCell::new(None) };
+}
+
+/// Redirects reports (command results printed with [`reportln!`], [`print_json`] and the
+/// reporter) of the current thread away from stdout until dropped, e.g. to keep them apart
+/// from data written to stdout.
+pub struct ReportRedirect {
+    /// The redirection this one replaced, put back when it is dropped
+    previous: Option<Box<dyn Write + Send>>,
+}
+
+impl ReportRedirect {
+    pub fn new(sink: Box<dyn Write + Send>) -> Self {
+        let previous = REPORT_SINK.with(|current| current.replace(Some(sink)));
+        ReportRedirect { previous }
+    }
+}
+
+impl Drop for ReportRedirect {
+    fn drop(&mut self) {
+        let sink = REPORT_SINK.with(|current| current.replace(self.previous.take()));
+        if let Some(Err(e)) = sink.map(|mut sink| sink.flush()) {
+            warn!("Failed to write the report: {}", e);
+        }
+    }
+}
+
+/// Writes a line of a report to stdout, or where it is redirected. Use [`reportln!`].
+pub fn report_line(args: fmt::Arguments<'_>) {
+    REPORT_SINK.with(|current| {
+        let mut current = current.borrow_mut();
+        match current.as_mut() {
+            Some(sink) => {
+                if let Err(e) = writeln!(sink, "{}", args) {
+                    // The rest of the report would fail the same way
+                    warn!("Failed to write the report: {}", e);
+                    *current = Some(Box::new(std::io::sink()));
+                }
+            }
+            None => println!("{}", args),
+        }
+    });
+}
+
+/// `println!` for command reports, following a [`ReportRedirect`].
+macro_rules! reportln {
+    ($($arg:tt)*) => {
+        $crate::output::report_line(format_args!($($arg)*))
+    };
+}
+pub(crate) use reportln;
+
+/// When to emit ANSI colors in text output.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolves the choice against the environment.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// ANSI styles used in text output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Bold,
+    Red,
+    Green,
+    Cyan,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Bold => "1",
+            Style::Red => "31",
+            Style::Green => "32",
+            Style::Cyan => "36",
+        }
+    }
+}
+
+/// Wraps `text` in the escape codes for `style` when `color` is enabled.
+pub fn paint(text: &str, style: Style, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint("x", Style::Red, false), "x");
+        assert_eq!(paint("x", Style::Green, true), "\x1b[32mx\x1b[0m");
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+
+    /// A sink whose contents stay readable after it was handed over.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_redirect_is_per_thread() {
+        let (outer, inner) = (Shared::default(), Shared::default());
+        let _outer = ReportRedirect::new(Box::new(outer.clone()));
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let _inner = ReportRedirect::new(Box::new(inner.clone()));
+                report_line(format_args!("inner"));
+            });
+        });
+        report_line(format_args!("outer"));
+        {
+            let _nested = ReportRedirect::new(Box::new(std::io::sink()));
+            report_line(format_args!("dropped"));
+        }
+        report_line(format_args!("restored"));
+
+        assert_eq!(*inner.0.lock().unwrap(), b"inner\n");
+        assert_eq!(*outer.0.lock().unwrap(), b"outer\nrestored\n");
+    }
+}
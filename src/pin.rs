@@ -0,0 +1,99 @@
+//! Project-local version pins: a `.<app>-version` file in a directory applies to it and
+//! every directory below, like `.ruby-version` or `.nvmrc`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tracing::debug;
+
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::identity;
+
+/// The version a project pins, and the file pinning it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    pub version: String,
+    pub path: PathBuf,
+}
+
+impl Pin {
+    /// Whether the running binary is the pinned version.
+    pub fn is_satisfied(&self) -> bool {
+        self.version == running_version()
+    }
+}
+
+/// Name of the pin file.
+pub fn file_name() -> String {
+    format!(".{}-version", identity::app_name())
+}
+
+/// The version of the running binary.
+pub fn running_version() -> &'static str {
+    constants::APP_VERSION
+}
+
+/// The pin applying to `dir`: the nearest pin file in it or one of its ancestors.
+pub fn find(dir: &Path) -> Result<Option<Pin>> {
+    let name = file_name();
+    let Some(path) = dir
+        .ancestors()
+        .map(|ancestor| ancestor.join(&name))
+        .find(|path| path.is_file())
+    else {
+        return Ok(None);
+    };
+    let contents = fs::read_to_string(&path).map_err(|source| Error::File {
+        action: "read",
+        path: path.clone(),
+        source,
+    })?;
+    let version = parse(&contents)
+        .ok_or_else(|| Error::Other(format!("No version found in {}", path.display())))?;
+    debug!("Version {} pinned by {}", version, path.display());
+    Ok(Some(Pin { version, path }))
+}
+
+/// The version in a pin file: its first line that is not blank or a `#` comment, without
+/// a leading `v`.
+fn parse(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.trim_start_matches('v').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("1.2.3\n"), Some(String::from("1.2.3")));
+        assert_eq!(
+            parse("# team version\n\n  v2.0.0-rc.1  \n"),
+            Some(String::from("2.0.0-rc.1"))
+        );
+        assert_eq!(parse("# nothing\n"), None);
+    }
+
+    #[test]
+    fn test_find_nearest() {
+        let dir = TempDir::new().unwrap();
+        let nested = dir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find(&nested).unwrap(), None);
+
+        fs::write(dir.path().join(file_name()), "1.0.0\n").unwrap();
+        fs::write(dir.path().join("a").join(file_name()), "v2.0.0\n").unwrap();
+        let pin = find(&nested).unwrap().unwrap();
+        assert_eq!(pin.version, "2.0.0");
+        assert_eq!(pin.path, dir.path().join("a").join(file_name()));
+
+        fs::write(dir.path().join("a").join(file_name()), "\n").unwrap();
+        assert!(find(&nested).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! External subcommands: an executable named `<app>-<name>` on `PATH` runs as `<app> <name>`,
+//! the way git and cargo find theirs.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use tracing::debug;
+
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::identity;
+
+/// Plugins found in the directories of `path` (a `PATH`-style list), by subcommand name.
+/// Earlier directories win.
+pub fn discover(path: Option<&std::ffi::OsStr>) -> BTreeMap<String, PathBuf> {
+    let prefix = format!("{}-", identity::app_name());
+    let mut plugins = BTreeMap::new();
+    for dir in path.map(env::split_paths).into_iter().flatten() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str() else {
+                continue;
+            };
+            let name = if cfg!(windows) {
+                name.strip_suffix(".exe").unwrap_or(name)
+            } else {
+                name
+            };
+            let Some(name) = name.strip_prefix(&prefix).filter(|name| !name.is_empty()) else {
+                continue;
+            };
+            if is_executable(&entry.path()) {
+                plugins
+                    .entry(name.to_string())
+                    .or_insert_with(|| entry.path());
+            }
+        }
+    }
+    plugins
+}
+
+/// Runs `plugin` with `args` and returns its exit code.
+pub fn run(name: &str, plugin: &Path, args: &[String]) -> Result<i32> {
+    debug!("Running plugin '{}': {}", name, plugin.display());
+    let status = Command::new(plugin)
+        .args(args)
+        .status()
+        .map_err(|source| Error::File {
+            action: "run plugin",
+            path: plugin.to_path_buf(),
+            source,
+        })?;
+    // Killed by a signal on Unix
+    Ok(status.code().unwrap_or(constants::EXIT_FAILURE))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn write_plugin(dir: &Path, name: &str, mode: u32) -> PathBuf {
+        let path = dir.join(format!("{}-{}", identity::app_name(), name));
+        fs::write(&path, "#!/bin/sh\nexit 0\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_discover() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let deploy = write_plugin(first.path(), "deploy", 0o755);
+        write_plugin(second.path(), "deploy", 0o755);
+        write_plugin(second.path(), "lint", 0o755);
+        write_plugin(second.path(), "notes", 0o644);
+        fs::write(second.path().join("unrelated"), "").unwrap();
+
+        let path = env::join_paths([first.path(), second.path()]).unwrap();
+        let plugins = discover(Some(&path));
+        assert_eq!(plugins.keys().collect::<Vec<_>>(), vec!["deploy", "lint"]);
+        assert_eq!(plugins["deploy"], deploy);
+    }
+}
@@ -0,0 +1,116 @@
+//! Confirmation prompts for destructive operations.
+//!
+//! Prompts are only shown when stdin and stderr are terminals. `-y/--yes` answers every
+//! confirmation with yes; without a terminal, confirmations also proceed (as scripts
+//! expect) and the decision is logged.
+
+use std::io::IsTerminal;
+
+use dialoguer::theme::{ColorfulTheme, SimpleTheme, Theme};
+use dialoguer::{Confirm, Select};
+use tracing::info;
+
+use crate::error::{Error, Result};
+
+/// Asks the user before destructive operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prompter {
+    assume_yes: bool,
+    interactive: bool,
+    color: bool,
+}
+
+impl Prompter {
+    pub fn new(assume_yes: bool, color: bool) -> Self {
+        Self {
+            assume_yes,
+            interactive: std::io::stdin().is_terminal() && std::io::stderr().is_terminal(),
+            color,
+        }
+    }
+
+    /// A prompter that never asks, answering as if run without a terminal.
+    #[cfg(test)]
+    pub fn non_interactive(assume_yes: bool) -> Self {
+        Self {
+            assume_yes,
+            interactive: false,
+            color: false,
+        }
+    }
+
+    /// The same prompter, answering every confirmation with yes.
+    pub fn assuming_yes(self) -> Self {
+        Self {
+            assume_yes: true,
+            ..self
+        }
+    }
+
+    /// Whether `--yes` was given.
+    pub fn assume_yes(self) -> bool {
+        self.assume_yes
+    }
+
+    /// Whether a question can be put to the user.
+    pub fn is_interactive(self) -> bool {
+        self.interactive && !self.assume_yes
+    }
+
+    /// Asks a yes/no `question` (defaulting to no); `--yes` and non-interactive runs
+    /// proceed without asking.
+    pub fn confirm(self, question: &str) -> Result<bool> {
+        if self.assume_yes {
+            info!("{} Yes (--yes)", question);
+            return Ok(true);
+        }
+        if !self.interactive {
+            info!("{} Yes (not a terminal)", question);
+            return Ok(true);
+        }
+        Confirm::with_theme(self.theme().as_ref())
+            .with_prompt(question)
+            .default(false)
+            .interact()
+            .map_err(prompt_error)
+    }
+
+    /// Lets the user pick one of `items`; `None` if the prompt was dismissed.
+    ///
+    /// Only call when [`Prompter::is_interactive`].
+    pub fn select(self, question: &str, items: &[&str]) -> Result<Option<usize>> {
+        Select::with_theme(self.theme().as_ref())
+            .with_prompt(question)
+            .items(items)
+            .default(0)
+            .interact_opt()
+            .map_err(prompt_error)
+    }
+
+    fn theme(self) -> Box<dyn Theme> {
+        if self.color {
+            Box::new(ColorfulTheme::default())
+        } else {
+            Box::new(SimpleTheme)
+        }
+    }
+}
+
+fn prompt_error(e: dialoguer::Error) -> Error {
+    let dialoguer::Error::IO(e) = e;
+    Error::Other(format!("Prompt failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_interactive_confirms() {
+        assert!(Prompter::non_interactive(false)
+            .confirm("Proceed?")
+            .unwrap());
+        assert!(Prompter::non_interactive(true).confirm("Proceed?").unwrap());
+        assert!(!Prompter::non_interactive(false).is_interactive());
+    }
+}
@@ -0,0 +1,286 @@
+//! Reloading the configuration of long-running commands without a restart.
+//!
+//! A reload is requested with SIGHUP on Unix. On Windows, which has no such signal, it is
+//! requested by setting the named event `Local\<name>-reload-<pid>`, e.g. from PowerShell
+//! with `[Threading.EventWaitHandle]::OpenExisting('Local\<name>-reload-1234').Set()`.
+//! The configuration is loaded and validated again as at startup; only a valid one takes
+//! effect, with every changed key logged.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::Result;
+use crate::reporter::Reporter;
+use crate::settings::Settings;
+
+/// A configuration loaded again, not yet in effect.
+#[derive(Debug)]
+pub struct Reloaded {
+    pub config: Config,
+    settings: Settings,
+}
+
+impl Reloaded {
+    /// Loads and validates the configuration the way `ctx`'s was loaded.
+    pub fn load(ctx: &Context) -> Result<Self> {
+        let (config, settings) = ctx.reload_config()?;
+        Ok(Self { config, settings })
+    }
+
+    /// Puts the configuration into effect: the log level first, so that it applies to the
+    /// changes logged next, then the settings commands read from `ctx`.
+    pub fn apply(self, ctx: &mut Context) {
+        let level = &self.settings.log_level;
+        if level.value != ctx.settings.log_level.value {
+            let filter = tracing_subscriber::EnvFilter::new(level.value.as_filter());
+            match &ctx.log_filter {
+                Some(handle) if handle.reload(filter).is_ok() => {
+                    info!("Log level set to {} ({})", level.value, level.origin)
+                }
+                // RUST_LOG takes precedence
+                _ => debug!("Log level left to RUST_LOG"),
+            }
+        }
+
+        let changes = diff(&ctx.config, &self.config);
+        if changes.is_empty() {
+            info!("Configuration reloaded, nothing changed");
+        }
+        for change in &changes {
+            info!("Configuration changed: {}", change);
+        }
+
+        ctx.format = self.settings.format.value;
+        ctx.reporter = Reporter::new(self.settings.accessible.value, self.settings.format.value);
+        ctx.config = self.config;
+        ctx.settings = self.settings;
+    }
+}
+
+/// A configuration key (dotted, as `config get` takes it) whose value changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub key: String,
+    /// `None` if the key was added
+    pub old: Option<Value>,
+    /// `None` if the key was removed
+    pub new: Option<Value>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<Value>| {
+            value
+                .as_ref()
+                .map_or_else(|| String::from("(unset)"), Value::to_string)
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.key,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+/// The values that differ between `old` and `new`, by key. Lists count as single values.
+pub fn diff(old: &Config, new: &Config) -> Vec<Change> {
+    let mut old_values = BTreeMap::new();
+    let mut new_values = BTreeMap::new();
+    // Sections at their defaults are left out of the plain serialization, which would
+    // make their default values look added once anything else in them is set
+    flatten(old.to_full_value().unwrap_or_default(), "", &mut old_values);
+    flatten(new.to_full_value().unwrap_or_default(), "", &mut new_values);
+
+    let mut changes = Vec::new();
+    for (key, old) in &old_values {
+        if new_values.get(key) != Some(old) {
+            changes.push(Change {
+                key: key.clone(),
+                old: Some(old.clone()),
+                new: new_values.get(key).cloned(),
+            });
+        }
+    }
+    for (key, new) in new_values {
+        if !old_values.contains_key(&key) {
+            changes.push(Change {
+                key,
+                old: None,
+                new: Some(new),
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    changes
+}
+
+fn flatten(value: Value, prefix: &str, values: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(child, &key, values);
+            }
+        }
+        value => {
+            values.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+/// Requests to reload, as they arrive.
+#[cfg(unix)]
+pub struct Requests {
+    hangup: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Requests {
+    pub fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self {
+            hangup: signal(SignalKind::hangup())?,
+        })
+    }
+
+    /// Waits for the next request.
+    pub async fn recv(&mut self) {
+        self.hangup.recv().await;
+    }
+}
+
+/// Requests to reload, as they arrive.
+#[cfg(windows)]
+pub struct Requests {
+    events: tokio::sync::mpsc::UnboundedReceiver<()>,
+}
+
+#[cfg(windows)]
+impl Requests {
+    pub fn new() -> Result<Self> {
+        let (sender, events) = tokio::sync::mpsc::unbounded_channel();
+        let name = event::listen(sender)?;
+        info!("Set the event {} to reload the configuration", name);
+        Ok(Self { events })
+    }
+
+    /// Waits for the next request.
+    pub async fn recv(&mut self) {
+        if self.events.recv().await.is_none() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(windows)]
+mod event {
+    use std::ffi::c_void;
+    use std::io;
+
+    use tokio::sync::mpsc::UnboundedSender;
+
+    use crate::identity;
+
+    const INFINITE: u32 = 0xFFFF_FFFF;
+    const WAIT_OBJECT_0: u32 = 0;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateEventW(
+            attributes: *const c_void,
+            manual_reset: i32,
+            initial_state: i32,
+            name: *const u16,
+        ) -> *mut c_void;
+        fn WaitForSingleObject(handle: *mut c_void, milliseconds: u32) -> u32;
+    }
+
+    /// Creates the auto-reset event of this process and sends to `sender` each time it is
+    /// set, from a thread of its own. Returns the event's name.
+    pub fn listen(sender: UnboundedSender<()>) -> io::Result<String> {
+        let name = format!(
+            "Local\\{}-reload-{}",
+            identity::app_name(),
+            std::process::id()
+        );
+        let wide: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+        // SAFETY: `wide` is NUL-terminated; no security attributes are passed
+        let handle = unsafe { CreateEventW(std::ptr::null(), 0, 0, wide.as_ptr()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // The handle stays open for the life of the process; as an address it can be sent
+        // to the thread
+        let handle = handle as usize;
+        std::thread::spawn(move || loop {
+            // SAFETY: the handle is a valid event handle that is never closed
+            let waited = unsafe { WaitForSingleObject(handle as *mut c_void, INFINITE) };
+            if waited != WAIT_OBJECT_0 || sender.send(()).is_err() {
+                break;
+            }
+        });
+        Ok(name)
+    }
+}
+
+/// No way to request a reload.
+#[cfg(not(any(unix, windows)))]
+pub struct Requests;
+
+#[cfg(not(any(unix, windows)))]
+impl Requests {
+    pub fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    pub async fn recv(&mut self) {
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_diff() {
+        let old = Config::default();
+        let mut new = old.clone();
+        let profile = new.profiles.get_mut(&new.default_profile).unwrap();
+        profile.log_level = String::from("trace");
+        new.daemon.watch = vec![PathBuf::from("incoming")];
+        new.update_check.enabled = !old.update_check.enabled;
+
+        assert_eq!(diff(&old, &old), Vec::new());
+        let changes: Vec<String> = diff(&old, &new).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            changes,
+            [
+                String::from("daemon.watch: (unset) -> [\"incoming\"]"),
+                format!(
+                    "profiles.{}.log_level: \"{}\" -> \"trace\"",
+                    old.default_profile,
+                    old.active_profile().unwrap().log_level
+                ),
+                format!(
+                    "update_check.enabled: {} -> {}",
+                    old.update_check.enabled, new.update_check.enabled
+                ),
+            ]
+        );
+        // Removed keys show the other way round
+        assert_eq!(diff(&new, &old)[0].new, None);
+    }
+}
@@ -0,0 +1,343 @@
+//! Human-facing status output: step announcements, results, progress and tables.
+//!
+//! The standard reporter draws progress bars and bracketed status markers. The accessible
+//! reporter (`--a11y` or `ACCESSIBLE=1`) is meant for screen readers: no animation, no
+//! color, plain sentences for each step and labeled lines instead of aligned tables. The
+//! Markdown reporter (`--format markdown`) prints tables and results as Markdown. The
+//! JSON reporter (`--format json`) draws nothing and emits phase transitions as JSON lines
+//! on stderr instead.
+
+use std::time::Instant;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::i18n::tr;
+use crate::output::{reportln, OutputFormat};
+
+/// How status output is rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Reporter {
+    /// Progress bars, `[SUCCESS]`-style markers and aligned tables
+    #[default]
+    Standard,
+    /// Screen-reader friendly plain sentences
+    Accessible,
+    /// Markdown tables and emphasized results, without progress bars
+    Markdown,
+    /// Like the standard reporter, but with phase events on stderr instead of progress bars
+    Json,
+}
+
+impl Reporter {
+    /// Accessibility takes precedence over the output format.
+    pub fn new(accessible: bool, format: OutputFormat) -> Self {
+        if accessible {
+            Reporter::Accessible
+        } else {
+            match format {
+                OutputFormat::Markdown => Reporter::Markdown,
+                OutputFormat::Json => Reporter::Json,
+                OutputFormat::Text => Reporter::Standard,
+            }
+        }
+    }
+
+    pub fn is_accessible(self) -> bool {
+        self == Reporter::Accessible
+    }
+
+    /// Announces that a step has started.
+    ///
+    /// Only the accessible reporter prints anything; the standard one relies on progress
+    /// bars and log output.
+    pub fn step(self, sentence: &str) {
+        if self.is_accessible() {
+            eprintln!("{}", sentence);
+        }
+    }
+
+    /// Reports a successful outcome on stdout.
+    pub fn success(self, message: &str) {
+        match self {
+            Reporter::Standard | Reporter::Json => reportln!("[SUCCESS] {}", message),
+            Reporter::Accessible => reportln!("{}: {}", tr!("reporter-success"), message),
+            Reporter::Markdown => reportln!("**{}:** {}", tr!("reporter-success"), message),
+        }
+    }
+
+    /// Reports a failed check on stdout.
+    pub fn failure(self, message: &str) {
+        match self {
+            Reporter::Standard | Reporter::Json => reportln!("[FAIL] {}", message),
+            Reporter::Accessible => reportln!("{}: {}", tr!("reporter-failed"), message),
+            Reporter::Markdown => reportln!("**{}:** {}", tr!("reporter-failed"), message),
+        }
+    }
+
+    /// Reports something the user may want to act on, on stderr.
+    pub fn notice(self, message: &str) {
+        match self {
+            Reporter::Standard | Reporter::Json => eprintln!("[NOTICE] {}", message),
+            Reporter::Accessible => eprintln!("{}: {}", tr!("reporter-notice"), message),
+            Reporter::Markdown => eprintln!("**{}:** {}", tr!("reporter-notice"), message),
+        }
+    }
+
+    /// A byte progress bar with `message`, hidden unless using the standard reporter.
+    pub fn progress(self, total: u64, message: &str) -> ProgressBar {
+        if self != Reporter::Standard {
+            return ProgressBar::hidden();
+        }
+        let pb = ProgressBar::new(total);
+        pb.set_style(
+            ProgressStyle::with_template(
+                "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})"
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+        );
+        pb.set_message(message.to_string());
+        pb
+    }
+
+    /// Starts the phase `name` of a longer operation, announced as `sentence`.
+    ///
+    /// The standard reporter shows a byte progress bar when `total` is known and a
+    /// spinner otherwise; the JSON reporter emits `phase_started` and, once the phase
+    /// ends, `phase_finished` (or `phase_failed` if it is dropped unfinished).
+    pub fn phase(self, name: &'static str, sentence: &str, total: Option<u64>) -> Phase {
+        self.step(sentence);
+        let bar = match total {
+            _ if self != Reporter::Standard => ProgressBar::hidden(),
+            Some(total) => self.progress(total, sentence.trim_end_matches('.')),
+            None => {
+                let spinner = ProgressBar::new_spinner();
+                spinner.set_style(
+                    ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {msg}")
+                        .unwrap(),
+                );
+                spinner.set_message(sentence.trim_end_matches('.').to_string());
+                spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+                spinner
+            }
+        };
+        let phase = Phase {
+            reporter: self,
+            name,
+            bar,
+            started: Instant::now(),
+            finished: false,
+        };
+        phase.event("phase_started", serde_json::json!({ "total": total }));
+        phase
+    }
+
+    /// Prints `rows` under `headers`, indented by `indent` (except in Markdown).
+    ///
+    /// The standard reporter aligns columns; the accessible one prints each row as a
+    /// single line of `Header: value` pairs, and the Markdown one a pipe table.
+    pub fn table(self, indent: &str, headers: &[impl AsRef<str>], rows: &[Vec<String>]) {
+        // Indented Markdown would render as a code block
+        let indent = if self == Reporter::Markdown {
+            ""
+        } else {
+            indent
+        };
+        let headers: Vec<&str> = headers.iter().map(AsRef::as_ref).collect();
+        for line in self.table_lines(&headers, rows) {
+            reportln!("{}{}", indent, line);
+        }
+    }
+
+    /// Like [`Reporter::table`], but on stderr, for reports that must not mix with the
+    /// command's output.
+    pub fn table_on_stderr(self, indent: &str, headers: &[impl AsRef<str>], rows: &[Vec<String>]) {
+        let indent = if self == Reporter::Markdown {
+            ""
+        } else {
+            indent
+        };
+        let headers: Vec<&str> = headers.iter().map(AsRef::as_ref).collect();
+        for line in self.table_lines(&headers, rows) {
+            eprintln!("{}{}", indent, line);
+        }
+    }
+
+    /// Writes a JSON event line on stderr (JSON reporter only).
+    fn event(self, event: &str, phase: &str, mut fields: serde_json::Value) {
+        if self != Reporter::Json {
+            return;
+        }
+        if let Some(fields) = fields.as_object_mut() {
+            fields.insert(String::from("event"), event.into());
+            fields.insert(String::from("phase"), phase.into());
+        }
+        eprintln!("{}", fields);
+    }
+
+    fn table_lines(self, headers: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+        match self {
+            Reporter::Standard | Reporter::Json => {
+                let widths: Vec<usize> = headers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, header)| {
+                        rows.iter()
+                            .filter_map(|row| row.get(i))
+                            .map(|cell| cell.chars().count())
+                            .chain([header.chars().count()])
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect();
+                let format_row = |cells: Vec<&str>| {
+                    let last = cells.len().saturating_sub(1);
+                    cells
+                        .iter()
+                        .enumerate()
+                        .map(|(i, cell)| {
+                            if i == last {
+                                cell.to_string()
+                            } else {
+                                format!("{:<width$}", cell, width = widths[i])
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join("  ")
+                };
+                std::iter::once(format_row(headers.to_vec()))
+                    .chain(
+                        rows.iter()
+                            .map(|row| format_row(row.iter().map(String::as_str).collect())),
+                    )
+                    .collect()
+            }
+            Reporter::Markdown => {
+                let format_row = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+                let escape = |cell: &str| cell.replace('|', "\\|");
+                [
+                    format_row(headers.iter().map(|header| escape(header)).collect()),
+                    format_row(headers.iter().map(|_| String::from("---")).collect()),
+                ]
+                .into_iter()
+                .chain(
+                    rows.iter()
+                        .map(|row| format_row(row.iter().map(|cell| escape(cell)).collect())),
+                )
+                .collect()
+            }
+            Reporter::Accessible => rows
+                .iter()
+                .map(|row| {
+                    headers
+                        .iter()
+                        .zip(row)
+                        .map(|(header, cell)| format!("{}: {}", header, cell))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Progress of one phase started with [`Reporter::phase`].
+pub struct Phase {
+    reporter: Reporter,
+    name: &'static str,
+    bar: ProgressBar,
+    started: Instant,
+    finished: bool,
+}
+
+impl Phase {
+    pub fn set_position(&self, position: u64) {
+        self.bar.set_position(position);
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+    }
+
+    /// Replaces the text next to the spinner or bar, e.g. with a count of items done.
+    pub fn set_message(&self, message: String) {
+        self.bar.set_message(message);
+    }
+
+    /// Ends the phase successfully.
+    pub fn finish(mut self) {
+        self.finished = true;
+        self.event(
+            "phase_finished",
+            serde_json::json!({ "position": self.bar.position() }),
+        );
+    }
+
+    fn event(&self, event: &str, mut fields: serde_json::Value) {
+        if let Some(fields) = fields.as_object_mut() {
+            fields.insert(
+                String::from("seconds"),
+                self.started.elapsed().as_secs_f64().into(),
+            );
+        }
+        self.reporter.event(event, self.name, fields);
+    }
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+        if !self.finished {
+            self.event("phase_failed", serde_json::json!({}));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<Vec<String>> {
+        vec![
+            vec![String::from("id"), String::from("integer")],
+            vec![String::from("description"), String::from("text")],
+        ]
+    }
+
+    #[test]
+    fn test_standard_table_is_aligned() {
+        assert_eq!(
+            Reporter::Standard.table_lines(&["Column", "Type"], &rows()),
+            vec![
+                "Column       Type",
+                "id           integer",
+                "description  text"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_accessible_table_is_labeled() {
+        assert_eq!(
+            Reporter::Accessible.table_lines(&["Column", "Type"], &rows()),
+            vec![
+                "Column: id, Type: integer",
+                "Column: description, Type: text"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_table() {
+        let rows = vec![vec![String::from("a|b"), String::from("text")]];
+        assert_eq!(
+            Reporter::Markdown.table_lines(&["Column", "Type"], &rows),
+            vec!["| Column | Type |", "| --- | --- |", "| a\\|b | text |"]
+        );
+        assert_eq!(
+            Reporter::new(true, OutputFormat::Markdown),
+            Reporter::Accessible
+        );
+        assert_eq!(Reporter::new(false, OutputFormat::Json), Reporter::Json);
+    }
+}
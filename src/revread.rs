@@ -0,0 +1,72 @@
+//! Reading from the end of a seekable input in blocks, so that the last lines of a
+//! multi-gigabyte file are found without scanning all of it.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Size of each backwards read.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Byte offset at which the last `lines` lines of `reader` start, or 0 if it has fewer.
+/// A line break at the very end terminates the last line rather than starting another.
+pub fn tail_start<R: Read + Seek>(reader: &mut R, lines: usize) -> io::Result<u64> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if lines == 0 {
+        return Ok(len);
+    }
+
+    let mut remaining = lines;
+    let mut block = vec![0; BLOCK_SIZE];
+    let mut end = len;
+    while end > 0 {
+        let start = end.saturating_sub(BLOCK_SIZE as u64);
+        let block = &mut block[..(end - start) as usize];
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(block)?;
+        for (i, &byte) in block.iter().enumerate().rev() {
+            let position = start + i as u64;
+            if byte != b'\n' || position == len - 1 {
+                continue;
+            }
+            remaining -= 1;
+            if remaining == 0 {
+                return Ok(position + 1);
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn tail(content: &[u8], lines: usize) -> &[u8] {
+        let start = tail_start(&mut Cursor::new(content), lines).unwrap();
+        &content[start as usize..]
+    }
+
+    #[test]
+    fn test_tail_start() {
+        assert_eq!(tail(b"a\nb\nc\n", 2), b"b\nc\n");
+        assert_eq!(tail(b"a\nb\nc", 2), b"b\nc");
+        assert_eq!(tail(b"a\nb\nc\n", 5), b"a\nb\nc\n");
+        assert_eq!(tail(b"a\nb\n", 0), b"");
+        assert_eq!(tail(b"", 3), b"");
+        assert_eq!(tail(b"a\r\n\nb\r\n", 2), b"\nb\r\n");
+    }
+
+    #[test]
+    fn test_tail_spans_blocks() {
+        let mut content = Vec::new();
+        for i in 0..50_000 {
+            content.extend_from_slice(format!("line {}\n", i).as_bytes());
+        }
+        assert!(content.len() > 3 * BLOCK_SIZE);
+        let expected: Vec<u8> = (40_000..50_000)
+            .flat_map(|i| format!("line {}\n", i).into_bytes())
+            .collect();
+        assert_eq!(tail(&content, 10_000), expected.as_slice());
+    }
+}
@@ -0,0 +1,230 @@
+//! Cron-style schedules: `minute hour day-of-month month day-of-week`, in UTC.
+//!
+//! Each field is `*`, a number, a range `a-b`, or a list of those separated by commas, and
+//! may be followed by a step (`*/15`, `8-18/2`). Day-of-week 0 and 7 are Sunday. As in
+//! cron, when both day fields are restricted a day matching either one is due. `@hourly`,
+//! `@daily`, `@weekly` and `@monthly` stand for the usual expressions.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+
+const DAY_SECONDS: u64 = 86_400;
+
+/// How far ahead due times are searched for: long enough for February 29 to fall on any
+/// day of the week.
+const HORIZON_DAYS: u64 = 366 * 28;
+
+/// A parsed schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day-of-month and day-of-week were restricted (not `*`)
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let invalid =
+            |reason: String| Error::Other(format!("Invalid schedule '{}': {}", expression, reason));
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(invalid(format!(
+                "expected 5 fields (minute hour day month weekday), found {}",
+                fields.len()
+            )));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7).map_err(&invalid)?;
+        // Sunday is both 0 and 7
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).map_err(&invalid)?,
+            hours: parse_field(hour, 0, 23).map_err(&invalid)?,
+            days: parse_field(day, 1, 31).map_err(&invalid)?,
+            months: parse_field(month, 1, 12).map_err(&invalid)?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+
+    /// The first due time strictly after `time`, or `None` if the schedule never comes due
+    /// (e.g. February 30).
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let start = (seconds / 60 + 1) * 60;
+        let first_day = start / DAY_SECONDS;
+        for day in first_day..first_day + HORIZON_DAYS {
+            if !self.is_due_on(day) {
+                continue;
+            }
+            let from = if day == first_day {
+                (start % DAY_SECONDS) / 60
+            } else {
+                0
+            };
+            let minute_of_day = (from..24 * 60).find(|minute| {
+                self.hours & (1 << (minute / 60)) != 0 && self.minutes & (1 << (minute % 60)) != 0
+            });
+            if let Some(minute_of_day) = minute_of_day {
+                let due = day * DAY_SECONDS + minute_of_day * 60;
+                return Some(UNIX_EPOCH + Duration::from_secs(due));
+            }
+        }
+        None
+    }
+
+    /// Whether the day `day` (counted from 1970-01-01) matches the day fields.
+    fn is_due_on(&self, day: u64) -> bool {
+        let (_, month, day_of_month) = civil_date(day);
+        // 1970-01-01 was a Thursday
+        let weekday = (day + 4) % 7;
+        let day_matches = self.days & (1 << day_of_month) != 0;
+        let weekday_matches = self.weekdays & (1 << weekday) != 0;
+        let days = if self.days_restricted && self.weekdays_restricted {
+            day_matches || weekday_matches
+        } else {
+            day_matches && weekday_matches
+        };
+        days && self.months & (1 << month) != 0
+    }
+}
+
+/// Bit set of the values between `min` and `max` that a field selects.
+fn parse_field(field: &str, min: u64, max: u64) -> std::result::Result<u64, String> {
+    let mut set = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<u64>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("bad step in '{}'", item)),
+            },
+            None => (item, 1),
+        };
+        let number = |text: &str| match text.parse::<u64>() {
+            Ok(n) if (min..=max).contains(&n) => Ok(n),
+            _ => Err(format!(
+                "'{}' is not a number from {} to {}",
+                text, min, max
+            )),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (number(first)?, number(last)?),
+            // A single value with a step runs to the end of the field, as in cron
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if first > last {
+            return Err(format!("empty range '{}'", range));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+/// `time` as `YYYY-MM-DD HH:MM UTC`.
+pub fn format_utc(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (year, month, day) = civil_date(seconds / DAY_SECONDS);
+    let minute_of_day = seconds % DAY_SECONDS / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        minute_of_day / 60,
+        minute_of_day % 60
+    )
+}
+
+/// Year, month (1-12) and day of month of the day `day` counted from 1970-01-01.
+fn civil_date(day: u64) -> (u64, u64, u64) {
+    // Days from 0000-03-01, so that leap days end the (March-based) year
+    let days = day + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    (year, month, day_of_month)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds since the epoch of a UTC date and time.
+    fn at(days: u64, hour: u64, minute: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(days * DAY_SECONDS + hour * 3600 + minute * 60)
+    }
+
+    // 2024-02-29 (a Thursday) is day 19782
+    const LEAP_DAY: u64 = 19_782;
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(LEAP_DAY), (2024, 2, 29));
+        assert_eq!(civil_date(LEAP_DAY + 1), (2024, 3, 1));
+        assert_eq!(format_utc(at(LEAP_DAY, 9, 5)), "2024-02-29 09:05 UTC");
+    }
+
+    #[test]
+    fn test_next_after() {
+        let next = |expression: &str, time| Schedule::parse(expression).unwrap().next_after(time);
+        let noon = at(LEAP_DAY, 12, 0);
+        assert_eq!(next("* * * * *", noon), Some(at(LEAP_DAY, 12, 1)));
+        assert_eq!(next("*/15 * * * *", noon), Some(at(LEAP_DAY, 12, 15)));
+        assert_eq!(next("30 9-17/4 * * *", noon), Some(at(LEAP_DAY, 13, 30)));
+        assert_eq!(next("@daily", noon), Some(at(LEAP_DAY + 1, 0, 0)));
+        // Sunday as 7; 2024-03-03 is the next Sunday
+        assert_eq!(next("0 6 * * 7", noon), Some(at(LEAP_DAY + 3, 6, 0)));
+        // Either day field matches when both are restricted: the 1st, or a Friday
+        assert_eq!(next("0 0 1 * 5", noon), Some(at(LEAP_DAY + 1, 0, 0)));
+        assert_eq!(
+            next("0 0 29 2 *", noon),
+            Some(at(LEAP_DAY + 366 + 365 * 3, 0, 0))
+        );
+        assert_eq!(next("0 0 30 2 *", noon), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expression in [
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+        ] {
+            assert!(Schedule::parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Sandboxed rhai scripts used by the `script` transform.
+//!
+//! A script is evaluated once per line with `line` (the text without its terminator) and
+//! `line_number` (1-based) in scope. The value of its last expression is the replacement:
+//! a string replaces the line, `()` drops it.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, EvalAltResult, Position, Scope, AST};
+use tracing::debug;
+
+use crate::error::{Error, Result};
+
+/// Default wall-clock budget for evaluating a single line.
+pub const DEFAULT_BUDGET_MS: u64 = 100;
+
+/// How many operations run between checks of the time budget.
+const BUDGET_CHECK_INTERVAL: u64 = 1024;
+
+/// A compiled script together with its sandboxed engine.
+#[derive(Clone)]
+pub struct Script {
+    path: PathBuf,
+    budget: Duration,
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    deadline: Arc<Mutex<Instant>>,
+}
+
+impl Script {
+    /// Reads and compiles the script at `path`.
+    pub fn load(path: &Path, budget: Duration) -> Result<Self> {
+        let source = std::fs::read_to_string(path).map_err(|source| Error::File {
+            action: "read script",
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let deadline = Arc::new(Mutex::new(Instant::now() + budget));
+        let engine = sandboxed_engine(Arc::clone(&deadline));
+        let ast = engine.compile(&source).map_err(|e| {
+            Error::Other(format!(
+                "Script syntax error at {}: {}",
+                location(path, e.1),
+                e.0
+            ))
+        })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            budget,
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+            deadline,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Evaluates the script for one line, returning the replacement or `None` to drop it.
+    pub fn apply_line(&self, line: &str, line_number: usize) -> Result<Option<String>> {
+        *self.deadline.lock().unwrap() = Instant::now() + self.budget;
+
+        let mut scope = Scope::new();
+        scope.push("line", line.to_string());
+        scope.push("line_number", line_number as i64);
+
+        let value = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+            .map_err(|e| self.eval_error(*e, line_number))?;
+
+        if value.is_unit() {
+            Ok(None)
+        } else if value.is_string() {
+            Ok(Some(value.into_string().unwrap_or_default()))
+        } else {
+            Err(Error::Other(format!(
+                "Script '{}' returned {} for input line {}; expected a string or () to drop the line",
+                self.path.display(),
+                value.type_name(),
+                line_number
+            )))
+        }
+    }
+
+    fn eval_error(&self, mut error: EvalAltResult, line_number: usize) -> Error {
+        let position = error.take_position();
+        let message = match error {
+            EvalAltResult::ErrorTerminated(..) => format!(
+                "exceeded the per-line time budget of {}ms",
+                self.budget.as_millis()
+            ),
+            other => other.to_string(),
+        };
+        Error::Other(format!(
+            "Script error at {} (input line {}): {}",
+            location(&self.path, position),
+            line_number,
+            message
+        ))
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script")
+            .field("path", &self.path)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+impl PartialEq for Script {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.budget == other.budget
+    }
+}
+
+impl Eq for Script {}
+
+/// Builds an engine without module loading or `eval`, with resource limits and a
+/// progress hook that stops evaluation once `deadline` has passed.
+fn sandboxed_engine(deadline: Arc<Mutex<Instant>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(1024 * 1024);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+    engine.on_print(|text| debug!("script: {}", text));
+    engine.on_debug(|text, _, position| debug!("script {}: {}", position, text));
+    engine.on_progress(move |operations| {
+        let expired =
+            operations % BUDGET_CHECK_INTERVAL == 0 && Instant::now() > *deadline.lock().unwrap();
+        expired.then(|| Dynamic::from("time budget exceeded"))
+    });
+    engine
+}
+
+fn location(path: &Path, position: Position) -> String {
+    match (position.line(), position.position()) {
+        (Some(line), Some(column)) => format!("{}:{}:{}", path.display(), line, column),
+        (Some(line), None) => format!("{}:{}", path.display(), line),
+        _ => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn load_script(source: &str) -> (TempDir, Result<Script>) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.rhai");
+        std::fs::write(&path, source).unwrap();
+        let script = Script::load(&path, Duration::from_millis(DEFAULT_BUDGET_MS));
+        (dir, script)
+    }
+
+    #[test]
+    fn test_replace_and_drop() {
+        let (_dir, script) =
+            load_script(r##"if line.starts_with("#") { () } else { `${line_number}: ${line}` }"##);
+        let script = script.unwrap();
+        assert_eq!(script.apply_line("# note", 1).unwrap(), None);
+        assert_eq!(
+            script.apply_line("text", 2).unwrap(),
+            Some(String::from("2: text"))
+        );
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let (_dir, script) = load_script("let x = ;\nline");
+        let message = script.unwrap_err().to_string();
+        assert!(message.contains("test.rhai:1:"), "{}", message);
+    }
+
+    #[test]
+    fn test_runtime_error_location() {
+        let (_dir, script) = load_script("let x = 1;\nmissing_fn(line)");
+        let message = script.unwrap().apply_line("a", 3).unwrap_err().to_string();
+        assert!(message.contains("test.rhai:2:"), "{}", message);
+        assert!(message.contains("input line 3"), "{}", message);
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("loop.rhai");
+        std::fs::write(&path, "loop { }").unwrap();
+        let script = Script::load(&path, Duration::from_millis(10)).unwrap();
+        let message = script.apply_line("a", 1).unwrap_err().to_string();
+        assert!(message.contains("time budget of 10ms"), "{}", message);
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let (_dir, script) = load_script(r#"import "other" as o; line"#);
+        assert!(script.unwrap().apply_line("a", 1).is_err());
+
+        let (_dir, script) = load_script(r#"eval("line")"#);
+        assert!(script.is_err());
+    }
+
+    #[test]
+    fn test_non_string_result() {
+        let (_dir, script) = load_script("42");
+        let message = script.unwrap().apply_line("a", 1).unwrap_err().to_string();
+        assert!(message.contains("expected a string"), "{}", message);
+    }
+}
@@ -0,0 +1,150 @@
+//! Sensitive values kept in the OS keychain rather than the configuration file.
+//!
+//! Lookups check the environment first so CI can inject secrets without a keychain.
+//! On Linux the kernel keyring is used, which keeps entries until logout or reboot.
+
+use std::fmt;
+
+use serde::Serialize;
+use tracing::debug;
+
+use crate::env_vars;
+use crate::error::{Error, Result};
+use crate::identity;
+
+/// Secrets known to the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret {
+    /// Token sent to the GitHub API (raises rate limits, allows private releases)
+    GithubToken,
+}
+
+impl Secret {
+    pub const ALL: [Secret; 1] = [Secret::GithubToken];
+
+    /// Account name of the keychain entry.
+    pub fn name(self) -> &'static str {
+        match self {
+            Secret::GithubToken => "github-token",
+        }
+    }
+
+    /// Environment variables that override the keychain, in order of precedence.
+    pub fn env_vars(self) -> &'static [&'static str] {
+        match self {
+            Secret::GithubToken => &[env_vars::GITHUB_TOKEN, "GITHUB_TOKEN"],
+        }
+    }
+
+    fn entry(self) -> Result<keyring::Entry> {
+        keyring::Entry::new(identity::app_name(), self.name()).map_err(keyring_error)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Where a secret's value came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "name", rename_all = "lowercase")]
+pub enum Source {
+    Env(&'static str),
+    Keyring,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Env(name) => write!(f, "environment variable {}", name),
+            Source::Keyring => write!(f, "OS keyring"),
+        }
+    }
+}
+
+/// Looks up `secret`, preferring the environment over the keychain.
+///
+/// An unavailable keychain is treated as "not set" so commands keep working without one.
+pub fn get(secret: Secret) -> Option<(String, Source)> {
+    for name in secret.env_vars() {
+        if let Some(value) = env_vars::var(name).filter(|v| !v.is_empty()) {
+            return Some((value, Source::Env(name)));
+        }
+    }
+
+    match secret
+        .entry()
+        .and_then(|entry| entry.get_password().map_err(keyring_error))
+    {
+        Ok(value) => Some((value, Source::Keyring)),
+        Err(e) => {
+            debug!("No {} in keyring: {}", secret, e);
+            None
+        }
+    }
+}
+
+/// Stores `value` for `secret` in the keychain.
+pub fn set(secret: Secret, value: &str) -> Result<()> {
+    secret.entry()?.set_password(value).map_err(|e| {
+        Error::Other(format!(
+            "Failed to store {} in the OS keyring: {} (set {} instead)",
+            secret,
+            e,
+            secret.env_vars()[0]
+        ))
+    })
+}
+
+/// Removes `secret` from the keychain; returns whether an entry existed.
+pub fn delete(secret: Secret) -> Result<bool> {
+    match secret.entry()?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+/// Shortened form of a secret that is safe to display.
+pub fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+fn keyring_error(e: keyring::Error) -> Error {
+    match e {
+        keyring::Error::NoEntry => Error::Other(String::from("No keyring entry")),
+        other => Error::Other(format!("Keyring unavailable: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask() {
+        assert_eq!(mask("ghp_1234567890abcdef"), "ghp_...cdef");
+        assert_eq!(mask("short"), "*****");
+    }
+
+    #[test]
+    fn test_env_takes_precedence() {
+        std::env::set_var(env_vars::GITHUB_TOKEN, "from-env");
+        assert_eq!(
+            get(Secret::GithubToken),
+            Some((
+                String::from("from-env"),
+                Source::Env(env_vars::GITHUB_TOKEN)
+            ))
+        );
+        std::env::remove_var(env_vars::GITHUB_TOKEN);
+    }
+}
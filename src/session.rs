@@ -0,0 +1,229 @@
+//! Session capture and replay for bug reproduction.
+//!
+//! `--record` writes the arguments, relevant environment, effective configuration and
+//! input digests of an invocation to a JSON file; `--replay` re-executes that invocation
+//! with the same settings after confirming the inputs are unchanged.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::constants;
+use crate::digest::{self, HashAlgorithm};
+use crate::env_vars;
+use crate::error::{Error, Result};
+
+/// Placeholder stored instead of the value of sensitive environment variables.
+const REDACTED: &str = "<redacted>";
+
+/// Name fragments marking an environment variable as sensitive.
+const SENSITIVE_MARKERS: [&str; 5] = ["TOKEN", "SECRET", "PASSWORD", "KEY", "CREDENTIAL"];
+
+/// Environment variables outside the application prefix that affect behavior.
+const EXTRA_ENV: [&str; 1] = ["RUST_LOG"];
+
+/// A recorded invocation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    /// Version of the binary that recorded the session
+    pub version: String,
+
+    /// Command-line arguments (without the program name and recording flags)
+    pub args: Vec<String>,
+
+    /// Working directory at record time
+    pub cwd: PathBuf,
+
+    /// Application environment variables, with sensitive values redacted
+    pub env: BTreeMap<String, String>,
+
+    /// Effective configuration after file loading and environment overrides
+    pub config: Config,
+
+    /// Digests of the files the command read
+    pub inputs: Vec<InputDigest>,
+}
+
+/// SHA-256 digest of a single input file.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InputDigest {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+impl Session {
+    /// Captures the current invocation.
+    pub fn capture(args: Vec<String>, config: &Config, inputs: &[PathBuf]) -> Result<Self> {
+        let env = std::env::vars()
+            .filter(|(name, _)| is_recorded_env(name))
+            .map(|(name, value)| {
+                let value = if is_sensitive(&name) {
+                    String::from(REDACTED)
+                } else {
+                    value
+                };
+                (name, value)
+            })
+            .collect();
+
+        let inputs = inputs
+            .iter()
+            .map(|path| {
+                Ok(InputDigest {
+                    path: path.clone(),
+                    sha256: hash_file(path)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            version: String::from(constants::APP_VERSION),
+            args,
+            cwd: std::env::current_dir()?,
+            env,
+            config: config.clone(),
+            inputs,
+        })
+    }
+
+    /// Writes the session as pretty-printed JSON.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)?;
+        info!("Recorded session to: {}", path.display());
+        Ok(())
+    }
+
+    /// Reads a previously recorded session.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::File {
+            action: "read session file",
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Restores the recorded working directory and environment for a replay.
+    ///
+    /// Application variables not present in the session are removed; redacted values
+    /// are left as they are in the current environment.
+    pub fn apply_env(&self) {
+        if self.cwd.is_dir() {
+            if let Err(e) = std::env::set_current_dir(&self.cwd) {
+                warn!(
+                    "Could not enter recorded directory {}: {}",
+                    self.cwd.display(),
+                    e
+                );
+            }
+        }
+
+        for (name, _) in std::env::vars().filter(|(name, _)| is_recorded_env(name)) {
+            if !self.env.contains_key(&name) {
+                std::env::remove_var(name);
+            }
+        }
+
+        for (name, value) in &self.env {
+            if value != REDACTED {
+                std::env::set_var(name, value);
+            }
+        }
+    }
+
+    /// Warns about environment differences and fails if any input changed since recording.
+    pub fn verify(&self) -> Result<()> {
+        if self.version != constants::APP_VERSION {
+            warn!(
+                "Session was recorded with version {}, replaying with {}",
+                self.version,
+                constants::APP_VERSION
+            );
+        }
+
+        for (name, value) in &self.env {
+            if value == REDACTED && std::env::var_os(name).is_none() {
+                warn!("Redacted variable {} is not set for this replay", name);
+            }
+        }
+
+        let mut changed = Vec::new();
+        for input in &self.inputs {
+            match hash_file(&input.path) {
+                Ok(digest) if digest == input.sha256 => {}
+                Ok(_) => changed.push(format!("{} (content differs)", input.path.display())),
+                Err(_) => changed.push(format!("{} (missing)", input.path.display())),
+            }
+        }
+
+        if !changed.is_empty() {
+            return Err(Error::Other(format!(
+                "Inputs changed since the session was recorded: {}",
+                changed.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the command-line arguments with any `--record`/`--replay` flags removed.
+pub fn strip_session_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut stripped = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--record" || arg == "--replay" {
+            args.next();
+        } else if !(arg.starts_with("--record=") || arg.starts_with("--replay=")) {
+            stripped.push(arg);
+        }
+    }
+    stripped
+}
+
+fn is_recorded_env(name: &str) -> bool {
+    name.starts_with(env_vars::PREFIX) || EXTRA_ENV.contains(&name)
+}
+
+fn is_sensitive(name: &str) -> bool {
+    let upper = name.to_uppercase();
+    SENSITIVE_MARKERS
+        .iter()
+        .any(|marker| upper.contains(marker))
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    Ok(digest::digest_reader(File::open(path)?, HashAlgorithm::Sha256)?.hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_session_args() {
+        let args = [
+            "-v",
+            "--record",
+            "s.json",
+            "run",
+            "--replay=x",
+            "--input",
+            "a",
+        ]
+        .map(String::from);
+        assert_eq!(strip_session_args(args), vec!["-v", "run", "--input", "a"]);
+    }
+
+    #[test]
+    fn test_is_sensitive() {
+        assert!(is_sensitive("APP_GITHUB_TOKEN"));
+        assert!(is_sensitive("app_api_key"));
+        assert!(!is_sensitive("APP_OUTPUT_DIR"));
+    }
+}
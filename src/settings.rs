@@ -0,0 +1,467 @@
+//! Effective settings, resolved in one place with a fixed order of precedence:
+//! built-in defaults < configuration file < active profile < environment < command-line flags.
+//!
+//! Every value records where it came from, which `config show --origin` reports.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde::Serialize;
+
+use crate::args::{GlobalArgs, LogLevel};
+use crate::config::{Config, Profile};
+use crate::env_vars;
+use crate::error::{Error, Result};
+use crate::output::{ColorChoice, OutputFormat};
+
+/// Where an effective value came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Origin {
+    /// Built-in default
+    Default,
+    /// Top-level key in a configuration file
+    File { file: PathBuf },
+    /// Key of the active profile in a configuration file
+    Profile { profile: String, file: PathBuf },
+    /// Environment variable
+    Env { name: &'static str },
+    /// Command-line flag
+    Flag { name: &'static str },
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File { file } => write!(f, "config file {}", file.display()),
+            Origin::Profile { profile, file } => {
+                write!(f, "profile '{}' in {}", profile, file.display())
+            }
+            Origin::Env { name } => write!(f, "environment variable {}", name),
+            Origin::Flag { name } => write!(f, "flag {}", name),
+        }
+    }
+}
+
+/// A value together with its origin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub origin: Origin,
+}
+
+impl<T> Setting<T> {
+    fn default(value: T) -> Self {
+        Self {
+            value,
+            origin: Origin::Default,
+        }
+    }
+
+    /// A value read from the loaded configuration, which serde filled with defaults
+    /// for keys the file left out.
+    fn loaded(value: T, origin: Option<Origin>) -> Self {
+        Self {
+            value,
+            origin: origin.unwrap_or(Origin::Default),
+        }
+    }
+
+    /// Replaces the value when a higher-precedence layer supplies one.
+    fn layer(&mut self, value: Option<T>, origin: Origin) {
+        if let Some(value) = value {
+            self.value = value;
+            self.origin = origin;
+        }
+    }
+}
+
+/// One row of `config show`.
+#[derive(Debug, Serialize)]
+pub struct Entry<'a> {
+    pub key: &'static str,
+    pub value: serde_json::Value,
+    pub origin: &'a Origin,
+}
+
+/// Every setting that more than one source can provide.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub config_file: Setting<String>,
+    pub profile: Setting<String>,
+    pub output_dir: Setting<PathBuf>,
+    pub log_level: Setting<LogLevel>,
+    pub parallel_jobs: Setting<u32>,
+    pub update_check: Setting<bool>,
+    pub update_check_budget_ms: Setting<u64>,
+    pub strict: Setting<bool>,
+    pub format: Setting<OutputFormat>,
+    pub color: Setting<ColorChoice>,
+    pub accessible: Setting<bool>,
+}
+
+impl Settings {
+    /// Resolves every setting from `config` (as loaded from its file), the environment
+    /// as seen through `env`, and the flags in `args`/`matches`.
+    pub fn resolve(
+        config: &Config,
+        args: &GlobalArgs,
+        matches: &ArgMatches,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let file = |key: &str| {
+            config
+                .origins
+                .get(key)
+                .map(|file| Origin::File { file: file.clone() })
+        };
+        let env_origin = |name: &'static str| Origin::Env { name };
+        let flag = |id: &str, name: &'static str| {
+            (matches.value_source(id) == Some(ValueSource::CommandLine))
+                .then_some(Origin::Flag { name })
+        };
+
+        let mut profile = Setting::loaded(config.default_profile.clone(), file("default_profile"));
+        profile.layer(env(env_vars::PROFILE), env_origin(env_vars::PROFILE));
+
+        // Profile values only count as set when a file wrote them; the built-in
+        // profiles' values are defaults
+        let name = profile.value.clone();
+        let fallback = Profile::default();
+        let active = config.profiles.get(&name).unwrap_or(&fallback);
+        let from_profile = |key: &str| {
+            config
+                .origins
+                .get(&format!("profiles.{}.{}", name, key))
+                .map(|file| Origin::Profile {
+                    profile: name.clone(),
+                    file: file.clone(),
+                })
+        };
+
+        let mut output_dir = Setting::loaded(active.output_dir.clone(), from_profile("output_dir"));
+        output_dir.layer(
+            env(env_vars::OUTPUT_DIR).map(PathBuf::from),
+            env_origin(env_vars::OUTPUT_DIR),
+        );
+
+        let mut log_level = Setting::default(LogLevel::default());
+        if let Some(origin) = from_profile("log_level") {
+            log_level.layer(Some(parse_log_level(&active.log_level)?), origin);
+        }
+        if let Some(value) = env(env_vars::LOG_LEVEL) {
+            let level = parse_log_level(&value).map_err(|e| invalid_env(env_vars::LOG_LEVEL, e))?;
+            log_level.layer(Some(level), env_origin(env_vars::LOG_LEVEL));
+        }
+        if let Some(origin) = flag("log_level", "--log-level") {
+            log_level.layer(args.log_level, origin);
+        }
+        if args.verbose > 0 {
+            let raised = log_level.value.increment(args.verbose);
+            log_level.layer(Some(raised), Origin::Flag { name: "--verbose" });
+        }
+
+        let mut parallel_jobs =
+            Setting::loaded(active.parallel_jobs, from_profile("parallel_jobs"));
+        if let Some(value) = env(env_vars::PARALLEL_JOBS) {
+            let jobs = value
+                .parse::<u32>()
+                .ok()
+                .filter(|jobs| *jobs > 0)
+                .ok_or_else(|| {
+                    invalid_env(env_vars::PARALLEL_JOBS, "expected a positive number")
+                })?;
+            parallel_jobs.layer(Some(jobs), env_origin(env_vars::PARALLEL_JOBS));
+        }
+
+        let mut update_check =
+            Setting::loaded(config.update_check.enabled, file("update_check.enabled"));
+        update_check.layer(
+            env(env_vars::UPDATE_CHECK)
+                .map(|value| matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on")),
+            env_origin(env_vars::UPDATE_CHECK),
+        );
+
+        let update_check_budget_ms = Setting::loaded(
+            config.update_check.budget_ms,
+            file("update_check.budget_ms"),
+        );
+
+        let mut format = Setting::loaded(
+            active.output_format.unwrap_or_default(),
+            from_profile("output_format"),
+        );
+        if let Some(origin) = flag("format", "--format") {
+            format.layer(Some(args.format), origin);
+        }
+
+        let mut strict = Setting::loaded(config.strict, file("strict"));
+        if let Some(origin) = flag("strict_config", "--strict-config") {
+            strict.layer(Some(args.strict_config), origin);
+        }
+
+        Ok(Self {
+            config_file: Setting {
+                value: args.config.clone(),
+                origin: match arg_origin(matches, "config", "--config", env_vars::CONFIG) {
+                    // Parsed from the environment, but reset by --no-env
+                    Origin::Env { name } if env(name).is_none() => Origin::Default,
+                    origin => origin,
+                },
+            },
+            profile,
+            output_dir,
+            log_level,
+            parallel_jobs,
+            update_check,
+            update_check_budget_ms,
+            strict,
+            format,
+            color: Setting {
+                value: args.color,
+                origin: arg_origin(matches, "color", "--color", ""),
+            },
+            accessible: Setting {
+                value: args.accessible,
+                origin: arg_origin(matches, "accessible", "--a11y", env_vars::ACCESSIBLE),
+            },
+        })
+    }
+
+    /// Writes the effective values back into `config`, so code reading the
+    /// configuration sees the same values as the settings report.
+    pub fn apply(&self, config: &mut Config) {
+        config.default_profile = self.profile.value.clone();
+        config.update_check.enabled = self.update_check.value;
+        config.update_check.budget_ms = self.update_check_budget_ms.value;
+        if let Some(profile) = config.profiles.get_mut(&self.profile.value) {
+            profile.output_dir = self.output_dir.value.clone();
+            profile.parallel_jobs = self.parallel_jobs.value;
+            if self.log_level.origin != Origin::Default {
+                profile.log_level = self.log_level.value.to_string();
+            }
+        }
+    }
+
+    /// All settings as `config show` lists them.
+    pub fn entries(&self) -> Vec<Entry<'_>> {
+        fn entry<'a, T: Serialize>(key: &'static str, setting: &'a Setting<T>) -> Entry<'a> {
+            Entry {
+                key,
+                value: serde_json::to_value(&setting.value).unwrap_or_default(),
+                origin: &setting.origin,
+            }
+        }
+        fn named<'a, T: ValueEnum>(key: &'static str, setting: &'a Setting<T>) -> Entry<'a> {
+            let value = setting
+                .value
+                .to_possible_value()
+                .map(|v| v.get_name().to_string());
+            Entry {
+                key,
+                value: serde_json::Value::from(value.unwrap_or_default()),
+                origin: &setting.origin,
+            }
+        }
+
+        vec![
+            entry("config_file", &self.config_file),
+            entry("profile", &self.profile),
+            entry("output_dir", &self.output_dir),
+            Entry {
+                key: "log_level",
+                value: serde_json::Value::from(self.log_level.value.to_string()),
+                origin: &self.log_level.origin,
+            },
+            entry("parallel_jobs", &self.parallel_jobs),
+            entry("update_check.enabled", &self.update_check),
+            entry("update_check.budget_ms", &self.update_check_budget_ms),
+            entry("strict", &self.strict),
+            named("format", &self.format),
+            named("color", &self.color),
+            entry("a11y", &self.accessible),
+        ]
+    }
+}
+
+/// Origin of a clap argument that may also be read from `env_name` by clap itself.
+fn arg_origin(
+    matches: &ArgMatches,
+    id: &str,
+    flag: &'static str,
+    env_name: &'static str,
+) -> Origin {
+    match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => Origin::Flag { name: flag },
+        Some(ValueSource::EnvVariable) => Origin::Env { name: env_name },
+        _ => Origin::Default,
+    }
+}
+
+/// Parses a profile or environment log level; profiles also accept `trace`.
+fn parse_log_level(value: &str) -> Result<LogLevel> {
+    if value.eq_ignore_ascii_case("trace") {
+        return Ok(LogLevel::Debug);
+    }
+    LogLevel::from_str(value).map_err(|e| Error::Other(e.to_string()))
+}
+
+fn invalid_env(name: &str, reason: impl fmt::Display) -> Error {
+    Error::Other(format!("Invalid value for {}: {}", name, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Args, FromArgMatches};
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn resolve(config: &Config, argv: &[&str], env: &[(&str, &str)]) -> Result<Settings> {
+        let command = GlobalArgs::augment_args(clap::Command::new("test"));
+        let matches = command
+            .try_get_matches_from(std::iter::once("test").chain(argv.iter().copied()))
+            .unwrap();
+        let args = GlobalArgs::from_arg_matches(&matches).unwrap();
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Settings::resolve(config, &args, &matches, |name| env.get(name).cloned())
+    }
+
+    fn load(contents: &str) -> (TempDir, PathBuf, Config) {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        std::fs::write(&path, contents).unwrap();
+        let config = Config::load(path.to_str().unwrap(), false).unwrap();
+        (temp_dir, path, config)
+    }
+
+    #[test]
+    fn test_defaults() {
+        let settings = resolve(&Config::default(), &[], &[]).unwrap();
+        assert_eq!(settings.profile.value, "local");
+        assert_eq!(settings.profile.origin, Origin::Default);
+        // The built-in profile's log level is not an explicit setting
+        assert_eq!(settings.log_level.value, LogLevel::Error);
+        assert_eq!(settings.output_dir.value, PathBuf::from("./output"));
+        assert!(settings
+            .entries()
+            .iter()
+            .all(|entry| *entry.origin == Origin::Default));
+    }
+
+    #[test]
+    fn test_precedence_file_profile_env_flag() {
+        let (_dir, path, config) = load(
+            r#"{"default_profile": "ci", "update_check": {"budget_ms": 50},
+                "profiles": {"ci": {"output_dir": "/ci", "log_level": "warning", "parallel_jobs": 2}}}"#,
+        );
+
+        let settings = resolve(&config, &[], &[]).unwrap();
+        assert_eq!(settings.profile.value, "ci");
+        assert_eq!(settings.profile.origin, Origin::File { file: path.clone() });
+        assert_eq!(settings.log_level.value, LogLevel::Warning);
+        assert_eq!(
+            settings.log_level.origin,
+            Origin::Profile {
+                profile: String::from("ci"),
+                file: path.clone()
+            }
+        );
+        assert_eq!(settings.update_check_budget_ms.value, 50);
+
+        let settings = resolve(
+            &config,
+            &["--log-level", "debug"],
+            &[
+                (env_vars::LOG_LEVEL, "info"),
+                (env_vars::PARALLEL_JOBS, "6"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(settings.log_level.value, LogLevel::Debug);
+        assert_eq!(
+            settings.log_level.origin,
+            Origin::Flag {
+                name: "--log-level"
+            }
+        );
+        assert_eq!(settings.parallel_jobs.value, 6);
+        assert_eq!(
+            settings.parallel_jobs.origin,
+            Origin::Env {
+                name: env_vars::PARALLEL_JOBS
+            }
+        );
+        assert_eq!(settings.output_dir.value, PathBuf::from("/ci"));
+    }
+
+    #[test]
+    fn test_verbose_raises_resolved_level() {
+        let (_dir, _path, config) = load(r#"{"profiles": {"local": {"log_level": "warning"}}}"#);
+        let settings = resolve(&config, &["-v"], &[]).unwrap();
+        assert_eq!(settings.log_level.value, LogLevel::Notice);
+        assert_eq!(
+            settings.log_level.origin,
+            Origin::Flag { name: "--verbose" }
+        );
+    }
+
+    #[test]
+    fn test_env_override_applies_to_config() {
+        let mut config = Config::default();
+        let settings = resolve(
+            &config,
+            &[],
+            &[
+                (env_vars::PROFILE, "ci"),
+                (env_vars::OUTPUT_DIR, "/custom/output"),
+            ],
+        )
+        .unwrap();
+        settings.apply(&mut config);
+
+        assert_eq!(config.default_profile, "ci");
+        assert_eq!(
+            config.profiles["ci"].output_dir,
+            PathBuf::from("/custom/output")
+        );
+        // Untouched values keep the profile's own settings
+        assert_eq!(config.profiles["ci"].log_level, "error");
+        assert_eq!(config.profiles["ci"].parallel_jobs, 1);
+    }
+
+    #[test]
+    fn test_invalid_env_values() {
+        let message = resolve(&Config::default(), &[], &[(env_vars::PARALLEL_JOBS, "0")])
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains(env_vars::PARALLEL_JOBS), "{}", message);
+        assert!(resolve(&Config::default(), &[], &[(env_vars::LOG_LEVEL, "loud")]).is_err());
+    }
+
+    #[test]
+    fn test_flag_origins() {
+        let settings = resolve(
+            &Config::default(),
+            &["--format", "json", "--strict-config"],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(settings.format.value, OutputFormat::Json);
+        assert_eq!(settings.format.origin, Origin::Flag { name: "--format" });
+        assert_eq!(
+            settings.strict.origin,
+            Origin::Flag {
+                name: "--strict-config"
+            }
+        );
+        assert_eq!(settings.color.origin, Origin::Default);
+    }
+}
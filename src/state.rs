@@ -0,0 +1,170 @@
+//! What earlier runs saw of their inputs, so incremental runs can skip unchanged files.
+//!
+//! A state file in the user's cache directory records the size, modification time and
+//! BLAKE3 hash of every input together with what was reported for it. An input whose size
+//! and modification time still match is unchanged without being read; one whose size
+//! matches but whose time does not is hashed, so touching a file does not count as a
+//! change.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::digest::{self, HashAlgorithm};
+use crate::error::{Error, Result};
+use crate::identity;
+
+/// An input as it was last seen, and what was recorded for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry<T> {
+    size: u64,
+    modified: SystemTime,
+    hash: String,
+    record: T,
+}
+
+/// State of one kind of run, loaded from and saved to a file.
+#[derive(Debug)]
+pub struct StateFile<T> {
+    path: PathBuf,
+    previous: BTreeMap<String, Entry<T>>,
+    current: BTreeMap<String, Entry<T>>,
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> StateFile<T> {
+    /// The state stored under `key` in the user's cache directory, or `None` if there is no
+    /// cache directory. A missing or unreadable state starts empty.
+    pub fn open(key: &str) -> Option<Self> {
+        let dirs = directories::ProjectDirs::from("", "", identity::app_name())?;
+        Some(Self::load(
+            dirs.cache_dir().join("state").join(format!("{}.json", key)),
+        ))
+    }
+
+    fn load(path: PathBuf) -> Self {
+        let previous = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|e| {
+                debug!("Ignoring unreadable state file {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self {
+            path,
+            previous,
+            current: BTreeMap::new(),
+        }
+    }
+
+    /// What was recorded for `path` if the file is unchanged since, keeping it for the next
+    /// run.
+    pub fn unchanged(&mut self, path: &Path) -> Result<Option<T>> {
+        let key = path.to_string_lossy().into_owned();
+        let Some(entry) = self.previous.remove(&key) else {
+            return Ok(None);
+        };
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?;
+        if metadata.len() != entry.size {
+            return Ok(None);
+        }
+        if modified != entry.modified && hash(path)? != entry.hash {
+            return Ok(None);
+        }
+        let record = entry.record.clone();
+        self.current.insert(key, Entry { modified, ..entry });
+        Ok(Some(record))
+    }
+
+    /// Records `record` for the file at `path` as it is now.
+    pub fn record(&mut self, path: &Path, record: T) -> Result<()> {
+        let metadata = fs::metadata(path)?;
+        let entry = Entry {
+            size: metadata.len(),
+            modified: metadata.modified()?,
+            hash: hash(path)?,
+            record,
+        };
+        self.current
+            .insert(path.to_string_lossy().into_owned(), entry);
+        Ok(())
+    }
+
+    /// Replaces the state file with the files seen by this run, so files no longer among
+    /// the inputs are forgotten.
+    pub fn save(&self) -> Result<()> {
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        let file_error = |action| {
+            move |source| Error::File {
+                action,
+                path: self.path.clone(),
+                source,
+            }
+        };
+        fs::create_dir_all(dir).map_err(file_error("create directory for"))?;
+        let mut file = tempfile::NamedTempFile::new_in(dir).map_err(file_error("write to"))?;
+        file.write_all(&serde_json::to_vec(&self.current)?)?;
+        file.persist(&self.path)
+            .map_err(|e| file_error("write to")(e.error))?;
+        debug!(
+            "Saved state of {} file(s) to {}",
+            self.current.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+fn hash(path: &Path) -> Result<String> {
+    Ok(digest::digest_reader(File::open(path)?, HashAlgorithm::Blake3)?.hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("state.json");
+        let (a, b) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        fs::write(&a, "one\n").unwrap();
+        fs::write(&b, "two\n").unwrap();
+
+        let mut state = StateFile::load(state_path.clone());
+        assert_eq!(state.unchanged(&a).unwrap(), None);
+        state.record(&a, 1).unwrap();
+        state.record(&b, 2).unwrap();
+        state.save().unwrap();
+
+        // Touching a file is not a change, new content of the same size is
+        let mut state = StateFile::load(state_path.clone());
+        File::options()
+            .write(true)
+            .open(&a)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(state.unchanged(&a).unwrap(), Some(1));
+        fs::write(&b, "owt\n").unwrap();
+        File::options()
+            .write(true)
+            .open(&b)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(state.unchanged(&b).unwrap(), None);
+        state.save().unwrap();
+
+        // Only what this run kept or recorded is remembered
+        let mut state = StateFile::<i32>::load(state_path);
+        assert_eq!(state.unchanged(&a).unwrap(), Some(1));
+        assert_eq!(state.unchanged(&b).unwrap(), None);
+    }
+}
@@ -0,0 +1,715 @@
+//! Text statistics computed by the `run` command.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::hll::HyperLogLog;
+
+/// Statistics of the files of a run, and their sum.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub per_file: Vec<FileStats>,
+    pub totals: FileStats,
+}
+
+impl StatsReport {
+    /// Adds up `per_file` into the totals. Top words and the advanced and whitespace
+    /// measures cannot be summed from per-file figures, so the totals leave them out.
+    pub fn new(per_file: Vec<FileStats>) -> Self {
+        let mut totals = FileStats::default();
+        let mut total_length = 0.0;
+        let mut min_length = usize::MAX;
+        for stats in &per_file {
+            totals.lines += stats.lines;
+            totals.words += stats.words;
+            totals.bytes += stats.bytes;
+            totals.chars += stats.chars;
+            totals.graphemes += stats.graphemes;
+            totals.blank_lines += stats.blank_lines;
+            totals.line_length.max = totals.line_length.max.max(stats.line_length.max);
+            if stats.lines > 0 {
+                total_length += stats.line_length.avg * stats.lines as f64;
+                min_length = min_length.min(stats.line_length.min);
+            }
+        }
+        if totals.lines > 0 {
+            totals.line_length.min = min_length;
+            totals.line_length.avg = total_length / totals.lines as f64;
+        }
+        Self { per_file, totals }
+    }
+
+    /// One flat row per file, then a `total` row, for tabular formats such as CSV.
+    pub fn rows(&self) -> Vec<StatsRow> {
+        let row = |path: String, stats: &FileStats| StatsRow {
+            path,
+            lines: stats.lines,
+            words: stats.words,
+            bytes: stats.bytes,
+            chars: stats.chars,
+            graphemes: stats.graphemes,
+            blank_lines: stats.blank_lines,
+            min_line_length: stats.line_length.min,
+            max_line_length: stats.line_length.max,
+            avg_line_length: stats.line_length.avg,
+        };
+        self.per_file
+            .iter()
+            .map(|stats| {
+                let path = stats.path.as_ref().map(|path| path.display().to_string());
+                row(path.unwrap_or_default(), stats)
+            })
+            .chain(std::iter::once(row(String::from("total"), &self.totals)))
+            .collect()
+    }
+}
+
+/// The counts of a [`FileStats`] without nested values.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsRow {
+    pub path: String,
+    pub lines: usize,
+    pub words: usize,
+    pub bytes: usize,
+    pub chars: usize,
+    pub graphemes: usize,
+    pub blank_lines: usize,
+    pub min_line_length: usize,
+    pub max_line_length: usize,
+    pub avg_line_length: f64,
+}
+
+/// Statistics for a single piece of text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileStats {
+    /// File the statistics are for, in a [`StatsReport`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub lines: usize,
+    pub words: usize,
+    pub bytes: usize,
+    pub chars: usize,
+    pub graphemes: usize,
+    pub blank_lines: usize,
+    pub line_length: LineLengths,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_words: Option<Vec<WordCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advanced: Option<AdvancedStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub whitespace: Option<WhitespaceStats>,
+}
+
+/// Line endings, trailing whitespace and indentation of a text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WhitespaceStats {
+    pub line_endings: LineEndings,
+    pub lf_lines: usize,
+    pub crlf_lines: usize,
+    /// Lines ending in spaces or tabs (before the line ending)
+    pub trailing_whitespace_lines: usize,
+    pub indentation: Indentation,
+    pub tab_indented_lines: usize,
+    pub space_indented_lines: usize,
+    /// Whether the text ends with a line ending (false for empty text)
+    pub final_newline: bool,
+}
+
+/// The line endings a text uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    /// A single line without a line ending, or no text
+    #[default]
+    None,
+    Lf,
+    Crlf,
+    Mixed,
+}
+
+/// What the indented lines of a text start with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Indentation {
+    /// No line is indented
+    #[default]
+    None,
+    Tabs,
+    Spaces,
+    Mixed,
+}
+
+impl fmt::Display for LineEndings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineEndings::None => "none",
+            LineEndings::Lf => "lf",
+            LineEndings::Crlf => "crlf",
+            LineEndings::Mixed => "mixed",
+        })
+    }
+}
+
+impl fmt::Display for Indentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Indentation::None => "none",
+            Indentation::Tabs => "tabs",
+            Indentation::Spaces => "spaces",
+            Indentation::Mixed => "mixed",
+        })
+    }
+}
+
+impl WhitespaceStats {
+    /// Measures `content`.
+    pub fn compute(content: &str) -> Self {
+        let mut whitespace = WhitespaceAccumulator::default();
+        whitespace.feed(content);
+        whitespace.finish()
+    }
+}
+
+/// Running state of [`WhitespaceStats`].
+#[derive(Clone, Default)]
+struct WhitespaceAccumulator {
+    stats: WhitespaceStats,
+    /// Whether any text was fed, which then decides `final_newline`
+    fed: bool,
+}
+
+impl WhitespaceAccumulator {
+    fn feed(&mut self, content: &str) {
+        let stats = &mut self.stats;
+        for line in content.split_inclusive('\n') {
+            let body = match line.strip_suffix('\n') {
+                Some(body) => match body.strip_suffix('\r') {
+                    Some(body) => {
+                        stats.crlf_lines += 1;
+                        body
+                    }
+                    None => {
+                        stats.lf_lines += 1;
+                        body
+                    }
+                },
+                None => line,
+            };
+            if body.ends_with([' ', '\t']) {
+                stats.trailing_whitespace_lines += 1;
+            }
+            match body.chars().next() {
+                Some('\t') => stats.tab_indented_lines += 1,
+                Some(' ') => stats.space_indented_lines += 1,
+                _ => {}
+            }
+        }
+        if !content.is_empty() {
+            stats.final_newline = content.ends_with('\n');
+            self.fed = true;
+        }
+    }
+
+    fn merge(&mut self, other: &WhitespaceAccumulator) {
+        let stats = &mut self.stats;
+        stats.lf_lines += other.stats.lf_lines;
+        stats.crlf_lines += other.stats.crlf_lines;
+        stats.trailing_whitespace_lines += other.stats.trailing_whitespace_lines;
+        stats.tab_indented_lines += other.stats.tab_indented_lines;
+        stats.space_indented_lines += other.stats.space_indented_lines;
+        // The last chunk with any text decides how the text ends
+        if other.fed {
+            stats.final_newline = other.stats.final_newline;
+            self.fed = true;
+        }
+    }
+
+    fn finish(&self) -> WhitespaceStats {
+        let stats = &self.stats;
+        WhitespaceStats {
+            line_endings: match (stats.lf_lines, stats.crlf_lines) {
+                (0, 0) => LineEndings::None,
+                (_, 0) => LineEndings::Lf,
+                (0, _) => LineEndings::Crlf,
+                _ => LineEndings::Mixed,
+            },
+            indentation: match (stats.tab_indented_lines, stats.space_indented_lines) {
+                (0, 0) => Indentation::None,
+                (_, 0) => Indentation::Tabs,
+                (0, _) => Indentation::Spaces,
+                _ => Indentation::Mixed,
+            },
+            ..stats.clone()
+        }
+    }
+}
+
+/// Duplication and randomness measures, computed in bounded memory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AdvancedStats {
+    /// Estimated number of distinct lines (HyperLogLog)
+    pub distinct_lines: u64,
+    /// Fraction of lines that repeat an earlier line (0 when there are no lines)
+    pub duplication_ratio: f64,
+    /// Shannon entropy of the byte distribution, in bits per byte (0-8)
+    pub entropy_bits_per_byte: f64,
+}
+
+impl AdvancedStats {
+    /// Measures the lines of `content` and the byte distribution of `raw`.
+    pub fn compute(content: &str, raw: &[u8]) -> Self {
+        let mut advanced = AdvancedAccumulator::default();
+        advanced.feed(content);
+        advanced.feed_raw(raw);
+        advanced.finish()
+    }
+}
+
+/// Running state of [`AdvancedStats`].
+#[derive(Clone)]
+struct AdvancedAccumulator {
+    distinct: HyperLogLog,
+    lines: u64,
+    byte_counts: [u64; 256],
+}
+
+impl Default for AdvancedAccumulator {
+    fn default() -> Self {
+        Self {
+            distinct: HyperLogLog::default(),
+            lines: 0,
+            byte_counts: [0; 256],
+        }
+    }
+}
+
+impl AdvancedAccumulator {
+    fn feed(&mut self, content: &str) {
+        for line in content.lines() {
+            self.distinct.insert(line);
+            self.lines += 1;
+        }
+    }
+
+    fn feed_raw(&mut self, raw: &[u8]) {
+        for &b in raw {
+            self.byte_counts[b as usize] += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &AdvancedAccumulator) {
+        self.distinct.merge(&other.distinct);
+        self.lines += other.lines;
+        for (count, other) in self.byte_counts.iter_mut().zip(other.byte_counts) {
+            *count += other;
+        }
+    }
+
+    fn finish(&self) -> AdvancedStats {
+        // The estimate can overshoot slightly; never report more distinct lines than lines
+        let distinct_lines = self.distinct.estimate().min(self.lines);
+        AdvancedStats {
+            distinct_lines,
+            duplication_ratio: if self.lines > 0 {
+                1.0 - distinct_lines as f64 / self.lines as f64
+            } else {
+                0.0
+            },
+            entropy_bits_per_byte: entropy(&self.byte_counts),
+        }
+    }
+}
+
+/// Shannon entropy of `bytes` in bits per byte.
+#[cfg(test)]
+fn byte_entropy(bytes: &[u8]) -> f64 {
+    let mut advanced = AdvancedAccumulator::default();
+    advanced.feed_raw(bytes);
+    entropy(&advanced.byte_counts)
+}
+
+/// Shannon entropy of a byte distribution, in bits per byte.
+fn entropy(counts: &[u64; 256]) -> f64 {
+    let total = counts.iter().sum::<u64>() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Line length distribution, measured in characters excluding line terminators.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineLengths {
+    pub min: usize,
+    pub max: usize,
+    pub avg: f64,
+}
+
+/// Occurrences of a normalized (lowercased, punctuation-trimmed) word.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WordCount {
+    pub word: String,
+    pub count: usize,
+}
+
+impl FileStats {
+    /// Computes statistics for `content`, including the `top_words` most frequent words if requested.
+    pub fn from_content(content: &str, top_words: Option<usize>) -> Self {
+        let mut stats = StatsAccumulator::new(top_words, false, false);
+        stats.feed(content);
+        stats.finish()
+    }
+}
+
+/// A [`FileStats`] computed piece by piece, for input read in chunks or split across
+/// threads. Every chunk but the last must end with a line break, so that no line, word
+/// or grapheme spans two chunks.
+#[derive(Clone)]
+pub struct StatsAccumulator {
+    report: FileStats,
+    total_length: usize,
+    min_length: usize,
+    top_words: Option<usize>,
+    word_counts: HashMap<String, usize>,
+    advanced: Option<AdvancedAccumulator>,
+    whitespace: Option<WhitespaceAccumulator>,
+}
+
+impl StatsAccumulator {
+    pub fn new(top_words: Option<usize>, advanced: bool, whitespace: bool) -> Self {
+        Self {
+            report: FileStats::default(),
+            total_length: 0,
+            min_length: usize::MAX,
+            top_words,
+            word_counts: HashMap::new(),
+            advanced: advanced.then(AdvancedAccumulator::default),
+            whitespace: whitespace.then(WhitespaceAccumulator::default),
+        }
+    }
+
+    /// Adds the text of a chunk. Lines, words, characters and graphemes are counted in
+    /// one pass, line by line: lines are found with memchr, and ASCII lines (checked a word
+    /// at a time) are counted on their bytes without decoding characters.
+    pub fn feed(&mut self, content: &str) {
+        let report = &mut self.report;
+        report.bytes += content.len();
+        let mut rest = content;
+        while !rest.is_empty() {
+            let (mut line, terminated) = match memchr::memchr(b'\n', rest.as_bytes()) {
+                Some(end) => {
+                    let line = &rest[..end];
+                    rest = &rest[end + 1..];
+                    (line, true)
+                }
+                None => (std::mem::take(&mut rest), false),
+            };
+            // The line ending is one grapheme, "\r\n" included
+            let mut ending_chars = usize::from(terminated);
+            if terminated {
+                if let Some(stripped) = line.strip_suffix('\r') {
+                    line = stripped;
+                    ending_chars = 2;
+                }
+            }
+            let (length, words, graphemes) = if line.is_ascii() {
+                (line.len(), ascii_words(line.as_bytes()), line.len())
+            } else {
+                (
+                    utf8_chars(line.as_bytes()),
+                    line.split_whitespace().count(),
+                    line.graphemes(true).count(),
+                )
+            };
+            report.lines += 1;
+            report.words += words;
+            report.chars += length + ending_chars;
+            report.graphemes += graphemes + usize::from(terminated);
+            if words == 0 {
+                report.blank_lines += 1;
+            }
+            self.total_length += length;
+            self.min_length = self.min_length.min(length);
+            report.line_length.max = report.line_length.max.max(length);
+        }
+
+        if self.top_words.is_some() {
+            for word in content.split_whitespace() {
+                let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+                if !word.is_empty() {
+                    *self.word_counts.entry(word.to_lowercase()).or_default() += 1;
+                }
+            }
+        }
+        if let Some(advanced) = &mut self.advanced {
+            advanced.feed(content);
+        }
+        if let Some(whitespace) = &mut self.whitespace {
+            whitespace.feed(content);
+        }
+    }
+
+    /// Adds the undecoded bytes of a chunk, measured by the byte entropy.
+    pub fn feed_raw(&mut self, raw: &[u8]) {
+        if let Some(advanced) = &mut self.advanced {
+            advanced.feed_raw(raw);
+        }
+    }
+
+    /// Adds the chunks counted by `other`, which followed those counted here.
+    pub fn merge(&mut self, other: StatsAccumulator) {
+        let report = &mut self.report;
+        report.lines += other.report.lines;
+        report.words += other.report.words;
+        report.bytes += other.report.bytes;
+        report.chars += other.report.chars;
+        report.graphemes += other.report.graphemes;
+        report.blank_lines += other.report.blank_lines;
+        report.line_length.max = report.line_length.max.max(other.report.line_length.max);
+        self.total_length += other.total_length;
+        self.min_length = self.min_length.min(other.min_length);
+        for (word, count) in other.word_counts {
+            *self.word_counts.entry(word).or_default() += count;
+        }
+        if let (Some(advanced), Some(other)) = (&mut self.advanced, &other.advanced) {
+            advanced.merge(other);
+        }
+        if let (Some(whitespace), Some(other)) = (&mut self.whitespace, &other.whitespace) {
+            whitespace.merge(other);
+        }
+    }
+
+    pub fn finish(self) -> FileStats {
+        let mut report = self.report;
+        if report.lines > 0 {
+            report.line_length.min = self.min_length;
+            report.line_length.avg = self.total_length as f64 / report.lines as f64;
+        }
+        report.top_words = self.top_words.map(|n| most_frequent(self.word_counts, n));
+        report.advanced = self.advanced.map(|advanced| advanced.finish());
+        report.whitespace = self.whitespace.map(|whitespace| whitespace.finish());
+        report
+    }
+}
+
+/// Number of whitespace-separated words in ASCII text.
+fn ascii_words(bytes: &[u8]) -> usize {
+    let mut words = 0;
+    let mut in_space = true;
+    for &byte in bytes {
+        // The ASCII characters of `char::is_whitespace`: \t, \n, \v, \f, \r and space
+        let space = matches!(byte, b'\t'..=b'\r' | b' ');
+        words += usize::from(in_space && !space);
+        in_space = space;
+    }
+    words
+}
+
+/// Number of characters in UTF-8 text: the bytes that are not continuation bytes.
+fn utf8_chars(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&byte| (byte as i8) >= -0x40).count()
+}
+
+/// Returns the `n` most frequent words, ties broken alphabetically.
+fn most_frequent(counts: HashMap<String, usize>, n: usize) -> Vec<WordCount> {
+    let mut counts: Vec<WordCount> = counts
+        .into_iter()
+        .map(|(word, count)| WordCount { word, count })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+    counts.truncate(n);
+    counts
+}
+
+/// Decimal megabytes per second; zero when no time was measured.
+pub fn throughput_mb_per_sec(bytes: u64, seconds: f64) -> f64 {
+    if seconds > 0.0 {
+        bytes as f64 / 1_000_000.0 / seconds
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_mb_per_sec() {
+        assert_eq!(throughput_mb_per_sec(5_000_000, 2.0), 2.5);
+        assert_eq!(throughput_mb_per_sec(1_000, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_single_pass_matches_std() {
+        let samples = [
+            "",
+            "\n",
+            "one\n\ntwo  three\n",
+            "no final newline",
+            "crlf\r\nline\r\n\r\n",
+            "bare\rcarriage\r",
+            "\t\x0b\x0c \n tabs\tand\x0bvertical\n",
+            "naïve café\u{a0}nbsp\u{3000}ideographic\n",
+            "e\u{301}\u{1f468}\u{200d}\u{1f469}\r\n\u{2028}\n",
+            "  \u{85}\n",
+        ];
+        for content in samples {
+            let stats = FileStats::from_content(content, None);
+            let lines: Vec<&str> = content.lines().collect();
+            assert_eq!(stats.lines, lines.len(), "{:?}", content);
+            assert_eq!(
+                stats.words,
+                content.split_whitespace().count(),
+                "{:?}",
+                content
+            );
+            assert_eq!(stats.chars, content.chars().count(), "{:?}", content);
+            assert_eq!(
+                stats.graphemes,
+                content.graphemes(true).count(),
+                "{:?}",
+                content
+            );
+            let blank = lines.iter().filter(|l| l.trim().is_empty()).count();
+            assert_eq!(stats.blank_lines, blank, "{:?}", content);
+            let max = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+            assert_eq!(stats.line_length.max, max, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn test_basic_counts() {
+        let report = FileStats::from_content("Hello, world!\n\nSecond line here\n", None);
+        assert_eq!(report.lines, 3);
+        assert_eq!(report.words, 5);
+        assert_eq!(report.bytes, 32);
+        assert_eq!(report.blank_lines, 1);
+        assert_eq!(report.line_length.min, 0);
+        assert_eq!(report.line_length.max, 16);
+        assert!((report.line_length.avg - 29.0 / 3.0).abs() < f64::EPSILON);
+        assert!(report.top_words.is_none());
+    }
+
+    #[test]
+    fn test_stats_report_totals() {
+        let mut first = FileStats::from_content("ab\ncdef\n", None);
+        first.path = Some(PathBuf::from("a.txt"));
+        let second = FileStats::from_content("\n\nxyz uvw\n", None);
+        let report = StatsReport::new(vec![first, second, FileStats::default()]);
+        let totals = &report.totals;
+        assert_eq!((totals.lines, totals.words, totals.bytes), (5, 4, 18));
+        assert_eq!(totals.blank_lines, 2);
+        assert_eq!((totals.line_length.min, totals.line_length.max), (0, 7));
+        assert!((totals.line_length.avg - 13.0 / 5.0).abs() < f64::EPSILON);
+
+        let rows = report.rows();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].path, "a.txt");
+        assert_eq!(rows[3].path, "total");
+        assert_eq!(rows[3].words, 4);
+    }
+
+    #[test]
+    fn test_unicode_counts() {
+        // "e" + combining acute accent is two chars but one grapheme
+        let report = FileStats::from_content("cafe\u{301} 👍", None);
+        assert_eq!(report.chars, 7);
+        assert_eq!(report.graphemes, 6);
+        assert_eq!(report.bytes, 11);
+    }
+
+    #[test]
+    fn test_advanced_stats() {
+        let stats = AdvancedStats::compute("a\nb\na\na\n", b"aaaa");
+        assert_eq!(stats.distinct_lines, 2);
+        assert!((stats.duplication_ratio - 0.5).abs() < f64::EPSILON);
+        assert_eq!(stats.entropy_bits_per_byte, 0.0);
+
+        assert!((byte_entropy(b"ab") - 1.0).abs() < f64::EPSILON);
+        let all: Vec<u8> = (0..=255).collect();
+        assert!((byte_entropy(&all) - 8.0).abs() < 1e-9);
+        assert_eq!(AdvancedStats::compute("", b""), AdvancedStats::default());
+    }
+
+    #[test]
+    fn test_whitespace_stats() {
+        let stats = WhitespaceStats::compute("\tone \r\n  two\nthree\t\n");
+        assert_eq!(stats.line_endings, LineEndings::Mixed);
+        assert_eq!((stats.lf_lines, stats.crlf_lines), (2, 1));
+        assert_eq!(stats.trailing_whitespace_lines, 2);
+        assert_eq!(stats.indentation, Indentation::Mixed);
+        assert_eq!(
+            (stats.tab_indented_lines, stats.space_indented_lines),
+            (1, 1)
+        );
+        assert!(stats.final_newline);
+
+        let stats = WhitespaceStats::compute("a\r\n  b");
+        assert_eq!(stats.line_endings, LineEndings::Crlf);
+        assert_eq!(stats.indentation, Indentation::Spaces);
+        assert!(!stats.final_newline);
+        assert_eq!(WhitespaceStats::compute(""), WhitespaceStats::default());
+    }
+
+    #[test]
+    fn test_accumulator_merges_chunks() {
+        let content = "The cat sat\n\nthe cat ran away\nlast line";
+        let mut whole = StatsAccumulator::new(Some(2), true, true);
+        whole.feed(content);
+        whole.feed_raw(content.as_bytes());
+
+        let mut first = StatsAccumulator::new(Some(2), true, true);
+        let mut second = first.clone();
+        let (head, tail) = content.split_at(content.find("\nthe").unwrap() + 1);
+        first.feed(head);
+        first.feed_raw(head.as_bytes());
+        second.feed(tail);
+        second.feed_raw(tail.as_bytes());
+        first.merge(second);
+
+        assert_eq!(first.finish(), whole.finish());
+    }
+
+    #[test]
+    fn test_empty_content() {
+        let report = FileStats::from_content("", Some(3));
+        assert_eq!(
+            report,
+            FileStats {
+                top_words: Some(vec![]),
+                ..FileStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_top_words() {
+        let report = FileStats::from_content("The cat. the DOG, the cat; a bird", Some(3));
+        assert_eq!(
+            report.top_words.unwrap(),
+            vec![
+                WordCount {
+                    word: String::from("the"),
+                    count: 3
+                },
+                WordCount {
+                    word: String::from("cat"),
+                    count: 2
+                },
+                WordCount {
+                    word: String::from("a"),
+                    count: 1
+                },
+            ]
+        );
+    }
+}
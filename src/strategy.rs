@@ -0,0 +1,265 @@
+//! How `run` reads an input, chosen by its size: whole in memory, streamed in chunks, or
+//! in chunks analyzed on several threads.
+
+use std::fmt;
+use std::io::{self, Read};
+use std::num::NonZeroUsize;
+use std::thread;
+
+use clap::ValueEnum;
+
+use crate::cancel;
+use crate::encoding::{self, Encoding};
+use crate::stats::{FileStats, StatsAccumulator};
+
+/// Inputs from this size (on disk) are streamed by `Auto`.
+pub const STREAMING_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Inputs from this size (on disk) are analyzed in parallel by `Auto`.
+pub const PARALLEL_THRESHOLD: u64 = 256 * 1024 * 1024;
+
+/// Target size of a chunk; a chunk extends to the end of its last line. Tests use small
+/// chunks so that several fit in little input.
+const CHUNK_SIZE: usize = if cfg!(test) {
+    64 * 1024
+} else {
+    4 * 1024 * 1024
+};
+
+/// Processing strategy for an input.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Choose by input size: memory below 16 MiB, streaming below 256 MiB, parallel above
+    #[default]
+    Auto,
+    /// Read the whole input into memory
+    Memory,
+    /// Read the input in chunks, keeping memory bounded
+    Streaming,
+    /// Read the input in chunks and analyze them on all cores, keeping memory bounded
+    Parallel,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+impl Strategy {
+    /// The strategy for an input of `size` bytes; only `Auto` depends on the size.
+    pub fn resolve(self, size: u64) -> Strategy {
+        match self {
+            Strategy::Auto if size >= PARALLEL_THRESHOLD => Strategy::Parallel,
+            Strategy::Auto if size >= STREAMING_THRESHOLD => Strategy::Streaming,
+            Strategy::Auto => Strategy::Memory,
+            strategy => strategy,
+        }
+    }
+}
+
+/// Computes statistics for UTF-8 text read from `reader` in chunks, one at a time for
+/// `Streaming` and a batch per available core for `Parallel`.
+///
+/// Returns `None` when the input is binary, not UTF-8, or not valid UTF-8 throughout,
+/// as decoding other encodings needs the whole input; the caller then starts over in
+/// memory.
+pub fn read_stats(
+    reader: &mut dyn Read,
+    strategy: Strategy,
+    encoding: Encoding,
+    top_words: Option<usize>,
+    advanced: bool,
+    whitespace: bool,
+) -> io::Result<Option<FileStats>> {
+    let threads = match strategy {
+        Strategy::Parallel => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        _ => 1,
+    };
+
+    let mut total = StatsAccumulator::new(top_words, advanced, whitespace);
+    let mut bytes = 0;
+    let mut carry = Vec::new();
+    let mut first = true;
+    let mut eof = false;
+    while !eof {
+        if cancel::is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        let mut batch = Vec::with_capacity(threads);
+        while batch.len() < threads && !eof {
+            let chunk;
+            (chunk, eof) = next_chunk(reader, &mut carry)?;
+            if !chunk.is_empty() {
+                batch.push(chunk);
+            }
+        }
+        if first {
+            if let Some(chunk) = batch.first() {
+                if encoding::is_binary(chunk, encoding)
+                    || encoding::decode(chunk, encoding).encoding != Encoding::Utf8
+                {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let analyzed: Vec<Option<StatsAccumulator>> = if batch.len() > 1 {
+            thread::scope(|scope| {
+                let workers: Vec<_> = batch
+                    .iter()
+                    .enumerate()
+                    .map(|(i, chunk)| {
+                        let bom = first && i == 0;
+                        scope.spawn(move || analyze(chunk, bom, top_words, advanced, whitespace))
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .map(|worker| worker.join().expect("chunk analysis panicked"))
+                    .collect()
+            })
+        } else {
+            batch
+                .iter()
+                .map(|chunk| analyze(chunk, first, top_words, advanced, whitespace))
+                .collect()
+        };
+        for stats in analyzed {
+            let Some(stats) = stats else {
+                return Ok(None);
+            };
+            total.merge(stats);
+        }
+        bytes += batch.iter().map(Vec::len).sum::<usize>();
+        first = false;
+    }
+
+    let mut stats = total.finish();
+    // Like the in-memory path, count the bytes read rather than the decoded text
+    stats.bytes = bytes;
+    Ok(Some(stats))
+}
+
+/// Statistics for one chunk, or `None` if it is not valid UTF-8. A byte order mark at the
+/// start of the input is not counted as text.
+fn analyze(
+    chunk: &[u8],
+    start: bool,
+    top_words: Option<usize>,
+    advanced: bool,
+    whitespace: bool,
+) -> Option<StatsAccumulator> {
+    let text = std::str::from_utf8(chunk).ok()?;
+    let text = if start {
+        text.strip_prefix('\u{feff}').unwrap_or(text)
+    } else {
+        text
+    };
+    let mut stats = StatsAccumulator::new(top_words, advanced, whitespace);
+    stats.feed(text);
+    stats.feed_raw(chunk);
+    Some(stats)
+}
+
+/// Reads the next chunk of at least [`CHUNK_SIZE`] bytes ending with a line break, or the
+/// rest of the input, and whether the input is exhausted. The start of the following
+/// line is kept in `carry` for the next chunk.
+fn next_chunk(reader: &mut dyn Read, carry: &mut Vec<u8>) -> io::Result<(Vec<u8>, bool)> {
+    let mut chunk = std::mem::take(carry);
+    loop {
+        let wanted = CHUNK_SIZE as u64;
+        let read = Read::take(&mut *reader, wanted).read_to_end(&mut chunk)?;
+        if (read as u64) < wanted {
+            return Ok((chunk, true));
+        }
+        // A line longer than a chunk makes the chunk grow until the line ends
+        if let Some(end) = chunk.iter().rposition(|&b| b == b'\n') {
+            *carry = chunk.split_off(end + 1);
+            return Ok((chunk, false));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Strategy::Auto.resolve(1024), Strategy::Memory);
+        assert_eq!(
+            Strategy::Auto.resolve(STREAMING_THRESHOLD),
+            Strategy::Streaming
+        );
+        assert_eq!(
+            Strategy::Auto.resolve(PARALLEL_THRESHOLD),
+            Strategy::Parallel
+        );
+        assert_eq!(
+            Strategy::Memory.resolve(PARALLEL_THRESHOLD),
+            Strategy::Memory
+        );
+        assert_eq!(Strategy::Parallel.resolve(0), Strategy::Parallel);
+    }
+
+    #[test]
+    fn test_chunked_stats_match_in_memory() {
+        // Several chunks, with a line spanning the first chunk boundary
+        let line = "the quick brown fox jumps over the lazy dog \u{e9}t\u{e9}\r\n";
+        let mut content = String::from("\u{feff}");
+        while content.len() < CHUNK_SIZE * 3 {
+            content.push_str(line);
+            content.push_str("\n   \n");
+        }
+        content.push_str("no trailing newline");
+
+        let mut expected =
+            FileStats::from_content(content.strip_prefix('\u{feff}').unwrap(), Some(3));
+        expected.bytes = content.len();
+        for strategy in [Strategy::Streaming, Strategy::Parallel] {
+            let stats = read_stats(
+                &mut content.as_bytes(),
+                strategy,
+                Encoding::Auto,
+                Some(3),
+                false,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+            assert_eq!(stats, expected, "{}", strategy);
+        }
+    }
+
+    #[test]
+    fn test_non_utf8_is_left_to_memory() {
+        let latin1 = b"caf\xe9\n";
+        let stats = read_stats(
+            &mut &latin1[..],
+            Strategy::Streaming,
+            Encoding::Auto,
+            None,
+            false,
+            false,
+        )
+        .unwrap();
+        assert!(stats.is_none());
+
+        let binary = b"\x00\x01\x02";
+        let stats = read_stats(
+            &mut &binary[..],
+            Strategy::Streaming,
+            Encoding::Auto,
+            None,
+            false,
+            false,
+        )
+        .unwrap();
+        assert!(stats.is_none());
+    }
+}
@@ -0,0 +1,152 @@
+//! "Did you mean" suggestions for mistyped subcommands, flags and configuration keys.
+
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::Command;
+
+/// Levenshtein distance between two strings, by characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// The candidate closest to `input`, if it is close enough to be a likely typo: within
+/// two edits (a third of the length for long names), or extending `input` as a prefix.
+pub fn closest<'a>(input: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (input.chars().count() / 3).max(2);
+    candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = edit_distance(input, candidate);
+            let prefix = input.len() >= 2 && candidate.starts_with(input);
+            (distance <= limit || prefix).then_some((distance, candidate))
+        })
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Adds suggestions to a clap parse error where clap has none of its own: unknown
+/// subcommands are matched against `plugins` as well as the built-in commands, and
+/// unknown flags against the flags of every subcommand.
+pub fn improve_error(mut error: clap::Error, command: &Command, plugins: &[String]) -> clap::Error {
+    match error.kind() {
+        ErrorKind::InvalidSubcommand => {
+            let Some(ContextValue::String(name)) = error.get(ContextKind::InvalidSubcommand) else {
+                return error;
+            };
+            let builtins = command
+                .get_subcommands()
+                .filter(|sub| !sub.is_hide_set())
+                .flat_map(|sub| std::iter::once(sub.get_name()).chain(sub.get_all_aliases()));
+            let candidates = builtins.chain(plugins.iter().map(String::as_str));
+            if let Some(suggestion) = closest(name, candidates) {
+                let suggestion = suggestion.to_string();
+                error.insert(
+                    ContextKind::SuggestedSubcommand,
+                    ContextValue::Strings(vec![suggestion]),
+                );
+            }
+        }
+        ErrorKind::UnknownArgument if error.get(ContextKind::SuggestedArg).is_none() => {
+            let Some(ContextValue::String(arg)) = error.get(ContextKind::InvalidArg) else {
+                return error;
+            };
+            let Some(flag) = arg.strip_prefix("--") else {
+                return error;
+            };
+            let flag = flag.split('=').next().unwrap_or(flag);
+
+            let mut options = Vec::new();
+            collect_flags(command, command.get_name(), &mut options);
+            let names = options.iter().map(|(_, long)| long.as_str());
+            if let Some(long) = closest(flag, names) {
+                let (path, long) = options
+                    .iter()
+                    .find(|(_, candidate)| candidate == long)
+                    .expect("suggestion is one of the options");
+                let tip = format!("'--{}' is an option of '{}'", long, path);
+                error.insert(
+                    ContextKind::Suggested,
+                    ContextValue::StyledStrs(vec![tip.into()]),
+                );
+            }
+        }
+        _ => {}
+    }
+    error
+}
+
+/// Collects `(command path, long flag)` for every subcommand below `command`.
+fn collect_flags(command: &Command, path: &str, options: &mut Vec<(String, String)>) {
+    for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        let sub_path = format!("{} {}", path, sub.get_name());
+        for arg in sub.get_arguments() {
+            if let Some(long) = arg.get_long().filter(|_| !arg.is_global_set()) {
+                options.push((sub_path.clone(), long.to_string()));
+            }
+        }
+        collect_flags(sub, &sub_path, options);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("parralel_jobs", "parallel_jobs"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    #[test]
+    fn test_closest() {
+        let commands = ["run", "upgrade", "config", "transform"];
+        assert_eq!(closest("rn", commands), Some("run"));
+        assert_eq!(closest("upgarde", commands), Some("upgrade"));
+        assert_eq!(closest("trans", commands), Some("transform"));
+        assert_eq!(closest("zzzzzz", commands), None);
+    }
+
+    #[test]
+    fn test_flag_of_other_subcommand() {
+        let command = Command::new("app")
+            .subcommand(Command::new("run").arg(clap::Arg::new("include").long("include")))
+            .subcommand(Command::new("diff"));
+        let error = command
+            .clone()
+            .try_get_matches_from(["app", "diff", "--includ", "x"])
+            .unwrap_err();
+        let rendered = improve_error(error, &command, &[]).to_string();
+        assert!(
+            rendered.contains("tip: '--include' is an option of 'app run'"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn test_subcommand_suggests_plugins() {
+        let command = Command::new("app").subcommand(Command::new("run"));
+        let error = command
+            .clone()
+            .try_get_matches_from(["app", "deploi"])
+            .unwrap_err();
+        let rendered = improve_error(error, &command, &[String::from("deploy")]).to_string();
+        assert!(rendered.contains("'deploy'"), "{}", rendered);
+    }
+}
@@ -0,0 +1,318 @@
+//! Statistics for delimited (CSV/TSV) data.
+
+use std::io::Read;
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::hll::HyperLogLog;
+
+/// Summary of a delimited table.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TableStats {
+    /// Data rows, excluding the header
+    pub rows: usize,
+    pub columns: Vec<ColumnStats>,
+}
+
+/// Per-column summary.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnStats {
+    pub name: String,
+    /// Cells that are missing or contain only whitespace
+    pub empty: usize,
+    pub inferred_type: ColumnType,
+    /// Approximate number of distinct non-empty values
+    pub distinct: u64,
+    /// Range and mean, for integer and float columns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numeric: Option<NumericStats>,
+}
+
+/// Summary of the values in a numeric column.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NumericStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+/// Narrowest type that fits every non-empty cell of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// No non-empty cells
+    Empty,
+    Boolean,
+    Integer,
+    Float,
+    /// ISO 8601 date, optionally followed by a time (`2024-05-01`, `2024-05-01T12:30:00Z`)
+    Date,
+    String,
+}
+
+impl ColumnType {
+    fn of(cell: &str) -> Self {
+        if cell.parse::<i64>().is_ok() {
+            ColumnType::Integer
+        } else if cell.parse::<f64>().is_ok() {
+            ColumnType::Float
+        } else if matches!(cell.to_lowercase().as_str(), "true" | "false") {
+            ColumnType::Boolean
+        } else if is_date(cell) {
+            ColumnType::Date
+        } else {
+            ColumnType::String
+        }
+    }
+
+    /// Widens `self` so that it also covers `other`.
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (ColumnType::Empty, t) | (t, ColumnType::Empty) => t,
+            (a, b) if a == b => a,
+            (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => {
+                ColumnType::Float
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Date => "date",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+/// Whether `cell` starts with a valid `YYYY-MM-DD` date, followed by nothing or by a
+/// `T`/space separated `HH:MM` time (seconds, fractions and offsets are not checked).
+fn is_date(cell: &str) -> bool {
+    let bytes = cell.as_bytes();
+    if bytes.len() < 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return false;
+    }
+    // Bytes rather than `&str` slices, which would panic inside a multibyte character
+    let number = |range: std::ops::Range<usize>| -> Option<u32> {
+        bytes[range].iter().try_fold(0, |n: u32, &b| {
+            b.is_ascii_digit().then(|| n * 10 + u32::from(b - b'0'))
+        })
+    };
+    let (Some(_), Some(month), Some(day)) = (number(0..4), number(5..7), number(8..10)) else {
+        return false;
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return false;
+    }
+
+    match &bytes[10..] {
+        [] => true,
+        [b'T' | b' ', ..] if bytes.len() >= 16 && bytes[13] == b':' => {
+            matches!((number(11..13), number(14..16)), (Some(h), Some(m)) if h < 24 && m < 60)
+        }
+        _ => false,
+    }
+}
+
+/// Running per-column state while records are read.
+#[derive(Default)]
+struct ColumnAccumulator {
+    distinct: HyperLogLog,
+    numeric_count: usize,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl ColumnAccumulator {
+    fn add(&mut self, cell: &str) {
+        self.distinct.insert(cell);
+        if let Ok(value) = cell.parse::<f64>() {
+            if self.numeric_count == 0 {
+                (self.min, self.max) = (value, value);
+            } else {
+                self.min = self.min.min(value);
+                self.max = self.max.max(value);
+            }
+            self.numeric_count += 1;
+            self.sum += value;
+        }
+    }
+
+    fn finish(self, column: &mut ColumnStats) {
+        column.distinct = self.distinct.estimate();
+        let numeric = matches!(
+            column.inferred_type,
+            ColumnType::Integer | ColumnType::Float
+        );
+        if numeric && self.numeric_count > 0 {
+            column.numeric = Some(NumericStats {
+                min: self.min,
+                max: self.max,
+                mean: self.sum / self.numeric_count as f64,
+            });
+        }
+    }
+}
+
+/// Default delimiter for a file name: tab for `.tsv`/`.tab`, comma otherwise.
+pub fn default_delimiter(path: &std::path::Path) -> u8 {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("tsv") || ext.eq_ignore_ascii_case("tab") => b'\t',
+        _ => b',',
+    }
+}
+
+/// Reads delimited records from `reader` one at a time, treating the first row as headers.
+pub fn analyze(reader: impl Read, delimiter: u8) -> Result<TableStats> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(reader);
+
+    let mut columns: Vec<ColumnStats> = reader
+        .headers()
+        .map_err(csv_error)?
+        .iter()
+        .map(|name| ColumnStats {
+            name: name.to_string(),
+            empty: 0,
+            inferred_type: ColumnType::Empty,
+            distinct: 0,
+            numeric: None,
+        })
+        .collect();
+    let mut accumulators: Vec<ColumnAccumulator> = columns
+        .iter()
+        .map(|_| ColumnAccumulator::default())
+        .collect();
+
+    let mut rows = 0;
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record).map_err(csv_error)? {
+        rows += 1;
+        for (i, (column, acc)) in columns.iter_mut().zip(&mut accumulators).enumerate() {
+            match record.get(i).map(str::trim) {
+                None | Some("") => column.empty += 1,
+                Some(cell) => {
+                    column.inferred_type = column.inferred_type.merge(ColumnType::of(cell));
+                    acc.add(cell);
+                }
+            }
+        }
+    }
+
+    for (column, acc) in columns.iter_mut().zip(accumulators) {
+        acc.finish(column);
+    }
+
+    Ok(TableStats { rows, columns })
+}
+
+fn csv_error(e: csv::Error) -> Error {
+    Error::InvalidInput {
+        format: "delimited data",
+        message: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_infers_types_and_empties() {
+        let data =
+            "id,price,name,active,notes\n1,9.5,apple,true,\n2,10,pear,false,\n3,,plum,TRUE\n";
+        let stats = analyze(data.as_bytes(), b',').unwrap();
+
+        assert_eq!(stats.rows, 3);
+        let types: Vec<ColumnType> = stats.columns.iter().map(|c| c.inferred_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Integer,
+                ColumnType::Float,
+                ColumnType::String,
+                ColumnType::Boolean,
+                ColumnType::Empty
+            ]
+        );
+        assert_eq!(stats.columns[1].empty, 1);
+        assert_eq!(stats.columns[4].empty, 3);
+    }
+
+    #[test]
+    fn test_analyze_numeric_and_distinct() {
+        let data =
+            "qty,price,when\n3,2.5,2024-01-31\n-1,4,2024-02-01T08:15:00Z\n3,1.5,2024-02-01\n";
+        let stats = analyze(data.as_bytes(), b',').unwrap();
+
+        let qty = &stats.columns[0];
+        assert_eq!(qty.distinct, 2);
+        assert_eq!(
+            qty.numeric,
+            Some(NumericStats {
+                min: -1.0,
+                max: 3.0,
+                mean: 5.0 / 3.0
+            })
+        );
+        assert_eq!(stats.columns[1].numeric.as_ref().unwrap().max, 4.0);
+
+        let when = &stats.columns[2];
+        assert_eq!(when.inferred_type, ColumnType::Date);
+        assert_eq!(when.distinct, 3);
+        assert_eq!(when.numeric, None);
+    }
+
+    #[test]
+    fn test_is_date() {
+        assert!(is_date("2024-05-01"));
+        assert!(is_date("2024-05-01 23:59"));
+        assert!(is_date("2024-05-01T12:30:00.123+02:00"));
+        assert!(!is_date("2024-13-01"));
+        assert!(!is_date("2024-05-01x"));
+        assert!(!is_date("2024-05-01T25:00"));
+        assert!(!is_date("05/01/2024"));
+        // Multibyte characters where digits are expected
+        assert!(!is_date("2024-05-0é"));
+        assert!(!is_date("2024-05-01 1é:00"));
+        assert!(!is_date("20é-05-01"));
+    }
+
+    #[test]
+    fn test_analyze_tsv() {
+        let stats = analyze("a\tb\nx\t1\n".as_bytes(), b'\t').unwrap();
+        assert_eq!(stats.columns.len(), 2);
+        assert_eq!(stats.columns[1].inferred_type, ColumnType::Integer);
+        assert_eq!(default_delimiter(std::path::Path::new("data.TSV")), b'\t');
+        assert_eq!(default_delimiter(std::path::Path::new("data.csv")), b',');
+    }
+
+    #[test]
+    fn test_merge_widens() {
+        assert_eq!(
+            ColumnType::Integer.merge(ColumnType::Float),
+            ColumnType::Float
+        );
+        assert_eq!(
+            ColumnType::Boolean.merge(ColumnType::Integer),
+            ColumnType::String
+        );
+        assert_eq!(
+            ColumnType::Empty.merge(ColumnType::Boolean),
+            ColumnType::Boolean
+        );
+        assert_eq!(ColumnType::Date.merge(ColumnType::Date), ColumnType::Date);
+        assert_eq!(
+            ColumnType::Date.merge(ColumnType::Integer),
+            ColumnType::String
+        );
+    }
+}
@@ -0,0 +1,487 @@
+//! A small template language in the style of Handlebars, for generating files from data.
+//!
+//! Supported: `{{name}}` and `{{a.b.0}}` lookups (also `{{{name}}}`), `this`, `@index`,
+//! `@key`, `@root.path` and `../path`, blocks `{{#each path}}`, `{{#if path}}` and
+//! `{{#unless path}}` with an optional `{{else}}`, and `{{! comments }}` or
+//! `{{!-- comments --}}`. Unlike Handlebars, values are inserted as they are: nothing is
+//! HTML-escaped. Everything else (helpers, partials, whitespace control, `{{else if}}`,
+//! ...) is rejected when parsing rather than rendered wrongly.
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Value(Path),
+    Each {
+        path: Path,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    If {
+        path: Path,
+        negate: bool,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// Where a lookup starts and the keys it follows from there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Path {
+    /// Number of `../` to go up, or `None` for `@root`
+    up: Option<usize>,
+    keys: Vec<String>,
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+/// One `{{...}}` tag or the text before it.
+enum Token<'a> {
+    Text(&'a str),
+    Tag { content: &'a str, line: usize },
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut tokens = tokens.into_iter();
+        let (nodes, end) = parse_nodes(&mut tokens)?;
+        match end {
+            None => Ok(Self { nodes }),
+            Some((tag, line)) => Err(invalid(line, format!("unexpected {{{{{}}}}}", tag))),
+        }
+    }
+
+    /// Renders the template with `data` as the root value.
+    pub fn render(&self, data: &Value) -> String {
+        let mut out = String::new();
+        let root = Frame {
+            value: data,
+            index: None,
+            key: None,
+        };
+        render_nodes(&self.nodes, &mut vec![root], &mut out);
+        out
+    }
+}
+
+fn invalid(line: usize, reason: impl std::fmt::Display) -> Error {
+    Error::Other(format!("Invalid template (line {}): {}", line, reason))
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let line = source[..source.len() - rest.len() + start]
+            .matches('\n')
+            .count()
+            + 1;
+        if rest[..start].ends_with('\\') {
+            return Err(invalid(line, "escaped tags (\\{{) are not supported"));
+        }
+        let (open, close) = if rest[start..].starts_with("{{{") {
+            ("{{{", "}}}")
+        } else if rest[start..].starts_with("{{!--") {
+            ("{{", "--}}")
+        } else {
+            ("{{", "}}")
+        };
+        let after = &rest[start + open.len()..];
+        let end = after
+            .find(close)
+            .ok_or_else(|| invalid(line, format!("'{}' is never closed", open)))?;
+        tokens.push(Token::Tag {
+            content: after[..end].trim(),
+            line,
+        });
+        rest = &after[end + close.len()..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    Ok(tokens)
+}
+
+/// The `{{else}}` or `{{/...}}` tag that ended a run of nodes, and its line.
+type End<'a> = Option<(&'a str, usize)>;
+
+/// Parses nodes up to the end of the input or an `{{else}}` / `{{/...}}` tag, which is
+/// returned with its line.
+fn parse_nodes<'a>(tokens: &mut impl Iterator<Item = Token<'a>>) -> Result<(Vec<Node>, End<'a>)> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        let (content, line) = match token {
+            Token::Text(text) => {
+                nodes.push(Node::Text(text.to_string()));
+                continue;
+            }
+            Token::Tag { content, line } => (content, line),
+        };
+        if content.starts_with('!') {
+            continue;
+        }
+        if let Some(feature) = unsupported(content) {
+            return Err(invalid(line, format!("{} are not supported", feature)));
+        }
+        if content == "else" || content.starts_with('/') {
+            return Ok((nodes, Some((content, line))));
+        }
+        let Some(block) = content.strip_prefix('#') else {
+            nodes.push(Node::Value(parse_path(content, line)?));
+            continue;
+        };
+
+        let (name, argument) = block.split_once(char::is_whitespace).unwrap_or((block, ""));
+        if !matches!(name, "each" | "if" | "unless") {
+            return Err(invalid(line, format!("unknown block '#{}'", name)));
+        }
+        let path = parse_path(argument.trim(), line)?;
+        let (body, mut end) = parse_nodes(tokens)?;
+        let mut otherwise = Vec::new();
+        if end.is_some_and(|(tag, _)| tag == "else") {
+            (otherwise, end) = parse_nodes(tokens)?;
+        }
+        match end {
+            Some((tag, _)) if tag.strip_prefix('/').map(str::trim) == Some(name) => {}
+            Some((tag, line)) => {
+                return Err(invalid(
+                    line,
+                    format!("expected {{{{/{}}}}}, found {{{{{}}}}}", name, tag),
+                ))
+            }
+            None => return Err(invalid(line, format!("{{{{#{}}}}} is never closed", name))),
+        }
+        nodes.push(match name {
+            "each" => Node::Each {
+                path,
+                body,
+                otherwise,
+            },
+            _ => Node::If {
+                path,
+                negate: name == "unless",
+                body,
+                otherwise,
+            },
+        });
+    }
+    Ok((nodes, None))
+}
+
+/// Names the Handlebars feature a tag uses that this subset does not implement.
+fn unsupported(content: &str) -> Option<&'static str> {
+    Some(match content {
+        _ if content.starts_with('~') || content.ends_with('~') => {
+            "whitespace control ({{~ and ~}})"
+        }
+        _ if content.starts_with('>') || content.starts_with("#>") => "partials ({{> name}})",
+        _ if content.starts_with("#*") || content.starts_with('*') => "decorators",
+        _ if content.starts_with('^') => "inverse blocks ({{^...}}); use {{else}} or {{#unless}}",
+        _ if content.starts_with('&') => "'{{&...}}' tags; values are never escaped anyway",
+        _ if content.starts_with("else ") => "chained {{else if}} blocks; nest the block instead",
+        _ if content.contains('(') => "subexpressions",
+        _ if content.contains('|') => "block parameters (as |name|)",
+        _ if content.contains('[') => "segment literals ([key])",
+        _ if !content.starts_with(['#', '/']) && content.contains(char::is_whitespace) => "helpers",
+        _ => return None,
+    })
+}
+
+fn parse_path(expression: &str, line: usize) -> Result<Path> {
+    if expression.is_empty() || expression.contains(char::is_whitespace) {
+        return Err(invalid(
+            line,
+            format!(
+                "bad expression '{}': expected one path such as name, a.b.0 or ../name",
+                expression
+            ),
+        ));
+    }
+    let mut rest = expression;
+    let mut up = Some(0);
+    if let Some(after) = rest.strip_prefix("@root") {
+        up = None;
+        rest = after.strip_prefix('.').unwrap_or(after);
+    } else {
+        while let Some(after) = rest.strip_prefix("../") {
+            up = up.map(|n| n + 1);
+            rest = after;
+        }
+    }
+    let keys = match rest {
+        "" | "this" | "." => Vec::new(),
+        _ => rest
+            .strip_prefix("this.")
+            .unwrap_or(rest)
+            .split('.')
+            .map(|key| {
+                if key.is_empty()
+                    || !key
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '@')
+                {
+                    return Err(invalid(
+                        line,
+                        format!(
+                            "bad expression '{}': expected one path such as name, a.b.0 or \
+                             ../name",
+                            expression
+                        ),
+                    ));
+                }
+                Ok(key.to_string())
+            })
+            .collect::<Result<Vec<_>>>()?,
+    };
+    if let Some(key) = keys.iter().find(|key| key.contains('@')) {
+        if keys.len() > 1 || !matches!(key.as_str(), "@index" | "@key") {
+            return Err(invalid(
+                line,
+                format!(
+                    "unknown data variable in '{}': only @index, @key and @root are supported",
+                    expression
+                ),
+            ));
+        }
+    }
+    Ok(Path { up, keys })
+}
+
+/// The value being rendered, and its position when inside `{{#each}}`.
+struct Frame<'a> {
+    value: &'a Value,
+    index: Option<usize>,
+    key: Option<String>,
+}
+
+fn render_nodes<'a>(nodes: &[Node], frames: &mut Vec<Frame<'a>>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value(path) => match lookup(path, frames) {
+                Lookup::Value(Value::String(text)) => out.push_str(text),
+                Lookup::Value(Value::Null) | Lookup::Missing => {}
+                Lookup::Value(value) => out.push_str(&value.to_string()),
+                Lookup::Meta(text) => out.push_str(&text),
+            },
+            Node::If {
+                path,
+                negate,
+                body,
+                otherwise,
+            } => {
+                let truthy = match lookup(path, frames) {
+                    Lookup::Value(value) => is_truthy(value),
+                    Lookup::Meta(text) => !text.is_empty() && text != "0",
+                    Lookup::Missing => false,
+                };
+                let branch = if truthy != *negate { body } else { otherwise };
+                render_nodes(branch, frames, out);
+            }
+            Node::Each {
+                path,
+                body,
+                otherwise,
+            } => {
+                let items: Vec<(Option<String>, &'a Value)> = match lookup(path, frames) {
+                    Lookup::Value(Value::Array(items)) => {
+                        items.iter().map(|item| (None, item)).collect()
+                    }
+                    Lookup::Value(Value::Object(map)) => map
+                        .iter()
+                        .map(|(key, value)| (Some(key.clone()), value))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                if items.is_empty() {
+                    render_nodes(otherwise, frames, out);
+                }
+                for (index, (key, value)) in items.into_iter().enumerate() {
+                    frames.push(Frame {
+                        value,
+                        index: Some(index),
+                        key,
+                    });
+                    render_nodes(body, frames, out);
+                    frames.pop();
+                }
+            }
+        }
+    }
+}
+
+enum Lookup<'a> {
+    Value(&'a Value),
+    /// `@index` or `@key`
+    Meta(String),
+    Missing,
+}
+
+fn lookup<'a>(path: &Path, frames: &[Frame<'a>]) -> Lookup<'a> {
+    let frame = match path.up {
+        None => &frames[0],
+        Some(up) => match frames.len().checked_sub(up + 1) {
+            Some(at) => &frames[at],
+            None => return Lookup::Missing,
+        },
+    };
+    match path.keys.first().map(String::as_str) {
+        Some("@index") => {
+            return frame
+                .index
+                .map_or(Lookup::Missing, |index| Lookup::Meta(index.to_string()))
+        }
+        Some("@key") => return frame.key.clone().map_or(Lookup::Missing, Lookup::Meta),
+        _ => {}
+    }
+    let mut value = frame.value;
+    for key in &path.keys {
+        let next = match value {
+            Value::Object(map) => map.get(key),
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        match next {
+            Some(next) => value = next,
+            None => return Lookup::Missing,
+        }
+    }
+    Lookup::Value(value)
+}
+
+/// Handlebars truthiness: `false`, `null`, `""`, `0` and `[]` are false.
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, data: &Value) -> String {
+        Template::parse(template).unwrap().render(data)
+    }
+
+    #[test]
+    fn test_values() {
+        let data = json!({"name": "app", "port": 8080, "tags": ["a", "b"], "none": null});
+        assert_eq!(
+            render(
+                "{{name}}:{{ port }} {{{tags.1}}}{{none}}{{missing.x}}",
+                &data
+            ),
+            "app:8080 b"
+        );
+        assert_eq!(render("{{tags}} {{! ignored }}", &data), "[\"a\",\"b\"] ");
+        assert_eq!(render("<{{name}}>", &json!({"name": "a&b"})), "<a&b>");
+    }
+
+    #[test]
+    fn test_blocks() {
+        let data = json!({
+            "name": "app",
+            "servers": [{"host": "a", "primary": true}, {"host": "b", "primary": false}],
+            "env": {"A": "1", "B": "2"},
+            "empty": []
+        });
+        assert_eq!(
+            render(
+                "{{#each servers}}{{@index}}={{host}}{{#if primary}}*{{/if}}@{{../name}};{{/each}}",
+                &data
+            ),
+            "0=a*@app;1=b@app;"
+        );
+        assert_eq!(
+            render("{{#each env}}{{@key}}={{this}} {{/each}}", &data),
+            "A=1 B=2 "
+        );
+        assert_eq!(
+            render("{{#each empty}}x{{else}}none{{/each}}", &data),
+            "none"
+        );
+        assert_eq!(
+            render("{{#unless empty}}no{{else}}yes{{/unless}}", &data),
+            "no"
+        );
+        assert_eq!(
+            render("{{#each servers}}{{@root.name}}{{/each}}", &data),
+            "appapp"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for template in [
+            "{{name",
+            "{{#if x}}open",
+            "{{#each x}}{{/if}}",
+            "{{/each}}",
+            "{{#with x}}{{/with}}",
+            "{{a b}}",
+            "{{a..b}}",
+        ] {
+            assert!(Template::parse(template).is_err(), "{}", template);
+        }
+        let error = Template::parse("ok\n\n{{#if x}}").unwrap_err().to_string();
+        assert!(error.contains("line 3"), "{}", error);
+    }
+
+    #[test]
+    fn test_rejects_unsupported_handlebars() {
+        for (template, reason) in [
+            ("{{lookup a b}}", "helpers are not supported"),
+            ("{{> header}}", "partials"),
+            ("{{>header}}", "partials"),
+            ("{{#> layout}}x{{/layout}}", "partials"),
+            ("{{~name~}}", "whitespace control"),
+            ("{{#if a}}x{{~else}}y{{/if}}", "whitespace control"),
+            ("{{#if a}}x{{else if b}}y{{/if}}", "{{else if}}"),
+            ("{{#if (eq a b)}}x{{/if}}", "subexpressions"),
+            (
+                "{{#each items as |item|}}{{item}}{{/each}}",
+                "block parameters",
+            ),
+            ("{{^items}}none{{/items}}", "inverse blocks"),
+            ("{{&name}}", "'{{&...}}' tags"),
+            ("{{*inline}}", "decorators"),
+            (
+                "{{#each a}}{{@first}}{{/each}}",
+                "only @index, @key and @root",
+            ),
+            ("{{a.@index}}", "only @index, @key and @root"),
+            ("{{[weird key]}}", "segment literals"),
+            ("{{a/b}}", "expected one path"),
+            ("\\{{name}}", "escaped tags"),
+        ] {
+            let error = Template::parse(template).unwrap_err().to_string();
+            assert!(error.contains(reason), "{}: {}", template, error);
+        }
+    }
+
+    #[test]
+    fn test_long_comments() {
+        assert_eq!(
+            render("a{{!-- {{name}} }} --}}b", &json!({"name": "x"})),
+            "ab"
+        );
+    }
+}
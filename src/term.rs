@@ -0,0 +1,64 @@
+//! Terminal properties used to lay out human-readable output.
+
+use std::io::IsTerminal;
+
+/// Help text wider than this is hard to scan, even on very wide terminals.
+pub const MAX_HELP_WIDTH: usize = 100;
+
+/// Width used when stdout is not a terminal and `COLUMNS` is unset.
+const FALLBACK_WIDTH: usize = 80;
+
+/// Current terminal width in columns, honoring `COLUMNS` when set.
+pub fn width() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|columns: &usize| *columns > 0)
+    {
+        return Some(columns);
+    }
+
+    let stdout = std::io::stdout();
+    if !stdout.is_terminal() {
+        return None;
+    }
+    terminal_size::terminal_size_of(&stdout).map(|(terminal_size::Width(w), _)| w as usize)
+}
+
+/// Width to wrap `--help` output at.
+pub fn help_width() -> usize {
+    width().unwrap_or(FALLBACK_WIDTH).min(MAX_HELP_WIDTH)
+}
+
+/// Makes every hidden argument and subcommand visible, recursively.
+pub fn reveal_hidden(command: clap::Command) -> clap::Command {
+    command
+        .mut_args(|arg| arg.hide(false))
+        .mut_subcommands(|sub| reveal_hidden(sub.hide(false)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, Command};
+
+    #[test]
+    fn test_reveal_hidden() {
+        let command = Command::new("app")
+            .arg(Arg::new("secret").long("secret").hide(true))
+            .subcommand(
+                Command::new("sub")
+                    .hide(true)
+                    .arg(Arg::new("inner").long("inner").hide(true)),
+            );
+
+        let command = reveal_hidden(command);
+        assert!(!command.find_subcommand("sub").unwrap().is_hide_set());
+        assert!(command.get_arguments().all(|arg| !arg.is_hide_set()));
+        assert!(command
+            .find_subcommand("sub")
+            .unwrap()
+            .get_arguments()
+            .all(|arg| !arg.is_hide_set()));
+    }
+}
@@ -0,0 +1,202 @@
+//! Token-bucket limit on the rate of file I/O, so that large scans of shared storage
+//! leave bandwidth for other workloads.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Bytes per MB, as in the reported throughput.
+const MB: f64 = 1_000_000.0;
+
+/// Tokens available at a point in time; goes negative while a large transfer is paid off.
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    /// Takes `bytes` tokens at `now` from a bucket holding up to `capacity` and refilling
+    /// at `rate` per second, returning how long to wait until the debt is paid.
+    fn take(&mut self, bytes: usize, now: Instant, rate: f64, capacity: f64) -> Duration {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.refilled = now;
+        self.tokens -= bytes as f64;
+        if self.tokens < 0.0 {
+            Duration::from_secs_f64(-self.tokens / rate)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// Counts the bytes read and written, pausing when they exceed the configured rate.
+///
+/// Up to one second's worth of bytes can be transferred in a burst.
+pub struct IoLimiter {
+    /// Bytes per second, if limited
+    rate: Option<f64>,
+    state: Mutex<State>,
+}
+
+struct State {
+    bucket: Bucket,
+    read: u64,
+    written: u64,
+    throttled: Duration,
+}
+
+/// Bytes transferred through an [`IoLimiter`] and the time spent waiting.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IoUsage {
+    pub read: u64,
+    pub written: u64,
+    pub throttled: Duration,
+}
+
+impl IoLimiter {
+    /// A limiter allowing `mb_per_sec` MB/s in total, or only counting when `None`.
+    pub fn new(mb_per_sec: Option<f64>) -> Self {
+        let rate = mb_per_sec.map(|limit| limit * MB);
+        Self {
+            rate,
+            state: Mutex::new(State {
+                bucket: Bucket {
+                    tokens: rate.unwrap_or_default(),
+                    refilled: Instant::now(),
+                },
+                read: 0,
+                written: 0,
+                throttled: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// The limit in MB/s, if any.
+    pub fn limit_mb_per_sec(&self) -> Option<f64> {
+        self.rate.map(|rate| rate / MB)
+    }
+
+    /// Bytes transferred so far.
+    pub fn usage(&self) -> IoUsage {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        IoUsage {
+            read: state.read,
+            written: state.written,
+            throttled: state.throttled,
+        }
+    }
+
+    /// Accounts for `bytes` transferred, sleeping while over the limit.
+    fn consume(&self, bytes: usize, write: bool) {
+        let wait = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if write {
+                state.written += bytes as u64;
+            } else {
+                state.read += bytes as u64;
+            }
+            let Some(rate) = self.rate else {
+                return;
+            };
+            let wait = state.bucket.take(bytes, Instant::now(), rate, rate);
+            state.throttled += wait;
+            wait
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    /// Wraps `reader` so that its reads count against the limit.
+    pub fn reader<R>(&self, inner: R) -> Throttled<'_, R> {
+        Throttled {
+            inner,
+            limiter: self,
+        }
+    }
+
+    /// Wraps `writer` so that its writes count against the limit.
+    pub fn writer<W>(&self, inner: W) -> Throttled<'_, W> {
+        Throttled {
+            inner,
+            limiter: self,
+        }
+    }
+}
+
+/// Reader or writer adapter accounting every byte to an [`IoLimiter`].
+pub struct Throttled<'a, T> {
+    inner: T,
+    limiter: &'a IoLimiter,
+}
+
+impl<R: Read> Read for Throttled<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.limiter.consume(n, false);
+        Ok(n)
+    }
+}
+
+impl<S: Seek> Seek for Throttled<'_, S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<W: Write> Write for Throttled<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.limiter.consume(n, true);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_paces() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 1000.0,
+            refilled: start,
+        };
+        assert_eq!(bucket.take(1000, start, 1000.0, 1000.0), Duration::ZERO);
+        assert_eq!(
+            bucket.take(500, start, 1000.0, 1000.0),
+            Duration::from_millis(500)
+        );
+        // After the debt is paid the bucket refills, but never beyond its capacity
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.take(1000, later, 1000.0, 1000.0), Duration::ZERO);
+        assert_eq!(
+            bucket.take(100, later, 1000.0, 1000.0),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn test_limiter_counts_transfers() {
+        let limiter = IoLimiter::new(None);
+        let mut content = String::new();
+        limiter
+            .reader(&b"hello\n"[..])
+            .read_to_string(&mut content)
+            .unwrap();
+        let mut output = Vec::new();
+        limiter.writer(&mut output).write_all(b"HELLO\n!").unwrap();
+
+        let usage = limiter.usage();
+        assert_eq!((usage.read, usage.written), (6, 7));
+        assert_eq!(usage.throttled, Duration::ZERO);
+        assert_eq!(limiter.limit_mb_per_sec(), None);
+    }
+}
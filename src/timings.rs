@@ -0,0 +1,154 @@
+//! Durations of the major phases of a run (`--timings`).
+//!
+//! Phases are tracing spans with the [`TARGET`] target, created at trace level so that
+//! log output at the usual levels does not show them. When `--timings` is given, [`layer`] records how
+//! long each one lasted, and [`print`] reports the totals per phase at the end.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::filter::{filter_fn, Filtered};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::i18n::tr;
+use crate::output::OutputFormat;
+use crate::reporter::Reporter;
+
+/// Target of phase spans, e.g. `trace_span!(target: timings::TARGET, "read")`.
+pub const TARGET: &str = "phase";
+
+static PHASES: Mutex<Vec<Phase>> = Mutex::new(Vec::new());
+
+/// Total time spent in one phase.
+#[derive(Debug, Clone, Serialize)]
+pub struct Phase {
+    pub name: &'static str,
+    /// Number of times the phase ran, e.g. once per input file
+    pub calls: u64,
+    #[serde(serialize_with = "as_seconds", rename = "seconds")]
+    pub total: Duration,
+}
+
+fn as_seconds<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Records the duration of phase spans, from creation to close.
+pub struct Recorder;
+
+/// When a span was created.
+struct Started(Instant);
+
+impl<S> Layer<S> for Recorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Started(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let elapsed = span
+            .extensions()
+            .get::<Started>()
+            .map(|Started(started)| started.elapsed());
+        if let Some(elapsed) = elapsed {
+            record(span.name(), elapsed);
+        }
+    }
+}
+
+/// The layer recording phases, ignoring every other span.
+pub fn layer<S>() -> Filtered<Recorder, impl tracing_subscriber::layer::Filter<S>, S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    Recorder.with_filter(filter_fn(|metadata| {
+        metadata.target() == TARGET && metadata.is_span()
+    }))
+}
+
+fn record(name: &'static str, elapsed: Duration) {
+    let mut phases = PHASES.lock().unwrap_or_else(|e| e.into_inner());
+    match phases.iter_mut().find(|phase| phase.name == name) {
+        Some(phase) => {
+            phase.calls += 1;
+            phase.total += elapsed;
+        }
+        None => phases.push(Phase {
+            name,
+            calls: 1,
+            total: elapsed,
+        }),
+    }
+}
+
+/// Phases recorded so far, in the order they first ran.
+pub fn phases() -> Vec<Phase> {
+    PHASES.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Prints the recorded phases on stderr, so they do not mix with command output: a
+/// table, or a JSON object with `--format json`.
+pub fn print(format: OutputFormat, reporter: Reporter) {
+    let phases = phases();
+    if format == OutputFormat::Json {
+        let report = serde_json::json!({ "timings": phases });
+        eprintln!("{}", report);
+        return;
+    }
+    let rows: Vec<Vec<String>> = phases
+        .iter()
+        .map(|phase| {
+            vec![
+                phase.name.to_string(),
+                phase.calls.to_string(),
+                format!("{:.3} ms", phase.total.as_secs_f64() * 1000.0),
+            ]
+        })
+        .collect();
+    eprintln!("{}", tr!("timings"));
+    let headers = [
+        tr!("header-phase"),
+        tr!("header-calls"),
+        tr!("header-total"),
+    ];
+    reporter.table_on_stderr("  ", &headers, &rows);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_phases_recorded_per_name() {
+        let subscriber = tracing_subscriber::registry().with(layer());
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..2 {
+                let _read = tracing::trace_span!(target: TARGET, "test read").entered();
+            }
+            // Spans of other targets are not phases
+            let _other = tracing::trace_span!("test other").entered();
+        });
+
+        let phases = phases();
+        let read = phases.iter().find(|p| p.name == "test read").unwrap();
+        assert_eq!(read.calls, 2);
+        assert!(!phases.iter().any(|p| p.name == "test other"));
+        let value = serde_json::to_value(read).unwrap();
+        assert!(value["seconds"].is_f64());
+    }
+}
@@ -0,0 +1,1027 @@
+//! Line-oriented text transforms and pipelines.
+//!
+//! A pipeline is a `|`-separated list of stages. Each stage is a transform name,
+//! optionally followed by `:` and comma-separated `key=value` options, e.g.
+//! `trim | dedupe-lines | truncate:width=40`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use tracing::{debug, info};
+
+use crate::cancel;
+use crate::error::{Error, Result};
+use crate::extsort;
+
+/// Registered transforms: name and description.
+pub const TRANSFORMS: &[(&str, &str)] = &[
+    ("uppercase", "Convert text to uppercase"),
+    ("lowercase", "Convert text to lowercase"),
+    (
+        "trim",
+        "Strip leading and trailing whitespace from each line",
+    ),
+    (
+        "dedupe-lines",
+        "Drop lines already seen earlier in the input",
+    ),
+    (
+        "sort-lines",
+        "Sort lines, keeping equal lines in order (`unique=true` keeps only the first)",
+    ),
+    (
+        "truncate",
+        "Cut lines to at most `width` characters (default 80)",
+    ),
+    (
+        "shuffle-lines",
+        "Randomly reorder lines (reproducible with `seed`)",
+    ),
+    (
+        "sample-lines",
+        "Keep a random sample of `n` lines (default 10) in input order",
+    ),
+    (
+        "line-endings",
+        "Convert line endings to `to=lf` (default) or `to=crlf`",
+    ),
+    (
+        "script",
+        "Run a rhai script per line (`script:file.rhai`; requires the `scripting` feature)",
+    ),
+];
+
+/// Inputs larger than this many bytes are shuffled via temporary bucket files.
+const SHUFFLE_SPILL_BYTES: usize = 64 * 1024 * 1024;
+
+/// Buckets the lines of a spilled shuffle, or of a bucket still above the spill size, are
+/// scattered into.
+const SHUFFLE_BUCKETS: usize = 16;
+
+/// Levels of buckets at most; buckets shrink sixteenfold per level, so deeper ones only
+/// come from a few very long lines, which are then shuffled in memory.
+const MAX_SHUFFLE_DEPTH: u32 = 4;
+
+/// A single configured transform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transform {
+    Uppercase,
+    Lowercase,
+    Trim,
+    DedupeLines,
+    SortLines {
+        unique: bool,
+    },
+    Truncate {
+        width: usize,
+    },
+    ShuffleLines {
+        seed: Option<u64>,
+    },
+    SampleLines {
+        n: usize,
+        seed: Option<u64>,
+    },
+    LineEndings {
+        to: LineEnding,
+    },
+    #[cfg(feature = "scripting")]
+    Script(crate::script::Script),
+}
+
+impl Transform {
+    /// Parses one stage such as `truncate:width=40`.
+    pub fn parse(stage: &str) -> Result<Self> {
+        let (name, options) = match stage.split_once(':') {
+            // `script:file.rhai` is shorthand for `script:path=file.rhai`
+            Some((name, path)) if name.trim() == "script" && !path.contains('=') => (
+                "script",
+                BTreeMap::from([(String::from("path"), path.trim().to_string())]),
+            ),
+            Some((name, options)) => (name.trim(), parse_options(stage, options)?),
+            None => (stage.trim(), BTreeMap::new()),
+        };
+
+        let mut options = StageOptions {
+            stage,
+            values: options,
+        };
+        let transform = match name {
+            "uppercase" => Transform::Uppercase,
+            "lowercase" => Transform::Lowercase,
+            "trim" => Transform::Trim,
+            "dedupe-lines" => Transform::DedupeLines,
+            "sort-lines" => Transform::SortLines {
+                unique: options.take_parsed("unique")?.unwrap_or(false),
+            },
+            "truncate" => Transform::Truncate {
+                width: options.take_parsed("width")?.unwrap_or(80),
+            },
+            "shuffle-lines" => Transform::ShuffleLines {
+                seed: options.take_parsed("seed")?,
+            },
+            "sample-lines" => Transform::SampleLines {
+                n: options.take_parsed("n")?.unwrap_or(10),
+                seed: options.take_parsed("seed")?,
+            },
+            "line-endings" => Transform::LineEndings {
+                to: options.take_parsed("to")?.unwrap_or_default(),
+            },
+            #[cfg(feature = "scripting")]
+            "script" => {
+                let path: std::path::PathBuf = options.take_parsed("path")?.ok_or_else(|| {
+                    Error::Other(format!("Transform '{}' requires a script path", stage))
+                })?;
+                let budget_ms = options
+                    .take_parsed("budget_ms")?
+                    .unwrap_or(crate::script::DEFAULT_BUDGET_MS);
+                Transform::Script(crate::script::Script::load(
+                    &path,
+                    std::time::Duration::from_millis(budget_ms),
+                )?)
+            }
+            #[cfg(not(feature = "scripting"))]
+            "script" => {
+                return Err(Error::Other(String::from(
+                    "Transform 'script' is not available: rebuild with `--features scripting`",
+                )))
+            }
+            "" => return Err(Error::Other(String::from("Empty transform stage"))),
+            _ => {
+                let available: Vec<&str> = TRANSFORMS.iter().map(|(name, _)| *name).collect();
+                return Err(Error::Other(format!(
+                    "Unknown transform '{}'. Available: {}",
+                    name,
+                    available.join(", ")
+                )));
+            }
+        };
+        options.finish()?;
+        Ok(transform)
+    }
+
+    /// Registry name of the transform.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transform::Uppercase => "uppercase",
+            Transform::Lowercase => "lowercase",
+            Transform::Trim => "trim",
+            Transform::DedupeLines => "dedupe-lines",
+            Transform::SortLines { .. } => "sort-lines",
+            Transform::Truncate { .. } => "truncate",
+            Transform::ShuffleLines { .. } => "shuffle-lines",
+            Transform::SampleLines { .. } => "sample-lines",
+            Transform::LineEndings { .. } => "line-endings",
+            #[cfg(feature = "scripting")]
+            Transform::Script(_) => "script",
+        }
+    }
+
+    /// Human-readable description from the registry.
+    pub fn description(&self) -> &'static str {
+        TRANSFORMS
+            .iter()
+            .find(|(name, _)| *name == self.name())
+            .map(|(_, description)| *description)
+            .unwrap_or_default()
+    }
+
+    /// Effective options, including defaults.
+    pub fn options(&self) -> BTreeMap<&'static str, String> {
+        let mut options = BTreeMap::new();
+        match self {
+            Transform::SortLines { unique: true } => {
+                options.insert("unique", String::from("true"));
+            }
+            Transform::Truncate { width } => {
+                options.insert("width", width.to_string());
+            }
+            Transform::ShuffleLines { seed: Some(seed) } => {
+                options.insert("seed", seed.to_string());
+            }
+            Transform::SampleLines { n, seed } => {
+                options.insert("n", n.to_string());
+                if let Some(seed) = seed {
+                    options.insert("seed", seed.to_string());
+                }
+            }
+            Transform::LineEndings { to } => {
+                options.insert("to", to.to_string());
+            }
+            #[cfg(feature = "scripting")]
+            Transform::Script(script) => {
+                options.insert("path", script.path().display().to_string());
+                options.insert("budget_ms", script.budget().as_millis().to_string());
+            }
+            _ => {}
+        }
+        options
+    }
+
+    /// Applies the transform to `content`.
+    pub fn apply(&self, content: &str) -> Result<String> {
+        let mut output = Vec::with_capacity(content.len());
+        self.apply_stream(&mut content.as_bytes(), &mut output)?;
+        String::from_utf8(output)
+            .map_err(|e| Error::from(io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+
+    /// Applies the transform to the lines of `input`, which must be UTF-8, writing the
+    /// result to `output` as it goes.
+    pub fn apply_stream(&self, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<()> {
+        match self {
+            Transform::Uppercase => map_lines(input, output, |line| Ok(Some(line.to_uppercase()))),
+            Transform::Lowercase => map_lines(input, output, |line| Ok(Some(line.to_lowercase()))),
+            Transform::Trim => map_lines(input, output, |line| Ok(Some(line.trim().to_string()))),
+            Transform::DedupeLines => extsort::unique_lines(input, output, extsort::SPILL_BYTES),
+            Transform::SortLines { unique } => {
+                extsort::sort_lines(input, output, *unique, extsort::SPILL_BYTES)
+            }
+            Transform::Truncate { width } => map_lines(input, output, |line| {
+                Ok(Some(line.chars().take(*width).collect()))
+            }),
+            Transform::ShuffleLines { seed } => {
+                let mut rng = SplitMix64::new(seed.unwrap_or_default());
+                shuffle_lines(input, output, &mut rng, SHUFFLE_SPILL_BYTES)
+            }
+            Transform::SampleLines { n, seed } => {
+                let mut rng = SplitMix64::new(seed.unwrap_or_default());
+                sample_lines(input, output, *n, &mut rng)
+            }
+            Transform::LineEndings { to } => convert_line_endings(input, output, *to),
+            #[cfg(feature = "scripting")]
+            Transform::Script(script) => {
+                let mut line_number = 0;
+                map_lines(input, output, |line| {
+                    line_number += 1;
+                    script.apply_line(line, line_number)
+                })
+            }
+        }
+    }
+
+    fn seed_mut(&mut self) -> Option<&mut Option<u64>> {
+        match self {
+            Transform::ShuffleLines { seed } | Transform::SampleLines { seed, .. } => Some(seed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        let options = self.options();
+        if !options.is_empty() {
+            let options: Vec<String> = options
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            write!(f, ":{}", options.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Line ending written by the `line-endings` transform.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineEnding::Lf => "lf",
+            LineEnding::Crlf => "crlf",
+        })
+    }
+}
+
+impl std::str::FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(value, true)
+    }
+}
+
+/// An ordered list of transforms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pipeline {
+    pub stages: Vec<Transform>,
+}
+
+/// Measurements for one stage of a pipeline run.
+#[derive(Debug, Clone, Serialize)]
+pub struct StageMetrics {
+    pub stage: String,
+    pub lines_in: usize,
+    pub lines_out: usize,
+    pub bytes_in: usize,
+    pub bytes_out: usize,
+    pub elapsed_ms: f64,
+}
+
+impl Pipeline {
+    /// Parses a `|`-separated pipeline definition whose stages may also reference
+    /// `named` pipelines (from the config file) as `@name`.
+    pub fn parse(definition: &str, named: &BTreeMap<String, Vec<String>>) -> Result<Self> {
+        let mut stages = Vec::new();
+        for stage in definition.split('|').map(str::trim) {
+            match stage.strip_prefix('@') {
+                Some(name) => {
+                    let definition = named.get(name).ok_or_else(|| {
+                        let defined: Vec<&str> = named.keys().map(String::as_str).collect();
+                        Error::Other(format!(
+                            "Unknown pipeline '@{}'. Defined in config: {}",
+                            name,
+                            if defined.is_empty() {
+                                String::from("(none)")
+                            } else {
+                                defined.join(", ")
+                            }
+                        ))
+                    })?;
+                    for stage in definition {
+                        stages.push(Transform::parse(stage.trim())?);
+                    }
+                }
+                None => stages.push(Transform::parse(stage)?),
+            }
+        }
+        Ok(Self { stages })
+    }
+
+    /// Gives every randomized stage without its own `seed` option the `default` seed,
+    /// or a time-based one (which is logged so the run can be reproduced).
+    pub fn resolve_seeds(&mut self, default: Option<u64>) {
+        let seed = default.unwrap_or_else(random_seed);
+
+        for stage in &mut self.stages {
+            let name = stage.name();
+            if let Some(slot @ None) = stage.seed_mut() {
+                *slot = Some(seed);
+                if default.is_none() {
+                    info!(
+                        "{} using random seed {} (pass --seed {} to reproduce)",
+                        name, seed, seed
+                    );
+                }
+            }
+        }
+    }
+
+    /// Runs every stage in order, returning the output and per-stage metrics.
+    pub fn apply(&self, content: &str) -> Result<(String, Vec<StageMetrics>)> {
+        let mut current = content.to_string();
+        let mut metrics = Vec::with_capacity(self.stages.len());
+
+        for stage in &self.stages {
+            cancel::check()?;
+            let started = Instant::now();
+            let next = stage.apply(&current)?;
+            metrics.push(StageMetrics {
+                stage: stage.to_string(),
+                lines_in: current.lines().count(),
+                lines_out: next.lines().count(),
+                bytes_in: current.len(),
+                bytes_out: next.len(),
+                elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+            });
+            current = next;
+        }
+
+        Ok((current, metrics))
+    }
+
+    /// Runs every stage in order on the lines of `input`, writing the output to `output`
+    /// as the last stage produces it and returning per-stage metrics. Stages hand their
+    /// output to the next through temporary files, so no stage needs the whole text in
+    /// memory.
+    pub fn apply_stream(
+        &self,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Result<Vec<StageMetrics>> {
+        let mut metrics = Vec::with_capacity(self.stages.len());
+        if self.stages.is_empty() {
+            io::copy(input, output)?;
+            return Ok(metrics);
+        }
+
+        let mut previous: Option<File> = None;
+        for (i, stage) in self.stages.iter().enumerate() {
+            cancel::check()?;
+            let started = Instant::now();
+            let source: Box<dyn BufRead + '_> = match previous.take() {
+                Some(mut file) => {
+                    file.seek(SeekFrom::Start(0))?;
+                    Box::new(BufReader::new(file))
+                }
+                None => Box::new(&mut *input),
+            };
+            let mut reader = BufReader::new(Counting::new(source));
+            let mut next = None;
+            let sink: Box<dyn Write + '_> = if i + 1 == self.stages.len() {
+                Box::new(&mut *output)
+            } else {
+                let file = tempfile::tempfile()?;
+                next = Some(file.try_clone()?);
+                Box::new(BufWriter::new(file))
+            };
+            let mut writer = Counting::new(sink);
+            stage.apply_stream(&mut reader, &mut writer)?;
+            writer.flush()?;
+            let reader = reader.get_ref();
+            metrics.push(StageMetrics {
+                stage: stage.to_string(),
+                lines_in: reader.lines(),
+                lines_out: writer.lines(),
+                bytes_in: reader.bytes(),
+                bytes_out: writer.bytes(),
+                elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+            });
+            previous = next;
+        }
+
+        Ok(metrics)
+    }
+}
+
+/// A time-based seed, for randomized work without a `--seed`.
+pub fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Options of a stage being parsed; unknown leftovers are rejected by `finish`.
+struct StageOptions<'a> {
+    stage: &'a str,
+    values: BTreeMap<String, String>,
+}
+
+impl StageOptions<'_> {
+    fn take_parsed<T: std::str::FromStr>(&mut self, key: &str) -> Result<Option<T>> {
+        match self.values.remove(key) {
+            Some(value) => value.parse().map(Some).map_err(|_| {
+                Error::Other(format!(
+                    "Invalid value '{}' for option '{}' in transform '{}'",
+                    value, key, self.stage
+                ))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self.values.keys().next() {
+            Some(key) => Err(Error::Other(format!(
+                "Unknown option '{}' in transform '{}'",
+                key, self.stage
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_options(stage: &str, options: &str) -> Result<BTreeMap<String, String>> {
+    options
+        .split(',')
+        .filter(|option| !option.trim().is_empty())
+        .map(|option| {
+            option
+                .split_once('=')
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .ok_or_else(|| {
+                    Error::Other(format!(
+                        "Expected key=value option, got '{}' in transform '{}'",
+                        option, stage
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Small, fast, seedable PRNG (SplitMix64) with output that is stable across platforms and releases.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..bound` (bound > 0), using rejection to avoid modulo bias.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - (u64::MAX % bound);
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+/// The next line of `input`, which must be UTF-8, ending with a line ending even if it
+/// is the last and has none.
+fn read_terminated_line(input: &mut dyn BufRead) -> Result<Option<String>> {
+    let mut line = Vec::new();
+    if input.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with(b"\n") {
+        line.push(b'\n');
+    }
+    let line =
+        String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some(line))
+}
+
+fn fisher_yates<T>(items: &mut [T], rng: &mut SplitMix64) {
+    for i in (1..items.len()).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// Shuffles the lines of `input` uniformly into `output`, each with a line ending.
+/// Inputs above `spill_bytes` are scattered into random temporary buckets as they are
+/// read; each bucket is then shuffled in memory (or, when still above `spill_bytes`,
+/// scattered again) and written out in turn, so about one bucket's lines are held at a
+/// time.
+fn shuffle_lines(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    rng: &mut SplitMix64,
+    spill_bytes: usize,
+) -> Result<()> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut held = 0;
+    while held <= spill_bytes {
+        let Some(line) = read_terminated_line(input)? else {
+            fisher_yates(&mut lines, rng);
+            for line in lines {
+                output.write_all(line.as_bytes())?;
+            }
+            return Ok(());
+        };
+        held += line.len();
+        lines.push(line);
+    }
+
+    let rest = std::iter::from_fn(|| read_terminated_line(input).transpose());
+    let buckets = scatter_lines(lines.into_iter().map(Ok).chain(rest), rng)?;
+    debug!("Shuffling via {} spill buckets", buckets.len());
+    shuffle_buckets(buckets, output, rng, spill_bytes, 1)
+}
+
+/// Writes each of `lines` to one of [`SHUFFLE_BUCKETS`] new temporary files picked at
+/// random, returning the files, rewound, and their sizes.
+fn scatter_lines(
+    lines: impl Iterator<Item = Result<String>>,
+    rng: &mut SplitMix64,
+) -> Result<Vec<(File, u64)>> {
+    let mut buckets = (0..SHUFFLE_BUCKETS)
+        .map(|_| tempfile::tempfile().map(|file| (BufWriter::new(file), 0)))
+        .collect::<io::Result<Vec<_>>>()?;
+    for line in lines {
+        let line = line?;
+        let (writer, size) = &mut buckets[rng.below(SHUFFLE_BUCKETS as u64) as usize];
+        writer.write_all(line.as_bytes())?;
+        *size += line.len() as u64;
+    }
+    buckets
+        .into_iter()
+        .map(|(writer, size)| {
+            let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+            file.seek(SeekFrom::Start(0))?;
+            Ok((file, size))
+        })
+        .collect()
+}
+
+/// Shuffles the lines of each bucket and writes them to `output`, bucket after bucket.
+/// Lines were scattered uniformly, so this is a uniform shuffle of all of them.
+fn shuffle_buckets(
+    buckets: Vec<(File, u64)>,
+    output: &mut dyn Write,
+    rng: &mut SplitMix64,
+    spill_bytes: usize,
+    depth: u32,
+) -> Result<()> {
+    for (file, size) in buckets {
+        cancel::check()?;
+        let mut reader = BufReader::new(file);
+        let lines = std::iter::from_fn(|| read_terminated_line(&mut reader).transpose());
+        if size > spill_bytes as u64 && depth < MAX_SHUFFLE_DEPTH {
+            let buckets = scatter_lines(lines, rng)?;
+            shuffle_buckets(buckets, output, rng, spill_bytes, depth + 1)?;
+        } else {
+            let mut lines = lines.collect::<Result<Vec<_>>>()?;
+            fisher_yates(&mut lines, rng);
+            for line in lines {
+                output.write_all(line.as_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Selects `n` lines of `input` by reservoir sampling and writes them in their original
+/// order, each with a line ending.
+fn sample_lines(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    n: usize,
+    rng: &mut SplitMix64,
+) -> Result<()> {
+    let mut reservoir = Reservoir::new(n);
+    for_each_line(input, |line| {
+        reservoir.offer(line.to_string(), rng);
+        Ok(())
+    })?;
+    for line in reservoir.into_items() {
+        output.write_all(line.as_bytes())?;
+        if !line.ends_with('\n') {
+            output.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+/// Selects `n` of `items` by reservoir sampling with a PRNG seeded by `seed`, and returns
+/// them in their original order.
+pub fn sample<T>(items: impl IntoIterator<Item = T>, n: usize, seed: u64) -> Vec<T> {
+    let mut rng = SplitMix64::new(seed);
+    let mut reservoir = Reservoir::new(n);
+    for item in items {
+        reservoir.offer(item, &mut rng);
+    }
+    reservoir.into_items()
+}
+
+/// A uniform sample of `n` of the items offered to it, with their positions.
+struct Reservoir<T> {
+    n: usize,
+    offered: usize,
+    items: Vec<(usize, T)>,
+}
+
+impl<T> Reservoir<T> {
+    fn new(n: usize) -> Self {
+        Self {
+            n,
+            offered: 0,
+            items: Vec::with_capacity(n),
+        }
+    }
+
+    fn offer(&mut self, item: T, rng: &mut SplitMix64) {
+        let i = self.offered;
+        self.offered += 1;
+        if self.items.len() < self.n {
+            self.items.push((i, item));
+        } else {
+            let j = rng.below(i as u64 + 1) as usize;
+            if j < self.n {
+                self.items[j] = (i, item);
+            }
+        }
+    }
+
+    /// The sampled items in the order they were offered.
+    fn into_items(mut self) -> Vec<T> {
+        self.items.sort_by_key(|(i, _)| *i);
+        self.items.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+/// Ends every terminated line of `input` with `to`; a last line without a line ending
+/// keeps going without one.
+fn convert_line_endings(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    to: LineEnding,
+) -> Result<()> {
+    for_each_line(input, |line| {
+        match line.strip_suffix('\n') {
+            Some(body) => {
+                output.write_all(body.strip_suffix('\r').unwrap_or(body).as_bytes())?;
+                output.write_all(to.as_str().as_bytes())?;
+            }
+            None => output.write_all(line.as_bytes())?,
+        }
+        Ok(())
+    })
+}
+
+/// Maps each line of `input` (without its terminator) through `f` into `output`,
+/// preserving line endings, and stops at the first error. Returning `None` drops the
+/// line.
+fn map_lines(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    mut f: impl FnMut(&str) -> Result<Option<String>>,
+) -> Result<()> {
+    for_each_line(input, |line| {
+        let body = line.trim_end_matches(['\r', '\n']);
+        if let Some(mapped) = f(body)? {
+            output.write_all(mapped.as_bytes())?;
+            output.write_all(&line.as_bytes()[body.len()..])?;
+        }
+        Ok(())
+    })
+}
+
+/// Calls `f` with each line of `input`, including its line ending, failing on lines that
+/// are not UTF-8.
+fn for_each_line(input: &mut dyn BufRead, mut f: impl FnMut(&str) -> Result<()>) -> Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        let line = std::str::from_utf8(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        f(line)?;
+    }
+}
+
+/// Counts the bytes and lines passing through a reader or writer.
+pub struct Counting<T> {
+    inner: T,
+    bytes: usize,
+    line_breaks: usize,
+    /// Whether the last byte seen started or continued a line without a line ending
+    open: bool,
+}
+
+impl<T> Counting<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            bytes: 0,
+            line_breaks: 0,
+            open: false,
+        }
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Lines seen, counted like [`str::lines`] does.
+    pub fn lines(&self) -> usize {
+        self.line_breaks + usize::from(self.open)
+    }
+
+    fn count(&mut self, data: &[u8]) {
+        self.bytes += data.len();
+        self.line_breaks += memchr::memchr_iter(b'\n', data).count();
+        if let Some(&last) = data.last() {
+            self.open = last != b'\n';
+        }
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pipeline() {
+        let pipeline =
+            Pipeline::parse("trim | dedupe-lines|truncate:width=3", &BTreeMap::new()).unwrap();
+        assert_eq!(
+            pipeline.stages,
+            vec![
+                Transform::Trim,
+                Transform::DedupeLines,
+                Transform::Truncate { width: 3 }
+            ]
+        );
+        assert_eq!(pipeline.stages[2].to_string(), "truncate:width=3");
+    }
+
+    #[test]
+    fn test_shuffle_is_seeded_permutation() {
+        let content: String = (0..200).map(|i| format!("line {}\n", i)).collect();
+        let pipeline = Pipeline::parse("shuffle-lines:seed=7", &BTreeMap::new()).unwrap();
+        let (first, _) = pipeline.apply(&content).unwrap();
+        let (second, _) = pipeline.apply(&content).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, content);
+
+        let mut sorted: Vec<&str> = first.lines().collect();
+        sorted.sort_by_key(|line| line[5..].parse::<u32>().unwrap());
+        assert_eq!(sorted.join("\n") + "\n", content);
+    }
+
+    #[test]
+    fn test_shuffle_spill_path() {
+        let shuffled = |content: &str, spill_bytes| {
+            let mut output = Vec::new();
+            let mut rng = SplitMix64::new(1);
+            shuffle_lines(&mut content.as_bytes(), &mut output, &mut rng, spill_bytes).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let content: String = (0..500).map(|i| format!("{}\n", i)).collect();
+        // Buckets above the spill size are scattered again, down to the deepest level
+        for spill_bytes in [64, 1] {
+            let spilled = shuffled(&content, spill_bytes);
+            assert_eq!(spilled, shuffled(&content, spill_bytes));
+            let mut numbers: Vec<u32> = spilled.lines().map(|l| l.parse().unwrap()).collect();
+            assert_ne!(numbers, (0..500).collect::<Vec<_>>());
+            numbers.sort();
+            assert_eq!(numbers, (0..500).collect::<Vec<_>>());
+        }
+
+        // Both paths frame lines alike, ending the last line without one
+        let content = "a\r\nb\n\nc";
+        let sorted = |shuffled: String| {
+            let mut lines: Vec<String> = shuffled.split_inclusive('\n').map(String::from).collect();
+            lines.sort();
+            lines
+        };
+        assert_eq!(
+            sorted(shuffled(content, SHUFFLE_SPILL_BYTES)),
+            sorted(shuffled(content, 1))
+        );
+        assert_eq!(sorted(shuffled(content, 1)), ["\n", "a\r\n", "b\n", "c\n"]);
+    }
+
+    #[test]
+    fn test_sample_lines_keeps_order() {
+        let content: String = (0..100).map(|i| format!("{}\n", i)).collect();
+        let sample_lines = |content: &str| {
+            Transform::SampleLines {
+                n: 5,
+                seed: Some(3),
+            }
+            .apply(content)
+            .unwrap()
+        };
+        let sample = sample_lines(&content);
+        let numbers: Vec<u32> = sample.lines().map(|l| l.parse().unwrap()).collect();
+        assert_eq!(numbers.len(), 5);
+        assert!(numbers.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sample, sample_lines(&content));
+        assert_eq!(sample_lines("a\nb"), "a\nb\n");
+    }
+
+    #[test]
+    fn test_named_pipelines() {
+        let named = BTreeMap::from([(
+            String::from("clean"),
+            vec![String::from("trim"), String::from("dedupe-lines")],
+        )]);
+        let pipeline = Pipeline::parse("@clean | uppercase", &named).unwrap();
+        assert_eq!(
+            pipeline.stages,
+            vec![
+                Transform::Trim,
+                Transform::DedupeLines,
+                Transform::Uppercase
+            ]
+        );
+
+        let message = Pipeline::parse("@dirty", &named).unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "Unknown pipeline '@dirty'. Defined in config: clean"
+        );
+    }
+
+    #[test]
+    fn test_resolve_seeds() {
+        let mut pipeline =
+            Pipeline::parse("shuffle-lines | sample-lines:seed=1", &BTreeMap::new()).unwrap();
+        pipeline.resolve_seeds(Some(9));
+        assert_eq!(
+            pipeline.stages[0],
+            Transform::ShuffleLines { seed: Some(9) }
+        );
+        assert_eq!(
+            pipeline.stages[1],
+            Transform::SampleLines {
+                n: 10,
+                seed: Some(1)
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Pipeline::parse("nope", &BTreeMap::new()).is_err());
+        assert!(Pipeline::parse("trim |", &BTreeMap::new()).is_err());
+        assert!(Pipeline::parse("truncate:width=abc", &BTreeMap::new()).is_err());
+        assert!(Pipeline::parse("truncate:size=3", &BTreeMap::new()).is_err());
+        assert!(Pipeline::parse("truncate:3", &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_sort_lines() {
+        let pipeline = Pipeline::parse("sort-lines:unique=true", &BTreeMap::new()).unwrap();
+        assert_eq!(pipeline.stages[0].to_string(), "sort-lines:unique=true");
+        let (out, _) = pipeline.apply("b\na\nb\nc").unwrap();
+        assert_eq!(out, "a\nb\nc\n");
+        assert!(Pipeline::parse("sort-lines:unique=maybe", &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let pipeline = Pipeline::parse("line-endings:to=crlf", &BTreeMap::new()).unwrap();
+        assert_eq!(pipeline.stages[0].to_string(), "line-endings:to=crlf");
+        let (out, _) = pipeline.apply("a\nb\r\nc").unwrap();
+        assert_eq!(out, "a\r\nb\r\nc");
+
+        let (out, _) = Pipeline::parse("line-endings", &BTreeMap::new())
+            .unwrap()
+            .apply("a\r\nb\n\r\n")
+            .unwrap();
+        assert_eq!(out, "a\nb\n\n");
+        assert!(Pipeline::parse("line-endings:to=cr", &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_apply_stream_matches_apply() {
+        let pipeline =
+            Pipeline::parse("trim | sort-lines | truncate:width=2", &BTreeMap::new()).unwrap();
+        let content = " pear\r\napple \nfig";
+        let (expected, metrics) = pipeline.apply(content).unwrap();
+        let mut output = Vec::new();
+        let streamed = pipeline
+            .apply_stream(&mut content.as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+        for (streamed, stage) in streamed.iter().zip(&metrics) {
+            assert_eq!(
+                (streamed.lines_in, streamed.lines_out),
+                (stage.lines_in, stage.lines_out)
+            );
+            assert_eq!(
+                (streamed.bytes_in, streamed.bytes_out),
+                (stage.bytes_in, stage.bytes_out)
+            );
+        }
+        assert_eq!(streamed.len(), 3);
+    }
+
+    #[test]
+    fn test_apply_preserves_line_endings() {
+        let pipeline = Pipeline::parse("trim|dedupe-lines", &BTreeMap::new()).unwrap();
+        let (out, metrics) = pipeline.apply("  a \r\nb\n a\nc").unwrap();
+        assert_eq!(out, "a\r\nb\nc");
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[1].lines_in, 4);
+        assert_eq!(metrics[1].lines_out, 3);
+    }
+}
@@ -0,0 +1,160 @@
+//! Extended attributes of files, on the platforms that have them (Linux and macOS).
+
+use std::io;
+use std::path::Path;
+
+/// Attributes that must not carry over to another file.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const SKIPPED: &[&[u8]] = &[
+    // Set on downloads; copying it would make Gatekeeper question the new binary
+    b"com.apple.quarantine",
+];
+
+/// Copies the extended attributes of `from` to `to`, skipping those that cannot be set
+/// (e.g. `security.*` without privileges). Returns the names of the attributes copied.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn copy_all(from: &Path, to: &Path) -> io::Result<Vec<String>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let from = CString::new(from.as_os_str().as_bytes())?;
+    let to = CString::new(to.as_os_str().as_bytes())?;
+    let names = read_buffer(|buf, size| sys::list(&from, buf, size))?;
+    let mut copied = Vec::new();
+    for name in names.split(|&b| b == 0).filter(|name| !name.is_empty()) {
+        if SKIPPED.contains(&name) {
+            continue;
+        }
+        let name = CString::new(name)?;
+        let result = read_buffer(|buf, size| sys::get(&from, &name, buf, size))
+            .and_then(|value| sys::set(&to, &name, &value));
+        match result {
+            Ok(()) => copied.push(name.to_string_lossy().into_owned()),
+            Err(e) => tracing::debug!(
+                "Not copying extended attribute {}: {}",
+                name.to_string_lossy(),
+                e
+            ),
+        }
+    }
+    Ok(copied)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn copy_all(_from: &Path, _to: &Path) -> io::Result<Vec<String>> {
+    Ok(Vec::new())
+}
+
+/// Calls `read` first to learn the size of the data, then to fetch it.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn read_buffer(read: impl Fn(*mut libc::c_void, usize) -> libc::ssize_t) -> io::Result<Vec<u8>> {
+    let size = read(std::ptr::null_mut(), 0);
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut buffer = vec![0u8; size as usize];
+    if buffer.is_empty() {
+        return Ok(buffer);
+    }
+    let size = read(buffer.as_mut_ptr().cast(), buffer.len());
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    buffer.truncate(size as usize);
+    Ok(buffer)
+}
+
+// In `sys`, paths and names are NUL-terminated and `buf` is either null with a size of 0
+// or valid for writes of `size` bytes
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::CStr;
+    use std::io;
+
+    pub fn list(path: &CStr, buf: *mut libc::c_void, size: usize) -> libc::ssize_t {
+        // SAFETY: see above
+        unsafe { libc::listxattr(path.as_ptr(), buf.cast(), size) }
+    }
+
+    pub fn get(path: &CStr, name: &CStr, buf: *mut libc::c_void, size: usize) -> libc::ssize_t {
+        // SAFETY: see above
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size) }
+    }
+
+    pub fn set(path: &CStr, name: &CStr, value: &[u8]) -> io::Result<()> {
+        // SAFETY: `value` is valid for reads of its length
+        let result = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use std::ffi::CStr;
+    use std::io;
+
+    pub fn list(path: &CStr, buf: *mut libc::c_void, size: usize) -> libc::ssize_t {
+        // SAFETY: see above
+        unsafe { libc::listxattr(path.as_ptr(), buf.cast(), size, 0) }
+    }
+
+    pub fn get(path: &CStr, name: &CStr, buf: *mut libc::c_void, size: usize) -> libc::ssize_t {
+        // SAFETY: see above
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size, 0, 0) }
+    }
+
+    pub fn set(path: &CStr, name: &CStr, value: &[u8]) -> io::Result<()> {
+        // SAFETY: `value` is valid for reads of its length
+        let result = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+                0,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_copy_all() {
+        let dir = TempDir::new().unwrap();
+        let (from, to) = (dir.path().join("from"), dir.path().join("to"));
+        fs::write(&from, "a").unwrap();
+        fs::write(&to, "b").unwrap();
+
+        let from_c = std::ffi::CString::new(from.to_str().unwrap()).unwrap();
+        let name = std::ffi::CString::new("user.origin").unwrap();
+        if sys::set(&from_c, &name, b"template").is_err() {
+            // The filesystem of the temporary directory has no user attributes
+            return;
+        }
+        assert_eq!(copy_all(&from, &to).unwrap(), ["user.origin"]);
+        let to_c = std::ffi::CString::new(to.to_str().unwrap()).unwrap();
+        let value = read_buffer(|buf, size| sys::get(&to_c, &name, buf, size)).unwrap();
+        assert_eq!(value, b"template");
+    }
+}
@@ -0,0 +1,43 @@
+use predicates::prelude::*;
+
+use crate::support::cli;
+
+#[test]
+fn test_auth_status_reports_env_token() {
+    cli()
+        .env("GITHUB_TOKEN", "ghp_abcdefgh12345678")
+        .arg("auth")
+        .arg("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "github-token: ghp_...5678 (from environment variable GITHUB_TOKEN)",
+        ))
+        .stdout(predicate::str::contains("abcdefgh").not());
+
+    let output = cli()
+        .env("GITHUB_TOKEN", "ghp_abcdefgh12345678")
+        .arg("--format")
+        .arg("json")
+        .arg("auth")
+        .arg("status")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let statuses: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(statuses[0]["name"], "github-token");
+    assert_eq!(statuses[0]["set"], true);
+    assert_eq!(statuses[0]["source"]["kind"], "env");
+    assert_eq!(statuses[0]["source"]["name"], "GITHUB_TOKEN");
+}
+
+#[test]
+fn test_auth_set_token_requires_value() {
+    cli()
+        .arg("auth")
+        .arg("set-token")
+        .write_stdin("\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No token provided"));
+}
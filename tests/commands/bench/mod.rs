@@ -0,0 +1,54 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+#[test]
+fn test_bench_reports_and_compares() {
+    let temp_dir = TempDir::new().unwrap();
+    let bench = || {
+        let mut cmd = cli();
+        cmd.current_dir(temp_dir.path())
+            .args(["bench", "--size", "1", "-n", "3", "--warmup", "0"]);
+        cmd
+    };
+
+    bench()
+        .arg("--save")
+        .arg("baseline.json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 iteration(s) after 0 warm-up"))
+        .stdout(predicate::str::is_match(r"(?m)^  p90 +[0-9.]+$").unwrap())
+        .stdout(predicate::str::contains("MB/s"));
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_dir.path().join("baseline.json")).unwrap())
+            .unwrap();
+    assert_eq!(saved["iterations"], 3);
+    assert!(saved["corpus_bytes"].as_u64().unwrap() >= 1_000_000);
+    assert!(saved["seconds"]["p50"].as_f64().unwrap() > 0.0);
+
+    let output = bench()
+        .args(["--strategy", "streaming", "--compare", "baseline.json"])
+        .arg("--format")
+        .arg("json")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["strategy"], "streaming");
+    // The same seed generates the same corpus
+    assert_eq!(report["corpus_lines"], saved["corpus_lines"]);
+    assert_eq!(report["baseline"]["version"], saved["version"]);
+    assert!(report["baseline"]["change_percent"].is_number());
+
+    fs::write(temp_dir.path().join("other.json"), "{}").unwrap();
+    bench()
+        .args(["--compare", "other.json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "other.json is not a benchmark result written by --save",
+        ));
+}
@@ -0,0 +1,112 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+#[test]
+fn test_run_cache_reuses_statistics() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache_home = temp_dir.path().join("cache");
+    fs::write(temp_dir.path().join("a.txt"), "one two\nthree\n").unwrap();
+    let run = |extra: &[&str]| {
+        let output = cli()
+            .current_dir(temp_dir.path())
+            .env("XDG_CACHE_HOME", &cache_home)
+            .args([
+                "--format",
+                "json",
+                "run",
+                "--input",
+                "a.txt",
+                "--stats-only",
+            ])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        report["files"][0].clone()
+    };
+
+    // Opt-in: nothing is cached by default
+    assert!(run(&[]).get("cached").is_none());
+    let first = run(&["--cache"]);
+    assert!(first.get("cached").is_none());
+    let second = run(&["--cache"]);
+    assert_eq!(second["cached"], true);
+    assert_eq!(second["stats"], first["stats"]);
+
+    // Other options or content miss the cache
+    assert!(run(&["--cache", "--top-words", "2"])
+        .get("cached")
+        .is_none());
+    fs::write(temp_dir.path().join("a.txt"), "changed\n").unwrap();
+    let changed = run(&["--cache"]);
+    assert!(changed.get("cached").is_none());
+    assert_eq!(changed["stats"]["words"], 1);
+
+    // The config enables it, and --no-cache overrides that
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"cache": {"enabled": true}}"#,
+    )
+    .unwrap();
+    assert_eq!(run(&[])["cached"], true);
+    assert!(run(&["--no-cache"]).get("cached").is_none());
+
+    let output = cli()
+        .env("XDG_CACHE_HOME", &cache_home)
+        .args(["--format", "json", "cache", "info"])
+        .output()
+        .unwrap();
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["entries"], 3);
+    assert!(info["dir"]
+        .as_str()
+        .unwrap()
+        .starts_with(cache_home.to_str().unwrap()));
+
+    cli()
+        .env("XDG_CACHE_HOME", &cache_home)
+        .args(["cache", "clean"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 3 cached result(s)"));
+    assert!(run(&[]).get("cached").is_none());
+}
+
+#[test]
+fn test_run_cache_keeps_thresholds() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache_home = temp_dir.path().join("cache");
+    fs::write(temp_dir.path().join("t.txt"), "one\ntwo\n").unwrap();
+    let run = || {
+        cli()
+            .current_dir(temp_dir.path())
+            .env("XDG_CACHE_HOME", &cache_home)
+            .args([
+                "--format",
+                "json",
+                "run",
+                "--input",
+                "t.txt",
+                "--stats-only",
+            ])
+            .args(["--fail-if-lines-over", "1", "--cache"])
+            .assert()
+            .code(3)
+    };
+
+    let first = run();
+    let first: serde_json::Value = serde_json::from_slice(&first.get_output().stdout).unwrap();
+    assert!(first["files"][0].get("cached").is_none());
+    // A cache hit must fail the same way
+    let second = run();
+    let second: serde_json::Value = serde_json::from_slice(&second.get_output().stdout).unwrap();
+    assert_eq!(second["files"][0]["cached"], true);
+    assert_eq!(
+        second["files"][0]["violations"],
+        first["files"][0]["violations"]
+    );
+}
@@ -0,0 +1,537 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+#[test]
+fn test_config_set_coerces_and_persists() {
+    let temp_dir = TempDir::new().unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("config")
+        .arg("set")
+        .arg("profiles.local.parallel_jobs")
+        .arg("8")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Set profiles.local.parallel_jobs = 8",
+        ));
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_dir.path().join("config.json")).unwrap())
+            .unwrap();
+    assert_eq!(saved["profiles"]["local"]["parallel_jobs"], 8);
+}
+
+#[test]
+fn test_config_set_rejects_type_mismatch() {
+    let temp_dir = TempDir::new().unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("config")
+        .arg("set")
+        .arg("profiles.local.parallel_jobs")
+        .arg("eight")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected integer"));
+
+    assert!(!temp_dir.path().join("config.json").exists());
+}
+
+#[test]
+fn test_config_set_json_value_yaml_file() {
+    let temp_dir = TempDir::new().unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("-C")
+        .arg("settings.yaml")
+        .arg("config")
+        .arg("set")
+        .arg("profiles.nightly")
+        .arg(r#"{"output_dir": "./nightly", "log_level": "debug", "parallel_jobs": 2}"#)
+        .arg("--json")
+        .assert()
+        .success();
+
+    let saved = fs::read_to_string(temp_dir.path().join("settings.yaml")).unwrap();
+    assert!(saved.contains("nightly:"));
+    assert!(saved.contains("output_dir: ./nightly"));
+}
+
+#[test]
+fn test_config_set_validates_before_saving() {
+    let temp_dir = TempDir::new().unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("config")
+        .arg("set")
+        .arg("default_profile")
+        .arg("missing")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Default profile 'missing' not found",
+        ));
+}
+
+#[test]
+fn test_config_schema() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("config")
+        .arg("schema")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema["$schema"], "http://json-schema.org/draft-07/schema#");
+    assert!(schema["definitions"]["Profile"].is_object());
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("config")
+        .arg("schema")
+        .arg("--output")
+        .arg("config.schema.json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Schema written to: config.schema.json",
+        ));
+    let written = fs::read_to_string(temp_dir.path().join("config.schema.json")).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&written).unwrap(),
+        schema
+    );
+}
+
+#[test]
+fn test_strict_config_flag() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("input.txt"), "text\n").unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"profiles": {"local": {"parralel_jobs": 2}}}"#,
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--stats-only")
+        .assert()
+        .success();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--strict-config")
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--stats-only")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Unknown configuration key(s) in config.json: 'profiles.local.parralel_jobs' (did you mean 'parallel_jobs'?)",
+        ));
+}
+
+#[test]
+fn test_config_set_keeps_extends_unresolved() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("base.json"),
+        r#"{"pipelines": {"clean": ["trim"]}}"#,
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"extends": ["base.json"]}"#,
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("config")
+        .arg("set")
+        .arg("update_check.budget_ms")
+        .arg("50")
+        .assert()
+        .success();
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_dir.path().join("config.json")).unwrap())
+            .unwrap();
+    assert_eq!(saved["extends"][0], "base.json");
+    assert_eq!(saved["update_check"]["budget_ms"], 50);
+    assert!(saved["pipelines"].get("clean").is_none());
+}
+
+#[test]
+fn test_config_set_key_defined_in_base() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("base.json"),
+        r#"{"default_profile": "team", "profiles": {"team": {"output_dir": "out"}}}"#,
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"extends": ["base.json"]}"#,
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("config")
+        .arg("set")
+        .arg("profiles.team.parallel_jobs")
+        .arg("3")
+        .assert()
+        .success();
+
+    // Only the key set is written; the profile it refines stays in the base
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_dir.path().join("config.json")).unwrap())
+            .unwrap();
+    assert_eq!(
+        saved,
+        serde_json::json!({"extends": ["base.json"], "profiles": {"team": {"parallel_jobs": 3}}})
+    );
+}
+
+/// Writes an executable editor script that replaces the edited file with `contents`.
+#[cfg(unix)]
+fn fake_editor(dir: &std::path::Path, contents: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = dir.join("editor.sh");
+    fs::write(
+        &script,
+        format!("#!/bin/sh\ncat > \"$1\" <<'EOF'\n{}\nEOF\n", contents),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_edit_creates_and_validates() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // A no-op editor leaves the freshly created defaults untouched
+    cli()
+        .current_dir(temp_dir.path())
+        .env_remove("VISUAL")
+        .env("EDITOR", "true")
+        .arg("config")
+        .arg("edit")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No changes made"));
+    assert!(temp_dir.path().join("config.json").exists());
+
+    let editor = fake_editor(temp_dir.path(), r#"{"default_profile": "ci"}"#);
+    cli()
+        .current_dir(temp_dir.path())
+        .env("VISUAL", &editor)
+        .env("EDITOR", "false")
+        .arg("config")
+        .arg("edit")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Configuration saved"));
+
+    let editor = fake_editor(temp_dir.path(), r#"{"default_profile": "missing"}"#);
+    cli()
+        .current_dir(temp_dir.path())
+        .env("VISUAL", &editor)
+        .arg("config")
+        .arg("edit")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid after editing"))
+        .stderr(predicate::str::contains(
+            "Default profile 'missing' not found",
+        ));
+
+    // A broken file can still be opened for repair
+    let editor = fake_editor(temp_dir.path(), r#"{"default_profile": "local"}"#);
+    cli()
+        .current_dir(temp_dir.path())
+        .env("VISUAL", &editor)
+        .arg("config")
+        .arg("edit")
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_config_show_origin() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"default_profile": "ci", "profiles": {"ci": {"output_dir": "/srv/ci", "parallel_jobs": 2}}}"#,
+    )
+    .unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .env("__TEMPLATE_ENV_PREFIX___PARALLEL_JOBS", "6")
+        .arg("--format")
+        .arg("json")
+        .arg("config")
+        .arg("show")
+        .arg("--origin")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let entry = |key: &str| {
+        entries
+            .iter()
+            .find(|entry| entry["key"] == key)
+            .unwrap()
+            .clone()
+    };
+
+    assert_eq!(entry("profile")["value"], "ci");
+    assert_eq!(entry("profile")["origin"]["kind"], "file");
+    assert_eq!(entry("output_dir")["value"], "/srv/ci");
+    assert_eq!(entry("output_dir")["origin"]["kind"], "profile");
+    assert_eq!(entry("parallel_jobs")["value"], 6);
+    assert_eq!(
+        entry("parallel_jobs")["origin"]["name"],
+        "__TEMPLATE_ENV_PREFIX___PARALLEL_JOBS"
+    );
+    assert_eq!(entry("format")["origin"]["name"], "--format");
+    assert_eq!(entry("log_level")["origin"]["kind"], "default");
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("config")
+        .arg("show")
+        .arg("--origin")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"output_dir\s+/srv/ci\s+profile 'ci' in config.json")
+                .unwrap(),
+        );
+}
+
+#[test]
+fn test_no_env_ignores_prefixed_variables() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"default_profile": "ci", "profiles": {"ci": {"parallel_jobs": 2}}}"#,
+    )
+    .unwrap();
+    let with_env = || {
+        let mut cmd = cli();
+        cmd.current_dir(temp_dir.path())
+            .env("__TEMPLATE_ENV_PREFIX___CONFIG", "missing.json")
+            .env("__TEMPLATE_ENV_PREFIX___PROFILE", "other")
+            .env("__TEMPLATE_ENV_PREFIX___PARALLEL_JOBS", "0");
+        cmd
+    };
+
+    // The invalid override fails without --no-env
+    with_env()
+        .arg("config")
+        .arg("show")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("PARALLEL_JOBS"));
+
+    let output = with_env()
+        .arg("--no-env")
+        .arg("--log-level")
+        .arg("info")
+        .arg("--format")
+        .arg("json")
+        .arg("config")
+        .arg("show")
+        .arg("--origin")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let entry = |key: &str| {
+        entries
+            .iter()
+            .find(|entry| entry["key"] == key)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(entry("config_file")["value"], "config.json");
+    assert_eq!(entry("config_file")["origin"]["kind"], "default");
+    assert_eq!(entry("profile")["value"], "ci");
+    assert_eq!(entry("parallel_jobs")["value"], 2);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for name in ["CONFIG", "PARALLEL_JOBS", "PROFILE"] {
+        let ignored = format!(
+            "Ignoring environment variable __TEMPLATE_ENV_PREFIX___{} (--no-env)",
+            name
+        );
+        assert!(stderr.contains(&ignored), "{}", stderr);
+    }
+
+    // References in the file are resolved the same way
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"default_profile": "${__TEMPLATE_ENV_PREFIX___PROFILE}"}"#,
+    )
+    .unwrap();
+    with_env()
+        .arg("--no-env")
+        .arg("config")
+        .arg("show")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Environment variable '__TEMPLATE_ENV_PREFIX___PROFILE' is ignored (--no-env)",
+        ));
+}
+
+#[test]
+fn test_project_config_discovery() {
+    let temp_dir = TempDir::new().unwrap();
+    let nested = temp_dir.path().join("packages/web");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(
+        temp_dir
+            .path()
+            .join(format!(".{}.yaml", env!("CARGO_PKG_NAME"))),
+        "profiles:\n  local:\n    output_dir: ./build\n    log_level: error\n    parallel_jobs: 3\n",
+    )
+    .unwrap();
+
+    let parallel_jobs = |search: bool| {
+        let mut command = cli();
+        command
+            .current_dir(&nested)
+            .arg("--format")
+            .arg("json")
+            .arg("config")
+            .arg("show");
+        if !search {
+            command.arg("--no-config-search");
+        }
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        let settings: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        settings["parallel_jobs"].clone()
+    };
+    assert_eq!(parallel_jobs(true), 3);
+    assert_eq!(parallel_jobs(false), 4);
+}
+
+#[test]
+fn test_config_set_scope() {
+    let temp_dir = TempDir::new().unwrap();
+    let system = temp_dir.path().join("system");
+    let user = temp_dir.path().join("user");
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project).unwrap();
+    let scoped = |scope: &str, value: &str| {
+        cli()
+            .current_dir(&project)
+            .env("__TEMPLATE_ENV_PREFIX___SYSTEM_CONFIG_DIR", &system)
+            .env("__TEMPLATE_ENV_PREFIX___USER_CONFIG_DIR", &user)
+            .args(["config", "set", "profiles.local.parallel_jobs", value])
+            .args(["--scope", scope])
+            .assert()
+            .success()
+    };
+    let parallel_jobs = || {
+        let output = cli()
+            .current_dir(&project)
+            .env("__TEMPLATE_ENV_PREFIX___SYSTEM_CONFIG_DIR", &system)
+            .env("__TEMPLATE_ENV_PREFIX___USER_CONFIG_DIR", &user)
+            .args(["--format", "json", "config", "show", "--origin"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+        let entry = entries
+            .into_iter()
+            .find(|entry| entry["key"] == "parallel_jobs")
+            .unwrap();
+        (entry["value"].clone(), entry["origin"]["file"].clone())
+    };
+
+    scoped("system", "2");
+    let system_file = system.join("config.yaml");
+    // Only the key is written, so the layer overrides nothing else
+    assert_eq!(
+        fs::read_to_string(&system_file).unwrap(),
+        "profiles:\n  local:\n    parallel_jobs: 2\n"
+    );
+    assert_eq!(parallel_jobs(), (2.into(), system_file.to_str().into()));
+
+    scoped("user", "3");
+    let user_file = user.join("config.yaml");
+    assert_eq!(parallel_jobs(), (3.into(), user_file.to_str().into()));
+
+    // Setting a lower scope than the one in effect says which file wins
+    scoped("system", "5").stderr(predicate::str::contains(format!(
+        "is also set in {}, which takes precedence over the system configuration",
+        user_file.display()
+    )));
+    assert_eq!(parallel_jobs().0, 3);
+
+    scoped("project", "4");
+    let project_file = project.join(format!(".{}.yaml", env!("CARGO_PKG_NAME")));
+    assert!(project_file.is_file());
+    assert_eq!(parallel_jobs().0, 4);
+}
+
+#[test]
+fn test_config_init_scope() {
+    let temp_dir = TempDir::new().unwrap();
+    let user = temp_dir.path().join("user");
+
+    let init = || {
+        cli()
+            .current_dir(temp_dir.path())
+            .env("__TEMPLATE_ENV_PREFIX___USER_CONFIG_DIR", &user)
+            .args(["config", "init", "--scope", "user"])
+            .assert()
+    };
+    init()
+        .success()
+        .stdout(predicate::str::contains("Created configuration file"));
+    assert_eq!(
+        fs::read_to_string(user.join("config.yaml")).unwrap(),
+        "{}\n"
+    );
+    init()
+        .failure()
+        .stderr(predicate::str::contains("pass --force to overwrite it"));
+
+    // Without a scope, the configuration file gets every default
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["config", "init"])
+        .assert()
+        .success();
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_dir.path().join("config.json")).unwrap())
+            .unwrap();
+    assert_eq!(saved["default_profile"], "local");
+}
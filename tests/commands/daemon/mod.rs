@@ -0,0 +1,335 @@
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+#[test]
+fn test_daemon_needs_directories_to_watch() {
+    let temp_dir = TempDir::new().unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["daemon", "--once"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to watch"));
+
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"daemon": {"watch": ["."], "run_args": ["--batch", "jobs.yaml"]}}"#,
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["daemon", "--once"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid daemon.run_args: the argument '--batch",
+        ));
+
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"daemon": {"watch": ["."], "schedule": "0 0 30 2 *"}}"#,
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["daemon", "--once"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("never comes due"));
+}
+
+#[test]
+fn test_daemon_once_processes_new_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache_home = temp_dir.path().join("cache");
+    fs::create_dir(temp_dir.path().join("in")).unwrap();
+    fs::write(temp_dir.path().join("in/a.txt"), "one two\n").unwrap();
+    fs::write(temp_dir.path().join("in/b.txt"), "three\n").unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"daemon": {"watch": ["in"], "run_args": ["--stats-only"]}}"#,
+    )
+    .unwrap();
+    let once = || {
+        cli()
+            .current_dir(temp_dir.path())
+            .env("XDG_CACHE_HOME", &cache_home)
+            .args(["daemon", "--once", "--pid-file", "daemon.pid"])
+            .assert()
+            .success()
+    };
+
+    once()
+        .stdout(predicate::str::contains("File statistics for 'in/a.txt'"))
+        .stdout(predicate::str::contains(
+            "Processed 2 new or changed file(s).",
+        ));
+    assert!(!temp_dir.path().join("daemon.pid").exists());
+    once().stdout(predicate::str::contains(
+        "Processed 0 new or changed file(s).",
+    ));
+
+    // Hidden files, e.g. uploads in progress, are left alone
+    fs::write(temp_dir.path().join("in/b.txt"), "three four\n").unwrap();
+    fs::write(temp_dir.path().join("in/.c.txt.part"), "partial\n").unwrap();
+    once()
+        .stdout(predicate::str::contains("in/b.txt"))
+        .stdout(predicate::str::contains("in/a.txt").not())
+        .stdout(predicate::str::contains(
+            "Processed 1 new or changed file(s).",
+        ));
+}
+
+#[test]
+fn test_daemon_picks_up_files_allowed_by_run_globs() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("in/logs")).unwrap();
+    fs::write(temp_dir.path().join("in/a.txt"), "one\n").unwrap();
+    fs::write(temp_dir.path().join("in/b.log"), "two\n").unwrap();
+    fs::write(temp_dir.path().join("in/logs/c.txt"), "three\n").unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"daemon": {"watch": ["in"], "run_args": ["--stats-only", "--include", "*.TXT", "--exclude", "logs/", "--glob-ignore-case"]}}"#,
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+        .args(["daemon", "--once", "--pid-file", "daemon.pid"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("File statistics for 'in/a.txt'"))
+        .stdout(predicate::str::contains("b.log").not())
+        .stdout(predicate::str::contains("c.txt").not())
+        .stdout(predicate::str::contains(
+            "Processed 1 new or changed file(s).",
+        ));
+}
+
+/// Waits up to ten seconds for `path` to exist.
+#[cfg(unix)]
+fn wait_for(path: &Path) -> bool {
+    wait_until(|| path.exists())
+}
+
+/// Waits up to ten seconds for `done` to hold.
+#[cfg(unix)]
+fn wait_until(mut done: impl FnMut() -> bool) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(10) {
+        if done() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+/// Waits up to ten seconds for a PID to be written to `path`, and returns it.
+#[cfg(unix)]
+fn wait_for_pid(path: &Path) -> Option<String> {
+    let mut pid = None;
+    wait_until(|| {
+        pid = fs::read_to_string(path).ok().filter(|pid| !pid.is_empty());
+        pid.is_some()
+    });
+    pid
+}
+
+/// A daemon started in `dir` with `args`, killed when dropped if it is still running. Its
+/// stderr is collected as it is written.
+#[cfg(unix)]
+struct Daemon {
+    child: std::process::Child,
+    stderr: std::sync::Arc<std::sync::Mutex<String>>,
+}
+
+#[cfg(unix)]
+impl Daemon {
+    fn spawn(dir: &Path, args: &[&str]) -> Self {
+        use std::io::Read;
+
+        let mut child =
+            std::process::Command::new(assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")))
+                .current_dir(dir)
+                .env("XDG_CACHE_HOME", dir.join("cache"))
+                .arg("daemon")
+                .args(args)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+        let stderr = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+        let (mut pipe, collected) = (child.stderr.take().unwrap(), stderr.clone());
+        std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            while let Ok(read @ 1..) = pipe.read(&mut buffer) {
+                collected
+                    .lock()
+                    .unwrap()
+                    .push_str(&String::from_utf8_lossy(&buffer[..read]));
+            }
+        });
+        Self { child, stderr }
+    }
+
+    fn signal(&self, name: &str) {
+        let status = std::process::Command::new("kill")
+            .args([name, &self.child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    /// Waits up to ten seconds for `text` to appear on stderr.
+    fn wait_for_stderr(&self, text: &str) -> bool {
+        wait_until(|| self.stderr().contains(text))
+    }
+
+    fn stderr(&self) -> String {
+        self.stderr.lock().unwrap().clone()
+    }
+
+    /// Waits up to ten seconds for the daemon to exit.
+    fn wait(&mut self) -> Option<std::process::ExitStatus> {
+        let mut status = None;
+        wait_until(|| {
+            status = self.child.try_wait().unwrap();
+            status.is_some()
+        });
+        status
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_watches_and_reloads() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    fs::create_dir(dir.join("in")).unwrap();
+    let config = |transform: &str, log_level: &str| {
+        let config = serde_json::json!({
+            "daemon": {
+                "watch": ["in"],
+                "run_args": ["--output-dir", "out", "--transform", transform],
+                "poll_secs": 1,
+            },
+            "profiles": {"local": {"log_level": log_level}},
+        });
+        fs::write(dir.join("config.json"), config.to_string()).unwrap();
+    };
+    config("uppercase", "error");
+
+    let mut daemon = Daemon::spawn(dir, &["--pid-file", "daemon.pid"]);
+    let pid = daemon.child.id().to_string();
+    assert_eq!(wait_for_pid(&dir.join("daemon.pid")), Some(pid.clone()));
+    cli()
+        .current_dir(dir)
+        .args(["daemon", "--pid-file", "daemon.pid"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "Another instance is running (pid {})",
+            pid
+        )));
+
+    fs::write(dir.join("in/a.txt"), "Hello\n").unwrap();
+    assert!(wait_for(&dir.join("out/a.txt")));
+    assert_eq!(
+        fs::read_to_string(dir.join("out/a.txt")).unwrap(),
+        "HELLO\n"
+    );
+
+    // SIGHUP picks up the new arguments and log level; an invalid configuration would be
+    // ignored
+    config("lowercase", "info");
+    daemon.signal("-HUP");
+    assert!(
+        daemon.wait_for_stderr("Configuration changed: daemon.run_args"),
+        "{}",
+        daemon.stderr()
+    );
+    fs::write(dir.join("in/b.txt"), "World\n").unwrap();
+    assert!(wait_for(&dir.join("out/b.txt")));
+    assert_eq!(
+        fs::read_to_string(dir.join("out/b.txt")).unwrap(),
+        "world\n"
+    );
+
+    // SIGTERM stops it cleanly, removing the PID file
+    daemon.signal("-TERM");
+    let status = daemon.wait();
+    assert!(
+        status.is_some_and(|status| status.success()),
+        "{:?}",
+        status
+    );
+    assert!(!dir.join("daemon.pid").exists());
+    let stderr = daemon.stderr();
+    assert!(stderr.contains("Log level set to info"), "{}", stderr);
+    assert!(
+        stderr.contains(r#"Configuration changed: daemon.run_args: ["--output-dir","out","--transform","uppercase"] -> ["--output-dir","out","--transform","lowercase"]"#),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_pid_file_without_lock() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    fs::create_dir(dir.join("in")).unwrap();
+    fs::write(dir.join("config.json"), r#"{"daemon": {"watch": ["in"]}}"#).unwrap();
+
+    let mut daemon = Daemon::spawn(dir, &["--pid-file", "run/daemon.pid", "--no-lock"]);
+    assert_eq!(
+        wait_for_pid(&dir.join("run/daemon.pid")),
+        Some(daemon.child.id().to_string())
+    );
+    daemon.signal("-TERM");
+    assert!(daemon.wait().is_some_and(|status| status.success()));
+    assert!(!dir.join("run/daemon.pid").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_ctrl_c_while_waiting_for_lock_exits() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    fs::create_dir(dir.join("in")).unwrap();
+    fs::write(dir.join("config.json"), r#"{"daemon": {"watch": ["in"]}}"#).unwrap();
+    let _holder = Daemon::spawn(dir, &["--pid-file", "daemon.pid"]);
+    assert!(wait_for_pid(&dir.join("daemon.pid")).is_some());
+
+    // Nothing checks for cancellation while waiting, so the first Ctrl-C ends it
+    let mut waiting = Daemon::spawn(
+        dir,
+        &[
+            "--pid-file",
+            "daemon.pid",
+            "--wait-for-lock",
+            "--log-level",
+            "info",
+        ],
+    );
+    assert!(waiting.wait_for_stderr("Waiting for another instance"));
+    waiting.signal("-INT");
+    assert_eq!(waiting.wait().and_then(|status| status.code()), Some(130));
+}
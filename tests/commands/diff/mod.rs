@@ -0,0 +1,122 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+fn fixtures() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "one\n2\nthree\nfour\nfive\n").unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_diff_unified() {
+    let temp_dir = fixtures();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("diff")
+        .arg("a.txt")
+        .arg("b.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--- a.txt\n+++ b.txt\n"))
+        .stdout(predicate::str::contains("@@ -1,4 +1,5 @@"))
+        .stdout(predicate::str::contains("-two\n+2\n"))
+        .stdout(predicate::str::contains("+five"))
+        .stdout(predicate::str::contains("\x1b[").not());
+}
+
+#[test]
+fn test_diff_color() {
+    let temp_dir = fixtures();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--color")
+        .arg("always")
+        .arg("diff")
+        .arg("a.txt")
+        .arg("b.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[31m-two\x1b[0m"))
+        .stdout(predicate::str::contains("\x1b[32m+2\x1b[0m"));
+}
+
+#[test]
+fn test_diff_stat() {
+    let temp_dir = fixtures();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("diff")
+        .arg("a.txt")
+        .arg("b.txt")
+        .arg("--stat")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a.txt => b.txt | 3 ++-"))
+        .stdout(predicate::str::contains(
+            "1 file changed, 2 insertions(+), 1 deletion(-)",
+        ));
+}
+
+#[test]
+fn test_diff_identical() {
+    let temp_dir = fixtures();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("diff")
+        .arg("a.txt")
+        .arg("a.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn test_diff_json() {
+    let temp_dir = fixtures();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("--format")
+        .arg("json")
+        .arg("diff")
+        .arg("a.txt")
+        .arg("b.txt")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["identical"], false);
+    assert_eq!(report["insertions"], 2);
+    assert_eq!(report["deletions"], 1);
+    assert_eq!(report["hunks"][0]["old_start"], 1);
+    assert_eq!(report["hunks"][0]["new_lines"], 5);
+    assert_eq!(report["hunks"][0]["lines"][1]["tag"], "-");
+    assert_eq!(report["hunks"][0]["lines"][1]["text"], "two");
+}
+
+#[test]
+fn test_diff_missing_file() {
+    let temp_dir = fixtures();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("diff")
+        .arg("a.txt")
+        .arg("missing.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Cannot open 'missing.txt'"))
+        .stderr(predicate::str::contains("\n  caused by: "))
+        .stderr(predicate::str::contains(
+            "\n  hint: Check that the path exists",
+        ));
+}
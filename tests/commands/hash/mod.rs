@@ -0,0 +1,120 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+const ABC_BLAKE3: &str = "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85";
+
+#[test]
+fn test_hash_files() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "abc").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["hash", "a.txt", "b.txt"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}  a.txt\n{}  b.txt\n",
+            ABC_SHA256, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        ));
+
+    cli()
+        .args(["hash", "--algorithm", "blake3", "-"])
+        .write_stdin("abc")
+        .assert()
+        .success()
+        .stdout(format!("{}  -\n", ABC_BLAKE3));
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .args(["--format", "json", "hash", "a.txt"])
+        .output()
+        .unwrap();
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(entries[0]["path"], "a.txt");
+    assert_eq!(entries[0]["algorithm"], "sha256");
+    assert_eq!(entries[0]["hex"], ABC_SHA256);
+
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["hash", "missing.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.txt"));
+}
+
+#[test]
+fn test_hash_check() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "abc").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "abc").unwrap();
+
+    // What `hash` prints verifies with `--check`
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .args(["hash", "a.txt", "b.txt"])
+        .output()
+        .unwrap();
+    fs::write(temp_dir.path().join("SHA256SUMS"), &output.stdout).unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["hash", "--check", "SHA256SUMS"])
+        .assert()
+        .success()
+        .stdout("a.txt: OK\nb.txt: OK\n");
+
+    fs::write(temp_dir.path().join("b.txt"), "changed").unwrap();
+    fs::write(
+        temp_dir.path().join("SHA256SUMS"),
+        format!(
+            "{}  a.txt\n{}  b.txt\n{} *gone.txt\n",
+            ABC_SHA256.to_uppercase(),
+            ABC_SHA256,
+            ABC_SHA256
+        ),
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["hash", "-c", "SHA256SUMS"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("a.txt: OK\n"))
+        .stdout(predicate::str::contains("b.txt: FAILED\n"))
+        .stdout(predicate::str::contains("gone.txt: FAILED open or read\n"))
+        .stderr(predicate::str::contains(
+            "2 of 3 file(s) failed verification",
+        ));
+
+    // The algorithm has to match the one the sums were made with
+    fs::write(
+        temp_dir.path().join("B3SUMS"),
+        format!("{}  a.txt\n", ABC_BLAKE3),
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["hash", "-a", "blake3", "-c", "B3SUMS"])
+        .assert()
+        .success();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["hash", "-c", "B3SUMS"])
+        .assert()
+        .failure();
+
+    fs::write(temp_dir.path().join("bad"), "not a checksum\n").unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["hash", "-c", "bad"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "bad:1: expected '<sha256>  <file>'",
+        ));
+}
@@ -0,0 +1,69 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+#[test]
+fn test_init_scaffolds_project() {
+    let temp_dir = TempDir::new().unwrap();
+    let home = temp_dir.path().join("home");
+
+    cli()
+        .current_dir(temp_dir.path())
+        .env("HOME", &home)
+        .env_remove("XDG_DATA_HOME")
+        .arg("init")
+        .arg("--completions")
+        .arg("bash")
+        .arg("--samples")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Created configuration file config.json",
+        ))
+        .stdout(predicate::str::contains("run --input samples/hello.txt"));
+
+    // The generated configuration loads, and the samples can be processed right away
+    let config = fs::read_to_string(temp_dir.path().join("config.json")).unwrap();
+    assert!(config.contains("default_profile"));
+    let completions = home
+        .join(".local/share/bash-completion/completions")
+        .join(env!("CARGO_PKG_NAME"));
+    assert!(fs::read_to_string(completions)
+        .unwrap()
+        .contains("complete "));
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("samples/hello.txt")
+        .assert()
+        .success();
+
+    // Existing files are kept unless --force is given
+    fs::write(temp_dir.path().join("config.json"), "{}").unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("init")
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"kept\""));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("config.json")).unwrap(),
+        "{}"
+    );
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("init")
+        .arg("--force")
+        .assert()
+        .success();
+    let parse = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+    assert_eq!(
+        parse(&fs::read_to_string(temp_dir.path().join("config.json")).unwrap()),
+        parse(&config)
+    );
+}
@@ -3,7 +3,20 @@ use tempfile::TempDir;
 
 use crate::support::cli;
 
+pub mod auth;
+pub mod bench;
+pub mod cache;
+pub mod config;
+pub mod daemon;
+pub mod diff;
+pub mod hash;
+pub mod init;
+pub mod package;
+pub mod render;
 pub mod run;
+pub mod selftest;
+pub mod transform;
+pub mod upgrade;
 
 #[test]
 fn test_cli_version() {
@@ -14,6 +27,33 @@ fn test_cli_version() {
         .stdout(predicate::str::contains(env!("CARGO_PKG_NAME")));
 }
 
+#[test]
+fn test_cli_version_verbose() {
+    cli()
+        .args(["--version", "--verbose"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("commit:"))
+        .stdout(predicate::str::contains("rustc:"))
+        .stdout(predicate::str::contains("target:"));
+}
+
+#[test]
+fn test_version_json() {
+    let output = cli()
+        .args(["version", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let version = env!("CARGO_PKG_VERSION");
+    assert_eq!(info["version"], version);
+    assert!(info["git_dirty"].is_boolean());
+    assert!(info["rustc"].as_str().unwrap().starts_with("rustc"));
+    assert!(!info["target"].as_str().unwrap().is_empty());
+    assert!(info["features"].is_array());
+}
+
 #[test]
 fn test_cli_help() {
     cli()
@@ -25,6 +65,51 @@ fn test_cli_help() {
         .stdout(predicate::str::contains("run"));
 }
 
+#[test]
+fn test_help_groups_global_options() {
+    cli()
+        .arg("run")
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Global options:"))
+        .stdout(predicate::str::contains("--help-all"))
+        .stdout(predicate::str::contains("--record <FILE>"))
+        .stdout(predicate::str::contains("--replay <FILE>"));
+}
+
+#[test]
+fn test_help_all_keeps_visible_options() {
+    let help = |flag: &str| {
+        let output = cli().args(["run", flag]).output().unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let help_all = help("--help-all");
+    assert!(help_all.contains("run [OPTIONS]"), "{}", help_all);
+    // Options shown by --help are shown by --help-all as well, which adds the hidden ones
+    for line in help("--help").lines() {
+        assert!(
+            help_all.contains(line),
+            "{:?} missing from:\n{}",
+            line,
+            help_all
+        );
+    }
+}
+
+#[test]
+fn test_help_wraps_to_columns() {
+    let output = cli().env("COLUMNS", "50").arg("--help").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // The usage line is never wrapped; everything else must fit
+    assert!(stdout
+        .lines()
+        .filter(|line| !line.starts_with("Usage:"))
+        .all(|line| line.chars().count() <= 50));
+}
+
 #[test]
 fn test_invalid_command() {
     cli()
@@ -34,6 +119,223 @@ fn test_invalid_command() {
         .stderr(predicate::str::contains("unrecognized subcommand"));
 }
 
+#[test]
+fn test_invalid_command_suggestions() {
+    cli()
+        .arg("upgarde")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "a similar subcommand exists: 'upgrade'",
+        ));
+
+    // Flags of another subcommand are pointed out
+    cli()
+        .arg("diff")
+        .arg("--include")
+        .arg("*.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("'--include' is an option of"))
+        .stderr(predicate::str::contains(" run'"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_plugin_subcommand() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let plugin = temp_dir
+        .path()
+        .join(format!("{}-hello", env!("CARGO_PKG_NAME")));
+    std::fs::write(&plugin, "#!/bin/sh\necho \"hello $*\"\nexit 4\n").unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    cli()
+        .env("PATH", temp_dir.path())
+        .arg("hello")
+        .arg("--name")
+        .arg("world")
+        .assert()
+        .code(4)
+        .stdout("hello --name world\n");
+
+    cli()
+        .env("PATH", temp_dir.path())
+        .arg("helo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "a similar subcommand exists: 'hello'",
+        ));
+}
+
+// XDG_CACHE_HOME places the cache directory on Linux only
+#[cfg(target_os = "linux")]
+#[test]
+fn test_renamed_build_keeps_its_own_directories() {
+    // An uncustomized build takes its name from the executable
+    let temp_dir = TempDir::new().unwrap();
+    let renamed = temp_dir.path().join("renamed-tool");
+    std::fs::copy(
+        assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")),
+        &renamed,
+    )
+    .unwrap();
+    std::fs::write(temp_dir.path().join("input.txt"), "one two\n").unwrap();
+
+    let cache = temp_dir.path().join("cache");
+    let output = std::process::Command::new(&renamed)
+        .current_dir(temp_dir.path())
+        .env("XDG_CACHE_HOME", &cache)
+        .args(["run", "--stats-only", "--cache", "-i", "input.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let names: Vec<_> = std::fs::read_dir(&cache)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["renamed-tool"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_alias_by_invoked_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let binary = assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME"));
+    let alias = temp_dir.path().join("wcx");
+    let renamed = temp_dir.path().join("renamed");
+    std::os::unix::fs::symlink(&binary, &alias).unwrap();
+    std::os::unix::fs::symlink(&binary, &renamed).unwrap();
+    std::fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"aliases": {"wcx": ["run", "--stats-only"]}}"#,
+    )
+    .unwrap();
+    std::fs::write(temp_dir.path().join("input.txt"), "one two\nthree\n").unwrap();
+
+    let output = std::process::Command::new(&alias)
+        .current_dir(temp_dir.path())
+        .args(["--format", "json", "-i", "input.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"][0]["stats"]["words"], 3);
+    // --stats-only: nothing was transformed
+    assert!(report["files"][0].get("output").is_none());
+
+    // --no-env keeps the environment from picking another configuration file
+    std::fs::write(
+        temp_dir.path().join("other.json"),
+        r#"{"aliases": {"wcx": ["version"]}}"#,
+    )
+    .unwrap();
+    let output = std::process::Command::new(&alias)
+        .current_dir(temp_dir.path())
+        .env("__TEMPLATE_ENV_PREFIX___CONFIG", "other.json")
+        .args(["--no-env", "--format", "json", "-i", "input.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"][0]["stats"]["words"], 3);
+
+    // Aliases in project configuration files apply like their other settings
+    let project = temp_dir.path().join("project");
+    std::fs::create_dir(&project).unwrap();
+    std::fs::write(
+        project.join(format!(".{}.yaml", env!("CARGO_PKG_NAME"))),
+        "aliases:\n  wcx: [run, --stats-only, -i, ../input.txt]\n",
+    )
+    .unwrap();
+    let output = std::process::Command::new(&alias)
+        .current_dir(&project)
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"][0]["stats"]["words"], 3);
+
+    // A name without an alias behaves like the binary itself
+    let output = std::process::Command::new(&renamed)
+        .current_dir(temp_dir.path())
+        .arg("--version")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn test_then_chains_commands() {
+    let output = cli()
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--text")
+        .arg("one")
+        .arg("--stats-only")
+        .arg("--then")
+        .arg("run")
+        .arg("--text")
+        .arg("two words")
+        .arg("--stats-only")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // Global options apply to every command in the chain
+    let reports: Vec<serde_json::Value> = serde_json::Deserializer::from_slice(&output.stdout)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0]["files"][0]["stats"]["words"], 1);
+    assert_eq!(reports[1]["files"][0]["stats"]["words"], 2);
+
+    // The chain stops at the first failure
+    cli()
+        .arg("run")
+        .arg("--input")
+        .arg("missing.txt")
+        .arg("--then")
+        .arg("run")
+        .arg("--text")
+        .arg("never")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("NEVER").not());
+
+    cli()
+        .arg("run")
+        .arg("--text")
+        .arg("x")
+        .arg("--then")
+        .arg("run")
+        .arg("--text")
+        .arg("y")
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Global option 'format' after --then must come before the first command",
+        ));
+
+    cli()
+        .arg("run")
+        .arg("--text")
+        .arg("x")
+        .arg("--then")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--then must be followed by a command",
+        ));
+}
+
 #[test]
 fn test_global_verbose_flag() {
     let temp_dir = TempDir::new().unwrap();
@@ -180,3 +482,246 @@ fn test_log_level_with_verbose() {
         .success()
         .stderr(predicate::str::contains("INFO"));
 }
+
+#[test]
+fn test_record_and_replay_session() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    std::fs::write(&test_file, "one two three").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .env("__TEMPLATE_ENV_PREFIX___GITHUB_TOKEN", "hunter2")
+        .arg("--record")
+        .arg("session.json")
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--stats-only")
+        .assert()
+        .success();
+
+    let session = std::fs::read_to_string(temp_dir.path().join("session.json")).unwrap();
+    assert!(session.contains("\"--stats-only\""));
+    assert!(session.contains("sha256"));
+    assert!(session.contains("<redacted>"));
+    assert!(!session.contains("hunter2"));
+    assert!(!session.contains("--record"));
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--replay")
+        .arg("session.json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Words: 3"));
+
+    // Replaying against modified inputs is refused
+    std::fs::write(&test_file, "changed").unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--replay")
+        .arg("session.json")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Inputs changed"));
+}
+
+#[test]
+fn test_missing_subcommand() {
+    cli()
+        .arg("-v")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("a subcommand is required"));
+}
+
+#[test]
+fn test_update_check_does_not_block_command() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    std::fs::write(&test_file, "content").unwrap();
+    std::fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"update_check": {"enabled": true, "budget_ms": 0}}"#,
+    )
+    .unwrap();
+
+    let started = std::time::Instant::now();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--stats-only")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Words: 1"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[test]
+fn test_timings_report_phases() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("input.txt"), "hello\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--timings")
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--output")
+        .arg("./output.txt")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Timings:"))
+        .stderr(predicate::str::is_match(r"(?m)^  config load +1 +[0-9.]+ ms$").unwrap())
+        .stderr(predicate::str::is_match(r"(?m)^  read +1 ").unwrap())
+        .stderr(predicate::str::is_match(r"(?m)^  process +1 ").unwrap())
+        .stderr(predicate::str::is_match(r"(?m)^  write +1 ").unwrap());
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("--timings")
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--stats-only")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // The command's JSON on stdout is unaffected
+    serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let report: serde_json::Value = serde_json::from_str(stderr.lines().last().unwrap()).unwrap();
+    let phases: Vec<&str> = report["timings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|phase| phase["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(phases, ["config load", "read", "process"]);
+}
+
+#[test]
+fn test_pinned_version_mismatch() {
+    let temp_dir = TempDir::new().unwrap();
+    let pin_file = temp_dir
+        .path()
+        .join(format!(".{}-version", env!("CARGO_PKG_NAME")));
+    std::fs::write(&pin_file, "# team version\n0.0.1\n").unwrap();
+    std::fs::create_dir(temp_dir.path().join("sub")).unwrap();
+
+    cli()
+        .current_dir(temp_dir.path().join("sub"))
+        .arg("version")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("pins version 0.0.1"))
+        .stderr(predicate::str::contains("upgrade --to-pinned"));
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--strict-version")
+        .arg("version")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pins version 0.0.1"));
+
+    let running = env!("CARGO_PKG_VERSION");
+    std::fs::write(&pin_file, format!("v{}\n", running)).unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--strict-version")
+        .arg("version")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("pins").not());
+
+    // A pin without a version only fails commands when pins are enforced
+    std::fs::write(&pin_file, "# team version\n").unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("version")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("No version found in"));
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--strict-version")
+        .arg("version")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No version found in"));
+}
+
+#[test]
+fn test_messages_follow_language() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("test.txt");
+    std::fs::write(&test_file, "one\ntwo\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["--lang", "de", "run", "--stats-only", "--input"])
+        .arg(&test_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Zeilen: 2"))
+        .stdout(predicate::str::contains("Lines").not());
+
+    // Table headers as well
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["--lang", "de", "run", "--stats-format", "table", "--input"])
+        .arg(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pfad"))
+        .stdout(predicate::str::contains("Path").not());
+
+    // Locale environment, with a region and encoding the catalogs do not name
+    cli()
+        .current_dir(temp_dir.path())
+        .env("LANG", "de_AT.UTF-8")
+        .args(["run", "--input"])
+        .arg(&test_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Verarbeitung abgeschlossen."));
+
+    // --lang wins over the environment, and unknown languages fall back to English
+    cli()
+        .current_dir(temp_dir.path())
+        .env("LC_ALL", "de_DE.UTF-8")
+        .args(["--lang", "xx", "run", "--input"])
+        .arg(&test_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Processing complete."))
+        .stderr(predicate::str::contains(
+            "No messages in 'xx' (available: en, de)",
+        ));
+
+    // Errors are translated too
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["--lang", "de", "run", "--input", "missing.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Tipp: Prüfen Sie, ob der Pfad existiert",
+        ));
+    std::fs::write(temp_dir.path().join("bad.json"), "{").unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args([
+            "--lang", "de", "run", "--mode", "json", "--input", "bad.json",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Ungültige Eingabe (JSON):"));
+}
@@ -0,0 +1,148 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+/// Repository for builds of the uncustomized template, which have none of their own.
+const REPOSITORY_ENV: &str = "__TEMPLATE_ENV_PREFIX___REPOSITORY";
+
+#[test]
+fn test_package_manifest_writes_all_kinds() {
+    let temp_dir = TempDir::new().unwrap();
+    let digest = "a".repeat(64);
+    fs::write(
+        temp_dir.path().join("SHA256SUMS"),
+        format!(
+            "{}  dist/{}-v2.0.0_aarch64-apple-darwin.tar.gz\n",
+            digest,
+            env!("CARGO_PKG_NAME")
+        ),
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .env(REPOSITORY_ENV, "acme/tool")
+        .arg("package")
+        .arg("manifest")
+        .arg("--version")
+        .arg("v2.0.0")
+        .arg("--checksums")
+        .arg("SHA256SUMS")
+        .arg("--output-dir")
+        .arg("packaging")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("homebrew manifest written to:"))
+        .stdout(predicate::str::contains("scoop manifest written to:"))
+        .stdout(predicate::str::contains("nfpm manifest written to:"));
+
+    let packaging = temp_dir.path().join("packaging");
+    let formula =
+        fs::read_to_string(packaging.join(format!("{}.rb", env!("CARGO_PKG_NAME")))).unwrap();
+    assert!(formula.contains("version \"2.0.0\""));
+    assert!(formula.contains(&format!(
+        "https://github.com/acme/tool/releases/download/v2.0.0/{}-v2.0.0_aarch64-apple-darwin.tar.gz",
+        env!("CARGO_PKG_NAME")
+    )));
+    assert!(formula.contains(&format!("sha256 \"{}\"", digest)));
+    assert!(formula.contains("REPLACE_WITH_SHA256"));
+
+    let scoop: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(packaging.join(format!("{}.json", env!("CARGO_PKG_NAME")))).unwrap(),
+    )
+    .unwrap();
+    assert!(scoop["architecture"]["64bit"]["url"]
+        .as_str()
+        .unwrap()
+        .ends_with("_x86_64-pc-windows-msvc.zip"));
+
+    let nfpm = fs::read_to_string(packaging.join("nfpm.yaml")).unwrap();
+    assert!(nfpm.contains("version: 2.0.0"));
+}
+
+#[test]
+fn test_package_manifest_single_kind_to_stdout() {
+    cli()
+        .env(REPOSITORY_ENV, "acme/tool")
+        .arg("package")
+        .arg("manifest")
+        .arg("--kind")
+        .arg("nfpm")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("# Build with: nfpm"))
+        .stdout(predicate::str::contains(format!(
+            "dst: /usr/bin/{}",
+            env!("CARGO_PKG_NAME")
+        )));
+}
+
+#[test]
+fn test_package_manifest_repeated_kinds_written_once() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .env(REPOSITORY_ENV, "acme/tool")
+        .args(["package", "manifest", "--output-dir", "packaging"])
+        .args(["--kind", "nfpm", "--kind", "homebrew", "--kind", "nfpm"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.matches("nfpm manifest written to:").count(), 1);
+    assert_eq!(stdout.matches("homebrew manifest written to:").count(), 1);
+    assert!(stdout.find("nfpm").unwrap() < stdout.find("homebrew").unwrap());
+}
+
+#[test]
+fn test_package_manifest_rejects_malformed_checksums() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("sums"), "not-a-digest file\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("package")
+        .arg("manifest")
+        .arg("--checksums")
+        .arg("sums")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sums:1: expected"));
+}
+
+#[test]
+fn test_package_manifest_repository_from_config() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"repository": "https://github.com/acme/from-config"}"#,
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .env_remove(REPOSITORY_ENV)
+        .args(["package", "manifest", "--kind", "scoop"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"homepage\": \"https://github.com/acme/from-config\"",
+        ));
+}
+
+#[test]
+fn test_package_manifest_requires_repository() {
+    let temp_dir = TempDir::new().unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .env_remove(REPOSITORY_ENV)
+        .args(["package", "manifest", "--kind", "nfpm"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No GitHub repository configured"));
+}
@@ -0,0 +1,135 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+#[test]
+fn test_render_with_data_file() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("hosts.tmpl"),
+        "# {{name}}\n{{#each hosts}}{{@index}} {{addr}}{{#if primary}} primary{{/if}}\n{{/each}}",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("data.yaml"),
+        "name: cluster\nhosts:\n  - addr: 10.0.0.1\n    primary: true\n  - addr: 10.0.0.2\n",
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["render", "hosts.tmpl", "--data", "data.yaml"])
+        .assert()
+        .success()
+        .stdout("# cluster\n0 10.0.0.1 primary\n1 10.0.0.2\n");
+
+    fs::write(temp_dir.path().join("data.json"), r#"{"name": "json"}"#).unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args([
+            "render",
+            "hosts.tmpl",
+            "-d",
+            "data.json",
+            "-o",
+            "./hosts.txt",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rendered 7 bytes to ./hosts.txt"));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("hosts.txt")).unwrap(),
+        "# json\n"
+    );
+
+    fs::write(temp_dir.path().join("bad.tmpl"), "{{#if name}}").unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["render", "bad.tmpl", "--data", "data.json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "bad.tmpl: Invalid template (line 1)",
+        ));
+}
+
+#[test]
+fn test_render_profile_values_and_dry_run() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"profiles": {"local": {"output_dir": "./build", "log_level": "warning", "parallel_jobs": 3}}}"#,
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("env.tmpl"),
+        "PROFILE={{profile}}\nJOBS={{parallel_jobs}}\nLOG={{log_level}}\n",
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["render", "env.tmpl"])
+        .assert()
+        .success()
+        .stdout("PROFILE=local\nJOBS=3\nLOG=warning\n");
+
+    // A bare output name goes to the profile's output_dir; --dry-run writes nothing
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .args([
+            "--format",
+            "json",
+            "render",
+            "env.tmpl",
+            "-o",
+            "app.env",
+            "--dry-run",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["written"], false);
+    assert_eq!(report["bytes"], 33);
+    assert!(report["output"].as_str().unwrap().ends_with("app.env"));
+    let written = temp_dir.path().join("build/app.env");
+    assert!(!written.exists());
+
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["render", "env.tmpl", "-o", "app.env"])
+        .assert()
+        .success();
+    assert!(fs::read_to_string(written)
+        .unwrap()
+        .starts_with("PROFILE=local\n"));
+}
+
+#[test]
+fn test_render_documents_and_rejects_unsupported_syntax() {
+    cli()
+        .args(["render", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("subset of Handlebars"))
+        .stdout(predicate::str::contains("Anything else is rejected"));
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("page.tmpl"),
+        "{{> header}}\n{{name}}\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("data.json"), r#"{"name": "x"}"#).unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["render", "page.tmpl", "--data", "data.json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "page.tmpl: Invalid template (line 1): partials ({{> name}}) are not supported",
+        ));
+}
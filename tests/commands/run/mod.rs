@@ -38,6 +38,26 @@ fn test_run_with_nonexistent_file() {
         .stderr(predicate::str::contains("File not found: nonexistent.txt"));
 }
 
+#[test]
+fn test_run_error_as_json() {
+    let output = cli()
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg("nonexistent.txt")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("[ERROR]"), "{}", stderr);
+    let error: serde_json::Value = serde_json::from_str(stderr.lines().last().unwrap()).unwrap();
+    assert_eq!(error["code"], "not_found");
+    assert_eq!(error["message"], "File not found: nonexistent.txt");
+    assert_eq!(error["hint"], "Check that the path exists");
+    assert_eq!(error["source_chain"], serde_json::json!([]));
+}
+
 #[test]
 fn test_run_stats_only() {
     let temp_dir = TempDir::new().unwrap();
@@ -74,10 +94,10 @@ fn test_run_with_output_file() {
         .arg("--input")
         .arg(test_file.to_str().unwrap())
         .arg("--output")
-        .arg("output.txt")
+        .arg("./output.txt")
         .assert()
         .success()
-        .stdout(predicate::str::contains("Output written to: output.txt"))
+        .stdout(predicate::str::contains("Output written to: ./output.txt"))
         .stdout(predicate::str::contains("[SUCCESS]"));
 
     // Verify output file was created with uppercase content
@@ -195,7 +215,7 @@ fn test_run_output_overwrites_existing() {
         .arg("--input")
         .arg(input.to_str().unwrap())
         .arg("--output")
-        .arg("output.txt")
+        .arg("./output.txt")
         .assert()
         .success();
 
@@ -227,6 +247,100 @@ fn test_run_with_config_option() {
         .stderr(predicate::str::contains("Using configuration file"));
 }
 
+#[test]
+fn test_run_checks_profile_output_dir_at_startup() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "test").unwrap();
+    fs::write(temp_dir.path().join("blocker"), "").unwrap();
+
+    let config_file = temp_dir.path().join("config.json");
+    fs::write(
+        &config_file,
+        r#"{"profiles": {"local": {"output_dir": "blocker/out"}}}"#,
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("-C")
+        .arg(&config_file)
+        .arg("--create-dirs")
+        .arg("run")
+        .arg("--input")
+        .arg(&test_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Output directory 'blocker/out' of profile 'local' could not be created",
+        ));
+
+    fs::write(
+        &config_file,
+        r#"{"profiles": {"local": {"output_dir": "results/today"}}}"#,
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("-C")
+        .arg(&config_file)
+        .arg("--create-dirs")
+        .arg("run")
+        .arg("--input")
+        .arg(&test_file)
+        .assert()
+        .success();
+    assert!(temp_dir.path().join("results/today").is_dir());
+}
+
+#[test]
+fn test_run_null_output_reports_throughput() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("in")).unwrap();
+    fs::write(temp_dir.path().join("in/a.txt"), "alpha\nbeta\n").unwrap();
+    fs::write(temp_dir.path().join("in/b.txt"), "gamma\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--null-output")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^Processed 2 file\(s\), 17 bytes in, 17 bytes out \(discarded\), in [0-9.]+ s: [0-9.]+ MB/s\n$").unwrap());
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--transform")
+        .arg("dedupe-lines | sample-lines:n=1")
+        .arg("--null-output")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["throughput"]["files"], 2);
+    assert_eq!(report["throughput"]["bytes_in"], 17);
+    assert!(report["throughput"]["bytes_out"].as_u64().unwrap() < 17);
+
+    cli()
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--null-output")
+        .arg("--output-dir")
+        .arg("out")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    assert!(!temp_dir.path().join("out").exists());
+}
+
 #[test]
 fn test_run_empty_file() {
     let temp_dir = TempDir::new().unwrap();
@@ -276,3 +390,2301 @@ fn test_run_help() {
         .stdout(predicate::str::contains("--output"))
         .stdout(predicate::str::contains("--stats-only"));
 }
+
+#[test]
+fn test_run_grep_keeps_matching_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("app.log");
+    fs::write(
+        &test_file,
+        "INFO start\nERROR disk full\nINFO done\nERROR timeout\n",
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg(test_file.to_str().unwrap())
+        .arg("--grep")
+        .arg("^ERROR")
+        .arg("--output")
+        .arg("./errors.log")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Matching lines: 2"));
+
+    let output = fs::read_to_string(temp_dir.path().join("errors.log")).unwrap();
+    assert_eq!(output, "ERROR disk full\nERROR timeout\n");
+}
+
+#[test]
+fn test_run_replace_with_capture_groups() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("names.txt");
+    fs::write(&test_file, "Lovelace, Ada\nHopper, Grace\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg(test_file.to_str().unwrap())
+        .arg("--replace")
+        .arg(r"(?m)^(\w+), (\w+)$")
+        .arg("$2 $1")
+        .arg("--output")
+        .arg("./out.txt")
+        .assert()
+        .success();
+
+    let output = fs::read_to_string(temp_dir.path().join("out.txt")).unwrap();
+    assert_eq!(output, "Ada Lovelace\nGrace Hopper\n");
+}
+
+#[test]
+fn test_run_invalid_regex() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "content").unwrap();
+
+    cli()
+        .arg("run")
+        .arg("--input")
+        .arg(test_file.to_str().unwrap())
+        .arg("--grep")
+        .arg("(unclosed")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid regular expression"));
+}
+
+#[test]
+fn test_run_directory_mirrors_layout() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("in");
+    fs::create_dir_all(input_dir.join("nested/deeper")).unwrap();
+    fs::write(input_dir.join("top.txt"), "top").unwrap();
+    fs::write(input_dir.join("nested/deeper/leaf.txt"), "leaf").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--output-dir")
+        .arg("out")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Processed 2 files"));
+
+    let out = temp_dir.path().join("out");
+    assert_eq!(fs::read_to_string(out.join("top.txt")).unwrap(), "TOP");
+    assert_eq!(
+        fs::read_to_string(out.join("nested/deeper/leaf.txt")).unwrap(),
+        "LEAF"
+    );
+}
+
+#[test]
+fn test_run_directory_include_exclude_globs() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("in");
+    fs::create_dir_all(input_dir.join("logs/old")).unwrap();
+    fs::write(input_dir.join("notes.TXT"), "notes").unwrap();
+    fs::write(input_dir.join("logs/app.txt"), "app").unwrap();
+    fs::write(input_dir.join("logs/old/app.txt"), "old").unwrap();
+    fs::write(input_dir.join("logs/app.log"), "log").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--output-dir")
+        .arg("out")
+        .arg("--include")
+        .arg("*.txt")
+        .arg("--exclude")
+        .arg("logs/old/")
+        .arg("--glob-ignore-case")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Processed 2 files"));
+
+    let out = temp_dir.path().join("out");
+    assert!(out.join("notes.TXT").exists());
+    assert!(out.join("logs/app.txt").exists());
+    assert!(!out.join("logs/app.log").exists());
+    assert!(!out.join("logs/old/app.txt").exists());
+}
+
+#[test]
+fn test_run_invalid_glob() {
+    let temp_dir = TempDir::new().unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg(".")
+        .arg("--stats-only")
+        .arg("--include")
+        .arg("[abc")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid glob '[abc'"));
+}
+
+#[test]
+fn test_run_on_modified_outputs() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("input.txt"), "fresh").unwrap();
+    let run = || {
+        let mut command = cli();
+        command
+            .current_dir(temp_dir.path())
+            .arg("run")
+            .arg("--input")
+            .arg("input.txt")
+            .arg("--output-dir")
+            .arg("out");
+        command
+    };
+
+    run().assert().success();
+    let output = temp_dir.path().join("out/input.txt");
+    assert_eq!(fs::read_to_string(&output).unwrap(), "FRESH");
+
+    // Unchanged outputs are rewritten without asking
+    run().assert().success();
+
+    fs::write(&output, "hand edited").unwrap();
+    run().assert().failure().stderr(predicate::str::contains(
+        "was modified since it was last written",
+    ));
+    assert_eq!(fs::read_to_string(&output).unwrap(), "hand edited");
+
+    run()
+        .arg("--on-modified")
+        .arg("skip")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Kept modified output"));
+    assert_eq!(fs::read_to_string(&output).unwrap(), "hand edited");
+
+    run()
+        .arg("--on-modified")
+        .arg("overwrite")
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "FRESH");
+
+    // --yes answers the prompt that would otherwise be shown
+    fs::write(&output, "edited again").unwrap();
+    run().arg("--yes").assert().success();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "FRESH");
+}
+
+#[test]
+fn test_run_dedupe_approx_reports_fp_rate() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("input.txt");
+    fs::write(&input, "x\ny\nx\nz\ny\n").unwrap();
+    let output = temp_dir.path().join("out.txt");
+
+    let result = cli()
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg(&input)
+        .arg("--output")
+        .arg(&output)
+        .arg("--dedupe-approx")
+        .arg("--dedupe-fp-rate")
+        .arg("0.01")
+        .output()
+        .unwrap();
+    assert!(result.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+    let dedupe = &report["files"][0]["dedupe"];
+    assert_eq!(dedupe["fp_rate"], 0.01);
+    assert_eq!(dedupe["lines_removed"], 2);
+    assert_eq!(fs::read_to_string(&output).unwrap(), "x\ny\nz\n");
+
+    // Streamed inputs are deduplicated line by line, with a filter that grows past its
+    // first capacity
+    let result = cli()
+        .args(["--format", "json", "run", "--input"])
+        .arg(&input)
+        .arg("--output")
+        .arg(&output)
+        .args(["--strategy", "streaming", "--dedupe-approx"])
+        .args(["--dedupe-capacity", "1"])
+        .output()
+        .unwrap();
+    assert!(result.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+    assert_eq!(report["files"][0]["dedupe"]["lines_removed"], 2);
+    assert_eq!(fs::read_to_string(&output).unwrap(), "x\ny\nz\n");
+
+    cli()
+        .arg("run")
+        .arg("--input")
+        .arg(&input)
+        .arg("--dedupe-approx")
+        .arg("--dedupe-fp-rate")
+        .arg("1.5")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("between 0 and 1"));
+}
+
+#[test]
+fn test_run_directory_flatten_collisions() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("in");
+    fs::create_dir_all(input_dir.join("a")).unwrap();
+    fs::create_dir_all(input_dir.join("b")).unwrap();
+    fs::write(input_dir.join("a/data.txt"), "first").unwrap();
+    fs::write(input_dir.join("b/data.txt"), "second").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--output-dir")
+        .arg("out")
+        .arg("--flatten")
+        .assert()
+        .success();
+
+    let out = temp_dir.path().join("out");
+    assert_eq!(fs::read_to_string(out.join("data.txt")).unwrap(), "FIRST");
+    assert_eq!(
+        fs::read_to_string(out.join("data-2.txt")).unwrap(),
+        "SECOND"
+    );
+}
+
+#[test]
+fn test_run_directory_rejects_single_output() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("in")).unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--output")
+        .arg("./out.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use --output-dir"));
+}
+
+#[test]
+fn test_run_extended_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "the cat\n\nthe dog and the bird\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg(test_file.to_str().unwrap())
+        .arg("--stats-only")
+        .arg("--top-words")
+        .arg("2")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Chars: 30"))
+        .stdout(predicate::str::contains("Blank lines: 1"))
+        .stdout(predicate::str::contains(
+            "Line length: min 0, max 20, avg 9.00",
+        ))
+        .stdout(predicate::str::contains("Top words:\n         3  the\n"))
+        .stdout(predicate::str::contains("Count").not());
+}
+
+#[test]
+fn test_run_json_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "b a b\n").unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--stats-only")
+        .arg("--top-words")
+        .arg("1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let stats = &report["files"][0]["stats"];
+    assert_eq!(report["files"][0]["path"], "input.txt");
+    assert_eq!(stats["words"], 3);
+    assert_eq!(stats["graphemes"], 6);
+    assert_eq!(stats["top_words"][0]["word"], "b");
+    assert_eq!(stats["top_words"][0]["count"], 2);
+}
+
+#[test]
+fn test_run_inline_text() {
+    let output = cli()
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--text")
+        .arg("how many words is this")
+        .arg("--stats-only")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"][0]["path"], "<text>");
+    assert_eq!(report["files"][0]["stats"]["words"], 5);
+
+    let temp_dir = TempDir::new().unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--text")
+        .arg("@-")
+        .arg("--output")
+        .arg("./out.txt")
+        .write_stdin("from stdin\n")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out.txt")).unwrap(),
+        "FROM STDIN\n"
+    );
+
+    cli()
+        .arg("run")
+        .arg("--text")
+        .arg("x")
+        .arg("--input")
+        .arg("file.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_run_profile_output_format() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"profiles": {"local": {"output_format": "json"}}}"#,
+    )
+    .unwrap();
+    let run = || {
+        let mut cmd = cli();
+        cmd.current_dir(temp_dir.path());
+        cmd
+    };
+
+    let output = run()
+        .arg("run")
+        .arg("--text")
+        .arg("a b")
+        .arg("--stats-only")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"][0]["stats"]["words"], 2);
+
+    // The flag still wins over the profile
+    run()
+        .arg("--format")
+        .arg("text")
+        .arg("run")
+        .arg("--text")
+        .arg("a b")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[SUCCESS]"));
+
+    run()
+        .arg("--format")
+        .arg("markdown")
+        .arg("run")
+        .arg("--text")
+        .arg("a b")
+        .arg("--top-words")
+        .arg("1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\n| Count | Word |\n| --- | --- |\n",
+        ))
+        .stdout(predicate::str::contains("**Success:**"));
+}
+
+#[test]
+fn test_run_decodes_latin1_and_utf16() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("latin1.txt"), b"caf\xE9 cr\xE8me\n").unwrap();
+
+    let mut utf16 = vec![0xFF, 0xFE];
+    utf16.extend("hello world\n".encode_utf16().flat_map(|u| u.to_le_bytes()));
+    fs::write(temp_dir.path().join("utf16.txt"), utf16).unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("latin1.txt")
+        .arg("--output")
+        .arg("./latin1.out")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Encoding: latin-1"));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("latin1.out")).unwrap(),
+        "CAFÉ CRÈME\n"
+    );
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("utf16.txt")
+        .arg("--stats-only")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Words: 2"))
+        .stdout(predicate::str::contains("Bytes: 26"))
+        .stdout(predicate::str::contains("Encoding: utf-16le"));
+}
+
+#[test]
+fn test_run_explicit_encoding_lossy() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("bad.txt"), b"ok \xFF\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("-L")
+        .arg("warning")
+        .arg("run")
+        .arg("--input")
+        .arg("bad.txt")
+        .arg("--encoding")
+        .arg("utf-8")
+        .arg("--stats-only")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("invalid sequences were replaced"));
+}
+
+#[test]
+fn test_run_transform_pipeline_reports_stages() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "  Apple \nbanana\n  Apple\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg(test_file.to_str().unwrap())
+        .arg("--transform")
+        .arg("trim | dedupe-lines | lowercase")
+        .arg("--output")
+        .arg("./out.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Transform stages:"))
+        .stdout(predicate::str::is_match(r"2\. dedupe-lines\s+lines 3 -> 2").unwrap());
+
+    let output = fs::read_to_string(temp_dir.path().join("out.txt")).unwrap();
+    assert_eq!(output, "apple\nbanana\n");
+}
+
+#[test]
+fn test_run_named_pipeline_from_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "  Apple \nbanana\n  Apple\n").unwrap();
+
+    let config_file = temp_dir.path().join("config.json");
+    fs::write(
+        &config_file,
+        r#"{"pipelines": {"clean": ["trim", "dedupe-lines", "lowercase"]}}"#,
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("-C")
+        .arg(&config_file)
+        .arg("run")
+        .arg("--input")
+        .arg(&test_file)
+        .arg("--transform")
+        .arg("@clean")
+        .arg("--output")
+        .arg("./out.txt")
+        .assert()
+        .success();
+
+    let output = fs::read_to_string(temp_dir.path().join("out.txt")).unwrap();
+    assert_eq!(output, "apple\nbanana\n");
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("-C")
+        .arg(&config_file)
+        .arg("run")
+        .arg("--input")
+        .arg(&test_file)
+        .arg("--transform")
+        .arg("@missing")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown pipeline '@missing'"));
+}
+
+#[test]
+fn test_run_invalid_named_pipeline_rejected_at_load() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "a\n").unwrap();
+
+    let config_file = temp_dir.path().join("config.json");
+    fs::write(&config_file, r#"{"pipelines": {"clean": ["trimm"]}}"#).unwrap();
+
+    cli()
+        .arg("-C")
+        .arg(&config_file)
+        .arg("run")
+        .arg("--input")
+        .arg(&test_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Pipeline 'clean'"));
+}
+
+#[test]
+fn test_run_binary_file_modes() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("blob.bin"),
+        b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR",
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("blob.bin")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Binary file skipped: blob.bin"));
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("blob.bin")
+        .arg("--binary")
+        .arg("bytes-only")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(binary)"))
+        .stdout(predicate::str::contains("Bytes: 16"))
+        .stdout(predicate::str::contains("Lines:").not());
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("blob.bin")
+        .arg("--binary")
+        .arg("error")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Binary file: blob.bin"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_directory_skips_symlink_loops() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("in");
+    fs::create_dir_all(input_dir.join("sub")).unwrap();
+    fs::write(input_dir.join("sub/file.txt"), "data").unwrap();
+    std::os::unix::fs::symlink("..", input_dir.join("sub/loop")).unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--stats-only")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("File statistics for"))
+        .stdout(predicate::str::contains("Skipped 1 unreadable path(s):"))
+        .stdout(predicate::str::contains("(symlink-loop)"));
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--stats-only")
+        .arg("--strict-fs")
+        .assert()
+        .failure();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_directory_fails_on_unwritable_output() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("in")).unwrap();
+    fs::write(temp_dir.path().join("in/a.txt"), "data").unwrap();
+    // Too long a path to create, while the input reads fine
+    let output_dir = vec!["d".repeat(250); 20].join("/");
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in")
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("unreadable").not())
+        .stderr(predicate::str::contains("Cannot create output directory"));
+}
+
+#[test]
+fn test_run_csv_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("export.csv"),
+        "id,name,score\n1,Ada,9.5\n2,,7\n3,Grace,\n",
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("export.csv")
+        .arg("--mode")
+        .arg("csv")
+        .arg("--stats-only")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rows: 3"))
+        .stdout(predicate::str::contains("Columns: 3"))
+        .stdout(predicate::str::is_match(r"name\s+string\s+1").unwrap())
+        .stdout(predicate::str::is_match(r"score\s+float\s+1").unwrap());
+}
+
+#[test]
+fn test_run_accessible_output() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("export.csv"),
+        "id,name,score\n1,Ada,9.5\n2,,7\n3,Grace,\n",
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--a11y")
+        .arg("run")
+        .arg("--input")
+        .arg("export.csv")
+        .arg("--mode")
+        .arg("csv")
+        .arg("--output")
+        .arg("./out.csv")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Column: name, Type: string, Empty: 1",
+        ))
+        .stdout(predicate::str::contains(
+            "Success: Output written to: ./out.csv",
+        ))
+        .stdout(predicate::str::contains("[SUCCESS]").not());
+
+    // ACCESSIBLE=1 is honored without the flag
+    cli()
+        .current_dir(temp_dir.path())
+        .env("ACCESSIBLE", "1")
+        .arg("run")
+        .arg("--input")
+        .arg("export.csv")
+        .arg("--fail-if-lines-over")
+        .arg("1")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Failed: export.csv:"));
+}
+
+#[test]
+fn test_run_tsv_mode_json() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("data.tsv"), "a\tb\n1\tx\n2\ty\n").unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg("data.tsv")
+        .arg("--mode")
+        .arg("csv")
+        .arg("--stats-only")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let table = &report["files"][0]["table"];
+    assert_eq!(table["rows"], 2);
+    assert_eq!(table["columns"][0]["name"], "a");
+    assert_eq!(table["columns"][0]["inferred_type"], "integer");
+    assert_eq!(table["columns"][0]["distinct"], 2);
+    assert_eq!(table["columns"][0]["numeric"]["min"], 1.0);
+    assert_eq!(table["columns"][0]["numeric"]["max"], 2.0);
+    assert_eq!(table["columns"][0]["numeric"]["mean"], 1.5);
+    assert_eq!(table["columns"][1]["inferred_type"], "string");
+    assert!(table["columns"][1].get("numeric").is_none());
+}
+
+#[test]
+fn test_run_json_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("events.ndjson"),
+        "{\"id\": 1, \"tags\": [\"a\", \"b\"]}\n{\"id\": 2, \"user\": {\"id\": 7}}\n",
+    )
+    .unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .args(["--format", "json", "run", "--input", "events.ndjson"])
+        .args(["--mode", "json", "--stats-only"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let json = &report["files"][0]["json"];
+    assert_eq!(json["documents"], 2);
+    assert_eq!(json["max_depth"], 2);
+    assert_eq!(json["keys"], 5);
+    assert_eq!(json["distinct_keys"], 3);
+    assert_eq!(json["max_array_len"], 2);
+
+    cli()
+        .current_dir(temp_dir.path())
+        .args([
+            "run",
+            "--input",
+            "events.ndjson",
+            "--mode",
+            "json",
+            "--stats-only",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Documents: 2"))
+        .stdout(predicate::str::contains("Keys: 5 (3 distinct)"))
+        .stdout(predicate::str::contains("Longest array: 2"));
+
+    fs::write(temp_dir.path().join("broken.json"), "{\"id\": [1, 2}\n").unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["run", "--input", "broken.json", "--mode", "json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid JSON"))
+        .stderr(predicate::str::contains("line 1"));
+}
+
+#[test]
+fn test_run_seeded_shuffle_is_reproducible() {
+    let temp_dir = TempDir::new().unwrap();
+    let content: String = (1..=50).map(|i| format!("row {}\n", i)).collect();
+    fs::write(temp_dir.path().join("rows.txt"), &content).unwrap();
+
+    for out in ["./a.txt", "./b.txt"] {
+        cli()
+            .current_dir(temp_dir.path())
+            .arg("run")
+            .arg("--input")
+            .arg("rows.txt")
+            .arg("--transform")
+            .arg("shuffle-lines | sample-lines:n=5")
+            .arg("--seed")
+            .arg("42")
+            .arg("--output")
+            .arg(out)
+            .assert()
+            .success();
+    }
+
+    let a = fs::read_to_string(temp_dir.path().join("a.txt")).unwrap();
+    let b = fs::read_to_string(temp_dir.path().join("b.txt")).unwrap();
+    assert_eq!(a, b);
+    assert_eq!(a.lines().count(), 5);
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn test_run_script_transform() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("input.txt"),
+        "keep\n# drop\nkeep too\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("filter.rhai"),
+        r##"if line.starts_with("#") { () } else { line.to_upper() }"##,
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--transform")
+        .arg("script:filter.rhai")
+        .arg("--output")
+        .arg("./out.txt")
+        .assert()
+        .success();
+
+    let output = fs::read_to_string(temp_dir.path().join("out.txt")).unwrap();
+    assert_eq!(output, "KEEP\nKEEP TOO\n");
+}
+
+#[cfg(not(feature = "scripting"))]
+#[test]
+fn test_run_script_transform_requires_feature() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("input.txt"), "text\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--transform")
+        .arg("script:filter.rhai")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--features scripting"));
+}
+
+#[test]
+fn test_run_hash() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("abc.txt");
+    fs::write(&input, "abc").unwrap();
+
+    cli()
+        .arg("run")
+        .arg("--input")
+        .arg(&input)
+        .arg("--stats-only")
+        .arg("--hash")
+        .arg("sha256")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Hash (sha256): ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ));
+
+    let output = cli()
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg(&input)
+        .arg("--stats-only")
+        .arg("--hash")
+        .arg("md5")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        report["files"][0]["digest"],
+        serde_json::json!({"algorithm": "md5", "hex": "900150983cd24fb0d6963f7d28e17f72"})
+    );
+}
+
+#[test]
+fn test_run_compressed_input_and_output() {
+    use flate2::write::GzEncoder;
+    use std::io::{Read, Write};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut encoder = GzEncoder::new(
+        fs::File::create(temp_dir.path().join("app.log.gz")).unwrap(),
+        flate2::Compression::default(),
+    );
+    encoder.write_all(b"hello\nworld\n").unwrap();
+    encoder.finish().unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("app.log.gz")
+        .arg("--stats-only")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Lines: 2"))
+        .stdout(predicate::str::contains("Compression: gzip"));
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("app.log.gz")
+        .arg("--output")
+        .arg("./out.txt")
+        .arg("--compress")
+        .arg("gzip")
+        .assert()
+        .success();
+
+    let mut output = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(temp_dir.path().join("out.txt")).unwrap())
+        .read_to_string(&mut output)
+        .unwrap();
+    assert_eq!(output, "HELLO\nWORLD\n");
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("app.log.gz")
+        .arg("--output")
+        .arg("./out.txt")
+        .arg("--compress")
+        .arg("xz")
+        .assert()
+        .success();
+
+    let mut output = String::new();
+    xz2::read::XzDecoder::new(fs::File::open(temp_dir.path().join("out.txt")).unwrap())
+        .read_to_string(&mut output)
+        .unwrap();
+    assert_eq!(output, "HELLO\nWORLD\n");
+}
+
+#[test]
+fn test_run_places_outputs_in_profile_output_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("in.txt"), "hello\n").unwrap();
+    fs::create_dir(temp_dir.path().join("docs")).unwrap();
+    fs::write(temp_dir.path().join("docs/a.txt"), "a\n").unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"profiles": {"local": {"output_dir": "./build/out"}}}"#,
+    )
+    .unwrap();
+    let out_dir = temp_dir.path().join("build/out");
+    let run = || {
+        let mut cmd = cli();
+        cmd.current_dir(temp_dir.path()).arg("run");
+        cmd
+    };
+
+    // A bare file name goes to the profile's directory, which is created and reported
+    run()
+        .arg("--input")
+        .arg("in.txt")
+        .arg("--output")
+        .arg("bare.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Output written to: {}",
+            std::path::absolute(&out_dir)
+                .unwrap()
+                .join("bare.txt")
+                .display()
+        )));
+    assert_eq!(
+        fs::read_to_string(out_dir.join("bare.txt")).unwrap(),
+        "HELLO\n"
+    );
+
+    // A path is used as given
+    run()
+        .arg("--input")
+        .arg("in.txt")
+        .arg("--output")
+        .arg("./here.txt")
+        .assert()
+        .success();
+    assert!(temp_dir.path().join("here.txt").exists());
+
+    // --emit-file names the output after the input, mirroring directories
+    run()
+        .arg("--input")
+        .arg("in.txt")
+        .arg("--emit-file")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(out_dir.join("in.txt")).unwrap(),
+        "HELLO\n"
+    );
+    run()
+        .arg("--input")
+        .arg("docs")
+        .arg("--emit-file")
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(out_dir.join("a.txt")).unwrap(), "A\n");
+
+    run()
+        .arg("--input")
+        .arg("in.txt")
+        .arg("--emit-file")
+        .arg("--output")
+        .arg("x.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_run_compression_level_and_profile_default() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("in.txt"), "hello\n".repeat(1000)).unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"profiles": {"local": {"output_dir": "./output", "compress": "zstd", "compression_level": 19}}}"#,
+    )
+    .unwrap();
+    let run = || {
+        let mut cmd = cli();
+        cmd.current_dir(temp_dir.path())
+            .arg("run")
+            .arg("--input")
+            .arg("in.txt")
+            .arg("--output")
+            .arg("./out.txt");
+        cmd
+    };
+
+    run().assert().success();
+    let written = fs::read(temp_dir.path().join("out.txt")).unwrap();
+    let decoded = zstd::decode_all(written.as_slice()).unwrap();
+    assert_eq!(decoded, "HELLO\n".repeat(1000).as_bytes());
+    let manifest = temp_dir
+        .path()
+        .join(format!(".{}-outputs.json", env!("CARGO_PKG_NAME")));
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(manifest).unwrap()).unwrap();
+    assert_eq!(manifest["compression"]["out.txt"], "zstd");
+
+    // The flag wins over the profile, and a level the format lacks fails before writing
+    run()
+        .arg("--compress")
+        .arg("gzip")
+        .arg("--compression-level")
+        .arg("12")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Compression level 12 is out of range for gzip (0-9)",
+        ));
+    run().arg("--compress").arg("none").assert().success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out.txt")).unwrap(),
+        "HELLO\n".repeat(1000)
+    );
+}
+
+#[test]
+fn test_run_fail_thresholds() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("app.log"), "ok\nTODO fix\nok\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("app.log")
+        .arg("--stats-only")
+        .arg("--fail-if-lines-over")
+        .arg("3")
+        .arg("--fail-if-bytes-over")
+        .arg("100")
+        .assert()
+        .success();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("app.log")
+        .arg("--stats-only")
+        .arg("--fail-if-lines-over")
+        .arg("2")
+        .arg("--fail-if-matches")
+        .arg("TODO")
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains(
+            "[FAIL] app.log: 3 lines exceeds limit of 2",
+        ))
+        .stdout(predicate::str::contains(
+            "[FAIL] app.log: 1 line(s) match 'TODO'",
+        ))
+        .stdout(predicate::str::contains("Lines: 3"))
+        .stderr(predicate::str::contains("Threshold exceeded"));
+}
+
+#[test]
+fn test_run_fail_threshold_json() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("big.txt"), "0123456789").unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg("big.txt")
+        .arg("--stats-only")
+        .arg("--fail-if-bytes-over")
+        .arg("5")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"][0]["violations"][0]["rule"], "bytes-over");
+}
+
+#[test]
+fn test_run_advanced_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("dupes.txt"), "a\nb\na\na\n").unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("dupes.txt")
+        .arg("--stats-only")
+        .arg("--advanced-stats")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Distinct lines (est.): 2"))
+        .stdout(predicate::str::contains("Duplication ratio: 0.5000"))
+        .stdout(predicate::str::contains("Entropy: "));
+}
+
+fn git(dir: &std::path::Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[test]
+fn test_run_files_from_git() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    git(root, &["init", "-q"]);
+    fs::write(root.join("old.txt"), "old\n").unwrap();
+    fs::write(root.join("edited.txt"), "v1\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-q", "-m", "initial"]);
+
+    fs::write(root.join("edited.txt"), "v2\n").unwrap();
+    fs::write(root.join("staged.txt"), "new\n").unwrap();
+    git(root, &["add", "staged.txt"]);
+    fs::write(root.join("untracked.txt"), "untracked\n").unwrap();
+
+    let selected = |selection: &str| {
+        let output = cli()
+            .current_dir(root)
+            .arg("--format")
+            .arg("json")
+            .arg("run")
+            .arg("--stats-only")
+            .arg("--files-from-git")
+            .arg(selection)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        report["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| {
+                std::path::Path::new(file["path"].as_str().unwrap())
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(selected("staged"), ["staged.txt"]);
+    assert_eq!(
+        selected("changed"),
+        ["edited.txt", "staged.txt", "untracked.txt"]
+    );
+
+    git(root, &["commit", "-q", "-am", "second"]);
+    assert_eq!(selected("HEAD~1.."), ["edited.txt", "staged.txt"]);
+}
+
+#[test]
+fn test_run_files_from_git_outside_repo() {
+    let temp_dir = TempDir::new().unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .env("GIT_CEILING_DIRECTORIES", temp_dir.path().parent().unwrap())
+        .arg("run")
+        .arg("--files-from-git")
+        .arg("staged")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("git diff"));
+}
+
+#[test]
+fn test_run_strategies_agree() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "alpha beta\n\ngamma alpha\nlast").unwrap();
+
+    let stats = |strategy: &str| {
+        let output = cli()
+            .args([
+                "run",
+                "--stats-only",
+                "--top-words",
+                "2",
+                "--advanced-stats",
+            ])
+            .args(["--format", "json", "--strategy", strategy, "--input"])
+            .arg(&test_file)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        report["files"][0]["stats"].clone()
+    };
+    let memory = stats("memory");
+    assert_eq!(memory["lines"], 4);
+    assert_eq!(stats("streaming"), memory);
+    assert_eq!(stats("parallel"), memory);
+}
+
+#[test]
+fn test_run_strategy_logged_at_debug() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("input.txt");
+    fs::write(&test_file, "one line\n").unwrap();
+
+    cli()
+        .args([
+            "run",
+            "--strategy",
+            "streaming",
+            "--log-level",
+            "debug",
+            "--replace",
+            "one",
+            "two",
+            "--input",
+        ])
+        .arg(&test_file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Not using streaming strategy: --replace needs the whole input",
+        ));
+}
+
+#[test]
+fn test_run_io_limit_summary() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("big.txt");
+    fs::write(&input, "x".repeat(29_999) + "\n").unwrap();
+
+    // 20 KB/s allows a one-second burst of 20 KB, then waits for the rest
+    let output = cli()
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg(&input)
+        .arg("--stats-only")
+        .arg("--io-limit")
+        .arg("0.02")
+        .arg("--summary")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let summary = &report["summary"];
+    assert_eq!(summary["files"], 1);
+    assert_eq!(summary["bytes_read"], 30_000);
+    assert_eq!(summary["bytes_written"], 0);
+    assert_eq!(summary["io_limit_mb_per_sec"], 0.02);
+    assert!(summary["throttled_seconds"].as_f64().unwrap() > 0.2);
+
+    let output_file = temp_dir.path().join("out.txt");
+    cli()
+        .arg("run")
+        .arg("--input")
+        .arg(&input)
+        .arg("--output")
+        .arg(&output_file)
+        .arg("--summary")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"Summary: 1 file\(s\), 30000 bytes read, 30000 bytes written in [0-9.]+ s \([0-9.]+ MB/s\)\n$",
+            )
+            .unwrap(),
+        );
+
+    cli()
+        .arg("run")
+        .arg("--input")
+        .arg(&input)
+        .arg("--io-limit")
+        .arg("0")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "expected a positive number of MB/s",
+        ));
+}
+
+#[test]
+fn test_run_max_memory() {
+    let temp_dir = TempDir::new().unwrap();
+    let line = "one two three\n";
+    fs::write(temp_dir.path().join("big.txt"), line.repeat(100_000)).unwrap();
+    fs::write(temp_dir.path().join("small.txt"), line).unwrap();
+
+    // Statistics of inputs above the limit are streamed, even when memory was asked for
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .args([
+            "-L", "warning", "--format", "json", "run", "--input", "big.txt",
+        ])
+        .args(["--stats-only", "--strategy", "memory", "--max-memory", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "big.txt is 2 MB, more than --max-memory allows (1 MB); streaming it instead"
+        ),
+        "{}",
+        stderr
+    );
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"][0]["stats"]["words"], 300_000);
+
+    // Transformed lines are streamed too, unless an option needs the whole input
+    cli()
+        .current_dir(temp_dir.path())
+        .args([
+            "-L",
+            "warning",
+            "run",
+            "--input",
+            "big.txt",
+            "--max-memory",
+            "1",
+        ])
+        .args(["--strategy", "memory", "--output", "./big.out"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("streaming it instead"));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("big.out")).unwrap(),
+        "ONE TWO THREE\n".repeat(100_000)
+    );
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["run", "--input", "big.txt", "--max-memory", "1"])
+        .args(["--replace", "one", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--replace needs the whole input in memory",
+        ));
+
+    // The profile sets the limit when the flag is absent; smaller inputs are unaffected
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"profiles": {"local": {"output_dir": "./output", "max_memory_mb": 1}}}"#,
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["run", "--input", "big.txt", "--sample", "10%"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--sample needs the whole input"));
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["run", "--input", "small.txt", "--output", "-"])
+        .assert()
+        .success()
+        .stdout("ONE TWO THREE\n");
+}
+
+#[test]
+fn test_run_min_free_space() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("in.txt"), "hello\n").unwrap();
+
+    // No filesystem has an exabyte to spare
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in.txt")
+        .arg("--output-dir")
+        .arg("out")
+        .arg("--min-free-space")
+        .arg("1000000000000")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Not enough free space in 'out'"));
+    assert!(!temp_dir.path().join("out").exists());
+
+    // The profile sets the minimum when the flag is absent
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"profiles": {"local": {"output_dir": "./output", "min_free_space_mb": 1000000000000}}}"#,
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in.txt")
+        .arg("--output")
+        .arg("./out.txt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--min-free-space"));
+    assert!(!temp_dir.path().join("out.txt").exists());
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("in.txt")
+        .arg("--output")
+        .arg("./out.txt")
+        .arg("--min-free-space")
+        .arg("0")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out.txt")).unwrap(),
+        "HELLO\n"
+    );
+}
+
+#[test]
+fn test_run_tail() {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let temp_dir = TempDir::new().unwrap();
+    let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+    fs::write(temp_dir.path().join("app.log"), &content).unwrap();
+    let mut encoder = GzEncoder::new(
+        fs::File::create(temp_dir.path().join("app.log.gz")).unwrap(),
+        flate2::Compression::default(),
+    );
+    encoder.write_all(content.as_bytes()).unwrap();
+    encoder.finish().unwrap();
+
+    // Read backwards from the end of a plain file, or in memory after decompressing
+    for input in ["app.log", "app.log.gz"] {
+        cli()
+            .current_dir(temp_dir.path())
+            .arg("run")
+            .arg("--input")
+            .arg(input)
+            .arg("--tail")
+            .arg("3")
+            .arg("--output")
+            .arg("./tail.txt")
+            .arg("--yes")
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("tail.txt")).unwrap(),
+            "LINE 8\nLINE 9\nLINE 10\n",
+            "{}",
+            input
+        );
+    }
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("app.log")
+        .arg("--tail")
+        .arg("2")
+        .arg("--stats-only")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Lines: 2"))
+        .stdout(predicate::str::contains("Bytes: 15"));
+
+    let utf16: Vec<u8> = [0xFF, 0xFE]
+        .into_iter()
+        .chain("a\nb\n".encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    fs::write(temp_dir.path().join("utf16.txt"), utf16).unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("utf16.txt")
+        .arg("--tail")
+        .arg("1")
+        .arg("--stats-only")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--tail does not support utf-16le input",
+        ));
+}
+
+#[test]
+fn test_run_output_report() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("input.txt"), "hello\nworld\n").unwrap();
+
+    // Data on stdout, the JSON report in its own file
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--output")
+        .arg("-")
+        .arg("--output-report")
+        .arg("report.json")
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .stdout("HELLO\nWORLD\n");
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_dir.path().join("report.json")).unwrap())
+            .unwrap();
+    assert_eq!(report["files"][0]["output"], "-");
+
+    // Without --output-report the report goes to stderr instead
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--output")
+        .arg("-")
+        .assert()
+        .success()
+        .stdout("HELLO\nWORLD\n")
+        .stderr(predicate::str::contains("Output written to stdout"));
+
+    // A text report can be redirected while the data goes to a file
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--output")
+        .arg("./out.txt")
+        .arg("--output-report")
+        .arg("report.txt")
+        .arg("--yes")
+        .assert()
+        .success()
+        .stdout("");
+    assert!(fs::read_to_string(temp_dir.path().join("report.txt"))
+        .unwrap()
+        .contains("Output written to: ./out.txt"));
+}
+
+#[test]
+fn test_run_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("raw.txt"), "hello\n").unwrap();
+    fs::write(
+        temp_dir.path().join("jobs.yaml"),
+        "jobs:
+  - name: stats
+    args: [--input, clean.txt, --stats-only]
+    depends_on: [clean]
+  - name: clean
+    args: [--input, raw.txt, --output, ./clean.txt]
+  - name: missing
+    args: [--input, missing.txt, --output, ./out.txt]
+  - name: after-missing
+    args: [--input, raw.txt, --stats-only]
+    depends_on: [missing]
+",
+    )
+    .unwrap();
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--batch")
+        .arg("jobs.yaml")
+        .arg("--plan")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("4 jobs in 2 stages"))
+        .stdout(predicate::str::is_match(r"2\s+stats\s+clean").unwrap());
+    assert!(!temp_dir.path().join("clean.txt").exists());
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--batch")
+        .arg("jobs.yaml")
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "1 of 4 batch jobs failed (1 skipped)",
+        ))
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("clean.txt")).unwrap(),
+        "HELLO\n"
+    );
+    // The batch report is the last JSON document on stdout, after those of the jobs
+    let stdout = String::from_utf8(output).unwrap();
+    let report: serde_json::Value =
+        serde_json::from_str(&stdout[stdout.rfind("\n{").unwrap()..]).unwrap();
+    let statuses: Vec<&str> = report["jobs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|job| job["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, ["succeeded", "succeeded", "failed", "skipped"]);
+
+    // Cycles are rejected before anything runs
+    fs::write(
+        temp_dir.path().join("cycle.yaml"),
+        "jobs:
+  - {name: a, args: [--input, raw.txt], depends_on: [b]}
+  - {name: b, args: [--input, raw.txt], depends_on: [a]}
+",
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--batch")
+        .arg("cycle.yaml")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("dependency cycle: a -> b -> a"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_run_stops_at_ctrl_c() {
+    let temp_dir = TempDir::new().unwrap();
+    let line = "the quick brown fox jumps over the lazy dog\n";
+    fs::write(temp_dir.path().join("big.txt"), line.repeat(1_000_000)).unwrap();
+
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")))
+        .current_dir(temp_dir.path())
+        .args([
+            "run",
+            "--input",
+            "big.txt",
+            "--stats-only",
+            "--strategy",
+            "streaming",
+            // Keeps the run going past the signal however fast counting gets
+            "--io-limit",
+            "20",
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(130), "{}", stderr);
+    assert!(stderr.contains("press Ctrl-C again"), "{}", stderr);
+    assert!(stderr.contains("Interrupted"), "{}", stderr);
+}
+
+#[test]
+fn test_run_stats_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().join("docs");
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "one two\nthree\n").unwrap();
+    fs::write(dir.join("b.txt"), "four\n").unwrap();
+
+    let stats_format = |format: &str| {
+        let output = cli()
+            .current_dir(temp_dir.path())
+            .arg("run")
+            .arg("--input")
+            .arg("docs")
+            .arg("--stats-only")
+            .arg("--stats-format")
+            .arg(format)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let report: serde_json::Value = serde_json::from_str(&stats_format("json")).unwrap();
+    assert_eq!(report["per_file"].as_array().unwrap().len(), 2);
+    assert_eq!(report["per_file"][1]["words"], 1);
+    assert_eq!(report["totals"]["words"], 4);
+    assert_eq!(report["totals"]["lines"], 3);
+
+    let yaml = stats_format("yaml");
+    assert!(yaml.contains("per_file:"));
+    assert!(yaml.contains("totals:"));
+
+    let csv = stats_format("csv");
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("path,lines,words,bytes"));
+    assert!(lines[3].starts_with("total,3,4,19,"));
+
+    let table = stats_format("table");
+    assert!(table.contains("Path"));
+    assert!(table.lines().last().unwrap().starts_with("total"));
+}
+
+#[test]
+fn test_run_directory_stats_table() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().join("docs");
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    fs::write(dir.join("b.txt"), "one two three\n").unwrap();
+    fs::write(dir.join("c.txt"), "one two\n").unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("docs")
+        .arg("--stats-only")
+        .arg("--sort-by")
+        .arg("words")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("Lines: "));
+    let rows: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.contains(".txt") || line.trim_start().starts_with("total"))
+        .collect();
+    assert_eq!(rows.len(), 4);
+    assert!(rows[0].contains("b.txt"));
+    assert!(rows[1].contains("c.txt"));
+    assert!(rows[2].contains("a.txt"));
+    let totals: Vec<&str> = rows[3].split_whitespace().collect();
+    assert_eq!(totals[..4], ["total", "3", "6", "26"]);
+}
+
+#[test]
+fn test_run_whitespace_stats_and_fix_line_endings() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("input.txt"), "a \r\n\tb\nc").unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--stats-only")
+        .arg("--whitespace-stats")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let whitespace = &report["files"][0]["stats"]["whitespace"];
+    assert_eq!(whitespace["line_endings"], "mixed");
+    assert_eq!(whitespace["trailing_whitespace_lines"], 1);
+    assert_eq!(whitespace["indentation"], "tabs");
+    assert_eq!(whitespace["final_newline"], false);
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("input.txt")
+        .arg("--output")
+        .arg("./output.txt")
+        .arg("--fix-line-endings")
+        .arg("crlf")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("output.txt")).unwrap(),
+        "a \r\n\tb\r\nc"
+    );
+}
+
+#[test]
+fn test_run_sort_and_unique() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("input.txt"),
+        "pear\napple\npear\nfig\n",
+    )
+    .unwrap();
+
+    // Streamed inputs are read line by line into the output, with the same result
+    for strategy in ["memory", "streaming"] {
+        let run = |flags: &[&str]| {
+            cli()
+                .current_dir(temp_dir.path())
+                .arg("run")
+                .arg("--input")
+                .arg("input.txt")
+                .arg("--output")
+                .arg("./output.txt")
+                .args(["--strategy", strategy])
+                .args(flags)
+                .assert()
+                .success();
+            fs::read_to_string(temp_dir.path().join("output.txt")).unwrap()
+        };
+        assert_eq!(run(&["--unique"]), "pear\napple\nfig\n");
+        assert_eq!(run(&["--sort"]), "apple\nfig\npear\npear\n");
+        assert_eq!(run(&["--sort", "--unique"]), "apple\nfig\npear\n");
+        assert_eq!(
+            run(&["--transform", "uppercase", "--unique"]),
+            "PEAR\nAPPLE\nFIG\n"
+        );
+    }
+
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["run", "--input", "input.txt", "--output", "-"])
+        .args(["--strategy", "streaming", "--sort", "--grep", "p"])
+        .assert()
+        .success()
+        .stdout("apple\npear\npear\n");
+
+    // An output replacing the input is only written once the input has been read
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["run", "--input", "input.txt", "--output", "./input.txt"])
+        .args(["--strategy", "streaming", "--sort"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("input.txt")).unwrap(),
+        "apple\nfig\npear\npear\n"
+    );
+}
+
+#[test]
+fn test_run_head_and_sample() {
+    let temp_dir = TempDir::new().unwrap();
+    let content: String = (1..=200).map(|i| format!("line {}\n", i)).collect();
+    fs::write(temp_dir.path().join("app.log"), content).unwrap();
+
+    let lines = |flags: &[&str]| {
+        let output = cli()
+            .current_dir(temp_dir.path())
+            .arg("--format")
+            .arg("json")
+            .arg("run")
+            .arg("--input")
+            .arg("app.log")
+            .arg("--stats-only")
+            .args(flags)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        report["files"][0]["stats"]["lines"].as_u64().unwrap()
+    };
+    assert_eq!(lines(&["--head", "5"]), 5);
+    assert_eq!(lines(&["--head", "5", "--hash", "sha256"]), 5);
+    assert_eq!(lines(&["--sample", "10%", "--seed", "3"]), 20);
+    assert_eq!(lines(&["--head", "50", "--sample", "50"]), 25);
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("app.log")
+        .arg("--head")
+        .arg("1")
+        .arg("--tail")
+        .arg("1")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_run_structured_mode_and_query() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("deploy.yaml"),
+        "services:\n  - name: web\n    port: 80\n  - name: db\n    port: 5432\n",
+    )
+    .unwrap();
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .arg("--format")
+        .arg("json")
+        .arg("run")
+        .arg("--input")
+        .arg("deploy.yaml")
+        .arg("--stats-only")
+        .arg("--mode")
+        .arg("structured")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let json = &report["files"][0]["json"];
+    assert_eq!(json["max_depth"], 3);
+    assert_eq!(json["max_array_len"], 2);
+    assert_eq!(json["types"]["$.services[*].port"][0], "integer");
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("deploy.yaml")
+        .arg("--stats-only")
+        .arg("--query")
+        .arg("$.services[*].name")
+        .assert()
+        .success()
+        .stdout("\"web\"\n\"db\"\n")
+        .stderr(predicate::str::contains("Query matches: 2"));
+
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg("--input")
+        .arg("deploy.yaml")
+        .arg("--query")
+        .arg("services")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid JSONPath"));
+}
+
+#[test]
+fn test_run_since_last() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache_home = temp_dir.path().join("cache");
+    let input = temp_dir.path().join("in");
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join("a.txt"), "alpha\n").unwrap();
+    fs::write(input.join("b.txt"), "beta\n").unwrap();
+    let run = || {
+        let output = cli()
+            .current_dir(temp_dir.path())
+            .env("XDG_CACHE_HOME", &cache_home)
+            .args(["--format", "json", "run", "--input", "in"])
+            .args(["--output-dir", "out", "--since-last"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let mut files: Vec<(String, bool)> = report["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| {
+                let name = file["path"].as_str().unwrap().replace('\\', "/");
+                (name, file.get("unchanged").is_some())
+            })
+            .collect();
+        files.sort();
+        files
+    };
+
+    let a = String::from("in/a.txt");
+    let b = String::from("in/b.txt");
+    assert_eq!(run(), [(a.clone(), false), (b.clone(), false)]);
+    assert_eq!(run(), [(a.clone(), true), (b.clone(), true)]);
+
+    // Only the changed file is processed again, and a deleted output is written again
+    fs::write(input.join("a.txt"), "alpha and more\n").unwrap();
+    fs::remove_file(temp_dir.path().join("out/b.txt")).unwrap();
+    assert_eq!(run(), [(a.clone(), false), (b.clone(), false)]);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out/a.txt")).unwrap(),
+        "ALPHA AND MORE\n"
+    );
+    assert_eq!(run(), [(a, true), (b, true)]);
+
+    // The text report marks the unchanged inputs
+    cli()
+        .current_dir(temp_dir.path())
+        .env("XDG_CACHE_HOME", &cache_home)
+        .args([
+            "run",
+            "--input",
+            "in",
+            "--output-dir",
+            "out",
+            "--since-last",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Output unchanged (cached)"));
+
+    // Other arguments start from scratch
+    cli()
+        .current_dir(temp_dir.path())
+        .env("XDG_CACHE_HOME", &cache_home)
+        .args(["run", "--input", "in/a.txt", "--stats-only", "--since-last"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("unchanged").not());
+}
+
+#[test]
+fn test_run_error_policy() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("in");
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join("a.json"), "{\"a\": 1}\n").unwrap();
+    fs::write(input.join("b.json"), "{\"a\":\n").unwrap();
+    fs::write(input.join("c.json"), "[1]\n").unwrap();
+    let run = |policy: &str| {
+        cli()
+            .current_dir(temp_dir.path())
+            .args([
+                "run",
+                "--mode",
+                "json",
+                "--input",
+                "in",
+                "--error-policy",
+                policy,
+            ])
+            .assert()
+            .failure()
+    };
+
+    run("fail-fast")
+        .stdout(predicate::str::contains("Failed to process").not())
+        .stderr(predicate::str::contains("Invalid JSON"));
+    // The other files are still reported, the failures listed after them
+    run("continue")
+        .stdout(predicate::str::contains("File statistics for 'in/c.json'"))
+        .stdout(predicate::str::contains(
+            "Failed to process 1 file(s):\n  in/b.json: Invalid JSON",
+        ))
+        .stderr(predicate::str::contains(
+            "1 of 3 files could not be processed",
+        ));
+    run("summarize")
+        .stdout(predicate::str::contains("File statistics for 'in/c.json'"))
+        .stdout(predicate::str::contains(
+            "Failed to process 1 file(s):\n  invalid JSON: 1",
+        ));
+
+    let output = cli()
+        .current_dir(temp_dir.path())
+        .args(["--format", "json", "run", "--mode", "json", "--input", "in"])
+        .args(["--error-policy", "continue"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["files"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        report["failed"][0]["path"]
+            .as_str()
+            .unwrap()
+            .replace('\\', "/"),
+        "in/b.json"
+    );
+}
+
+#[test]
+fn test_run_error_policy_with_io_errors() {
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("in");
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join("a.txt"), "one\n").unwrap();
+    fs::write(input.join("b.txt.gz"), "not gzip\n").unwrap();
+    let run = |args: &[&str]| {
+        cli()
+            .current_dir(temp_dir.path())
+            .args(["run", "--input", "in", "--stats-only"])
+            .args(args)
+            .assert()
+    };
+
+    // Data that cannot be decoded is a failure under the policy, not an unreadable path
+    run(&["--error-policy", "continue"])
+        .failure()
+        .stdout(predicate::str::contains("File statistics for 'in/a.txt'"))
+        .stdout(predicate::str::contains("Skipped").not())
+        .stdout(predicate::str::contains("in/b.txt.gz: IO error"))
+        .stderr(predicate::str::contains("Failed to process in/b.txt.gz"));
+    run(&["--error-policy", "summarize"])
+        .failure()
+        .stdout(predicate::str::contains(
+            "Failed to process 1 file(s):\n  unexpected end of file: 1",
+        ));
+}
+
+#[test]
+fn test_run_batch_with_one_worker() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("raw.txt"), "hello\n").unwrap();
+    fs::write(
+        temp_dir.path().join("one.yaml"),
+        "jobs:\n  - {name: only, args: [--input, raw.txt, --output, ./one.txt]}\n",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("two.yaml"),
+        "jobs:
+  - {name: first, args: [--input, raw.txt, --output, ./first.txt]}
+  - {name: second, args: [--input, first.txt, --output, ./second.txt], depends_on: [first]}
+",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("config.json"),
+        r#"{"default_profile": "serial", "profiles": {"serial": {"output_dir": ".", "parallel_jobs": 1}}}"#,
+    )
+    .unwrap();
+
+    // A single job, and every job with parallel_jobs: 1, runs on the command's own thread
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["run", "--batch", "one.yaml"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("one.txt")).unwrap(),
+        "HELLO\n"
+    );
+    cli()
+        .current_dir(temp_dir.path())
+        .args(["--config", "config.json", "run", "--batch", "two.yaml"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("second.txt")).unwrap(),
+        "HELLO\n"
+    );
+}
+
+#[test]
+fn test_run_batch_jobs_share_output_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut jobs = String::from("jobs:\n");
+    for i in 1..=4 {
+        let input = temp_dir.path().join(format!("in{}", i));
+        fs::create_dir(&input).unwrap();
+        fs::write(input.join(format!("file{}.txt", i)), "text\n").unwrap();
+        jobs.push_str(&format!(
+            "  - {{name: job{i}, args: [--input, in{i}, --output-dir, od]}}\n"
+        ));
+    }
+    fs::write(temp_dir.path().join("jobs.yaml"), jobs).unwrap();
+
+    // The jobs run in one process, which must not lock itself out of the directory
+    cli()
+        .current_dir(temp_dir.path())
+        .env("XDG_CACHE_HOME", temp_dir.path().join("cache"))
+        .args(["run", "--batch", "jobs.yaml"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Another instance").not());
+    let manifest = fs::read_to_string(
+        temp_dir
+            .path()
+            .join(format!("od/.{}-outputs.json", env!("CARGO_PKG_NAME"))),
+    )
+    .unwrap();
+    for i in 1..=4 {
+        assert!(temp_dir.path().join(format!("od/file{}.txt", i)).exists());
+        // Every job's outputs are recorded
+        assert!(manifest.contains(&format!("file{}.txt", i)), "{}", manifest);
+    }
+}
@@ -0,0 +1,45 @@
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+use crate::support::cli;
+
+#[test]
+fn test_selftest_passes() {
+    let temp_dir = TempDir::new().unwrap();
+    cli()
+        .env("XDG_CACHE_HOME", temp_dir.path())
+        .arg("selftest")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("PASS stats"))
+        .stdout(predicate::str::contains("PASS cache"))
+        .stdout(predicate::str::contains("SKIP network"))
+        .stdout(predicate::str::contains("All 4 self-test cases passed"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_selftest_reports_failures() {
+    let temp_dir = TempDir::new().unwrap();
+    // The cache directory cannot be created below a file
+    let cache = temp_dir.path().join("cache");
+    std::fs::write(&cache, "").unwrap();
+
+    let output = cli()
+        .env("XDG_CACHE_HOME", &cache)
+        .arg("--format")
+        .arg("json")
+        .arg("selftest")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["failed"], 1);
+    let cases = report["cases"].as_array().unwrap();
+    let cache_case = cases.iter().find(|case| case["name"] == "cache").unwrap();
+    assert_eq!(cache_case["status"], "fail");
+    assert_eq!(
+        cases.iter().find(|case| case["name"] == "stats").unwrap()["status"],
+        "pass"
+    );
+}
@@ -0,0 +1,44 @@
+use predicates::prelude::*;
+
+use crate::support::cli;
+
+#[test]
+fn test_transform_explain() {
+    cli()
+        .arg("transform")
+        .arg("explain")
+        .arg("trim | dedupe-lines | truncate:width=40")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pipeline with 3 stage(s)"))
+        .stdout(predicate::str::contains("2. dedupe-lines"))
+        .stdout(predicate::str::contains("width = 40"));
+}
+
+#[test]
+fn test_transform_explain_json() {
+    let output = cli()
+        .arg("--format")
+        .arg("json")
+        .arg("transform")
+        .arg("explain")
+        .arg("truncate")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stages[0]["name"], "truncate");
+    assert_eq!(stages[0]["options"]["width"], "80");
+}
+
+#[test]
+fn test_transform_explain_unknown_stage() {
+    cli()
+        .arg("transform")
+        .arg("explain")
+        .arg("trim | sparkle")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown transform 'sparkle'"));
+}
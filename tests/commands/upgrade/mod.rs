@@ -0,0 +1,419 @@
+use predicates::prelude::*;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use crate::support::{cli, cli_at};
+
+#[test]
+fn test_upgrade_list_help() {
+    cli()
+        .arg("upgrade")
+        .arg("list")
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--limit"))
+        .stdout(predicate::str::contains("--channel"));
+}
+
+#[test]
+fn test_upgrade_list_rejects_upgrade_options() {
+    cli()
+        .arg("upgrade")
+        .arg("--force")
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_upgrade_list_limit_range() {
+    cli()
+        .arg("upgrade")
+        .arg("list")
+        .arg("--limit")
+        .arg("0")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--limit"));
+}
+
+#[test]
+fn test_upgrade_rejects_invalid_env_option() {
+    cli()
+        .arg("upgrade")
+        .env("__TEMPLATE_ENV_PREFIX___UPGRADE_FORCE", "maybe")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid value for __TEMPLATE_ENV_PREFIX___UPGRADE_FORCE: expected true or false, got 'maybe'",
+        ));
+}
+
+#[test]
+fn test_upgrade_rejects_invalid_version_requirement() {
+    cli()
+        .arg("upgrade")
+        .arg("--version")
+        .arg("^one")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid version requirement '^one'",
+        ));
+}
+
+#[test]
+fn test_upgrade_to_pinned() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("upgrade")
+        .arg("--to-pinned")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "No .{}-version file found",
+            env!("CARGO_PKG_NAME")
+        )));
+
+    // Already on the pinned version: nothing to download
+    let running = env!("CARGO_PKG_VERSION");
+    std::fs::write(
+        temp_dir
+            .path()
+            .join(format!(".{}-version", env!("CARGO_PKG_NAME"))),
+        running,
+    )
+    .unwrap();
+    cli()
+        .current_dir(temp_dir.path())
+        .arg("--a11y")
+        .arg("upgrade")
+        .arg("--to-pinned")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(format!(
+            "Already on version {}, pinned by",
+            running
+        )));
+
+    cli()
+        .arg("upgrade")
+        .arg("--to-pinned")
+        .arg("--version")
+        .arg("1.0.0")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+/// File name of the binary, installed or in a release archive.
+fn binary_name() -> String {
+    format!("{}{}", env!("CARGO_PKG_NAME"), std::env::consts::EXE_SUFFIX)
+}
+
+/// Serves `files` (by path, e.g. `/release.tar.gz`) over HTTP on localhost, to any
+/// number of requests, from a thread of its own. Returns the server's URL.
+fn serve(files: Vec<(String, Vec<u8>)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for mut socket in listener.incoming().flatten() {
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match socket.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+            let (status, content) = match files.iter().find(|(served, _)| served == path) {
+                Some((_, content)) => ("200 OK", &content[..]),
+                None => ("404 Not Found", &b""[..]),
+            };
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                content.len()
+            );
+            let _ = socket
+                .write_all(head.as_bytes())
+                .and_then(|()| socket.write_all(content));
+        }
+    });
+    url
+}
+
+/// A release archive holding `binary` as the executable.
+fn release_archive(binary: &[u8]) -> Vec<u8> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(binary.len() as u64);
+    header.set_mode(0o755);
+    header.set_cksum();
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::fast(),
+    ));
+    archive
+        .append_data(&mut header, binary_name(), binary)
+        .unwrap();
+    archive.into_inner().unwrap().finish().unwrap()
+}
+
+/// A copy of the binary installed in a directory of its own, configured to upgrade from a
+/// local server to a release whose executable is `NEW_BINARY`.
+struct Installed {
+    dir: TempDir,
+    cache: PathBuf,
+    exe: PathBuf,
+    original: Vec<u8>,
+}
+
+const NEW_BINARY: &[u8] = b"#!/bin/sh\necho upgraded\n";
+
+impl Installed {
+    /// Installs the copy, keeping downloads in `cache` (`None` for a directory next to it).
+    fn new(cache: Option<&Path>) -> Self {
+        Self::with_checksum(cache, None)
+    }
+
+    /// Like [`Installed::new`], with the release's `.sha256` file served as `checksum`.
+    fn with_checksum(cache: Option<&Path>, checksum: Option<String>) -> Self {
+        let dir = TempDir::new().unwrap();
+        let exe = dir.path().join("bin").join(binary_name());
+        fs::create_dir(exe.parent().unwrap()).unwrap();
+        fs::copy(assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")), &exe).unwrap();
+        let original = fs::read(&exe).unwrap();
+
+        let mut files = vec![(String::from("/release.tar.gz"), release_archive(NEW_BINARY))];
+        if let Some(checksum) = checksum {
+            files.push((
+                String::from("/release.tar.gz.sha256"),
+                checksum.into_bytes(),
+            ));
+        }
+        let url = format!("{}/release.tar.gz", serve(files));
+        fs::write(
+            dir.path().join("config.json"),
+            serde_json::json!({ "upgrade": { "asset_url": url } }).to_string(),
+        )
+        .unwrap();
+        let cache = cache.map_or_else(|| dir.path().join("cache"), Path::to_path_buf);
+        Self {
+            dir,
+            cache,
+            exe,
+            original,
+        }
+    }
+
+    /// The installed copy's `upgrade` to version 9.9.9.
+    fn command(&self) -> assert_cmd::Command {
+        let mut command = cli_at(&self.exe);
+        command
+            .current_dir(self.dir.path())
+            .env("XDG_CACHE_HOME", &self.cache)
+            .args(["--yes", "upgrade", "--version", "9.9.9"]);
+        command
+    }
+
+    fn upgrade(&self) -> assert_cmd::assert::Assert {
+        self.command().assert()
+    }
+
+    fn is_upgraded(&self) -> bool {
+        fs::read(&self.exe).unwrap() == NEW_BINARY
+    }
+
+    fn is_original(&self) -> bool {
+        fs::read(&self.exe).unwrap() == self.original
+    }
+}
+
+#[test]
+fn test_upgrade_replaces_installed_binary() {
+    let installed = Installed::new(None);
+    installed.upgrade().success();
+    assert!(installed.is_upgraded());
+    // Windows deletes the previous binary from a detached helper once it exited
+    #[cfg(unix)]
+    assert!(!installed.exe.with_extension("old").exists());
+}
+
+#[test]
+fn test_upgrade_verifies_published_checksum() {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(release_archive(NEW_BINARY));
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    let installed = Installed::with_checksum(None, Some(format!("{}  release.tar.gz\n", hex)));
+    installed
+        .command()
+        .args(["--format", "json"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(r#""phase":"verify""#));
+    assert!(installed.is_upgraded());
+
+    let installed = Installed::with_checksum(None, Some(format!("{}\n", "0".repeat(64))));
+    installed
+        .upgrade()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Checksum mismatch for release.tar.gz",
+        ));
+    assert!(installed.is_original());
+}
+
+#[test]
+fn test_upgrade_checksum_policy_from_env() {
+    let name = "__TEMPLATE_ENV_PREFIX___UPGRADE_CHECKSUM";
+    // Nothing is published next to the configured asset URL
+    let installed = Installed::new(None);
+    installed
+        .command()
+        .env(name, "require")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "No checksum is published for release.tar.gz",
+        ));
+    assert!(installed.is_original());
+
+    installed
+        .command()
+        .env(name, "maybe")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "Invalid value for {}: expected require, auto or skip, got 'maybe'",
+            name
+        )));
+
+    // The flag takes precedence
+    installed
+        .command()
+        .env(name, "require")
+        .args(["--checksum", "skip"])
+        .assert()
+        .success();
+    assert!(installed.is_upgraded());
+}
+
+#[test]
+fn test_upgrade_install_dir_from_env() {
+    let installed = Installed::new(None);
+    let dir = installed.dir.path().join("opt").join("bin");
+    installed
+        .command()
+        .env("__TEMPLATE_ENV_PREFIX___UPGRADE_INSTALL_DIR", &dir)
+        .assert()
+        .success();
+    assert_eq!(fs::read(dir.join(binary_name())).unwrap(), NEW_BINARY);
+    assert!(installed.is_original());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_upgrade_permission_denied_keeps_installed_binary() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Privileges bypass the permissions that make the install fail
+    // SAFETY: geteuid has no preconditions and cannot fail
+    if unsafe { libc::geteuid() } == 0 {
+        eprintln!("Skipping: running as root");
+        return;
+    }
+    let installed = Installed::new(None);
+    let bin = installed.exe.parent().unwrap();
+    fs::set_permissions(bin, fs::Permissions::from_mode(0o555)).unwrap();
+    let assert = installed.upgrade();
+    fs::set_permissions(bin, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Permission denied"));
+    assert!(installed.is_original());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_upgrade_across_filesystems() {
+    use std::os::unix::fs::MetadataExt;
+
+    // The download is moved into place from a tmpfs, unless it shares a filesystem with
+    // the temporary directory
+    let shm = Path::new("/dev/shm");
+    let device = |path: &Path| fs::metadata(path).map(|metadata| metadata.dev());
+    let Ok(cache) = TempDir::new_in(shm) else {
+        eprintln!("Skipping: no /dev/shm");
+        return;
+    };
+    let installed = Installed::new(Some(cache.path()));
+    if device(shm).ok() == device(installed.dir.path()).ok() {
+        eprintln!("Skipping: /dev/shm is on the same filesystem as the installed binary");
+        return;
+    }
+
+    installed
+        .command()
+        .args(["-L", "debug"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("is on another filesystem"));
+    assert!(installed.is_upgraded());
+    assert!(!installed.exe.with_extension("new").exists());
+}
+
+#[cfg(windows)]
+#[test]
+fn test_upgrade_locked_backup_keeps_installed_binary() {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    // Another process holding the backup open without sharing keeps the running binary
+    // from being moved aside
+    let installed = Installed::new(None);
+    let backup = installed.exe.with_extension("old");
+    fs::write(&backup, "locked").unwrap();
+    let _locked = fs::OpenOptions::new()
+        .read(true)
+        .share_mode(0)
+        .open(&backup)
+        .unwrap();
+
+    installed.upgrade().failure().stderr(
+        predicate::str::contains("in use by another process")
+            .or(predicate::str::contains("Permission denied")),
+    );
+    assert!(installed.is_original());
+}
+
+#[test]
+fn test_upgrade_check() {
+    let installed = Installed::new(None);
+    let check = |version: &str| {
+        let mut command = cli_at(&installed.exe);
+        command
+            .current_dir(installed.dir.path())
+            .env("XDG_CACHE_HOME", &installed.cache)
+            .args(["--a11y", "upgrade", "--check", "--version", version]);
+        command.assert().success()
+    };
+
+    check(env!("CARGO_PKG_VERSION")).stderr(predicate::str::contains(format!(
+        "Already on the latest version, {}.",
+        env!("CARGO_PKG_VERSION")
+    )));
+    check("9.9.9").stderr(predicate::str::contains(format!(
+        "Version 9.9.9 is available (running {}).",
+        env!("CARGO_PKG_VERSION")
+    )));
+    assert!(installed.is_original());
+}
@@ -1,6 +1,18 @@
 use assert_cmd::Command;
+use std::path::Path;
 
-/// Create a new Command instance for the CLI binary
+/// Create a new Command instance for the CLI binary, with messages in English whatever
+/// the locale of the machine running the tests
 pub fn cli() -> Command {
-    Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap()
+    cli_at(&assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")))
+}
+
+/// Like [`cli`], for a copy of the binary at `program`
+pub fn cli_at(program: &Path) -> Command {
+    let mut command = Command::new(program);
+    command
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES")
+        .env_remove("LANG");
+    command
 }